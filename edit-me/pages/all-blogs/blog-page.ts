@@ -1,5 +1,4 @@
-//The below will be replaced once the website is compiled
-<r-dynamicContentLoader> 
+import { setDynamicElementsContent } from "<root>/shared/code/dynamicContentLoader";
 
 document.addEventListener("DOMContentLoaded", setDynamicElementsContent);
 // ====================================================
@@ -1,3 +1,5 @@
+import { setDynamicElementsContent } from "<root>/shared/code/dynamicContentLoader";
+
 document.addEventListener("DOMContentLoaded", setDynamicElementsContent);
 
 //--content getters--
@@ -24,9 +26,6 @@ async function contentGetter_loaded_txt() {
 //GROTE FUNCTIONS
 //////////////////////////
 
-//start of -----------------------------------------------------
-<r-dynamicContentLoader>
-//end of -----------------------------------------------------
 
 
 //////////////////////////
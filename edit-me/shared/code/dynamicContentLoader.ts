@@ -6,7 +6,7 @@
 //   }
 // }
 
-async function setDynamicElementsContent(): Promise<void> {
+export async function setDynamicElementsContent(): Promise<void> {
   const prefix: string = "D-";
   const corrContentFuncNameStart: string = "contentGetter_";
   console.log("Loading the dynamic elements...");
@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::components::resolve_relative_reference;
+use crate::images::content_hash;
+use crate::source_maps::strip_source_mapping_url;
+use crate::vfs::FileSystem;
+
+// === PER-PAGE CSS / JS BUNDLING ===
+// Every <r-component> adds its own <link rel="stylesheet"> / <script defer> to the page, so a page made of a dozen
+// components makes a dozen requests. With bundling on, a page's component css / js is joined into one file of each:
+//      <link rel="stylesheet" href="shared/reusables/top-bar/top-bar.css">
+//      <link rel="stylesheet" href="shared/reusables/signpost/signpost.css">     ->  <link rel="stylesheet" href="bundles/1a2b3c4d.css">
+// Pages that use the same set of components (e.g. the language versions of a page) share the same bundle.
+//
+// In 'shared' mode, the components used by many pages go into one shared bundle instead (bundles/shared.css / .js),
+// which the browser downloads once and re-uses on every page; each page's bundle then only has its other components.
+//
+// The page's own css / js (e.g. index.css) and the global files aren't component files, so are left alone.
+// bundles/bundles.json records which components each bundle is made of, so the bundles can be rebuilt in later
+// (incremental) builds when a component changes, even though the pages that use them weren't recompiled.
+
+/// The folder (in the output directory) that the bundles are written to.
+pub const BUNDLE_DIRECTORY: &str = "bundles";
+
+/// The record of which components each bundle is made of, kept in the bundle directory.
+pub const BUNDLE_RECORD_FILE: &str = "bundles.json";
+
+/// The name (without extension) of the shared bundle.
+pub const SHARED_BUNDLE_NAME: &str = "shared";
+
+/// Where the component files live, relative to the output directory.
+const COMPONENT_OUTPUT_DIRECTORY: &str = "shared/reusables/";
+
+/// How the component css / js is bundled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleMode {
+    /// One css and one js bundle per page.
+    Page,
+    /// Components used by at least `min_pages` pages go into one shared bundle; the rest go into each page's own bundle.
+    Shared { min_pages: usize },
+}
+
+/// The two kinds of file that get bundled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BundleKind {
+    Css,
+    Js,
+}
+
+impl BundleKind {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BundleKind::Css => "css",
+            BundleKind::Js => "js",
+        }
+    }
+
+    /// The tag that loads a file of this kind, in the same form the component expander writes.
+    pub fn tag(&self, url: &str) -> String {
+        match self {
+            BundleKind::Css => format!("<link rel=\"stylesheet\" href=\"{}\">", url),
+            BundleKind::Js => format!("<script defer src=\"{}\"></script>", url),
+        }
+    }
+}
+
+/// What was bundled in the last build, so it can be re-bundled without the original component tags.
+/// (All paths are relative to the output directory)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleRecord {
+    /// Bundle name (e.g. "1a2b3c4d.css") -> the component files it is made of, in order.
+    pub bundles: BTreeMap<String, Vec<String>>,
+    /// Page (e.g. "blog/index.html") -> the component files it uses.
+    pub pages: BTreeMap<String, Vec<String>>,
+}
+
+/// The outcome of `bundle_component_assets`.
+#[derive(Debug, Default)]
+pub struct BundleSummary {
+    pub pages_bundled: usize,
+    pub bundles_written: usize,
+    pub bundles_removed: usize,
+}
+
+/// A <link> / <script> tag in a page that loads a component file or a bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetTag {
+    /// Byte range of the tag (and the newline after it, if any) in the page.
+    pub start: usize,
+    pub end: usize,
+    pub kind: BundleKind,
+    /// The file it loads, relative to the output directory, e.g. "shared/reusables/top-bar/top-bar.css".
+    pub path: String,
+}
+
+/// Finds the tags in a page that load component css / js files or bundles.
+///
+/// # Arguments
+/// * `html` - The page.
+/// * `html_path` - Where the page is (the urls in it are relative to it).
+/// * `output_dir` - The root of the website.
+pub fn find_bundleable_tags(html: &str, html_path: &Path, output_dir: &Path) -> Vec<AssetTag> {
+    // (a <link> may have been deferred by the critical css stage of an earlier build, so can have more attributes)
+    let tag_regex = Regex::new(r#"(?i)<link\s+rel="stylesheet"\s+href="([^"]+)"[^>]*>\n?|<script\s+defer\s+src="([^"]+)"\s*>\s*</script>\n?"#).unwrap();
+    let mut tags = Vec::new();
+
+    for captures in tag_regex.captures_iter(html) {
+        let (kind, url) = match (captures.get(1), captures.get(2)) {
+            (Some(href), _) => (BundleKind::Css, href.as_str()),
+            (None, Some(src)) => (BundleKind::Js, src.as_str()),
+            _ => continue,
+        };
+        if url.contains("://") || url.starts_with("//") {
+            continue;
+        }
+        let resolved = resolve_relative_reference(html_path, url);
+        if !resolved.starts_with(output_dir) {
+            continue;
+        }
+        let path = relative_path(&resolved, output_dir);
+
+        let is_component = path.starts_with(COMPONENT_OUTPUT_DIRECTORY);
+        let is_bundle = path.starts_with(&format!("{}/", BUNDLE_DIRECTORY));
+        if (is_component || is_bundle) && path.ends_with(&format!(".{}", kind.extension())) {
+            let whole_match = captures.get(0).unwrap();
+            tags.push(AssetTag { start: whole_match.start(), end: whole_match.end(), kind, path });
+        }
+    }
+    tags
+}
+
+/// Sorts component files into dependency order: components that are used by other components come first,
+/// so the css of the component using them can override theirs.
+/// The order is that of the reusables compilation order file, which already lists components before the components that use them.
+/// Components not in that list keep their order, after the listed ones.
+///
+/// # Arguments
+/// * `component_files` - Component files, relative to the output directory, e.g. "shared/reusables/top-bar/top-bar.css".
+/// * `compilation_order` - The component folders, in order, relative to the output directory, e.g. "shared/reusables/top-bar".
+pub fn sort_into_dependency_order(component_files: &mut [String], compilation_order: &[String]) {
+    let position = |file: &String| compilation_order.iter().position(|folder| Path::new(file).parent() == Some(Path::new(folder))).unwrap_or(usize::MAX);
+    component_files.sort_by_key(position); // (a stable sort, so unlisted components keep their order)
+}
+
+/// Rewrites the relative url()s in some css, so they still point at the same files when the css is moved.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use websiteCompiler::bundling::rebase_css_urls;
+/// let css = ".a { background: url('images/star.png'); } .b { background: url(data:image/png;base64,AA); }";
+/// assert_eq!(
+///     rebase_css_urls(css, Path::new("site/shared/reusables/a/a.css"), Path::new("site/bundles/x.css")),
+///     ".a { background: url('../shared/reusables/a/images/star.png'); } .b { background: url(data:image/png;base64,AA); }"
+/// );
+/// ```
+pub fn rebase_css_urls(css: &str, from_file: &Path, to_file: &Path) -> String {
+    let url_regex = Regex::new(r#"url\(\s*(['"]?)([^'")]+)(['"]?)\s*\)"#).unwrap();
+    let to_directory = to_file.parent().unwrap_or(Path::new(""));
+
+    url_regex.replace_all(css, |captures: &regex::Captures| {
+        let url = &captures[2];
+        let is_relative = !(url.contains(':') || url.starts_with('/') || url.starts_with('#'));
+        if !is_relative {
+            return captures[0].to_string();
+        }
+        let suffix_start = url.find(['?', '#']).unwrap_or(url.len());
+        let target = resolve_relative_reference(from_file, url);
+        let rebased = pathdiff::diff_paths(&target, to_directory).unwrap_or(target);
+        format!("url({}{}{}{})", &captures[1], rebased.to_string_lossy().replace('\\', "/"), &url[suffix_start..], &captures[3])
+    }).to_string()
+}
+
+/// Joins component files into a bundle. A component's content that is the same as one already in the bundle is left out.
+fn build_bundle(fs: &dyn FileSystem, output_dir: &Path, bundle_path: &Path, kind: BundleKind, component_files: &[String]) -> Result<String> {
+    let mut bundle = String::new();
+    let mut included_contents: HashSet<String> = HashSet::new();
+
+    for component_file in component_files {
+        let component_path = output_dir.join(component_file);
+        let Ok(content) = fs.read_to_string(&component_path) else {
+            warn!("Warning: bundled component file '{}' not found", component_path.display());
+            continue;
+        };
+        if !included_contents.insert(content_hash(content.as_bytes())) {
+            continue;
+        }
+        // (a component's source map doesn't describe the bundle, so the bundle doesn't link to it)
+        let content = strip_source_mapping_url(&content);
+        let content = match kind {
+            BundleKind::Css => rebase_css_urls(&content, &component_path, bundle_path),
+            BundleKind::Js => content,
+        };
+        bundle.push_str(&format!("/* {} */\n{}", component_file, content.trim_end()));
+        // (';' so the end of one script can't run on into the next)
+        bundle.push_str(if kind == BundleKind::Js { "\n;\n" } else { "\n" });
+    }
+    Ok(bundle)
+}
+
+/// A page, its component / bundle tags, and the component files it uses (by kind).
+struct PageAssets {
+    html_path: PathBuf,
+    html: String,
+    tags: Vec<AssetTag>,
+    components: BTreeMap<BundleKind, Vec<String>>,
+}
+
+/// Returns a path relative to the output directory, with '/' separators.
+fn relative_path(path: &Path, output_dir: &Path) -> String {
+    path.strip_prefix(output_dir).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Returns the bundle name for a set of component files; the same set always gets the same name.
+pub fn bundle_name(component_files: &[String], kind: BundleKind) -> String {
+    format!("{}.{}", &content_hash(component_files.join("\n").as_bytes())[..8], kind.extension())
+}
+
+/// The bundling stage of the build: replaces every page's component <link> / <script> tags with bundles,
+/// and (re)writes every bundle. Pages that were bundled in an earlier build are re-bundled too (using the bundle record),
+/// so changes to components / the shared bundle reach every page.
+///
+/// # Arguments
+/// * `fs` - Where the website is. e.g. &DiskFileSystem
+/// * `output_dir` - The compiled website, e.g. 'actual-website-do-not-edit'.
+/// * `mode` - Per page bundles only, or a shared bundle as well.
+/// * `compilation_order` - The component folders in dependency order, relative to the output directory (see `sort_into_dependency_order`).
+pub fn bundle_component_assets(fs: &dyn FileSystem, output_dir: &Path, mode: BundleMode, compilation_order: &[String]) -> Result<BundleSummary> {
+    let mut summary = BundleSummary::default();
+    let bundle_dir = output_dir.join(BUNDLE_DIRECTORY);
+    let record_path = bundle_dir.join(BUNDLE_RECORD_FILE);
+    let old_record: BundleRecord = match fs.read_to_string(&record_path) {
+        Ok(json) => serde_json::from_str(&json).with_context(|| format!("Failed to read the bundle record '{}'", record_path.display()))?,
+        Err(_) => BundleRecord::default(),
+    };
+
+    // 1) The component files of every page (whether linked directly, or via an earlier build's bundle)
+    let html_files: Vec<PathBuf> = fs.walk_files(output_dir)
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+        .collect();
+
+    let mut pages: Vec<PageAssets> = Vec::new();
+    for html_path in html_files {
+        let html = fs.read_to_string(&html_path).with_context(|| format!("Failed to read '{}'", html_path.display()))?;
+        let tags = find_bundleable_tags(&html, &html_path, output_dir);
+        if tags.is_empty() {
+            continue;
+        }
+        // A recompiled page has the component tags; a page bundled by an earlier build has bundle tags instead
+        let page_key = relative_path(&html_path, output_dir);
+        let linked_components: Vec<String> = tags.iter().filter(|tag| tag.path.starts_with(COMPONENT_OUTPUT_DIRECTORY)).map(|tag| tag.path.clone()).collect();
+        let component_files: Vec<String> = if !linked_components.is_empty() {
+            linked_components
+        } else if let Some(files) = old_record.pages.get(&page_key) {
+            files.clone()
+        } else {
+            tags.iter().filter_map(|tag| old_record.bundles.get(tag.path.trim_start_matches(&format!("{}/", BUNDLE_DIRECTORY)))).flatten().cloned().collect()
+        };
+
+        let mut components: BTreeMap<BundleKind, Vec<String>> = BTreeMap::new();
+        for file in component_files {
+            let kind = if file.ends_with(".css") { BundleKind::Css } else { BundleKind::Js };
+            let page_files = components.entry(kind).or_default();
+            if !page_files.contains(&file) {
+                page_files.push(file);
+            }
+        }
+        pages.push(PageAssets { html_path, html, tags, components });
+    }
+
+    // 2) Which components go in the shared bundle
+    let shared_files: BTreeSet<String> = match mode {
+        BundleMode::Page => BTreeSet::new(),
+        BundleMode::Shared { min_pages } => {
+            let mut page_counts: BTreeMap<&String, usize> = BTreeMap::new();
+            for page in &pages {
+                for file in page.components.values().flatten() {
+                    *page_counts.entry(file).or_default() += 1;
+                }
+            }
+            page_counts.into_iter().filter(|(_, count)| *count >= min_pages.max(2)).map(|(file, _)| file.clone()).collect()
+        }
+    };
+
+    // 3) Rewrite each page to load its bundles
+    let mut new_record = BundleRecord::default();
+    for PageAssets { html_path, html, tags, components } in &pages {
+        let mut bundle_tags = String::new();
+        for (kind, files) in components {
+            let (mut shared, mut own): (Vec<String>, Vec<String>) = files.iter().cloned().partition(|file| shared_files.contains(file));
+            sort_into_dependency_order(&mut shared, compilation_order);
+            sort_into_dependency_order(&mut own, compilation_order);
+
+            for (name, bundle_files) in [(format!("{}.{}", SHARED_BUNDLE_NAME, kind.extension()), shared), (bundle_name(&own, *kind), own)] {
+                if bundle_files.is_empty() {
+                    continue;
+                }
+                let bundle_path = bundle_dir.join(&name);
+                let url = pathdiff::diff_paths(&bundle_path, html_path.parent().unwrap_or(Path::new(""))).unwrap_or(bundle_path);
+                bundle_tags.push_str(&kind.tag(&url.to_string_lossy().replace('\\', "/")));
+                bundle_tags.push('\n');
+                // (the shared bundle has every shared component, not just the ones this page uses)
+                let record_files = if name.starts_with(SHARED_BUNDLE_NAME) {
+                    shared_files.iter().filter(|file| file.ends_with(&format!(".{}", kind.extension()))).cloned().collect()
+                } else {
+                    bundle_files
+                };
+                new_record.bundles.insert(name, record_files);
+            }
+        }
+
+        new_record.pages.insert(relative_path(html_path, output_dir), components.values().flatten().cloned().collect());
+
+        // The bundles go where the first component tag was
+        let mut new_html = String::with_capacity(html.len());
+        let mut last_end = 0;
+        for (index, tag) in tags.iter().enumerate() {
+            new_html.push_str(&html[last_end..tag.start]);
+            if index == 0 {
+                new_html.push_str(&bundle_tags);
+            }
+            last_end = tag.end;
+        }
+        new_html.push_str(&html[last_end..]);
+
+        if new_html != *html {
+            fs.write(html_path, new_html.as_bytes()).with_context(|| format!("Failed to write '{}'", html_path.display()))?;
+        }
+        summary.pages_bundled += 1;
+    }
+
+    // 4) Write the bundles, and remove the ones no page uses any more
+    if !new_record.bundles.is_empty() {
+        fs.create_dir_all(&bundle_dir).with_context(|| format!("Failed to create '{}'", bundle_dir.display()))?;
+    }
+    for (name, files) in &new_record.bundles {
+        let bundle_path = bundle_dir.join(name);
+        let kind = if name.ends_with(".css") { BundleKind::Css } else { BundleKind::Js };
+        let mut ordered_files = files.clone();
+        sort_into_dependency_order(&mut ordered_files, compilation_order);
+        let content = build_bundle(fs, output_dir, &bundle_path, kind, &ordered_files)?;
+        if fs.read_to_string(&bundle_path).ok().as_deref() != Some(content.as_str()) {
+            fs.write(&bundle_path, content.as_bytes()).with_context(|| format!("Failed to write '{}'", bundle_path.display()))?;
+        }
+        summary.bundles_written += 1;
+    }
+    for name in old_record.bundles.keys().filter(|name| !new_record.bundles.contains_key(*name)) {
+        let _ = fs.remove_file(&bundle_dir.join(name));
+        summary.bundles_removed += 1;
+    }
+    if !new_record.bundles.is_empty() || fs.exists(&record_path) {
+        fs.write(&record_path, (serde_json::to_string_pretty(&new_record)? + "\n").as_bytes()).with_context(|| format!("Failed to write '{}'", record_path.display()))?;
+    }
+
+    Ok(summary)
+}
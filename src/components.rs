@@ -1,21 +1,21 @@
-use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::io::{self, Write};
-use std::process::Command;
-use anyhow::{anyhow, Result};
+use std::time::SystemTime;
+use std::io;
+use anyhow::Result;
+use log::{debug, error, info, warn};
 use regex::{Regex};
 use std::collections::HashSet;
-use walkdir::WalkDir;
+use crate::config::PlaceholderSettings;
+use crate::vfs::{CommandRunner, FileSystem};
 
 // === DETERMINING WHICH FILES TO UPDATE ===
 
 /// Function to read the last compile time from the hidden file
 /// usage: Add to the top of the compilation script:
-/// let time = read_last_compile_time(last_compile_time_file);
-pub fn read_last_compile_time(last_compile_time_file: &str) -> u64 {
-    if Path::new(last_compile_time_file).exists() {
-        let content: String = fs::read_to_string(last_compile_time_file).unwrap_or_else(|_| "0".to_string());
+/// let time = read_last_compile_time(&DiskFileSystem, last_compile_time_file);
+pub fn read_last_compile_time(fs: &dyn FileSystem, last_compile_time_file: &str) -> u64 {
+    if fs.exists(Path::new(last_compile_time_file)) {
+        let content: String = fs.read_to_string(Path::new(last_compile_time_file)).unwrap_or_else(|_| "0".to_string());
         let parsed_time: u64 = content.trim().parse::<u64>().unwrap_or(0);
         parsed_time
     } else {
@@ -25,37 +25,34 @@ pub fn read_last_compile_time(last_compile_time_file: &str) -> u64 {
 
 /// Function to update the last compile time in the hidden file
 /// usage: Add to the end of the compilation script:
-/// update_last_compile_time(time, last_compile_time_file).unwrap();
-pub fn update_last_compile_time(the_time: u64, last_compile_time_file: &str) -> io::Result<()> {
-    let mut file: fs::File = fs::File::create(last_compile_time_file)?;
-    writeln!(file, "{}", the_time)?;
-    Ok(())
+/// update_last_compile_time(&DiskFileSystem, time, last_compile_time_file).unwrap();
+pub fn update_last_compile_time(fs: &dyn FileSystem, the_time: u64, last_compile_time_file: &str) -> io::Result<()> {
+    fs.write(Path::new(last_compile_time_file), format!("{}\n", the_time).as_bytes())
 }
 
-/// Function to get all files in the source directory
-pub fn files_in_source_dir(dir: &str) -> Result<Vec<PathBuf>> {
-    let output = Command::new("find").arg(dir).arg("-type").arg("f").output()?;
-    let files_vec: Vec<PathBuf> = String::from_utf8_lossy(&output.stdout).lines().map(|line| PathBuf::from(line)).collect();
+/// Function to get all files in the source directory (of `fs`, e.g. &DiskFileSystem)
+pub fn files_in_source_dir(fs: &dyn FileSystem, dir: &str) -> Result<Vec<PathBuf>> {
+    let files_vec: Vec<PathBuf> = fs.walk_files(Path::new(dir));
     Ok(files_vec)
 }
 
 /// Function to filter files by modification time and return only the newer ones.
 ///
 /// # Usage
-/// `let newly_modified_files = only_modify_time_newer_than_last_compile_time(&all_files, last_compile_time)?;`
+/// `let newly_modified_files = only_modify_time_newer_than_last_compile_time(&DiskFileSystem, &all_files, last_compile_time)?;`
 ///
 /// # Parameters
+/// * `fs`: Where the files are. Example: `&DiskFileSystem`
 /// * `all_files`: A list of file paths to check. Example: `&vec![PathBuf::from("src/main.rs")]`
 /// * `last_compile_time`: The timestamp to compare file modification times against. 
-pub fn only_modify_time_newer_than_last_compile_time( all_files: &Vec<PathBuf>, last_compile_time: u64) -> Result<Vec<PathBuf>> {
+pub fn only_modify_time_newer_than_last_compile_time(fs: &dyn FileSystem, all_files: &Vec<PathBuf>, last_compile_time: u64) -> Result<Vec<PathBuf>> {
     let mut newer_files: Vec<PathBuf> = Vec::new();
 
     for file_path in all_files {
-        if file_path.is_file() {
-            // Get file metadata, which includes modification time.
+        if fs.is_file(file_path) {
+            // Get the file's modification time.
             // The '?' operator will propagate any errors, like if the file doesn't exist.
-            let metadata: fs::Metadata = fs::metadata(&file_path)?;
-            let file_mod_time = metadata.modified()?.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+            let file_mod_time = fs.modified(file_path)?.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
 
             // If file modification time is newer than last compile time, add to the list
             if file_mod_time > last_compile_time {
@@ -90,55 +87,59 @@ pub fn get_relative_path(filepath: &Path) -> String {
     }
 }
 
+/// The relative path from a file back to the root of the website (the folder it is published in), as `../` segments.
+/// Unlike `get_relative_path` (which assumes every file is two folders down), it is worked out from where the file actually is,
+/// so it is right at any depth (e.g. a page with a permalink, or an output directory of build/site).
+/// e.g. 'build/site/blog/post.html' in 'build/site' -> '../'
+/// (A file that isn't in `root_dir` is taken to be relative to the root. e.g. 'blog/social/post.html' -> '../../')
+pub fn relative_path_to_root(file: &Path, root_dir: &Path) -> String {
+    let relative = file.strip_prefix(root_dir).unwrap_or(file);
+    "../".repeat(relative.components().filter(|c| matches!(c, std::path::Component::Normal(_))).count().saturating_sub(1))
+}
 
-// === REPLACING COMPONENT PLACEHOLDERS ===
-
-/// Searches a file for a placeholder and replaces it with a calculated relative path.
-/// This is a direct port of the `replace_root_placeholder_with_relative_path_new` shell function.
-/// @param search_regex = a regex that will match '<root>'. E.g. If user has imported an image: <root>/shared/images/img1.png
-/// @param root_placeholder = the actual placeholder. (i.e. '<root>')
-/// @param target_file = the path to the target file (either relative or absolute if it starts with '/')
-/// @what this does => Replaces '<root>' with the '../../..' etc. string required to get from that file to the root of the project, if using 'cd' in the terminal
-pub fn replace_root_placeholder_with_relative_path_new( search_text: &str, root_placeholder: &str, target_file: &Path,) -> Result<()> {
-    if !target_file.is_file() {
-        println!("-> Warning! the target file: {} is not a file...", target_file.display());
-        return Ok(()); // Silently skip if the file doesn't exist.
+/// Resolves a relative url (e.g. an href / src) against the directory of the file it is in, without touching the file system.
+/// Any ?query or #fragment is dropped.
+/// e.g. 'site/blog/post.html' + '../shared/global.css?v=2' -> 'site/shared/global.css'
+pub fn resolve_relative_reference(from_file: &Path, reference: &str) -> PathBuf {
+    let reference = reference.split(['?', '#']).next().unwrap_or_default();
+    let joined = from_file.parent().unwrap_or(Path::new("")).join(reference);
+
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                resolved.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => resolved.push(other),
+        }
     }
+    resolved
+}
 
-    // Read raw bytes
-    let bytes = fs::read(target_file)?;
-    let content = match String::from_utf8(bytes) {
-        Ok(s) => s,
-        Err(_) => {
-            // Not valid UTF-8 → probably binary (like .ico)
-            return Ok(());
-        }
-    };
+// === REPLACING COMPONENT PLACEHOLDERS ===
 
-    // Return early if no match (grep -F style)
+/// Replaces a file's root placeholders with the relative path back to the root.
+/// This is a port of the `replace_root_placeholder_with_relative_path_new` shell function, on a file's content (in memory).
+/// @param search_text = the text that shows the content has root placeholders. E.g. If user has imported an image: <root>/shared/images/img1.png
+/// @param root_placeholder = the actual placeholder. (i.e. '<root>')
+/// @param relative_root = the relative path from the file the content is written to, back to the root (e.g. "../", see `relative_path_to_root`)
+/// @what this does => Replaces '<root>/' with the '../../..' etc. string required to get from that file to the root of the project, if using 'cd' in the terminal
+pub fn replace_root_placeholder_in_content(content: &str, search_text: &str, root_placeholder: &str, relative_root: &str) -> String {
     if !content.contains(search_text) {
-        return Ok(());
+        return content.to_string();
     }
 
-    // Append dummy to ensure same segment behavior as original
-    let path_with_slash = target_file.join("");
-    let sub_text = get_relative_path(&path_with_slash);
-
     let final_placeholder = format!("{}/", root_placeholder);
-    let new_content = content.replace(&final_placeholder, &sub_text);
-    fs::write(target_file, new_content)?;
-
-    Ok(())
+    content.replace(&final_placeholder, relative_root)
 }
 
 
 // === JAVASCRIPT COMPONENTS ===
-pub fn add_reusable_javascript_components( javascript_file: &Path, root_folder: &Path, shared_code_folder: &str, re_start: &str, re_end: &str,) -> Result<()> {
-    if !javascript_file.is_file() {
-        return Ok(());
-    }
-
-    let mut content = fs::read_to_string(javascript_file)?;
+/// Pastes the shared code of each <r-name> tag in some typescript in place of the tag. (e.g. <r-utils> -> edit-me/shared/code/utils.ts)
+/// (The shared code is read from `fs`, e.g. &DiskFileSystem)
+pub fn add_reusable_javascript_components_to_content(fs: &dyn FileSystem, content: &str, root_folder: &Path, shared_code_folder: &str, re_start: &str, re_end: &str) -> Result<String> {
+    let mut content = content.to_string();
     let re = Regex::new(&format!(r"(?m){}(.*?){}", regex::escape(re_start), regex::escape(re_end)))?;
 
     let captures: Vec<(String, String)> = re.captures_iter(&content)
@@ -148,58 +149,46 @@ pub fn add_reusable_javascript_components( javascript_file: &Path, root_folder:
     for (full_tag, component_name) in captures {
         let component_path = root_folder.join("shared").join(shared_code_folder).join(format!("{}.ts", component_name));
 
-        if !component_path.is_file() {
-            eprintln!("ERROR!!!!!!! Associated file ({}) for component '{}' not found.", component_path.display(), full_tag);
-            report_missing_file_error(&component_path, &component_name, &full_tag);
+        if !fs.is_file(&component_path) {
+            error!("ERROR!!!!!!! Associated file ({}) for component '{}' not found.", component_path.display(), full_tag);
+            report_missing_file_error(fs, &component_path, &component_name, &full_tag);
             continue;
         }
 
-        let component_file_content = fs::read_to_string(component_path)?;
+        let component_file_content = fs.read_to_string(&component_path)?;
         content = content.replace(&full_tag, &component_file_content);
     }
 
-    fs::write(javascript_file, content)?;
-    Ok(())
+    Ok(content)
 }
 
-fn report_missing_file_error(file_path: &std::path::Path, component_name: &str, full_tag: &str) {
+fn report_missing_file_error(fs: &dyn FileSystem, file_path: &std::path::Path, component_name: &str, full_tag: &str) {
 
     if let Some(parent_dir) = file_path.parent() {
-        if parent_dir.exists() {
-            eprintln!("ERROR!!!!!!! Associated file ({}) for component '{}' not found. ({})", 
+        if fs.exists(parent_dir) {
+            error!("ERROR!!!!!!! Associated file ({}) for component '{}' not found. ({})", 
                      file_path.display(), component_name, full_tag);
             
             // List contents of parent directory
-            if let Ok(entries) = std::fs::read_dir(parent_dir) {
-                eprintln!("Contents of parent directory {}:", parent_dir.display());
-                for entry in entries.flatten() {
-                    eprintln!("  {}", entry.file_name().to_string_lossy());
+            if let Ok(entries) = fs.read_dir(parent_dir) {
+                warn!("Contents of parent directory {}:", parent_dir.display());
+                for entry in entries {
+                    warn!("  {}", entry.file_name().unwrap_or_default().to_string_lossy());
                 }
             }
         } else {
-            eprintln!("ERROR!!!!!!! Parent dir {} doesn't exist either!", parent_dir.display());
+            error!("ERROR!!!!!!! Parent dir {} doesn't exist either!", parent_dir.display());
         }
     }
-
-
-    println!("/shared/code");
-    match std::fs::read_dir("actual-website-do-not-edit/shared/code") { Ok(entries) => entries.flatten().for_each(|e| println!("{}", e.file_name().to_string_lossy())), Err(e) => println!("Error: {}", e) }
-    println!("/shared/");
-    match std::fs::read_dir("actual-website-do-not-edit/shared") { Ok(entries) => entries.flatten().for_each(|e| println!("{}", e.file_name().to_string_lossy())), Err(e) => println!("Error: {}", e) }
-    println!("/");
-    match std::fs::read_dir("actual-website-do-not-edit") { Ok(entries) => entries.flatten().for_each(|e| println!("{}", e.file_name().to_string_lossy())), Err(e) => println!("Error: {}", e) }
-    println!(".");
-    match std::fs::read_dir(".") { Ok(entries) => entries.flatten().for_each(|e| println!("{}", e.file_name().to_string_lossy())), Err(e) => println!("Error: {}", e) }
 }
 
 
 // === HTML COMPONENTS ===
 
-/// Recursively finds and replaces HTML component tags with their corresponding HTML, CSS, and JS.
-pub fn replace_html_component_placeholders( html_file: &Path, src_directory: &Path, re_start: &str, re_end: &str, root_placeholder: &str, template_prefix: &str, none_prefix: &str, re_param_s: &str, re_param_e: &str, display_name_placeholder_start: &str, display_name_placeholder_end: &str) -> Result<()> {
-    if !html_file.is_file() { return Ok(()); }
-
-    let mut content = fs::read_to_string(html_file)?;
+/// Recursively finds and replaces HTML component tags with their corresponding HTML, CSS, and JS, in a file's content (in memory).
+/// (The components are read from `fs`, e.g. &DiskFileSystem. The tags, parameters, ... are written with `placeholders`, the site's [placeholders])
+pub fn replace_html_component_placeholders_in_content(fs: &dyn FileSystem, mut content: String, src_directory: &Path, placeholders: &PlaceholderSettings) -> Result<String> {
+    let PlaceholderSettings { component_start: re_start, component_end: re_end, root: root_placeholder, template_prefix, none_prefix, param_start: re_param_s, param_end: re_param_e, display_name_start: display_name_placeholder_start, display_name_end: display_name_placeholder_end, .. } = placeholders;
     let mut collected_params = std::collections::HashMap::new(); // This will store the parameters across iterations.
     
     let component_regex = Regex::new(&format!(r"{}([a-zA-Z0-9_-]+)([^>]*){}", regex::escape(re_start), regex::escape(re_end)))?; // Regex to find a component tag, its name, and the full parameters string.
@@ -234,40 +223,40 @@ pub fn replace_html_component_placeholders( html_file: &Path, src_directory: &Pa
         let associated_folder = PathBuf::from("shared/reusables").join(&component_name);
         let associated_folder_in_src = src_directory.join(&associated_folder);
 
-        if !associated_folder_in_src.is_dir() {
-            eprintln!("ERROR! Associated folder for component '{}' not found. Expected a folder: {}", component_name, associated_folder_in_src.display());
+        if !fs.is_dir(&associated_folder_in_src) {
+            error!("ERROR! Associated folder for component '{}' not found. Expected a folder: {}", component_name, associated_folder_in_src.display());
             content = content.replace(&full_tag, &format!("<!-- ERROR: Component '{}' directory not found. -->", component_name));
 
 
             // -- verbosely output parent directory for debugging --
             if let Some(parent) = associated_folder_in_src.parent() {
-                if parent.is_dir() {
-                    eprintln!("Contents of parent folder '{}':", parent.display());
-                    match fs::read_dir(parent) {
+                if fs.is_dir(parent) {
+                    warn!("Contents of parent folder '{}':", parent.display());
+                    match fs.read_dir(parent) {
                         Ok(entries) => {
-                            for entry in entries.flatten() {
-                                eprintln!(" - {}", entry.file_name().to_string_lossy());
+                            for entry in entries {
+                                warn!(" - {}", entry.file_name().unwrap_or_default().to_string_lossy());
                             }
                         }
                         Err(err) => {
-                            eprintln!("Could not read contents of parent folder: {}", err);
+                            warn!("Could not read contents of parent folder: {}", err);
                         }
                     }
                 } else {
-                    eprintln!("Parent folder '{}' does not exist!", parent.display());
+                    warn!("Parent folder '{}' does not exist!", parent.display());
                 }
             } else {
-                eprintln!("No parent folder found for '{}'", associated_folder_in_src.display());
+                warn!("No parent folder found for '{}'", associated_folder_in_src.display());
             }
             // -----------------------------------------------------
             continue;
         }
 
         let associated_html_in_src = associated_folder_in_src.join(format!("{}.html", component_name));
-        let mut html_to_insert = if associated_html_in_src.is_file() {
-            fs::read_to_string(&associated_html_in_src)?
+        let mut html_to_insert = if fs.is_file(&associated_html_in_src) {
+            fs.read_to_string(&associated_html_in_src)?
         } else {
-            eprintln!("ERROR! Associated HTML file for component '{}' not found", component_name);
+            error!("ERROR! Associated HTML file for component '{}' not found", component_name);
             format!("<!-- ERROR: HTML file for Component '{}' not found. -->", component_name)
         };
         
@@ -385,7 +374,7 @@ pub fn replace_html_component_placeholders( html_file: &Path, src_directory: &Pa
             
             // Check for the UNCOMPILED .scss file.
             let associated_scss_in_src = associated_folder_in_src.join(format!("{}.scss", &component_name));
-            if associated_scss_in_src.exists() {
+            if fs.exists(&associated_scss_in_src) {
                 // Create a link to the final COMPILED .css file.
                 let css_path = format!("{}/{}.css", associated_folder.to_string_lossy(), component_name);
                 let css_link = format!("<link rel=\"stylesheet\" href=\"{}/{}\">\n", root_placeholder, css_path);
@@ -394,7 +383,7 @@ pub fn replace_html_component_placeholders( html_file: &Path, src_directory: &Pa
 
             // Check for the UNCOMPILED .ts file.
             let associated_ts_in_src = associated_folder_in_src.join(format!("{}.ts", &component_name));
-            if associated_ts_in_src.exists() {
+            if fs.exists(&associated_ts_in_src) {
                 // Create a link to the final COMPILED .js file.
                 let js_path = format!("{}/{}.js", associated_folder.to_string_lossy(), component_name);
                 let js_link = format!("<script defer src=\"{}/{}\"></script>\n", root_placeholder, js_path);
@@ -408,8 +397,7 @@ pub fn replace_html_component_placeholders( html_file: &Path, src_directory: &Pa
         content = content.replace(&full_tag, &processed_html);
     }
 
-    fs::write(html_file, content)?;
-    Ok(())
+    Ok(content)
 }
 
 
@@ -473,7 +461,9 @@ fn extract_display_name_from_filepath(filepath: &str) -> String {
 ///
 /// ```no_run
 /// // Corresponds to shell: compile_scss_file "$scss_source" "$scss_output"
-/// match compile_scss_file("path/to/style.scss", "path/to/style.css") {
+/// use websiteCompiler::components::compile_scss_file;
+/// use websiteCompiler::vfs::{DiskFileSystem, SystemCommandRunner};
+/// match compile_scss_file(&DiskFileSystem, &SystemCommandRunner, "path/to/style.scss", "path/to/style.css", false, false) {
 ///     Ok(_) => println!("Compilation successful!"),
 ///     Err(e) => eprintln!("Error: {}", e),
 /// }
@@ -481,39 +471,46 @@ fn extract_display_name_from_filepath(filepath: &str) -> String {
 ///
 /// # Arguments
 ///
+/// * `fs` - Where the files are. e.g. &DiskFileSystem
+/// * `commands` - What runs `sass`. e.g. &SystemCommandRunner (the real sass reads and writes these paths on disk, whatever `fs` is)
 /// * `scss_source` - The path to the source SCSS file.
 /// * `css_output` - The path where the final CSS file will be saved.
+/// * `compressed` - Have sass write compressed (minified) css. (Used for production builds; see minify.rs)
+/// * `source_map` - Have sass write a source map next to the css (e.g. style.css.map). (Used for dev builds; see source_maps.rs)
 ///
 /// # Returns
 ///
 /// * `Ok(())` if compilation is successful.
 /// * `Err(String)` with an error message if the source file is not found or if the `sass` command fails.
-pub fn compile_scss_file(scss_source: &str, css_output: &str) -> Result<(), String> {
+pub fn compile_scss_file(fs: &dyn FileSystem, commands: &dyn CommandRunner, scss_source: &str, css_output: &str, compressed: bool, source_map: bool) -> Result<(), String> {
     let source_path = Path::new(scss_source);
     
     // check if the source file exists
-    if !source_path.exists() {
+    if !fs.exists(source_path) {
         let warning_msg = format!("warning: source scss file not found: {}", scss_source);
-        println!("{}", warning_msg);
+        info!("{}", warning_msg);
         // In shell, this was `return 1`. In Rust, we return an Err.
         return Err(warning_msg);
     }
     
-    println!("compiling scss: {} -> {}", scss_source, css_output);
+    info!("compiling scss: {} -> {}", scss_source, css_output);
     
     // create directory for the css file if it doesn't exist
     // `Path::parent` returns the directory containing the file.
     if let Some(parent_dir) = Path::new(css_output).parent() {
-        fs::create_dir_all(parent_dir).map_err(|e| format!("failed to create output directory: {}", e))?;
+        fs.create_dir_all(parent_dir).map_err(|e| format!("failed to create output directory: {}", e))?;
     }
     
     // compile scss to css by calling the external `sass` command
-    let output = Command::new("sass").arg(scss_source).arg(css_output).output().map_err(|e| format!("failed to execute sass command. Is `sass` installed and in your PATH? Error: {}", e))?;
+    let style = if compressed { "--style=compressed" } else { "--style=expanded" };
+    let source_map_option = if source_map { "--source-map" } else { "--no-source-map" };
+    let args: Vec<String> = [style, source_map_option, scss_source, css_output].iter().map(|arg| arg.to_string()).collect();
+    let output = commands.run("sass", &args).map_err(|e| format!("failed to execute sass command. Is `sass` installed and in your PATH? Error: {}", e))?;
 
     // Check if the command executed successfully.
-    if !output.status.success() {
-        let error_msg = format!( "error compiling scss file: {}\nSass Error Output:\n{}", scss_source, String::from_utf8_lossy(&output.stderr));
-        eprintln!("{}", error_msg);
+    if !output.success {
+        // (not printed here: the caller prints it, with the paths pointed back at the edit-me files; see scss.rs)
+        let error_msg = format!( "error compiling scss file: {}\nSass Error Output:\n{}", scss_source, output.stderr);
         return Err(error_msg);
     }
     
@@ -528,7 +525,9 @@ pub fn compile_scss_file(scss_source: &str, css_output: &str) -> Result<(), Stri
 ///
 /// ```no_run
 /// // Corresponds to shell: compile_typescript_file "$ts_source" "$js_output"
-/// match compile_typescript_file("path/to/app.ts", "path/to/app.js") {
+/// use websiteCompiler::components::compile_typescript_file;
+/// use websiteCompiler::vfs::{DiskFileSystem, SystemCommandRunner};
+/// match compile_typescript_file(&DiskFileSystem, &SystemCommandRunner, "path/to/app.ts", "path/to/app.js", false) {
 ///     Ok(_) => println!("Compilation successful!"),
 ///     Err(e) => eprintln!("Error: {}", e),
 /// }
@@ -536,47 +535,54 @@ pub fn compile_scss_file(scss_source: &str, css_output: &str) -> Result<(), Stri
 ///
 /// # Arguments
 ///
+/// * `fs` - Where the files are. e.g. &DiskFileSystem
+/// * `commands` - What runs `tsc`. e.g. &SystemCommandRunner (the real tsc reads and writes these paths on disk, whatever `fs` is)
 /// * `ts_source` - The path to the source TypeScript file.
 /// * `js_output` - The path where the final JavaScript file will be saved.
+/// * `source_map` - Have tsc write a source map next to the javascript (e.g. app.js.map). (Used for dev builds; see source_maps.rs)
 ///
 /// # Returns
 ///
 /// * `Ok(())` if compilation and file removal are successful.
 /// * `Err(String)` with an error message if any step fails.
-pub fn compile_typescript_file(ts_source: &str, js_output: &str) -> Result<(), String> {
+pub fn compile_typescript_file(fs: &dyn FileSystem, commands: &dyn CommandRunner, ts_source: &str, js_output: &str, source_map: bool) -> Result<(), String> {
     let source_path = Path::new(ts_source);
 
     // check if the source file exists
-    if !source_path.is_file() {
+    if !fs.is_file(source_path) {
         let warning_msg = format!("warning: source typescript file not found: {}", ts_source);
-        println!("{}", warning_msg);
+        info!("{}", warning_msg);
         return Err(warning_msg);
     }
 
-    println!("compiling and minifying TypeScript: {} -> {}", ts_source, js_output);
+    info!("compiling and minifying TypeScript: {} -> {}", ts_source, js_output);
 
     // create directory for the js file if it doesn't exist
     if let Some(parent_dir) = Path::new(js_output).parent() {
-        fs::create_dir_all(parent_dir).map_err(|e| format!("failed to create output directory: {}", e))?;
+        fs.create_dir_all(parent_dir).map_err(|e| format!("failed to create output directory: {}", e))?;
     }
 
     // compile and minify with tsc
-    let output = Command::new("tsc").arg("--target").arg("es2015").arg(ts_source).arg("--outfile").arg(js_output).output().map_err(|e| format!("failed to execute tsc command. Is `typescript` installed and in your PATH? Error: {}", e))?;
+    let mut args: Vec<String> = ["--target", "es2015", ts_source, "--outfile", js_output].iter().map(|arg| arg.to_string()).collect();
+    if source_map {
+        args.push("--sourceMap".to_string());
+    }
+    let output = commands.run("tsc", &args).map_err(|e| format!("failed to execute tsc command. Is `typescript` installed and in your PATH? Error: {}", e))?;
 
-    if !output.status.success() {
+    if !output.success {
         // Capture both stdout and stderr for complete error information
-        let stdout_str = String::from_utf8_lossy(&output.stdout);
-        let stderr_str = String::from_utf8_lossy(&output.stderr);
+        let stdout_str = &output.stdout;
+        let stderr_str = &output.stderr;
 
         let mut error_output = String::new();
         if !stdout_str.trim().is_empty() {
-            error_output.push_str(&stdout_str);
+            error_output.push_str(stdout_str);
         }
         if !stderr_str.trim().is_empty() {
             if !error_output.is_empty() {
                 error_output.push('\n');
             }
-            error_output.push_str(&stderr_str);
+            error_output.push_str(stderr_str);
         }
 
         let error_msg = format!(
@@ -584,7 +590,6 @@ pub fn compile_typescript_file(ts_source: &str, js_output: &str) -> Result<(), S
             ts_source, 
             error_output
         );
-        eprintln!("{}", error_msg);
         return Err(error_msg);
     }
     
@@ -612,6 +617,7 @@ pub fn compile_typescript_file(ts_source: &str, js_output: &str) -> Result<(), S
 ///
 /// # Arguments
 ///
+/// * `fs` - Where the files are. e.g. &DiskFileSystem
 /// * `source_dir` - e.g. "/home/john/documents/proj/edit-me"
 /// * `re_start` - e.g. "<r-"
 /// * `re_end` - e.g. ">"
@@ -637,7 +643,7 @@ pub fn compile_typescript_file(ts_source: &str, js_output: &str) -> Result<(), S
 /// - Looks for any html files which import those html files (i.e. assuming the original html was a component), and adds those to the list
 /// - Keeps doing this until its added all files which are linked 
 // Usage: get_all_files_that_need_recompiling "$SOURCE_DIR" "$RE_START" "$RE_END" "$newly_modified_files" "$COMPONENTS_DIR" "all_files_that_are_to_be_compiled"
-pub fn get_all_files_that_need_recompiling( source_dir: &str, re_start: &str, re_end: &str, all_new_files: &HashSet<String>, components_dir: &str,) -> HashSet<String> {
+pub fn get_all_files_that_need_recompiling(fs: &dyn FileSystem, source_dir: &str, re_start: &str, re_end: &str, all_new_files: &HashSet<String>, components_dir: &str,) -> HashSet<String> {
     // TODO refactor to be like:
     // all_html_need_recompiling = ...
     // other_html_files_which_import_any_files_to_be_recompiled = until(notChanged(lenListBefore, lenListAfter) do: allHtmlFiles | doesImportFileBeingRecompiled(all_html_need_recompiling)
@@ -658,12 +664,12 @@ pub fn get_all_files_that_need_recompiling( source_dir: &str, re_start: &str, re
     // Create a regex to find component tags. e.g., <r-.*?>
     let tag_regex = Regex::new(&format!("{}{}{}", regex::escape(re_start), r"(.*?)", regex::escape(re_end))).expect("Failed to create regex.");
 
-    println!("getting all files that need recompiling; splitting the new files into components and other");
+    debug!("getting all files that need recompiling; splitting the new files into components and other");
 
     // === Step 1: Load the current modified list into a variable (HashSet for efficiency) ===
     // Using a HashSet automatically handles duplicates and provides fast lookups.
     let mut current_modified: HashSet<String> = all_new_files.clone();
-    println!("1) current modified: {:?}", current_modified);
+    debug!("1) current modified: {:?}", current_modified);
 
     // ==========================================================
     // 0) Are any of the items in the all_new_files list reusable *html* components?
@@ -674,46 +680,45 @@ pub fn get_all_files_that_need_recompiling( source_dir: &str, re_start: &str, re
 
     if !modified_html_components.is_empty() {
         // === Get a list of all html files in the project ===
-        let walker: walkdir::IntoIter = WalkDir::new(source_dir).into_iter();
-        let html_files: Vec<walkdir::DirEntry> = walker.filter_map(Result::ok).filter(|e| e.file_type().is_file() && e.path().extension().map_or(false, |ext| ext == "html")).collect();
+        let html_files: Vec<PathBuf> = fs.walk_files(Path::new(source_dir)).into_iter().filter(|path| path.extension().is_some_and(|ext| ext == "html")).collect();
 
         // === Step 2: Start an infinite loop to scan and expand the list ===
-        println!("step 2");
+        debug!("step 2");
         loop {
             let before_count:usize = current_modified.len();
 
             // === Step 3: Loop through all HTML files in the source directory ===
             for entry in &html_files {
-                let html_file_path:&Path = entry.path();
+                let html_file_path:&Path = entry;
                 let html_file_str:String = html_file_path.to_string_lossy().to_string();
 
-                println!("\t\t\tdoing step 3:   checking the html file: {}", html_file_str);
+                debug!("\t\t\tdoing step 3:   checking the html file: {}", html_file_str);
 
                 // === Step 4: Skip if it's already in the modified list ===
                 if current_modified.contains(&html_file_str) {
-                     println!("\t\t.. the html file: {} is contained in the current modified list.", html_file_str);
+                     debug!("\t\t.. the html file: {} is contained in the current modified list.", html_file_str);
                      continue;
                 }
                 
-                println!("\tdoing step 4:");
+                debug!("\tdoing step 4:");
 
                 // So at this point, we have a html file that isn't in the list, which might contain a reference to one of the items in the list (if so, this html needs adding to that list as well!)
                 // i.e. this html file may import and use on the reusable html components
                 // === Step 5: Extract all tags that match the reusable component pattern (e.g. <r-...>) ===
 
-                let content : String = match std::fs::read_to_string(html_file_path) {
+                let content : String = match fs.read_to_string(html_file_path) {
                     Ok(c) => c,            // `content` is bound to this string
                     Err(_) => continue,    // skip to next loop iteration
                 };
                 for cap in tag_regex.captures_iter(&content) {
                     let component_tag = &cap[0]; // The full match, e.g., "<r-top-bar>"
-                    println!("\t\t!!!!!! step 5: found a tag: {}", component_tag);
+                    debug!("\t\t!!!!!! step 5: found a tag: {}", component_tag);
 
                     // === GETTING THE NAME OF THAT COMPONENT ===
                     // Strip surrounding whitespace from the tag
                     // The regex capture group already gives us the inner content.
                     let component_tag_contents : &str = &cap[1];
-                    println!("  ....(...) In the file {}, just found a tag... '{}'", html_file_str, component_tag);
+                    debug!("  ....(...) In the file {}, just found a tag... '{}'", html_file_str, component_tag);
 
                     // get the component name, which should be the first word
                     let associated_reusable_name : &str = component_tag_contents.split_whitespace().next().unwrap_or("");
@@ -724,25 +729,25 @@ pub fn get_all_files_that_need_recompiling( source_dir: &str, re_start: &str, re
                     // === GET THE EXPECTED PATH WITHIN THE REUSABLES FOLDER OF THAT COMPONENT ===
                     let associated_reusable_dir_path : String = Path::new(components_dir).join(associated_reusable_name).join(format!("{}.html", associated_reusable_name)).to_string_lossy().to_string();
 
-                    println!("\t\tasoc reus dir: {}", associated_reusable_dir_path);
+                    debug!("\t\tasoc reus dir: {}", associated_reusable_dir_path);
                     
                     // === Step 6: If any component in the modified list matches this one, add this HTML file ===
                     //             i.e. The file being added *must* be a *html* file, which imports one of the reusable html components which has been modified!
-                    println!("6) ??? checking whether the current modified: {:?} contains the assoc reus...", current_modified);
+                    debug!("6) ??? checking whether the current modified: {:?} contains the assoc reus...", current_modified);
                     if modified_html_components.contains(&associated_reusable_dir_path) {
-                        println!("!!!!!!!!!!!!!FOUND A NEW FILE !!!!!!!!!!!!!!!!");
-                        println!("Adding the file: {} to the list of files to recompile, since it uses the modified component '{}'", html_file_str, associated_reusable_name);
+                        debug!("!!!!!!!!!!!!!FOUND A NEW FILE !!!!!!!!!!!!!!!!");
+                        debug!("Adding the file: {} to the list of files to recompile, since it uses the modified component '{}'", html_file_str, associated_reusable_name);
                         current_modified.insert(html_file_str.clone());
 
                         // Insert into modified_html_components only if it belongs in components_dir
                         if html_file_str.contains(components_dir) {
-                            println!("Also a component! adding to the components list...");
+                            debug!("Also a component! adding to the components list...");
                             modified_html_components.insert(html_file_str.clone());
                         }
-                        println!("  ()()()()() updated modified after adding: {:?}", current_modified);
+                        debug!("  ()()()()() updated modified after adding: {:?}", current_modified);
                         break; // Stop checking this file — it's already added
                     }
-                    println!();
+                    debug!("");
                 }
             }
             
@@ -757,22 +762,22 @@ pub fn get_all_files_that_need_recompiling( source_dir: &str, re_start: &str, re
 
             // === Step 7 & 8: If no new files were added, stop looping ===
             // We just check if the size has changed.
-            println!(" 7777777777777777777777777777777777777777777777777");
-            println!("updated modified count: {}", current_modified.len());
-            println!("current modified count: {}", before_count);
+            debug!(" 7777777777777777777777777777777777777777777777777");
+            debug!("updated modified count: {}", current_modified.len());
+            debug!("current modified count: {}", before_count);
 
             if current_modified.len() == before_count {
                 break;
             }
 
-            println!(" 8888888888888888888888888888888888888888888888888");
-            println!("8) the updated modified is not the same as the current modified, looping again.");
+            debug!(" 8888888888888888888888888888888888888888888888888");
+            debug!("8) the updated modified is not the same as the current modified, looping again.");
             // === Step 9: Otherwise, continue the loop with the updated list ===
             // No need to copy variables; the loop just continues with the modified `current_modified` HashSet.
         }
     }
 
-    println!("()()()()()() returning the current modified: {:?}", current_modified);
+    debug!("()()()()()() returning the current modified: {:?}", current_modified);
 
     // // === Step 10: Output the final list ===
     // // Convert the HashSet back to a newline-separated string.
@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use crate::translations::line_and_column;
+use crate::vfs::FileSystem;
+
+// === SITE CONFIGURATION (site.toml) ===
+// Where a site's folders are, the placeholders its files use, and how it is built, so the same compiler can build any site.
+// The project root is the folder site.toml is in (or, for a site without one, the folder its edit-me folder is in). It is found
+// by looking in the working directory (or the folder given with `--project`), then its parent, and so on up.
+// Every path (in site.toml, and the compiler's own files, e.g. .last_compiled) is relative to the project root, so a build
+// run from anywhere inside the project is the same as one run from its root.
+// Every key is optional (the defaults are the ones this website uses), and a key that isn't one of these is an error:
+//
+//      [site]
+//      base-url = "https://example.com"        (the address the website is published at)
+//      default-language = "en"                 (the language of un-suffixed files, e.g. ugly-people.md vs ugly-people.jp.md)
+//
+//      [paths]
+//      source-dir = "edit-me"                  (pages/ and shared/ (with shared/reusables/ and shared/code/) are inside it)
+//      output-dir = "actual-website-do-not-edit"
+//      page-text-dir = "edit-me/shared/page_text"
+//      shared-code-folder = "code"             (the folder of shared/ that <r-name> code is pasted from into typescript)
+//      blog-folder = "blog"                    (html published in this folder of the output is a blog post)
+//      blog-template-top = "edit-me/shared/reusables/template-blog-top/template-blog-top.html"
+//      blog-template-bottom = "edit-me/shared/reusables/template-blog-bottom/template-blog-bottom.html"
+//      templates-dir = "templates"             (what `new` makes pages / components / blog posts from; see scaffold.rs)
+//
+//      [placeholders]
+//      root = "<root>"                         (replaced with the relative path to the website's root)
+//      component-start = "<r-"                 component-end = ">"                 (<r-top-bar>)
+//      param-start = "{"                       param-end = "}"                     ({title} in a component)
+//      display-name-start = "["               display-name-end = "]"              ([image] in a component)
+//      template-prefix = "@template:"          none-prefix = "@none:"              (<r-x inner=@template:side-bar>)
+//      scss-import-start = "@use"
+//
+//      [build]                                 (the same as the command line flags, which override them)
+//      production = false      github-pages = false      hash-assets = false      critical-css = false
+//      check-links = false     (look up every internal link of the built website; see link_checker.rs)
+//      bundle = "none"         (or "page" / "shared")
+//      scss-compiler = "auto"  (or "built-in" / "sass")
+//      source-maps = "external"  (or "hidden" / "none"; the default for production builds is "none")
+//      links = "relative"      (or "absolute" (/shared/...) / "url" (https://example.com/shared/..., from base-url); see links.rs)
+//
+//      [profiles.release]                      (picked with `--profile release`; overrides [build])
+//      production = true
+//      hash-assets = true
+//
+//      [permalinks]                            (where pages are published: a folder of the output -> a pattern; see routing.rs)
+//      "blog" = "/blog/:category/:slug/"
+//
+//      [github-pages]                          (used by `--github-pages` and `deploy`; see github_pages.rs)
+//      base-path = "/repo"                     (for a project site, user.github.io/repo/; by default the path of base-url)
+//      cname = "www.example.com"               not-found-page = "not-found.html"
+
+/// The name of the configuration file.
+pub const CONFIG_FILE_NAME: &str = "site.toml";
+
+/// The source folder of a site without a site.toml (the folder it is in is the project root).
+const DEFAULT_SOURCE_DIR: &str = "edit-me";
+
+/// Everything in site.toml.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SiteConfig {
+    /// The project root, which every path is relative to. (Not set in site.toml: it is the folder site.toml is in)
+    #[serde(skip)]
+    pub root: PathBuf,
+    pub site: SiteSettings,
+    pub paths: PathSettings,
+    pub placeholders: PlaceholderSettings,
+    pub build: BuildSettings,
+    /// Named sets of build settings, each applied over [build] when picked. e.g. "release" -> production = true
+    pub profiles: BTreeMap<String, BuildSettings>,
+    /// The permalink pattern of each folder's pages. e.g. "blog" -> "/blog/:category/:slug/"
+    pub permalinks: BTreeMap<String, String>,
+    pub github_pages: GitHubPagesSettings,
+}
+
+/// [site]: settings for the whole website.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SiteSettings {
+    /// The address the website is published at. e.g. https://example.com
+    pub base_url: Option<String>,
+    /// The language of un-suffixed files. e.g. "en" (ugly-people.md, vs. ugly-people.jp.md)
+    pub default_language: String,
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        SiteSettings { base_url: None, default_language: "en".to_string() }
+    }
+}
+
+/// [paths]: where the site's folders and files are.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PathSettings {
+    pub source_dir: PathBuf,
+    pub output_dir: PathBuf,
+    /// The translation catalogs (en.json, jp.toml, ...).
+    pub page_text_dir: Option<PathBuf>,
+    /// The folder of shared/ that <r-name> code is pasted from into typescript files.
+    pub shared_code_folder: String,
+    /// Html published in this folder (of the output directory), or one of its subfolders, is a blog post.
+    pub blog_folder: PathBuf,
+    pub blog_template_top: Option<PathBuf>,
+    pub blog_template_bottom: Option<PathBuf>,
+    /// The templates `new` makes pages / components / blog posts from (its page/, component/ and blog/ folders).
+    pub templates_dir: PathBuf,
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        PathSettings {
+            source_dir: PathBuf::from(DEFAULT_SOURCE_DIR),
+            output_dir: PathBuf::from("actual-website-do-not-edit"),
+            page_text_dir: None,
+            shared_code_folder: "code".to_string(),
+            blog_folder: PathBuf::from("blog"),
+            blog_template_top: None,
+            blog_template_bottom: None,
+            templates_dir: PathBuf::from("templates"),
+        }
+    }
+}
+
+impl PathSettings {
+    /// The shared folder of the source directory. e.g. edit-me/shared
+    pub fn shared_dir(&self) -> PathBuf {
+        self.source_dir.join("shared")
+    }
+
+    /// The reusable components. e.g. edit-me/shared/reusables
+    pub fn components_dir(&self) -> PathBuf {
+        self.shared_dir().join("reusables")
+    }
+
+    /// The translation catalogs. (by default edit-me/shared/page_text)
+    pub fn page_text_dir(&self) -> PathBuf {
+        self.page_text_dir.clone().unwrap_or_else(|| self.shared_dir().join("page_text"))
+    }
+
+    /// What goes before each blog post. (by default the template-blog-top component)
+    pub fn blog_template_top(&self) -> PathBuf {
+        self.blog_template_top.clone().unwrap_or_else(|| self.components_dir().join("template-blog-top/template-blog-top.html"))
+    }
+
+    /// What goes after each blog post. (by default the template-blog-bottom component)
+    pub fn blog_template_bottom(&self) -> PathBuf {
+        self.blog_template_bottom.clone().unwrap_or_else(|| self.components_dir().join("template-blog-bottom/template-blog-bottom.html"))
+    }
+}
+
+/// [github-pages]: how the website is published on GitHub Pages.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GitHubPagesSettings {
+    /// What every link from the root starts with, for a project site (user.github.io/repo/). e.g. "/repo"
+    pub base_path: Option<String>,
+    /// The custom domain, written to the CNAME file. e.g. "www.example.com"
+    pub cname: Option<String>,
+    /// The page (of the output directory) published as 404.html. e.g. not-found.html
+    pub not_found_page: Option<PathBuf>,
+}
+
+/// [placeholders]: the special text in the source files.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PlaceholderSettings {
+    pub root: String,
+    pub component_start: String,
+    pub component_end: String,
+    pub param_start: String,
+    pub param_end: String,
+    pub display_name_start: String,
+    pub display_name_end: String,
+    pub template_prefix: String,
+    pub none_prefix: String,
+    pub scss_import_start: String,
+}
+
+impl Default for PlaceholderSettings {
+    fn default() -> Self {
+        PlaceholderSettings {
+            root: "<root>".to_string(),
+            component_start: "<r-".to_string(),
+            component_end: ">".to_string(),
+            param_start: "{".to_string(),
+            param_end: "}".to_string(),
+            display_name_start: "[".to_string(),
+            display_name_end: "]".to_string(),
+            template_prefix: "@template:".to_string(),
+            none_prefix: "@none:".to_string(),
+            scss_import_start: "@use".to_string(),
+        }
+    }
+}
+
+/// [build] / [profiles.name]: how the website is built. (Anything not set is left to the next level down)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuildSettings {
+    pub production: Option<bool>,
+    pub github_pages: Option<bool>,
+    pub hash_assets: Option<bool>,
+    pub critical_css: Option<bool>,
+    pub check_links: Option<bool>,
+    pub bundle: Option<BundleSetting>,
+    pub scss_compiler: Option<ScssCompilerSetting>,
+    pub source_maps: Option<SourceMapSetting>,
+    pub links: Option<LinkSetting>,
+}
+
+impl BuildSettings {
+    /// These settings, with the ones `other` sets replacing them.
+    pub fn overridden_by(&self, other: &BuildSettings) -> BuildSettings {
+        BuildSettings {
+            production: other.production.or(self.production),
+            github_pages: other.github_pages.or(self.github_pages),
+            hash_assets: other.hash_assets.or(self.hash_assets),
+            critical_css: other.critical_css.or(self.critical_css),
+            check_links: other.check_links.or(self.check_links),
+            bundle: other.bundle.or(self.bundle),
+            scss_compiler: other.scss_compiler.or(self.scss_compiler),
+            source_maps: other.source_maps.or(self.source_maps),
+            links: other.links.or(self.links),
+        }
+    }
+}
+
+/// `bundle`: how component css / js is bundled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BundleSetting {
+    None,
+    Page,
+    Shared,
+}
+
+/// `scss-compiler`: what compiles the scss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScssCompilerSetting {
+    Auto,
+    BuiltIn,
+    Sass,
+}
+
+/// `source-maps`: where the source maps go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceMapSetting {
+    External,
+    Hidden,
+    None,
+}
+
+/// `links`: how the links to the website's own files are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkSetting {
+    Relative,
+    Absolute,
+    Url,
+}
+
+impl SiteConfig {
+    /// Makes the paths relative to the project root (rather than to it being the working directory). Absolute paths are left as they are.
+    ///
+    /// # Arguments
+    /// * `root` - The project root. e.g. ../my-website ("" leaves the paths as they are)
+    pub fn with_root(mut self, root: &Path) -> Self {
+        let paths = &mut self.paths;
+        paths.source_dir = root.join(&paths.source_dir);
+        paths.output_dir = root.join(&paths.output_dir);
+        paths.page_text_dir = paths.page_text_dir.as_ref().map(|path| root.join(path));
+        paths.blog_template_top = paths.blog_template_top.as_ref().map(|path| root.join(path));
+        paths.blog_template_bottom = paths.blog_template_bottom.as_ref().map(|path| root.join(path));
+        paths.templates_dir = root.join(&paths.templates_dir);
+        self.root = root.to_path_buf();
+        self
+    }
+
+    /// A file / folder of the project. e.g. .last_compiled -> ../my-website/.last_compiled
+    pub fn project_path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// The build settings of a profile: [build], with [profiles.name] over it.
+    ///
+    /// # Arguments
+    /// * `profile` - The profile's name, or None for just [build].
+    ///
+    /// # Returns
+    /// * `Err` if there isn't a profile with that name.
+    pub fn build_settings(&self, profile: Option<&str>) -> Result<BuildSettings> {
+        match profile {
+            None => Ok(self.build.clone()),
+            Some(name) => match self.profiles.get(name) {
+                Some(settings) => Ok(self.build.overridden_by(settings)),
+                None => {
+                    let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                    Err(anyhow!("There is no build profile called '{}' in {} (the profiles are: {})", name, CONFIG_FILE_NAME, if names.is_empty() { "none".to_string() } else { names.join(", ") }))
+                }
+            },
+        }
+    }
+}
+
+/// Finds the project root: the nearest folder (the directory, its parent, and so on up) with a site.toml or an edit-me folder in it.
+///
+/// # Arguments
+/// * `fs` - Where to look. e.g. &DiskFileSystem
+/// * `start_dir` - Where to start looking. (usually the working directory)
+///
+/// # Returns
+/// * The project root, or None if `start_dir` isn't inside a project.
+pub fn find_project_root(fs: &dyn FileSystem, start_dir: &Path) -> Option<PathBuf> {
+    start_dir.ancestors()
+        .find(|dir| fs.is_file(&dir.join(CONFIG_FILE_NAME)) || fs.is_dir(&dir.join(DEFAULT_SOURCE_DIR)))
+        .map(Path::to_path_buf)
+}
+
+/// Reads a project's configuration: its site.toml (or the default settings, if it doesn't have one), with the paths made relative to its root.
+///
+/// # Arguments
+/// * `fs` - Where site.toml is read from. e.g. &DiskFileSystem
+/// * `root` - The project root. e.g. ../my-website
+///
+/// # Returns
+/// * `Err` naming the file, line and key if site.toml is invalid.
+pub fn load_project(fs: &dyn FileSystem, root: &Path) -> Result<SiteConfig> {
+    let config_path = root.join(CONFIG_FILE_NAME);
+    let config = if fs.is_file(&config_path) { load_config(fs, &config_path)? } else { SiteConfig::default() };
+    Ok(config.with_root(root))
+}
+
+/// Reads a site.toml file.
+/// (It is read from `fs`, e.g. &DiskFileSystem)
+///
+/// # Returns
+/// * `Err` naming the file, line and key if it can't be read, isn't valid toml, or has a key / value that isn't allowed.
+pub fn load_config(fs: &dyn FileSystem, path: &Path) -> Result<SiteConfig> {
+    let content = fs.read_to_string(path).map_err(|e| anyhow!("Failed to read '{}': {}", path.display(), e))?;
+    parse_config(&content).map_err(|message| anyhow!("{}:{}", path.display(), message))
+}
+
+/// Parses the content of a site.toml file.
+///
+/// # Returns
+/// * `Err` with where the problem is and what it is. e.g. "3:1: unknown field `outptu-dir`, expected one of ... (at paths.outptu-dir)"
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::config::parse_config;
+/// let config = parse_config("[paths]\noutput-dir = \"public\"\n").unwrap();
+/// assert_eq!(config.paths.output_dir.to_str(), Some("public"));
+/// assert_eq!(config.paths.source_dir.to_str(), Some("edit-me"));
+/// assert!(parse_config("[paths]\noutput-directory = \"public\"\n").is_err());
+/// ```
+pub fn parse_config(content: &str) -> Result<SiteConfig, String> {
+    let deserializer = toml::Deserializer::new(content);
+    serde_path_to_error::deserialize::<_, SiteConfig>(deserializer).map_err(|error| {
+        let key_path = error.path().to_string();
+        let inner = error.into_inner();
+        let (line, column) = inner.span().map_or((1, 1), |span| line_and_column(content, span.start));
+        let location = format!("{}:{}", line, column);
+        let key = if key_path == "." || key_path.is_empty() { String::new() } else { format!(" (at {})", key_path) };
+        format!("{}: {}{}", location, inner.message().trim(), key)
+    })
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{anyhow, Context, Result};
+use lightningcss::rules::{CssRule, CssRuleList};
+use lightningcss::stylesheet::{ParserOptions, PrinterOptions, StyleSheet};
+use lightningcss::traits::ToCss;
+use regex::Regex;
+use crate::bundling::rebase_css_urls;
+use crate::components::resolve_relative_reference;
+use crate::images::tag_attribute;
+use crate::vfs::FileSystem;
+
+// === CRITICAL CSS INLINING ===
+// A <link rel="stylesheet"> blocks the page from being drawn until the css has downloaded. With critical css on,
+// the css rules that the page's html actually uses are copied into a <style> in its <head>, so the page can be drawn
+// straight away, and the stylesheets themselves are loaded without blocking:
+//      <link rel="stylesheet" href="index.css">   ->   <style data-critical-css>.index-main-wrapper{...}</style>
+//                                                      <link rel="stylesheet" href="index.css" media="print" onload="this.media='all'" data-critical-css>
+//                                                      <noscript data-critical-css><link rel="stylesheet" href="index.css"></noscript>
+// (the same trick pages/index does by hand with noncritical.scss, but worked out for every page)
+//
+// "Used" means used by the html as compiled (components expanded); what the page's javascript adds later, and the css
+// for other pages in shared files, is left to the deferred stylesheet. Where the page is cut off by the screen can't
+// be known without a browser, so the whole of the initial html counts.
+// The full stylesheet is still loaded (not just the remainder), so once it arrives the cascade is exactly as written.
+// Stylesheets that are already deferred by hand (e.g. media="print") are left alone.
+
+/// The attribute that marks everything this stage adds to a page, so it can be redone in later builds.
+pub const CRITICAL_CSS_MARKER: &str = "data-critical-css";
+
+/// The outcome of `inline_critical_css_in_directory`.
+#[derive(Debug, Default)]
+pub struct CriticalCssSummary {
+    pub pages_processed: usize,
+    pub stylesheets_deferred: usize,
+    pub bytes_inlined: usize,
+    /// Stylesheets that couldn't be parsed, with the reason. Their <link>s are left as they were.
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+/// The element names, classes, ids and attribute names used in some html.
+#[derive(Debug, Default)]
+pub struct MarkupNames {
+    pub elements: HashSet<String>,
+    pub classes: HashSet<String>,
+    pub ids: HashSet<String>,
+    pub attributes: HashSet<String>,
+}
+
+impl MarkupNames {
+    /// Collects the names used in the tags of some html.
+    pub fn from_html(html: &str) -> MarkupNames {
+        let tag_regex = Regex::new(r"<([a-zA-Z][a-zA-Z0-9-]*)((?:[^>'\x22]|'[^']*'|\x22[^\x22]*\x22)*)>").unwrap();
+        let attribute_regex = Regex::new(r#"([a-zA-Z_:@][-a-zA-Z0-9_:.@]*)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>'"]+)))?"#).unwrap();
+        let mut names = MarkupNames::default();
+
+        for tag in tag_regex.captures_iter(html) {
+            names.elements.insert(tag[1].to_lowercase());
+            for attribute in attribute_regex.captures_iter(&tag[2]) {
+                let name = attribute[1].to_lowercase();
+                let value = attribute.get(2).or(attribute.get(3)).or(attribute.get(4)).map_or("", |m| m.as_str());
+                match name.as_str() {
+                    "class" => names.classes.extend(value.split_whitespace().map(String::from)),
+                    "id" => { names.ids.insert(value.trim().to_string()); }
+                    _ => {}
+                }
+                names.attributes.insert(name);
+            }
+        }
+        names
+    }
+}
+
+/// Returns true if a (single, not comma separated) css selector could match the html the names were collected from.
+/// This errs on the side of true: a selector counts as used if every element, class, id and attribute it names is in the html,
+/// whether or not they are in the right place relative to each other. Pseudo-classes / elements (e.g. :hover, ::before) are ignored,
+/// as are the selectors inside them (e.g. `:not(.hidden)` doesn't need a `.hidden`).
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::critical_css::{selector_is_used, MarkupNames};
+/// let names = MarkupNames::from_html(r#"<div class="top-bar dark"><a id="home" href="/">Home</a></div>"#);
+/// assert!(selector_is_used(".top-bar.dark > a#home:hover::after", &names));
+/// assert!(selector_is_used("a[href^='/']:not(.hidden)", &names));
+/// assert!(!selector_is_used(".top-bar .signpost", &names));
+/// ```
+pub fn selector_is_used(selector: &str, names: &MarkupNames) -> bool {
+    // (called for every selector of every stylesheet of every page, so the patterns are only compiled once)
+    // Pseudo-classes / elements, with any arguments (nested up to two levels deep, e.g. :not(:nth-child(2)))
+    static PSEUDO_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"::?[a-zA-Z-]+(?:\((?:[^()]|\((?:[^()]|\([^()]*\))*\))*\))?").unwrap());
+    static ATTRIBUTE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[\s*([a-zA-Z_:@][-a-zA-Z0-9_:.@]*)[^\]]*\]").unwrap());
+    static NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([.#]?)((?:[-a-zA-Z0-9_]|\\.)+)").unwrap());
+    let (pseudo_regex, attribute_regex, name_regex) = (&*PSEUDO_REGEX, &*ATTRIBUTE_REGEX, &*NAME_REGEX);
+
+    let without_pseudo = pseudo_regex.replace_all(selector, " ");
+    for attribute in attribute_regex.captures_iter(&without_pseudo) {
+        if !names.attributes.contains(&attribute[1].to_lowercase()) {
+            return false;
+        }
+    }
+    let without_attributes = attribute_regex.replace_all(&without_pseudo, " ");
+
+    for captures in name_regex.captures_iter(&without_attributes) {
+        let name = captures[2].replace('\\', "");
+        let is_used = match &captures[1] {
+            "." => names.classes.contains(&name),
+            "#" => names.ids.contains(&name),
+            _ if name.chars().next().is_some_and(|c| c.is_ascii_alphabetic()) => names.elements.contains(&name.to_lowercase()),
+            _ => true, // e.g. the numbers in a keyframe-like selector; nothing to check
+        };
+        if !is_used {
+            return false;
+        }
+    }
+    true
+}
+
+/// Returns the rules of a stylesheet that the html uses (see `selector_is_used`).
+/// Rules inside @media / @supports / @layer / @container blocks are filtered the same way, and empty blocks are dropped.
+/// Other at-rules (@font-face, @keyframes, @property, ...) are kept, as they only take effect if something uses them,
+/// apart from @import, which would block the page again.
+///
+/// # Arguments
+/// * `css` - The stylesheet.
+/// * `names` - The names used in the page (see `MarkupNames::from_html`).
+///
+/// # Returns
+/// * The critical css (minified, since it goes in every page), or an error if the css can't be parsed.
+pub fn extract_critical_css(css: &str, names: &MarkupNames) -> Result<String> {
+    let mut stylesheet = StyleSheet::parse(css, ParserOptions::default()).map_err(|e| anyhow!("{}", e))?;
+    retain_used_rules(&mut stylesheet.rules, names);
+    let result = stylesheet.to_css(PrinterOptions { minify: true, ..PrinterOptions::default() }).map_err(|e| anyhow!("{}", e))?;
+    Ok(result.code)
+}
+
+/// Removes the rules the html doesn't use from a list of rules (see `extract_critical_css`).
+fn retain_used_rules(rules: &mut CssRuleList, names: &MarkupNames) {
+    rules.0.retain_mut(|rule| match rule {
+        CssRule::Style(style_rule) => style_rule.selectors.0.iter().any(|selector| {
+            selector.to_css_string(PrinterOptions::default()).ok().is_none_or(|selector| selector_is_used(&selector, names))
+        }),
+        CssRule::Media(block) => { retain_used_rules(&mut block.rules, names); !block.rules.0.is_empty() }
+        CssRule::Supports(block) => { retain_used_rules(&mut block.rules, names); !block.rules.0.is_empty() }
+        CssRule::LayerBlock(block) => { retain_used_rules(&mut block.rules, names); !block.rules.0.is_empty() }
+        CssRule::Container(block) => { retain_used_rules(&mut block.rules, names); !block.rules.0.is_empty() }
+        CssRule::Import(_) => false,
+        _ => true,
+    });
+}
+
+/// Undoes `inline_critical_css`: removes the inlined <style> and <noscript> fallbacks, and makes the deferred stylesheets normal again.
+/// (so a page can be re-done after its stylesheets change, without having been recompiled)
+pub fn restore_deferred_stylesheets(html: &str) -> String {
+    let added_regex = Regex::new(&format!(r"(?is)<style {0}>.*?</style>\n?|<noscript {0}>.*?</noscript>\n?", CRITICAL_CSS_MARKER)).unwrap();
+    let deferred_regex = Regex::new(&format!(r#"(?i)<link rel="stylesheet" href="([^"]*)" media="print" onload="this.media='all'" {}>"#, CRITICAL_CSS_MARKER)).unwrap();
+    let html = added_regex.replace_all(html, "");
+    deferred_regex.replace_all(&html, r#"<link rel="stylesheet" href="$1">"#).to_string()
+}
+
+/// Inlines a page's critical css, and defers its stylesheets (see the top of this file).
+///
+/// # Arguments
+/// * `fs` - Where the stylesheets are read from. e.g. &DiskFileSystem
+/// * `html` - The page.
+/// * `html_path` - Where the page is (its stylesheet links are relative to it).
+/// * `output_dir` - The root of the website (links starting with '/' are relative to it).
+///
+/// # Returns
+/// * The new page, the number of stylesheets deferred, the number of bytes of css inlined,
+///   and any stylesheets that couldn't be parsed (which are left as normal links).
+pub fn inline_critical_css(fs: &dyn FileSystem, html: &str, html_path: &Path, output_dir: &Path) -> (String, usize, usize, Vec<(PathBuf, String)>) {
+    let html = restore_deferred_stylesheets(html);
+    let names = MarkupNames::from_html(&html);
+    let link_regex = Regex::new(r"(?i)<link\b[^>]*>").unwrap();
+    // (the <link>s in <noscript> fallbacks are for when the deferred ones can't load, so are left as they are)
+    let noscript_regex = Regex::new(r"(?is)<noscript\b.*?</noscript>").unwrap();
+    let noscript_ranges: Vec<(usize, usize)> = noscript_regex.find_iter(&html).map(|m| (m.start(), m.end())).collect();
+
+    let mut critical_css = String::new();
+    let mut failures = Vec::new();
+    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+
+    for link in link_regex.find_iter(&html) {
+        if noscript_ranges.iter().any(|(start, end)| link.start() > *start && link.end() <= *end) {
+            continue;
+        }
+        let tag = link.as_str();
+        let is_stylesheet = tag_attribute(tag, "rel").is_some_and(|rel| rel.eq_ignore_ascii_case("stylesheet"));
+        let Some(href) = tag_attribute(tag, "href") else { continue };
+        // Already deferred (e.g. media="print" onload=...), or not a file on this website
+        if !is_stylesheet || tag_attribute(tag, "media").is_some() || tag_attribute(tag, "onload").is_some() || href.contains("://") || href.starts_with("//") {
+            continue;
+        }
+        let css_path = match href.strip_prefix('/') {
+            Some(from_root) => resolve_relative_reference(&output_dir.join("index.html"), from_root),
+            None => resolve_relative_reference(html_path, &href),
+        };
+        let Ok(css) = fs.read_to_string(&css_path) else { continue };
+
+        match extract_critical_css(&css, &names) {
+            Ok(css) => {
+                critical_css.push_str(&rebase_css_urls(&css, &css_path, html_path));
+                replacements.push((link.start(), link.end(), format!(
+                    "<link rel=\"stylesheet\" href=\"{0}\" media=\"print\" onload=\"this.media='all'\" {1}>\n<noscript {1}><link rel=\"stylesheet\" href=\"{0}\"></noscript>",
+                    href, CRITICAL_CSS_MARKER
+                )));
+            }
+            Err(e) => failures.push((css_path, format!("{:#}", e))),
+        }
+    }
+
+    if replacements.is_empty() {
+        return (html, 0, 0, failures);
+    }
+
+    // The critical css goes before the first stylesheet, so it is in place before anything else in the <head> loads
+    let mut new_html = String::with_capacity(html.len() + critical_css.len());
+    let mut last_end = 0;
+    for (index, (start, end, replacement)) in replacements.iter().enumerate() {
+        new_html.push_str(&html[last_end..*start]);
+        if index == 0 && !critical_css.is_empty() {
+            new_html.push_str(&format!("<style {}>{}</style>\n", CRITICAL_CSS_MARKER, critical_css));
+        }
+        new_html.push_str(replacement);
+        last_end = *end;
+    }
+    new_html.push_str(&html[last_end..]);
+    (new_html, replacements.len(), critical_css.len(), failures)
+}
+
+/// The critical css stage of the build: inlines the critical css of every page in the output directory.
+/// Every page is re-done each build (not just the recompiled ones), since a page's stylesheets can change without it changing.
+///
+/// # Arguments
+/// * `fs` - Where the website is. e.g. &DiskFileSystem
+/// * `output_dir` - The compiled website, e.g. 'actual-website-do-not-edit'.
+pub fn inline_critical_css_in_directory(fs: &dyn FileSystem, output_dir: &Path) -> Result<CriticalCssSummary> {
+    let mut summary = CriticalCssSummary::default();
+    let html_files = fs.walk_files(output_dir)
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "html"));
+
+    for html_path in html_files {
+        let html = fs.read_to_string(&html_path).with_context(|| format!("Failed to read '{}'", html_path.display()))?;
+        let (new_html, stylesheets_deferred, bytes_inlined, failures) = inline_critical_css(fs, &html, &html_path, output_dir);
+        if new_html != html {
+            fs.write(&html_path, new_html.as_bytes()).with_context(|| format!("Failed to write '{}'", html_path.display()))?;
+        }
+        if stylesheets_deferred > 0 {
+            summary.pages_processed += 1;
+        }
+        summary.stylesheets_deferred += stylesheets_deferred;
+        summary.bytes_inlined += bytes_inlined;
+        for failure in failures {
+            if !summary.failures.iter().any(|(path, _)| *path == failure.0) {
+                summary.failures.push(failure);
+            }
+        }
+    }
+    Ok(summary)
+}
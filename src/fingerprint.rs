@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use regex::Regex;
+use crate::components::resolve_relative_reference;
+use crate::images::content_hash;
+use crate::vfs::FileSystem;
+
+// === CONTENT-HASHED ASSET FILENAMES (CACHE BUSTING) ===
+// Browsers / CDNs cache css, js, images and fonts by url, so a changed file with the same name can be served stale.
+// With fingerprinting on, every asset also gets a copy with a hash of its content in the name:
+//      shared/reusables/top-bar/top-bar.css  ->  shared/reusables/top-bar/top-bar.3f9a1c2b.css
+// and every reference to it (html attributes, css url()s, js strings) is rewritten to point at that copy.
+// A changed file gets a new name, so it is never served from a stale cache.
+//
+// The un-hashed originals are kept, since some urls are built at runtime (e.g. by the dynamic content javascript),
+// where they can't be rewritten.
+// An asset manifest (asset-manifest.json) maps the original paths to the hashed ones.
+
+/// The file types that get fingerprinted, in the order they are processed.
+/// (css can refer to images / fonts, and js to any of them, so each kind is hashed after the things it can refer to)
+pub const FINGERPRINTED_EXTENSION_GROUPS: [&[&str]; 3] = [
+    &["png", "jpg", "jpeg", "gif", "svg", "webp", "avif", "ico", "woff", "woff2", "ttf", "otf", "eot"],
+    &["css"],
+    &["js"],
+];
+
+/// Other files that can refer to assets, and so have their references rewritten.
+pub const REFERENCING_EXTENSIONS: [&str; 1] = ["html"];
+
+/// The name of the manifest file, written to the root of the output directory.
+pub const ASSET_MANIFEST_FILE: &str = "asset-manifest.json";
+
+/// Number of hex characters of the content hash that go in a file name.
+pub const FINGERPRINT_LENGTH: usize = 8;
+
+/// Maps original asset paths to their fingerprinted paths, both relative to the output directory.
+/// e.g. "shared/reusables/top-bar/top-bar.css" -> "shared/reusables/top-bar/top-bar.3f9a1c2b.css"
+pub type AssetManifest = BTreeMap<String, String>;
+
+/// Returns the fingerprint group (see `FINGERPRINTED_EXTENSION_GROUPS`) of a file, or None if it isn't fingerprinted.
+fn fingerprint_group(path: &Path) -> Option<usize> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    FINGERPRINTED_EXTENSION_GROUPS.iter().position(|group| group.contains(&extension.as_str()))
+}
+
+/// Returns the file name of an asset with a fingerprint added.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::fingerprint::fingerprinted_file_name;
+/// assert_eq!(fingerprinted_file_name("top-bar.css", "3f9a1c2b"), "top-bar.3f9a1c2b.css");
+/// assert_eq!(fingerprinted_file_name("LICENSE", "3f9a1c2b"), "LICENSE.3f9a1c2b");
+/// ```
+pub fn fingerprinted_file_name(file_name: &str, fingerprint: &str) -> String {
+    match file_name.rfind('.') {
+        Some(index) if index > 0 => format!("{}.{}{}", &file_name[..index], fingerprint, &file_name[index..]),
+        _ => format!("{}.{}", file_name, fingerprint),
+    }
+}
+
+/// Returns the original file name of a fingerprinted file name, or None if it doesn't have a fingerprint.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::fingerprint::strip_fingerprint;
+/// assert_eq!(strip_fingerprint("top-bar.3f9a1c2b.css"), Some("top-bar.css".to_string()));
+/// assert_eq!(strip_fingerprint("top-bar.css"), None);
+/// ```
+pub fn strip_fingerprint(file_name: &str) -> Option<String> {
+    let fingerprint_regex = Regex::new(&format!(r"^(.+)\.[0-9a-f]{{{}}}(\.[^.]+)?$", FINGERPRINT_LENGTH)).unwrap();
+    let captures = fingerprint_regex.captures(file_name)?;
+    Some(format!("{}{}", &captures[1], captures.get(2).map_or("", |m| m.as_str())))
+}
+
+/// Returns the fingerprint of some content: the start of its hex sha256 hash.
+pub fn content_fingerprint(content: &[u8]) -> String {
+    content_hash(content)[..FINGERPRINT_LENGTH].to_string()
+}
+
+/// Rewrites every reference to an asset in the manifest to point at its fingerprinted copy.
+/// References are found in attribute values, srcsets, css url()s / @imports and js strings alike;
+/// anything that looks like a path to a fingerprinted file type, and resolves to an asset in the manifest, is rewritten.
+/// References to an older fingerprint of an asset are updated too, so this can be run again after the asset changes.
+///
+/// # Arguments
+/// * `content` - The content of an html / css / js file.
+/// * `file_path` - The path of that file (relative references are resolved against its directory).
+/// * `output_dir` - The root of the website (references starting with '/' are resolved against it).
+/// * `manifest` - The fingerprinted assets.
+///
+/// # Returns
+/// * The rewritten content.
+pub fn rewrite_asset_references(content: &str, file_path: &Path, output_dir: &Path, manifest: &AssetManifest) -> String {
+    let extensions: Vec<&str> = FINGERPRINTED_EXTENSION_GROUPS.iter().flat_map(|group| group.iter().copied()).collect();
+    // (the regex crate has no look-behind, so the character before the path is captured, and put back as it was)
+    let reference_regex = Regex::new(&format!(
+        r#"(?i)(^|[^A-Za-z0-9_\-~.%@/])(/?(?:[A-Za-z0-9_\-~.%@]+/)*[A-Za-z0-9_\-~.%@]+\.(?:{}))([?#][^"'\s()<>,]*)?"#,
+        extensions.join("|")
+    )).unwrap();
+
+    reference_regex.replace_all(content, |captures: &regex::Captures| {
+        let (before, reference, suffix) = (&captures[1], &captures[2], captures.get(3).map_or("", |m| m.as_str()));
+        match fingerprinted_reference(reference, file_path, output_dir, manifest) {
+            Some(new_reference) => format!("{}{}{}", before, new_reference, suffix),
+            None => captures[0].to_string(),
+        }
+    }).to_string()
+}
+
+/// Returns what a single reference should be changed to, or None if it isn't to a fingerprinted asset.
+fn fingerprinted_reference(reference: &str, file_path: &Path, output_dir: &Path, manifest: &AssetManifest) -> Option<String> {
+    let resolved = match reference.strip_prefix('/') {
+        Some(from_root) => resolve_relative_reference(&output_dir.join("index.html"), from_root),
+        None => resolve_relative_reference(file_path, reference),
+    };
+    let relative = resolved.strip_prefix(output_dir).ok()?.to_string_lossy().replace('\\', "/");
+    let (directory, file_name) = match reference.rfind('/') {
+        Some(index) => reference.split_at(index + 1),
+        None => ("", reference),
+    };
+
+    // e.g. "top-bar.css", or an old "top-bar.11111111.css"
+    let fingerprinted_path = match manifest.get(&relative) {
+        Some(path) => path,
+        None => {
+            let original_name = strip_fingerprint(file_name)?;
+            let original_relative = match relative.rfind('/') {
+                Some(index) => format!("{}/{}", &relative[..index], original_name),
+                None => original_name,
+            };
+            manifest.get(&original_relative)?
+        }
+    };
+    let new_file_name = fingerprinted_path.rsplit('/').next()?;
+    Some(format!("{}{}", directory, new_file_name))
+}
+
+/// Fingerprints every asset in the output directory: rewrites the references inside css / js,
+/// writes a hashed copy of each asset, rewrites the references in the html, and writes the manifest.
+/// Hashed copies left over from older versions of an asset are deleted.
+///
+/// # Arguments
+/// * `fs` - Where the website is. e.g. &DiskFileSystem
+/// * `output_dir` - The compiled website, e.g. 'actual-website-do-not-edit'.
+///
+/// # Returns
+/// * The manifest of all fingerprinted assets.
+pub fn fingerprint_assets(fs: &dyn FileSystem, output_dir: &Path) -> Result<AssetManifest> {
+    let all_files: Vec<PathBuf> = fs.walk_files(output_dir);
+
+    // Hashed copies from previous builds aren't assets themselves
+    let is_old_copy = |path: &Path| -> bool {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        strip_fingerprint(&file_name).is_some_and(|original| fs.is_file(&path.with_file_name(original)))
+    };
+    let (old_copies, files): (Vec<PathBuf>, Vec<PathBuf>) = all_files.into_iter().partition(|path| fingerprint_group(path).is_some() && is_old_copy(path));
+
+    // 1) Assets, in group order, so things are hashed after the things they refer to
+    let mut manifest = AssetManifest::new();
+    for group in 0..FINGERPRINTED_EXTENSION_GROUPS.len() {
+        for asset_path in files.iter().filter(|path| fingerprint_group(path) == Some(group)) {
+            let mut content = fs.read(asset_path).with_context(|| format!("Failed to read '{}'", asset_path.display()))?;
+            if let Ok(text) = std::str::from_utf8(&content) {
+                let rewritten = rewrite_asset_references(text, asset_path, output_dir, &manifest);
+                if rewritten != text {
+                    fs.write(asset_path, rewritten.as_bytes()).with_context(|| format!("Failed to write '{}'", asset_path.display()))?;
+                    content = rewritten.into_bytes();
+                }
+            }
+
+            let file_name = asset_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let hashed_path = asset_path.with_file_name(fingerprinted_file_name(&file_name, &content_fingerprint(&content)));
+            if !fs.is_file(&hashed_path) {
+                fs.write(&hashed_path, &content).with_context(|| format!("Failed to write '{}'", hashed_path.display()))?;
+            }
+
+            let relative = |path: &Path| path.strip_prefix(output_dir).unwrap_or(path).to_string_lossy().replace('\\', "/");
+            manifest.insert(relative(asset_path), relative(&hashed_path));
+        }
+    }
+
+    // 2) Hashed copies of older versions
+    let current_copies: Vec<PathBuf> = manifest.values().map(|path| output_dir.join(path)).collect();
+    for old_copy in old_copies.iter().filter(|path| !current_copies.contains(path)) {
+        fs.remove_file(old_copy).with_context(|| format!("Failed to delete '{}'", old_copy.display()))?;
+    }
+
+    // 3) The pages
+    for html_path in files.iter().filter(|path| path.extension().and_then(|e| e.to_str()).is_some_and(|e| REFERENCING_EXTENSIONS.contains(&e))) {
+        let html = fs.read_to_string(html_path).with_context(|| format!("Failed to read '{}'", html_path.display()))?;
+        let rewritten = rewrite_asset_references(&html, html_path, output_dir, &manifest);
+        if rewritten != html {
+            fs.write(html_path, rewritten.as_bytes()).with_context(|| format!("Failed to write '{}'", html_path.display()))?;
+        }
+    }
+
+    write_asset_manifest(fs, &output_dir.join(ASSET_MANIFEST_FILE), &manifest)?;
+    Ok(manifest)
+}
+
+/// Writes the asset manifest (to `fs`) as json.
+pub fn write_asset_manifest(fs: &dyn FileSystem, path: &Path, manifest: &AssetManifest) -> Result<()> {
+    let json = serde_json::to_string_pretty(manifest)?;
+    fs.write(path, (json + "\n").as_bytes()).with_context(|| format!("Failed to write asset manifest '{}'", path.display()))
+}
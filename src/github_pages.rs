@@ -0,0 +1,277 @@
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{bail, Context, Result};
+use log::info;
+use regex::{Captures, Regex};
+use crate::config::{SiteConfig, CONFIG_FILE_NAME};
+use crate::links::{absolute_links_in_html, is_relative_link};
+use crate::vfs::{CommandRunner, FileSystem};
+
+// === GITHUB PAGES ===
+// `--github-pages` (github-pages = true in [build]) makes the output ready to publish on GitHub Pages:
+//      -> .html is taken off the <a href> links to the website's pages (GitHub Pages serves about.html at /about)
+//      -> for a project site (user.github.io/repo/), the base path is put in front of every link from the root (/x.css -> /repo/x.css)
+//      -> .nojekyll (so files and folders starting with _ are published), and CNAME (for a custom domain)
+//      -> 404.html: the site's own not-found page, with its links made absolute (it is shown at any url), or a plain one
+// Set in site.toml (every key is optional):
+//      [github-pages]
+//      base-path = "/repo"                     (by default the path of [site] base-url, e.g. https://user.github.io/repo -> /repo)
+//      cname = "www.example.com"
+//      not-found-page = "not-found.html"       (the page published as 404.html; by default 404.html, if the site has one)
+//
+// `deploy --to <folder>` builds the website like that, then copies it into a local checkout of the GitHub Pages repository
+// (replacing everything in it but its .git), and commits it there. (Pushing the commit is left to you)
+
+/// The file that stops GitHub Pages running the website through Jekyll.
+pub const NO_JEKYLL_FILE: &str = ".nojekyll";
+/// The file that gives GitHub Pages the website's custom domain.
+pub const CNAME_FILE: &str = "CNAME";
+/// The page GitHub Pages shows for a url that has no file.
+pub const NOT_FOUND_FILE: &str = "404.html";
+
+/// The 404.html of a site that doesn't have its own not-found page. ({home} is the url of the home page)
+const DEFAULT_NOT_FOUND_PAGE: &str = "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Page not found</title>\n</head>\n<body>\n<h1>Page not found</h1>\n<p>There isn't a page here. <a href=\"{home}\">Go to the home page</a></p>\n</body>\n</html>\n";
+
+/// Matches the href / src links of html.
+static LINK_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(\s(?:href|src|action)\s*=\s*["'])([^"']*)(["'])"#).unwrap());
+/// Matches the href links of html's <a> tags.
+static ANCHOR_LINK_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(<a[^>]*\s+href\s*=\s*["'])([^"']*)(["'])"#).unwrap());
+
+/// What making the output ready for GitHub Pages did.
+#[derive(Debug, Default)]
+pub struct GitHubPagesSummary {
+    /// The html / css files that were changed.
+    pub files_changed: usize,
+    /// The files written for GitHub Pages. e.g. [.nojekyll, 404.html]
+    pub special_files: Vec<PathBuf>,
+}
+
+/// What a deploy did.
+#[derive(Debug, Default)]
+pub struct Deployment {
+    /// How many files were copied into the checkout.
+    pub files_copied: usize,
+    /// False if the checkout already had this website, so there was nothing to commit.
+    pub committed: bool,
+}
+
+/// The base path of the website (what every link from the root starts with), without a / at the end.
+/// e.g. "/repo" for a project site at https://user.github.io/repo/, "" for a user site or a custom domain
+pub fn base_path(config: &SiteConfig) -> String {
+    let base_path = match &config.github_pages.base_path {
+        Some(base_path) => base_path.clone(),
+        // e.g. https://user.github.io/repo/ -> /repo/
+        None => config.site.base_url.as_deref()
+            .and_then(|url| url.split_once("://"))
+            .and_then(|(_, rest)| rest.find('/').map(|start| rest[start..].to_string()))
+            .unwrap_or_default(),
+    };
+    let base_path = base_path.trim_matches('/');
+    if base_path.is_empty() { String::new() } else { format!("/{}", base_path) }
+}
+
+/// Makes a built website ready to publish on GitHub Pages. (see the top of this file)
+///
+/// # Arguments
+/// * `fs` - Where the website is. e.g. &DiskFileSystem
+/// * `output_dir` - The built website. e.g. actual-website-do-not-edit
+/// * `config` - The site's [github-pages] settings.
+///
+/// # Returns
+/// * `Err` if a file couldn't be read / written, or the not-found page set in site.toml doesn't exist.
+pub fn prepare_for_github_pages(fs: &dyn FileSystem, output_dir: &Path, config: &SiteConfig) -> Result<GitHubPagesSummary> {
+    let settings = &config.github_pages;
+    let base_path = base_path(config);
+    let mut summary = GitHubPagesSummary::default();
+
+    // 404.html first, as its links are made absolute (and then have the base path, like every other link)
+    let not_found_path = output_dir.join(NOT_FOUND_FILE);
+    let not_found_page = match &settings.not_found_page {
+        Some(page) => {
+            let page_path = output_dir.join(page);
+            if !fs.is_file(&page_path) {
+                bail!("The not-found page '{}' (not-found-page in {}) isn't in the website", page.display(), CONFIG_FILE_NAME);
+            }
+            Some((page_path, page.clone()))
+        }
+        None => fs.is_file(&not_found_path).then(|| (not_found_path.clone(), PathBuf::from(NOT_FOUND_FILE))),
+    };
+    let not_found_content = match not_found_page {
+        Some((page_path, page)) => {
+            let content = fs.read_to_string(&page_path).with_context(|| format!("Failed to read '{}'", page_path.display()))?;
+            absolute_links_in_html(&content, &page, &format!("{}/", base_path))
+        }
+        None => DEFAULT_NOT_FOUND_PAGE.replace("{home}", &format!("{}/", base_path)),
+    };
+    fs.write(&not_found_path, not_found_content.as_bytes()).with_context(|| format!("Failed to write '{}'", not_found_path.display()))?;
+
+    // The links
+    for file_path in fs.walk_files(output_dir) {
+        let content = match file_path.extension().and_then(|e| e.to_str()) {
+            Some("html") => fs.read_to_string(&file_path)?,
+            Some("css") if !base_path.is_empty() => fs.read_to_string(&file_path)?,
+            _ => continue,
+        };
+        let mut changed = content.clone();
+        if file_path.extension().is_some_and(|extension| extension == "html") {
+            changed = without_html_extensions_in_html(&changed);
+            changed = with_base_path_in_html(&changed, &base_path);
+        } else {
+            changed = with_base_path_in_css(&changed, &base_path);
+        }
+        if changed != content {
+            fs.write(&file_path, changed.as_bytes()).with_context(|| format!("Failed to write '{}'", file_path.display()))?;
+            summary.files_changed += 1;
+        }
+    }
+
+    // The files GitHub Pages looks for
+    let no_jekyll_path = output_dir.join(NO_JEKYLL_FILE);
+    fs.write(&no_jekyll_path, b"").with_context(|| format!("Failed to write '{}'", no_jekyll_path.display()))?;
+    summary.special_files.extend([no_jekyll_path, not_found_path]);
+    if let Some(cname) = &settings.cname {
+        let cname_path = output_dir.join(CNAME_FILE);
+        fs.write(&cname_path, format!("{}\n", cname.trim()).as_bytes()).with_context(|| format!("Failed to write '{}'", cname_path.display()))?;
+        summary.special_files.push(cname_path);
+    }
+    Ok(summary)
+}
+
+/// Puts the base path in front of the links of html that are from the root. (Links that already have it are left alone)
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::github_pages::with_base_path_in_html;
+/// let html = r#"<link href="/blog-page.css"><a href="/">Home</a><a href="/repo/about">About</a><a href="about">About</a>"#;
+/// assert_eq!(with_base_path_in_html(html, "/repo"), r#"<link href="/repo/blog-page.css"><a href="/repo/">Home</a><a href="/repo/about">About</a><a href="about">About</a>"#);
+/// ```
+pub fn with_base_path_in_html(content: &str, base_path: &str) -> String {
+    if base_path.is_empty() {
+        return content.to_string();
+    }
+    LINK_REGEX.replace_all(content, |captures: &Captures| {
+        format!("{}{}{}", &captures[1], with_base_path(&captures[2], base_path), &captures[3])
+    }).to_string()
+}
+
+/// Puts the base path in front of the url()s of css that are from the root. e.g. url(/images/a.png) -> url(/repo/images/a.png)
+pub fn with_base_path_in_css(content: &str, base_path: &str) -> String {
+    static URL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(url\(\s*["']?)([^"')]*)"#).unwrap());
+    if base_path.is_empty() {
+        return content.to_string();
+    }
+    URL_REGEX.replace_all(content, |captures: &Captures| format!("{}{}", &captures[1], with_base_path(&captures[2], base_path))).to_string()
+}
+
+/// Takes `.html` off the <a href> links of html to pages of the website (GitHub Pages serves about.html at /about),
+/// keeping their ?query / #fragment. Links to other websites are left as they are.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::github_pages::without_html_extensions_in_html;
+/// let html = r#"<a href="about.html#team">Team</a><a href="/blog/sleep.html?v=2">Sleep</a><a href="https://example.com/a.html">Elsewhere</a>"#;
+/// assert_eq!(without_html_extensions_in_html(html), r#"<a href="about#team">Team</a><a href="/blog/sleep?v=2">Sleep</a><a href="https://example.com/a.html">Elsewhere</a>"#);
+/// ```
+pub fn without_html_extensions_in_html(content: &str) -> String {
+    ANCHOR_LINK_REGEX.replace_all(content, |captures: &Captures| {
+        format!("{}{}{}", &captures[1], without_html_extension(&captures[2]), &captures[3])
+    }).to_string()
+}
+
+/// A link without its `.html`, if it is to a page of the website (from the root, or relative). e.g. about.html#team -> about#team
+fn without_html_extension(link: &str) -> String {
+    if !(is_from_root(link) || is_relative_link(link)) {
+        return link.to_string();
+    }
+    let (path, suffix) = link.split_at(link.find(['?', '#']).unwrap_or(link.len()));
+    match path.strip_suffix(".html") {
+        Some(page) => format!("{}{}", page, suffix),
+        None => link.to_string(),
+    }
+}
+
+/// True if a link is from the root of this website. e.g. /about.css (not //cdn.example.com/a.css, which is another website)
+fn is_from_root(link: &str) -> bool {
+    link.starts_with('/') && !link.starts_with("//")
+}
+
+/// A link, with the base path in front if it is from the root (and doesn't have it yet).
+fn with_base_path(link: &str, base_path: &str) -> String {
+    let from_root = is_from_root(link);
+    let has_base_path = link == base_path || link.strip_prefix(base_path).is_some_and(|rest| rest.starts_with(['/', '?', '#']));
+    if from_root && !has_base_path { format!("{}{}", base_path, link) } else { link.to_string() }
+}
+
+// ============================================================
+// DEPLOYING
+// ============================================================
+
+/// Copies a built website into a local checkout of the GitHub Pages repository (replacing everything in it but its .git),
+/// and commits it there.
+///
+/// # Arguments
+/// * `fs` - Where the website and the checkout are. e.g. &DiskFileSystem
+/// * `commands` - Runs git. e.g. &SystemCommandRunner
+/// * `output_dir` - The built website. e.g. actual-website-do-not-edit
+/// * `checkout` - The repository's folder. e.g. ../user.github.io
+/// * `message` - The commit message.
+///
+/// # Returns
+/// * `Err` if the folder isn't a git checkout, has the website's project in it, or git failed.
+pub fn deploy(fs: &dyn FileSystem, commands: &dyn CommandRunner, output_dir: &Path, checkout: &Path, message: &str) -> Result<Deployment> {
+    if !fs.is_dir(output_dir) {
+        bail!("There is no website to deploy ('{}' doesn't exist); build it first", output_dir.display());
+    }
+    if !fs.exists(&checkout.join(".git")) {
+        bail!("'{}' isn't a git checkout (it has no .git); clone the GitHub Pages repository there first", checkout.display());
+    }
+    // (everything in the checkout is deleted, so it mustn't be (or have in it) the project itself)
+    let canonical_checkout = fs.canonicalize(checkout).with_context(|| format!("Failed to find '{}'", checkout.display()))?;
+    let canonical_output_dir = fs.canonicalize(output_dir).with_context(|| format!("Failed to find '{}'", output_dir.display()))?;
+    if canonical_output_dir.starts_with(&canonical_checkout) || canonical_checkout.starts_with(&canonical_output_dir) {
+        bail!("'{}' has the website's project in it (or is in its output); deploy to a separate checkout of the GitHub Pages repository", checkout.display());
+    }
+
+    info!("Replacing everything in {} (except .git) with {}", checkout.display(), output_dir.display());
+    for entry in fs.read_dir(checkout).with_context(|| format!("Failed to read '{}'", checkout.display()))? {
+        if entry.file_name().is_some_and(|name| name == ".git") {
+            continue;
+        }
+        let removed = if fs.is_dir(&entry) { fs.remove_dir_all(&entry) } else { fs.remove_file(&entry) };
+        removed.with_context(|| format!("Failed to delete '{}'", entry.display()))?;
+    }
+    let mut deployment = Deployment::default();
+    for path in fs.walk(output_dir) {
+        let destination = checkout.join(path.strip_prefix(output_dir).unwrap_or(&path));
+        if fs.is_dir(&path) {
+            fs.create_dir_all(&destination).with_context(|| format!("Failed to create '{}'", destination.display()))?;
+        } else {
+            fs.copy(&path, &destination).with_context(|| format!("Failed to copy '{}' to '{}'", path.display(), destination.display()))?;
+            deployment.files_copied += 1;
+        }
+    }
+
+    git(commands, checkout, &["add", "--all"])?;
+    if git(commands, checkout, &["status", "--porcelain"])?.trim().is_empty() {
+        info!("Nothing changed since the last deploy, so there is nothing to commit");
+        return Ok(deployment);
+    }
+    git(commands, checkout, &["commit", "--quiet", "--message", message])?;
+    deployment.committed = true;
+    Ok(deployment)
+}
+
+/// Runs a git command in a checkout.
+///
+/// # Returns
+/// * What it printed.
+/// * `Err` with git's message if it failed (or git isn't installed).
+fn git(commands: &dyn CommandRunner, checkout: &Path, args: &[&str]) -> Result<String> {
+    let mut full_args = vec!["-C".to_string(), checkout.to_string_lossy().to_string()];
+    full_args.extend(args.iter().map(|arg| arg.to_string()));
+    let output = commands.run("git", &full_args).with_context(|| "Failed to run git (is it installed?)")?;
+    if !output.success {
+        bail!("git {} failed: {}", args.join(" "), output.stderr.trim());
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,759 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config::SiteConfig;
+use crate::translations::{catalog_language_code, find_catalog_file, find_catalog_files, parse_catalog, CatalogFormat};
+use crate::vfs::FileSystem;
+
+// Object to represent a translation catalog, e.g. en.json's contents: { "page": { "key": "text", ... }, ... }
+// (serde_json's map keeps the file's order, so rewriting a catalog doesn't shuffle it)
+pub type Catalog = Map<String, Value>;
+
+// Where the english text each translation was made from is kept, e.g. edit-me/shared/page_text/source-text/jp.json
+// Used to spot translations whose english text has since changed.
+pub const SOURCE_TEXT_FOLDER: &str = "source-text";
+
+// Catalog section for text used on many pages (e.g. the top bar). A page's key can live here instead of under the page.
+pub const SHARED_PAGE: &str = "shared";
+
+/// Matches a `<div>TEXT=page.key</div>` placeholder, capturing page.key. e.g. <div>TEXT=index.nav-title</div>
+/// (Both the extraction below and the language versions of a build (see processors.rs) find the keys with it)
+pub static TEXT_PLACEHOLDER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<div>TEXT=([a-zA-Z0-9_\.-]+)</div>").unwrap());
+
+
+// ============================================================
+// EXTRACTING TRANSLATION KEYS
+// ============================================================
+
+/// A single use of a translation key found in the source files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslationReference {
+    pub page: String,        // e.g. "index" (from <body data-page="index">)
+    pub key: String,         // e.g. "learn-more" (from add-text-section="learn-more")
+    pub source_text: String, // e.g. "Learn more" (the text already in the element, if any)
+    pub file: PathBuf,       // e.g. edit-me/shared/reusables/category-filter/category-filter.html
+}
+
+// Everything one html file contributes, before components are resolved to the pages that use them
+struct FileTranslationUsage {
+    page: Option<String>,
+    keys: Vec<(String, String)>,                 // (key, source text)
+    explicit_keys: Vec<(String, String)>,        // (page, key) from <div>TEXT=page.key</div>
+    used_components: Vec<String>,
+}
+
+/// Scans the html files in the source directory for translation references:
+/// * `add-text-section="key"` attributes, which belong to the page named by that file's `<body data-page="...">`
+/// * `<div>TEXT=page.key</div>` placeholders
+///
+/// Reusable components usually have no `data-page` of their own, so their keys are credited to every page that uses them
+/// (directly, or through other components).
+///
+/// # Arguments
+/// * `fs` - Where the html is read from. e.g. &DiskFileSystem
+/// * `source_dir` - e.g. "edit-me"
+/// * `components_dir` - e.g. "edit-me/shared/reusables"
+/// * `re_start` / `re_end` - the component placeholder delimiters, e.g. "<r-" and ">"
+///
+/// # Returns
+/// The references, de-duplicated by (page, key) and sorted.
+pub fn extract_translation_references(fs: &dyn FileSystem, source_dir: &Path, components_dir: &Path, re_start: &str, re_end: &str) -> Result<Vec<TranslationReference>> {
+    let data_page_regex = Regex::new(r#"data-page\s*=\s*["']([^"']+)["']"#)?;
+    let text_section_regex = Regex::new(r#"<[a-zA-Z][^>]*?\badd-text-section\s*=\s*["']([^"']*)["'][^>]*>([^<]*)"#)?;
+    let component_regex = Regex::new(&format!(r"{}([a-zA-Z0-9_-]+)[^>]*{}", regex::escape(re_start), regex::escape(re_end)))?;
+
+    let mut usages: Vec<(PathBuf, FileTranslationUsage)> = Vec::new();
+    for path in fs.walk_files(source_dir) {
+        if path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let Ok(content) = fs.read_to_string(&path) else { continue };
+
+        let usage = FileTranslationUsage {
+            page: data_page_regex.captures(&content).map(|c| c[1].to_string()),
+            keys: text_section_regex.captures_iter(&content)
+                .filter(|c| !c[1].trim().is_empty())
+                .map(|c| (c[1].trim().to_string(), c[2].split_whitespace().collect::<Vec<&str>>().join(" ")))
+                .collect(),
+            explicit_keys: TEXT_PLACEHOLDER_REGEX.captures_iter(&content)
+                .filter_map(|c| c[1].split_once('.').map(|(page, key)| (page.to_string(), key.to_string())))
+                .collect(),
+            used_components: component_regex.captures_iter(&content).map(|c| c[1].to_string()).collect(),
+        };
+        usages.push((path, usage));
+    }
+
+    // component name -> index into usages, e.g. "category-filter" -> edit-me/shared/reusables/category-filter/category-filter.html
+    let components: HashMap<String, usize> = usages.iter().enumerate()
+        .filter(|(_, (path, _))| path.starts_with(components_dir))
+        .filter_map(|(index, (path, _))| path.file_stem().map(|stem| (stem.to_string_lossy().to_string(), index)))
+        .collect();
+
+    let mut references: Vec<TranslationReference> = Vec::new();
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    let mut add_reference = |page: &str, key: &str, source_text: &str, file: &Path| {
+        if seen.insert((page.to_string(), key.to_string())) {
+            references.push(TranslationReference { page: page.to_string(), key: key.to_string(), source_text: source_text.to_string(), file: file.to_path_buf() });
+        } else if !source_text.is_empty() {
+            // Prefer a reference that has some text to offer as the english version
+            if let Some(existing) = references.iter_mut().find(|r| r.page == page && r.key == key && r.source_text.is_empty()) {
+                existing.source_text = source_text.to_string();
+                existing.file = file.to_path_buf();
+            }
+        }
+    };
+
+    for (path, usage) in &usages {
+        for (page, key) in &usage.explicit_keys {
+            add_reference(page, key, "", path);
+        }
+
+        let Some(page) = &usage.page else { continue };
+
+        // Follow the component tree down from this page
+        let mut to_visit: Vec<usize> = vec![usages.iter().position(|(p, _)| p == path).unwrap_or_default()];
+        let mut visited: HashSet<usize> = HashSet::new();
+        while let Some(index) = to_visit.pop() {
+            if !visited.insert(index) {
+                continue;
+            }
+            let (file, file_usage) = &usages[index];
+            for (key, source_text) in &file_usage.keys {
+                add_reference(page, key, source_text, file);
+            }
+            to_visit.extend(file_usage.used_components.iter().filter_map(|name| components.get(name)).copied());
+        }
+    }
+
+    references.sort_by(|a, b| (&a.page, &a.key).cmp(&(&b.page, &b.key)));
+    Ok(references)
+}
+
+
+// ============================================================
+// CATALOGS
+// ============================================================
+
+/// Returns a language's catalog file, in whichever format it is written (en.json / en.toml / en.yaml).
+/// A language without a catalog yet gets a new JSON one. e.g. (page_text, "jp") -> page_text/jp.json
+/// (The file is looked for in `fs`, e.g. &DiskFileSystem)
+pub fn catalog_path(fs: &dyn FileSystem, page_text_dir: &Path, language_code: &str) -> PathBuf {
+    find_catalog_file(fs, page_text_dir, language_code).unwrap_or_else(|| page_text_dir.join(format!("{}.json", language_code)))
+}
+
+/// Reads a translation catalog, e.g. edit-me/shared/page_text/en.json
+/// The catalog is validated first (see `translations::parse_catalog`), so mistakes are reported where they are rather than rewritten.
+/// A missing file is read as an empty catalog, so that new languages can be started from nothing.
+/// (It is read from `fs`, e.g. &DiskFileSystem)
+pub fn read_catalog(fs: &dyn FileSystem, path: &Path) -> Result<Catalog> {
+    if !fs.is_file(path) {
+        return Ok(Catalog::new());
+    }
+    let format = CatalogFormat::from_path(path).ok_or_else(|| anyhow!("{:?} is not a .json, .toml or .yaml translation catalog", path))?;
+    let content = fs.read_to_string(path).with_context(|| format!("Failed to read translation catalog {:?}", path))?;
+    parse_catalog(path, &content, format)?;
+
+    let parsed: Value = match format {
+        CatalogFormat::Json => serde_json::from_str(&content)?,
+        CatalogFormat::Toml => toml::from_str(&content)?,
+        CatalogFormat::Yaml => serde_yaml_ng::from_str(&content)?,
+    };
+    match parsed {
+        Value::Object(catalog) => Ok(catalog),
+        _ => Err(anyhow!("Translation catalog {:?} must be a table of pages", path)),
+    }
+}
+
+/// Writes a translation catalog in the format of its extension. JSON is indented with 4 spaces like the hand-written ones.
+/// (It is written to `fs`, e.g. &DiskFileSystem)
+pub fn write_catalog(fs: &dyn FileSystem, path: &Path, catalog: &Catalog) -> Result<()> {
+    let output: Vec<u8> = match CatalogFormat::from_path(path) {
+        Some(CatalogFormat::Toml) => toml::to_string_pretty(catalog)?.into_bytes(),
+        Some(CatalogFormat::Yaml) => serde_yaml_ng::to_string(catalog)?.into_bytes(),
+        _ => {
+            let mut output = Vec::new();
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+            let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+            catalog.serialize(&mut serializer)?;
+            output.push(b'\n');
+            output
+        }
+    };
+
+    if let Some(parent) = path.parent() {
+        fs.create_dir_all(parent).with_context(|| format!("Failed to create directory for {:?}", path))?;
+    }
+    fs.write(path, &output).with_context(|| format!("Failed to write translation catalog {:?}", path))
+}
+
+/// Looks up the text of "page.key" in a catalog
+pub fn catalog_text<'a>(catalog: &'a Catalog, page: &str, key: &str) -> Option<&'a str> {
+    catalog.get(page).and_then(|page_value| page_value.get(key)).and_then(|text| text.as_str())
+}
+
+/// Sets the text of "page.key" in a catalog, adding the page if needed
+pub fn set_catalog_text(catalog: &mut Catalog, page: &str, key: &str, text: &str) {
+    let page_value = catalog.entry(page.to_string()).or_insert_with(|| Value::Object(Map::new()));
+    if !page_value.is_object() {
+        *page_value = Value::Object(Map::new());
+    }
+    if let Value::Object(page_map) = page_value {
+        page_map.insert(key.to_string(), Value::String(text.to_string()));
+    }
+}
+
+/// Every "page.key" with text in a catalog, in catalog order. e.g. [("index", "learn-more", "Learn more"), ...]
+pub fn catalog_entries(catalog: &Catalog) -> Vec<(String, String, String)> {
+    let mut entries = Vec::new();
+    for (page, page_value) in catalog {
+        let Value::Object(page_map) = page_value else { continue };
+        for (key, text) in page_map {
+            if let Some(text) = text.as_str() {
+                entries.push((page.clone(), key.clone(), text.to_string()));
+            }
+        }
+    }
+    entries
+}
+
+/// The result of bringing a catalog up to date with the source files.
+#[derive(Debug, Default, PartialEq)]
+pub struct CatalogUpdate {
+    pub added: Vec<String>,  // "page.key"s that were missing from the catalog
+    pub unused: Vec<String>, // "page.key"s in the catalog that no source file references (kept, since scripts may add text at runtime)
+}
+
+/// Adds every referenced key that is missing from the catalog, using the text found in the source as its value.
+/// A key found in the catalog's "shared" section counts as present for every page.
+pub fn update_catalog(catalog: &mut Catalog, references: &[TranslationReference]) -> CatalogUpdate {
+    let mut update = CatalogUpdate::default();
+    let mut referenced: HashSet<(String, String)> = HashSet::new();
+
+    for reference in references {
+        if catalog_text(catalog, &reference.page, &reference.key).is_some() {
+            referenced.insert((reference.page.clone(), reference.key.clone()));
+        } else if catalog_text(catalog, SHARED_PAGE, &reference.key).is_some() {
+            referenced.insert((SHARED_PAGE.to_string(), reference.key.clone()));
+        } else {
+            set_catalog_text(catalog, &reference.page, &reference.key, &reference.source_text);
+            referenced.insert((reference.page.clone(), reference.key.clone()));
+            update.added.push(format!("{}.{}", reference.page, reference.key));
+        }
+    }
+
+    for (page, key, _) in catalog_entries(catalog) {
+        if !referenced.contains(&(page.clone(), key.clone())) {
+            update.unused.push(format!("{}.{}", page, key));
+        }
+    }
+
+    update
+}
+
+
+// ============================================================
+// STALE TRANSLATIONS
+// ============================================================
+
+/// Path of the record of which english text a language's translations were made from.
+/// e.g. edit-me/shared/page_text + "jp" -> edit-me/shared/page_text/source-text/jp.json
+pub fn source_text_record_path(page_text_dir: &Path, language_code: &str) -> PathBuf {
+    page_text_dir.join(SOURCE_TEXT_FOLDER).join(format!("{}.json", language_code))
+}
+
+/// One key of the default language catalog, and its state in a translated catalog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslationUnit {
+    pub context: String,              // "page.key", e.g. "index.learn-more"
+    pub source: String,               // the current english text
+    pub target: String,               // the translation, or "" if there is none
+    pub stale_source: Option<String>, // the english text the translation was made from, if that has since changed
+}
+
+/// Lines up the translated catalog against the default language catalog, one unit per default language key.
+/// A translation is stale if the english text it was made from (per the source text record) is no longer the current english text.
+pub fn build_translation_units(default_catalog: &Catalog, translated_catalog: &Catalog, source_record: &Catalog) -> Vec<TranslationUnit> {
+    catalog_entries(default_catalog).into_iter().map(|(page, key, source)| {
+        let target = catalog_text(translated_catalog, &page, &key).unwrap_or_default().to_string();
+        let stale_source = match catalog_text(source_record, &page, &key) {
+            Some(translated_from) if !target.is_empty() && translated_from != source => Some(translated_from.to_string()),
+            _ => None,
+        };
+        TranslationUnit { context: format!("{}.{}", page, key), source, target, stale_source }
+    }).collect()
+}
+
+/// Returns the "page.key"s whose translation was made from english text that has since changed.
+pub fn find_stale_translations(default_catalog: &Catalog, translated_catalog: &Catalog, source_record: &Catalog) -> Vec<String> {
+    build_translation_units(default_catalog, translated_catalog, source_record)
+        .into_iter()
+        .filter(|unit| unit.stale_source.is_some())
+        .map(|unit| unit.context)
+        .collect()
+}
+
+
+// ============================================================
+// PO / XLIFF FILES
+// ============================================================
+
+/// Formats of the files handed to translators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExchangeFormat {
+    Po,
+    Xliff,
+}
+
+impl ExchangeFormat {
+    /// Picks the format from a file's extension (.po, .xlf or .xliff)
+    pub fn from_path(path: &Path) -> Option<ExchangeFormat> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("po") | Some("pot") => Some(ExchangeFormat::Po),
+            Some("xlf") | Some("xliff") => Some(ExchangeFormat::Xliff),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ExchangeFormat> {
+        match name {
+            "po" => Some(ExchangeFormat::Po),
+            "xliff" | "xlf" => Some(ExchangeFormat::Xliff),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExchangeFormat::Po => "po",
+            ExchangeFormat::Xliff => "xlf",
+        }
+    }
+}
+
+/// A translation read back from a PO / XLIFF file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedUnit {
+    pub context: String,    // "page.key"
+    pub source: String,     // the english text the translator saw
+    pub target: String,     // the translation
+    pub needs_review: bool, // marked fuzzy / needs-review, so not ready to be published
+}
+
+/// A parsed PO / XLIFF file.
+#[derive(Debug, Default)]
+pub struct ImportedFile {
+    pub language_code: Option<String>, // from the PO "Language:" header / XLIFF target-language
+    pub units: Vec<ImportedUnit>,
+}
+
+/// Writes the units as a gettext PO file. Each key is a `msgctxt`, so identical english text on different keys stays separate.
+/// Stale translations are marked `fuzzy`, with the english text they were made from as the previous `#| msgid`.
+pub fn write_po(units: &[TranslationUnit], source_language: &str, target_language: &str) -> String {
+    let mut output = String::new();
+    output.push_str("msgid \"\"\nmsgstr \"\"\n");
+    output.push_str(&format!("\"Language: {}\\n\"\n", target_language));
+    output.push_str(&format!("\"X-Source-Language: {}\\n\"\n", source_language));
+    output.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+
+    for unit in units {
+        output.push('\n');
+        if let Some(stale_source) = &unit.stale_source {
+            output.push_str("#, fuzzy\n");
+            for line in po_string("#| msgid", stale_source).lines() {
+                output.push_str(&format!("{}\n", if line.starts_with("#|") { line.to_string() } else { format!("#| {}", line) }));
+            }
+        }
+        output.push_str(&po_string("msgctxt", &unit.context));
+        output.push_str(&po_string("msgid", &unit.source));
+        output.push_str(&po_string("msgstr", &unit.target));
+    }
+    output
+}
+
+// e.g. msgid "Learn more" / msgid ""\n"line one\n"\n"line two"
+fn po_string(keyword: &str, text: &str) -> String {
+    let escaped = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"").replace('\t', "\\t").replace('\n', "\\n");
+    if !text.contains('\n') {
+        return format!("{} \"{}\"\n", keyword, escaped(text));
+    }
+    let mut output = format!("{} \"\"\n", keyword);
+    for line in text.split_inclusive('\n') {
+        output.push_str(&format!("\"{}\"\n", escaped(line)));
+    }
+    output
+}
+
+fn po_unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('r') => output.push('\r'),
+            Some(other) => output.push(other),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+/// Reads a gettext PO file written by `write_po` (or by a translation tool that has edited one).
+pub fn parse_po(content: &str) -> Result<ImportedFile> {
+    #[derive(Default)]
+    struct Entry { context: String, id: String, translation: String, fuzzy: bool, has_msgstr: bool }
+
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut current = Entry::default();
+    let mut current_field: Option<&str> = None;
+
+    for (line_number, raw_line) in content.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // Comments come before their entry, so one after a msgstr starts the next entry
+        if line.starts_with('#') {
+            if current.has_msgstr {
+                entries.push(std::mem::take(&mut current));
+                current_field = None;
+            }
+            if line.starts_with("#,") && line.contains("fuzzy") {
+                current.fuzzy = true;
+            }
+            continue;
+        }
+
+        let (field, quoted) = match line.split_once(' ') {
+            Some((keyword @ ("msgctxt" | "msgid" | "msgstr"), rest)) => (Some(keyword), rest.trim()),
+            _ => (None, line),
+        };
+        if !(quoted.len() >= 2 && quoted.starts_with('"') && quoted.ends_with('"')) {
+            return Err(anyhow!("PO line {}: expected a quoted string, found: {}", line_number + 1, raw_line));
+        }
+        let text = po_unescape(&quoted[1..quoted.len() - 1]);
+
+        match field {
+            Some(keyword) => {
+                if keyword != "msgstr" && current.has_msgstr {
+                    entries.push(std::mem::take(&mut current));
+                }
+                current.has_msgstr |= keyword == "msgstr";
+                current_field = Some(keyword);
+            }
+            None if current_field.is_none() => return Err(anyhow!("PO line {}: text outside of an entry: {}", line_number + 1, raw_line)),
+            None => {}
+        }
+
+        match current_field {
+            Some("msgctxt") => current.context.push_str(&text),
+            Some("msgid") => current.id.push_str(&text),
+            _ => current.translation.push_str(&text),
+        }
+    }
+    if current.has_msgstr {
+        entries.push(current);
+    }
+
+    let mut file = ImportedFile::default();
+    for entry in entries {
+        if entry.id.is_empty() && entry.context.is_empty() {
+            // The header
+            file.language_code = entry.translation.lines()
+                .find_map(|line| line.strip_prefix("Language:"))
+                .map(|code| code.trim().to_string())
+                .filter(|code| !code.is_empty());
+            continue;
+        }
+        file.units.push(ImportedUnit { context: entry.context, source: entry.id, target: entry.translation, needs_review: entry.fuzzy });
+    }
+    Ok(file)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    // CDATA sections are taken as-is
+    if let Some(inner) = text.trim().strip_prefix("<![CDATA[").and_then(|t| t.strip_suffix("]]>")) {
+        return inner.to_string();
+    }
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// Writes the units as an XLIFF 1.2 file. Each key is a `trans-unit` id.
+/// Stale translations get `state="needs-review-translation"` and a note with the english text they were made from.
+pub fn write_xliff(units: &[TranslationUnit], source_language: &str, target_language: &str) -> String {
+    let mut output = String::new();
+    output.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str("<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n");
+    output.push_str(&format!("  <file original=\"page_text\" datatype=\"plaintext\" source-language=\"{}\" target-language=\"{}\">\n", xml_escape(source_language), xml_escape(target_language)));
+    output.push_str("    <body>\n");
+    for unit in units {
+        let state = match (&unit.stale_source, unit.target.is_empty()) {
+            (_, true) => "new",
+            (Some(_), false) => "needs-review-translation",
+            (None, false) => "translated",
+        };
+        output.push_str(&format!("      <trans-unit id=\"{}\">\n", xml_escape(&unit.context)));
+        output.push_str(&format!("        <source>{}</source>\n", xml_escape(&unit.source)));
+        output.push_str(&format!("        <target state=\"{}\">{}</target>\n", state, xml_escape(&unit.target)));
+        if let Some(stale_source) = &unit.stale_source {
+            output.push_str(&format!("        <note>English text changed. Translated from: {}</note>\n", xml_escape(stale_source)));
+        }
+        output.push_str("      </trans-unit>\n");
+    }
+    output.push_str("    </body>\n  </file>\n</xliff>\n");
+    output
+}
+
+/// Reads an XLIFF 1.2 file written by `write_xliff` (or by a translation tool that has edited one).
+pub fn parse_xliff(content: &str) -> Result<ImportedFile> {
+    let file_regex = Regex::new(r#"<file\b[^>]*\btarget-language\s*=\s*["']([^"']*)["']"#)?;
+    let unit_regex = Regex::new(r#"(?s)<trans-unit\b([^>]*)>(.*?)</trans-unit>"#)?;
+    let id_regex = Regex::new(r#"\bid\s*=\s*["']([^"']*)["']"#)?;
+    let source_regex = Regex::new(r#"(?s)<source\b[^>]*>(.*?)</source>"#)?;
+    let target_regex = Regex::new(r#"(?s)<target\b([^>]*?)(?:/>|>(.*?)</target>)"#)?;
+    let state_regex = Regex::new(r#"\bstate\s*=\s*["']([^"']*)["']"#)?;
+
+    if !content.contains("<xliff") {
+        return Err(anyhow!("Not an XLIFF file (no <xliff> element found)"));
+    }
+
+    let mut file = ImportedFile {
+        language_code: file_regex.captures(content).map(|c| xml_unescape(&c[1])).filter(|code| !code.is_empty()),
+        units: Vec::new(),
+    };
+
+    for unit_caps in unit_regex.captures_iter(content) {
+        let id = id_regex.captures(&unit_caps[1]).map(|c| xml_unescape(&c[1]))
+            .ok_or_else(|| anyhow!("XLIFF trans-unit without an id: {}", &unit_caps[0]))?;
+        let body = &unit_caps[2];
+        let source = source_regex.captures(body).map(|c| xml_unescape(&c[1])).unwrap_or_default();
+        let (target, state) = match target_regex.captures(body) {
+            Some(c) => (
+                c.get(2).map(|t| xml_unescape(t.as_str())).unwrap_or_default(),
+                state_regex.captures(&c[1]).map(|s| s[1].to_string()).unwrap_or_default(),
+            ),
+            None => (String::new(), String::new()),
+        };
+        file.units.push(ImportedUnit { context: id, source, target, needs_review: state.starts_with("needs-review") });
+    }
+    Ok(file)
+}
+
+/// What happened to the units of an imported file.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub untranslated: usize,         // no translation given
+    pub needs_review: Vec<String>,   // fuzzy / needs-review units, left out until the translator signs them off
+    pub outdated: Vec<String>,       // translated from english text that has since changed (imported, but still stale)
+    pub unknown_keys: Vec<String>,   // keys which aren't in the default language catalog
+}
+
+/// Applies imported translations to a language's catalog, and records the english text each one was translated from.
+pub fn apply_imported_units(translated_catalog: &mut Catalog, source_record: &mut Catalog, default_catalog: &Catalog, units: &[ImportedUnit]) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+
+    for unit in units {
+        let Some((page, key)) = unit.context.split_once('.') else {
+            summary.unknown_keys.push(unit.context.clone());
+            continue;
+        };
+        let Some(current_source) = catalog_text(default_catalog, page, key) else {
+            summary.unknown_keys.push(unit.context.clone());
+            continue;
+        };
+        if unit.target.is_empty() {
+            summary.untranslated += 1;
+            continue;
+        }
+        if unit.needs_review {
+            summary.needs_review.push(unit.context.clone());
+            continue;
+        }
+
+        set_catalog_text(translated_catalog, page, key, &unit.target);
+        set_catalog_text(source_record, page, key, &unit.source);
+        if unit.source != current_source {
+            summary.outdated.push(unit.context.clone());
+        }
+        summary.imported += 1;
+    }
+
+    summary
+}
+
+
+// ============================================================
+// THE i18n COMMAND (extract / export / import)
+// ============================================================
+
+/// The translations a language is missing, or has out of date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageStatus {
+    pub language_code: String,
+    pub missing: usize,
+    pub stale: Vec<TranslationUnit>, // translated from english text that has since changed
+}
+
+/// What `extract_translations` found and changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractReport {
+    pub keys_found: usize,              // the keys used in the html
+    pub catalog_path: PathBuf,          // the default language catalog, e.g. edit-me/shared/page_text/en.json
+    pub added: Vec<String>,             // "page.key"s added to it
+    pub unused: Vec<String>,            // "page.key"s in it that nothing uses (kept)
+    pub languages: Vec<LanguageStatus>, // every other language
+}
+
+/// What `export_translations` wrote.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportReport {
+    pub path: PathBuf, // e.g. edit-me/shared/page_text/jp.po
+    pub keys: usize,
+    pub missing: usize,
+    pub stale: usize,
+}
+
+/// What `import_translations` read in.
+#[derive(Debug, PartialEq)]
+pub struct ImportReport {
+    pub language_code: String,
+    pub catalog_path: PathBuf,         // the catalog the translations went into, e.g. edit-me/shared/page_text/jp.json
+    pub default_catalog_path: PathBuf, // the catalog the keys have to be in, e.g. edit-me/shared/page_text/en.json
+    pub summary: ImportSummary,
+}
+
+/// Adds the translation keys used in the html to the default language catalog, and reports what the other languages are missing.
+///
+/// # Arguments
+/// * `fs` - Where the html and the catalogs are. e.g. &DiskFileSystem
+/// * `config` - The site. (its source folder, page_text folder, default language and component placeholders)
+///
+/// # Returns
+/// * The keys found and added, and the state of each other language.
+/// * `Err` if the html or a catalog can't be read, or the default language catalog can't be written.
+pub fn extract_translations(fs: &dyn FileSystem, config: &SiteConfig) -> Result<ExtractReport> {
+    let page_text_dir = config.paths.page_text_dir();
+    let default_language = config.site.default_language.as_str();
+    let default_catalog_path = catalog_path(fs, &page_text_dir, default_language);
+
+    let references = extract_translation_references(fs, &config.paths.source_dir, &config.paths.components_dir(), &config.placeholders.component_start, &config.placeholders.component_end)?;
+    let mut default_catalog = read_catalog(fs, &default_catalog_path)?;
+    let update = update_catalog(&mut default_catalog, &references);
+    if !update.added.is_empty() {
+        write_catalog(fs, &default_catalog_path, &default_catalog)?;
+    }
+
+    let mut languages = Vec::new();
+    for language_code in translation_language_codes(fs, &page_text_dir)? {
+        if language_code == default_language {
+            continue;
+        }
+        let translated_catalog = read_catalog(fs, &catalog_path(fs, &page_text_dir, &language_code))?;
+        let source_record = read_catalog(fs, &source_text_record_path(&page_text_dir, &language_code))?;
+        let units = build_translation_units(&default_catalog, &translated_catalog, &source_record);
+        languages.push(LanguageStatus {
+            missing: units.iter().filter(|unit| unit.target.is_empty()).count(),
+            stale: units.into_iter().filter(|unit| unit.stale_source.is_some()).collect(),
+            language_code,
+        });
+    }
+
+    Ok(ExtractReport { keys_found: references.len(), catalog_path: default_catalog_path, added: update.added, unused: update.unused, languages })
+}
+
+/// Writes a language's translations (and the english they are of) to a PO / XLIFF file for a translator.
+///
+/// # Arguments
+/// * `fs` - Where the catalogs are read from, and the file written. e.g. &DiskFileSystem
+/// * `config` - The site.
+/// * `language_code` - e.g. "jp"
+/// * `format` - e.g. ExchangeFormat::Po
+/// * `out_path` - Where to write it; by default <page_text>/<lang>.po (or .xlf)
+pub fn export_translations(fs: &dyn FileSystem, config: &SiteConfig, language_code: &str, format: ExchangeFormat, out_path: Option<&Path>) -> Result<ExportReport> {
+    let page_text_dir = config.paths.page_text_dir();
+    let default_language = config.site.default_language.as_str();
+    let path = out_path.map(Path::to_path_buf).unwrap_or_else(|| page_text_dir.join(format!("{}.{}", language_code, format.extension())));
+
+    let default_catalog = read_catalog(fs, &catalog_path(fs, &page_text_dir, default_language))?;
+    let translated_catalog = read_catalog(fs, &catalog_path(fs, &page_text_dir, language_code))?;
+    let source_record = read_catalog(fs, &source_text_record_path(&page_text_dir, language_code))?;
+    let units = build_translation_units(&default_catalog, &translated_catalog, &source_record);
+
+    let content = match format {
+        ExchangeFormat::Po => write_po(&units, default_language, language_code),
+        ExchangeFormat::Xliff => write_xliff(&units, default_language, language_code),
+    };
+    fs.write(&path, content.as_bytes()).with_context(|| format!("Failed to write {:?}", path))?;
+
+    Ok(ExportReport {
+        keys: units.len(),
+        missing: units.iter().filter(|unit| unit.target.is_empty()).count(),
+        stale: units.iter().filter(|unit| unit.stale_source.is_some()).count(),
+        path,
+    })
+}
+
+/// Reads a translated PO / XLIFF file back into its language's catalog (see `apply_imported_units`).
+///
+/// # Arguments
+/// * `fs` - Where the file is read from, and the catalogs written. e.g. &DiskFileSystem
+/// * `config` - The site.
+/// * `file_path` - e.g. jp.po
+/// * `language_code` - The file's language; by default the one named in the file.
+///
+/// # Returns
+/// * The language, its catalog, and what was imported.
+/// * `Err` if the file can't be read, doesn't say its language, or is of the default language (which is edited directly).
+pub fn import_translations(fs: &dyn FileSystem, config: &SiteConfig, file_path: &Path, language_code: Option<&str>) -> Result<ImportReport> {
+    let page_text_dir = config.paths.page_text_dir();
+    let default_language = config.site.default_language.as_str();
+    let default_catalog_path = catalog_path(fs, &page_text_dir, default_language);
+
+    let format = ExchangeFormat::from_path(file_path).ok_or_else(|| anyhow!("Unknown translation file type {:?}; expected .po, .xlf or .xliff", file_path))?;
+    let content = fs.read_to_string(file_path).with_context(|| format!("Failed to read {:?}", file_path))?;
+    let imported = match format {
+        ExchangeFormat::Po => parse_po(&content),
+        ExchangeFormat::Xliff => parse_xliff(&content),
+    }.with_context(|| format!("Failed to parse {:?}", file_path))?;
+
+    let language_code = language_code.map(str::to_string).or(imported.language_code)
+        .ok_or_else(|| anyhow!("{:?} doesn't say which language it is; pass --lang", file_path))?;
+    if language_code == default_language {
+        bail!("Refusing to import into the default language ({}); edit {} directly", default_language, default_catalog_path.display());
+    }
+
+    let catalog_path = catalog_path(fs, &page_text_dir, &language_code);
+    let source_record_path = source_text_record_path(&page_text_dir, &language_code);
+    let default_catalog = read_catalog(fs, &default_catalog_path)?;
+    let mut translated_catalog = read_catalog(fs, &catalog_path)?;
+    let mut source_record = read_catalog(fs, &source_record_path)?;
+
+    let summary = apply_imported_units(&mut translated_catalog, &mut source_record, &default_catalog, &imported.units);
+    write_catalog(fs, &catalog_path, &translated_catalog)?;
+    write_catalog(fs, &source_record_path, &source_record)?;
+
+    Ok(ImportReport { language_code, catalog_path, default_catalog_path, summary })
+}
+
+/// The language codes of the catalogs in the page_text directory, e.g. ["en", "ie", "jp"]
+fn translation_language_codes(fs: &dyn FileSystem, page_text_dir: &Path) -> Result<Vec<String>> {
+    Ok(find_catalog_files(fs, page_text_dir)?
+        .iter()
+        .filter_map(|path| catalog_language_code(path).map(str::to_string))
+        .collect())
+}
@@ -0,0 +1,399 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use image::DynamicImage;
+use image::imageops::FilterType;
+use rayon::prelude::*;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use crate::components::resolve_relative_reference;
+use crate::fingerprint::strip_fingerprint;
+use crate::vfs::FileSystem;
+
+// === RESPONSIVE IMAGES ===
+// Images referenced by an <img> tag get smaller / more modern versions generated next to them:
+//      shared/images/page-images/products/boot.jpg
+//      shared/images/page-images/products/boot-480w.avif, boot-480w.webp, boot-960w.avif, ...
+// and the <img> tag is wrapped in a <picture>, so the browser picks the best one it supports:
+//      <picture>
+//          <source type="image/avif" srcset="boot-480w.avif 480w, boot-960w.avif 960w" sizes="100vw">
+//          <source type="image/webp" srcset="boot-480w.webp 480w, boot-960w.webp 960w" sizes="100vw">
+//          <img src="boot.jpg" width="1200" height="900" ...>
+//      </picture>
+// Encoding is slow, so every variant is cached by the hash of the image's content; unchanged images are never re-encoded.
+
+/// File types that variants are generated for. (svg doesn't need them, and gif may be animated)
+pub const PROCESSABLE_IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+/// A modern image format that variants are encoded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VariantFormat {
+    Avif,
+    Webp,
+}
+
+impl VariantFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VariantFormat::Avif => "avif",
+            VariantFormat::Webp => "webp",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            VariantFormat::Avif => "image/avif",
+            VariantFormat::Webp => "image/webp",
+        }
+    }
+}
+
+/// Which variants to generate, and how the cache of encoded variants is used.
+#[derive(Debug, Clone)]
+pub struct ImageSettings {
+    /// Widths (in pixels) of the generated variants. Widths larger than the image itself are skipped.
+    pub widths: Vec<u32>,
+    /// Formats in order of preference; the browser uses the first one it supports.
+    pub formats: Vec<VariantFormat>,
+    /// Encoding quality, 1-100.
+    pub quality: u8,
+    /// The `sizes` attribute given to the <source> tags when the <img> doesn't have its own.
+    pub default_sizes: String,
+    /// Only use the variants already in the cache, without adding any (or creating the cache), e.g. for a check,
+    /// which mustn't change the project. The variants that aren't in it are encoded straight into the website.
+    pub read_only_cache: bool,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        ImageSettings {
+            widths: vec![480, 960, 1600],
+            formats: vec![VariantFormat::Avif, VariantFormat::Webp],
+            quality: 75,
+            default_sizes: "100vw".to_string(),
+            read_only_cache: false,
+        }
+    }
+}
+
+/// A generated variant of an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageVariant {
+    pub path: PathBuf,
+    pub width: u32,
+    pub format: VariantFormat,
+}
+
+/// An image, along with its intrinsic size and the variants generated for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessedImage {
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<ImageVariant>,
+}
+
+/// The outcome of `make_images_responsive`.
+#[derive(Debug, Default)]
+pub struct ResponsiveImagesSummary {
+    pub images_processed: usize,
+    pub variants_encoded: usize,
+    pub variants_from_cache: usize,
+    pub html_files_rewritten: usize,
+    /// Images that couldn't be read / encoded, with the reason. Their <img> tags are left as they were.
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+/// Returns true for images that variants can be generated for (based on the extension).
+pub fn is_processable_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| PROCESSABLE_IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Returns the widths to generate for an image of the given width.
+/// Only widths smaller than the image are used, plus the image's own width (so there's always a full size modern version).
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::images::variant_widths;
+/// assert_eq!(variant_widths(1200, &[480, 960, 1600]), vec![480, 960, 1200]);
+/// assert_eq!(variant_widths(300, &[480, 960, 1600]), vec![300]);
+/// ```
+pub fn variant_widths(original_width: u32, widths: &[u32]) -> Vec<u32> {
+    let mut result: BTreeSet<u32> = widths.iter().copied().filter(|w| *w > 0 && *w < original_width).collect();
+    result.insert(original_width);
+    result.into_iter().collect()
+}
+
+/// Returns the file name of a variant, which sits next to the original image.
+/// Works on both file paths and urls.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::images::{variant_file_name, VariantFormat};
+/// assert_eq!(variant_file_name("../shared/images/boot.jpg", 480, VariantFormat::Webp), "../shared/images/boot-480w.webp");
+/// ```
+pub fn variant_file_name(image_path: &str, width: u32, format: VariantFormat) -> String {
+    let (directory, file_name) = match image_path.rfind('/') {
+        Some(index) => image_path.split_at(index + 1),
+        None => ("", image_path),
+    };
+    let stem = match file_name.rfind('.') {
+        Some(index) if index > 0 => &file_name[..index],
+        _ => file_name,
+    };
+    format!("{}{}-{}w.{}", directory, stem, width, format.extension())
+}
+
+/// Returns the hex sha256 hash of some content.
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Generates the variants of a single image next to it, re-using previously encoded variants from the cache.
+///
+/// # Arguments
+/// * `fs` - Where the image and the cache are. e.g. &DiskFileSystem
+/// * `image_path` - The image, e.g. 'actual-website-do-not-edit/shared/images/boot.jpg'.
+/// * `cache_dir` - Where encoded variants are kept between builds. Created if it doesn't exist (unless `settings.read_only_cache`).
+/// * `settings` - Which variants to generate.
+///
+/// # Returns
+/// * The processed image, and the number of variants that had to be encoded (i.e. weren't cached).
+pub fn process_image(fs: &dyn FileSystem, image_path: &Path, cache_dir: &Path, settings: &ImageSettings) -> Result<(ProcessedImage, usize)> {
+    let content = fs.read(image_path).with_context(|| format!("Failed to read image '{}'", image_path.display()))?;
+    let (width, height) = image::ImageReader::new(Cursor::new(&content))
+        .with_guessed_format()?
+        .into_dimensions()
+        .with_context(|| format!("Failed to read the size of '{}'", image_path.display()))?;
+
+    // The cache key covers everything that changes the encoded output
+    let hash = content_hash(&content);
+    if !settings.read_only_cache {
+        fs.create_dir_all(cache_dir).with_context(|| format!("Failed to create image cache '{}'", cache_dir.display()))?;
+    }
+
+    let mut decoded: Option<DynamicImage> = None;
+    let mut encoded_count = 0;
+    let mut variants = Vec::new();
+    let image_path_str = image_path.to_string_lossy();
+
+    for format in &settings.formats {
+        for variant_width in variant_widths(width, &settings.widths) {
+            let cached_path = cache_dir.join(format!("{}-{}w-q{}.{}", &hash[..32], variant_width, settings.quality, format.extension()));
+            let variant_path = PathBuf::from(variant_file_name(&image_path_str, variant_width, *format));
+
+            if !fs.is_file(&cached_path) {
+                if decoded.is_none() {
+                    decoded = Some(image::load_from_memory(&content).with_context(|| format!("Failed to decode '{}'", image_path.display()))?);
+                }
+                let original = decoded.as_ref().unwrap();
+                let resized = if variant_width == width { original.clone() } else { original.resize(variant_width, u32::MAX, FilterType::Lanczos3) };
+                let bytes = encode_image(&resized, *format, settings.quality).with_context(|| format!("Failed to encode '{}' as {}", image_path.display(), format.extension()))?;
+                encoded_count += 1;
+                if settings.read_only_cache {
+                    fs.write(&variant_path, &bytes).with_context(|| format!("Failed to write '{}'", variant_path.display()))?;
+                    variants.push(ImageVariant { path: variant_path, width: variant_width, format: *format });
+                    continue;
+                }
+
+                // Write under a temporary name first, so a cancelled build can't leave a half written cache entry
+                let partial_path = cached_path.with_extension("partial");
+                fs.write(&partial_path, &bytes)?;
+                fs.rename(&partial_path, &cached_path)?;
+            }
+
+            let is_up_to_date = fs.file_size(&variant_path).ok().zip(fs.file_size(&cached_path).ok()).is_some_and(|(a, b)| a == b);
+            if !is_up_to_date {
+                fs.copy(&cached_path, &variant_path).with_context(|| format!("Failed to copy '{}' to '{}'", cached_path.display(), variant_path.display()))?;
+            }
+            variants.push(ImageVariant { path: variant_path, width: variant_width, format: *format });
+        }
+    }
+
+    Ok((ProcessedImage { width, height, variants }, encoded_count))
+}
+
+/// Encodes an image in the given format.
+fn encode_image(image: &DynamicImage, format: VariantFormat, quality: u8) -> Result<Vec<u8>> {
+    let rgba = image.to_rgba8();
+    match format {
+        VariantFormat::Webp => {
+            let encoded = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height()).encode(quality as f32);
+            Ok(encoded.to_vec())
+        }
+        VariantFormat::Avif => {
+            let mut bytes = Vec::new();
+            // Speed 10 is the fastest setting; slower ones only shave a few percent off the size, for several times the encoding time
+            let encoder = image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut bytes, 10, quality);
+            DynamicImage::ImageRgba8(rgba).write_with_encoder(encoder)?;
+            Ok(bytes)
+        }
+    }
+}
+
+// === REWRITING <img> TAGS ===
+
+/// Returns the value of an attribute in a single html tag, e.g. `src` in `<img src="a.jpg">`.
+pub fn tag_attribute(tag: &str, name: &str) -> Option<String> {
+    let attribute_regex = Regex::new(&format!(r#"(?i)\s{}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#, regex::escape(name))).ok()?;
+    let captures = attribute_regex.captures(tag)?;
+    captures.get(1).or(captures.get(2)).or(captures.get(3)).map(|m| m.as_str().to_string())
+}
+
+/// Returns the src of every <img> tag in some html.
+pub fn find_img_sources(html: &str) -> Vec<String> {
+    let img_regex = Regex::new(r"(?is)<img\b[^>]*>").unwrap();
+    img_regex.find_iter(html).filter_map(|m| tag_attribute(m.as_str(), "src")).collect()
+}
+
+/// Returns true if the src is a file on this website (as opposed to another website, inline data, or an un-filled template parameter).
+pub fn is_local_image_source(src: &str) -> bool {
+    let src = src.trim();
+    !(src.is_empty() || src.contains("://") || src.starts_with("//") || src.starts_with("data:") || src.starts_with('/') || src.contains('{'))
+}
+
+/// Wraps every <img> tag that has processed variants in a <picture> with a <source> per format,
+/// and gives it its intrinsic width / height (so the page doesn't jump about as images load).
+/// Tags that are already inside a <picture>, or already have a srcset, are left alone.
+///
+/// # Arguments
+/// * `html` - The html to rewrite.
+/// * `resolve` - Returns the processed image for an <img> src, or None to leave the tag alone.
+///
+/// # Returns
+/// * The rewritten html.
+pub fn rewrite_img_tags(html: &str, resolve: impl Fn(&str) -> Option<ProcessedImage>, default_sizes: &str) -> String {
+    let img_regex = Regex::new(r"(?is)<img\b[^>]*>").unwrap();
+    let lowercase_html = html.to_lowercase();
+    let mut result = String::with_capacity(html.len());
+    let mut last_end = 0;
+
+    for img_match in img_regex.find_iter(html) {
+        let tag = img_match.as_str();
+        let before = &lowercase_html[..img_match.start()];
+        let is_in_picture = before.rfind("<picture").is_some_and(|open| before.rfind("</picture").is_none_or(|close| close < open));
+        if is_in_picture || tag_attribute(tag, "srcset").is_some() {
+            continue;
+        }
+        let Some(src) = tag_attribute(tag, "src") else { continue };
+        let Some(image) = resolve(&src) else { continue };
+        if image.variants.is_empty() {
+            continue;
+        }
+
+        // Keep the original tag, but make sure it has its intrinsic size
+        let mut new_tag = tag.to_string();
+        for (name, value) in [("width", image.width), ("height", image.height)] {
+            if tag_attribute(&new_tag, name).is_none() {
+                let insert_at = new_tag.trim_end_matches('>').trim_end_matches('/').trim_end().len();
+                new_tag.insert_str(insert_at, &format!(r#" {}="{}""#, name, value));
+            }
+        }
+        let sizes = tag_attribute(tag, "sizes").unwrap_or_else(|| default_sizes.to_string());
+
+        // One <source> per format, with every width of that format
+        let mut formats: BTreeMap<VariantFormat, Vec<String>> = BTreeMap::new();
+        for variant in &image.variants {
+            formats.entry(variant.format).or_default().push(format!("{} {}w", variant_file_name(&src, variant.width, variant.format), variant.width));
+        }
+        let mut picture = String::from("<picture>");
+        for (format, srcset) in &formats {
+            picture.push_str(&format!(r#"<source type="{}" srcset="{}" sizes="{}">"#, format.mime_type(), srcset.join(", "), sizes));
+        }
+        picture.push_str(&new_tag);
+        picture.push_str("</picture>");
+
+        result.push_str(&html[last_end..img_match.start()]);
+        result.push_str(&picture);
+        last_end = img_match.end();
+    }
+
+    result.push_str(&html[last_end..]);
+    result
+}
+
+/// Resolves an <img> src against the directory of the html file it is in, without touching the file system.
+/// e.g. 'site/blog/post.html' + '../shared/images/boot.jpg?v=2' -> 'site/shared/images/boot.jpg'
+pub fn resolve_image_source(html_file: &Path, src: &str) -> PathBuf {
+    resolve_relative_reference(html_file, src)
+}
+
+/// Returns the original of a fingerprinted copy of an image (see fingerprint.rs), or the path as it was.
+/// e.g. 'shared/images/boot.3f9a1c2b.jpg' -> 'shared/images/boot.jpg'
+fn original_image_path(fs: &dyn FileSystem, image_path: PathBuf) -> PathBuf {
+    let file_name = image_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    match strip_fingerprint(&file_name).map(|original| image_path.with_file_name(original)) {
+        Some(original_path) if fs.is_file(&original_path) => original_path,
+        _ => image_path,
+    }
+}
+
+/// The image stage of the build: generates the variants of every image used by an <img> tag in the output,
+/// and rewrites those tags to use them.
+///
+/// # Arguments
+/// * `fs` - Where the website and the cache are. e.g. &DiskFileSystem
+/// * `output_dir` - The compiled website, e.g. 'actual-website-do-not-edit'.
+/// * `cache_dir` - Where encoded variants are kept between builds.
+/// * `settings` - Which variants to generate.
+pub fn make_images_responsive(fs: &dyn FileSystem, output_dir: &Path, cache_dir: &Path, settings: &ImageSettings) -> Result<ResponsiveImagesSummary> {
+    let mut summary = ResponsiveImagesSummary::default();
+    let html_files: Vec<PathBuf> = fs.walk_files(output_dir)
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+        .collect();
+
+    // 1) Every image used by the pages.
+    // (Images that are already in a <picture> are included, so their variants get regenerated if the image changed.
+    //  Their src may have been fingerprinted by an earlier build, in which case it's the original image that counts.)
+    let mut image_paths: BTreeSet<PathBuf> = BTreeSet::new();
+    for html_file in &html_files {
+        let html = fs.read_to_string(html_file).with_context(|| format!("Failed to read '{}'", html_file.display()))?;
+        for src in find_img_sources(&html).iter().filter(|src| is_local_image_source(src)) {
+            let image_path = original_image_path(fs, resolve_image_source(html_file, src));
+            if is_processable_image(&image_path) && fs.is_file(&image_path) {
+                image_paths.insert(image_path);
+            }
+        }
+    }
+
+    // 2) Generate the variants (in parallel, since encoding is slow)
+    let results: Vec<(PathBuf, Result<(ProcessedImage, usize)>)> = image_paths
+        .into_par_iter()
+        .map(|image_path| {
+            let result = process_image(fs, &image_path, cache_dir, settings);
+            (image_path, result)
+        })
+        .collect();
+
+    let mut processed: BTreeMap<PathBuf, ProcessedImage> = BTreeMap::new();
+    for (image_path, result) in results {
+        match result {
+            Ok((image, encoded_count)) => {
+                summary.images_processed += 1;
+                summary.variants_encoded += encoded_count;
+                summary.variants_from_cache += image.variants.len() - encoded_count;
+                processed.insert(image_path, image);
+            }
+            Err(e) => summary.failures.push((image_path, format!("{:#}", e))),
+        }
+    }
+
+    // 3) Rewrite the <img> tags
+    for html_file in &html_files {
+        let html = fs.read_to_string(html_file)?;
+        let rewritten = rewrite_img_tags(
+            &html,
+            |src| if is_local_image_source(src) { processed.get(&resolve_image_source(html_file, src)).cloned() } else { None },
+            &settings.default_sizes,
+        );
+        if rewritten != html {
+            fs.write(html_file, rewritten.as_bytes()).map_err(|e| anyhow!("Failed to write '{}': {}", html_file.display(), e))?;
+            summary.html_files_rewritten += 1;
+        }
+    }
+
+    Ok(summary)
+}
@@ -1,2 +1,24 @@
 pub mod components;
 
+pub mod localization;
+pub mod i18n;
+pub mod translations;
+pub mod images;
+pub mod fingerprint;
+pub mod minify;
+pub mod bundling;
+pub mod critical_css;
+pub mod typescript_modules;
+pub mod source_maps;
+pub mod scss;
+pub mod processors;
+pub mod staging;
+pub mod config;
+pub mod site;
+pub mod vfs;
+pub mod scaffold;
+pub mod serve;
+pub mod routing;
+pub mod github_pages;
+pub mod links;
+pub mod link_checker;
@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{Context, Result};
+use regex::Regex;
+use crate::config::SiteConfig;
+use crate::fingerprint::strip_fingerprint;
+use crate::github_pages::base_path;
+use crate::links::{map_css_urls, map_html_links, resolve_link_in_website};
+use crate::vfs::FileSystem;
+
+// === CHECKING THE LINKS ===
+// After a build, every link to the website's own files is looked up in the output, so a broken link (or a missing image)
+// is found by the build rather than by someone clicking it:
+//      -> html: href / src / srcset / action / poster (so the link-path of components and the img-file of images too)
+//      -> css: url()
+// A link can be relative, from the root (/shared/...), with the base path (/repo/shared/...), or a url of the website itself
+// (https://example.com/shared/...); with a base path, a link from the domain's root without it (/shared/...) is broken.
+// It is found if it is a file, a folder with an index.html, or (as GitHub Pages serves about.html at /about) a page without
+// its .html. A #fragment has to be the id (or name) of something on the page it points at.
+// Each broken link is reported with the edit-me file of the page it is on.
+// (Links to other websites, e-mail addresses, data, unfilled {parameters}, links in html comments, and links made by
+// typescript aren't checked; neither are fragments of things typescript adds to a page, so use #top, or none, for those)
+
+/// Matches html comments (whose links aren't checked).
+static COMMENT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+
+/// Matches the ids (and names) of the things on a page, which #fragments point at.
+static ID_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?i)\s(?:id|name)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+))"#).unwrap());
+
+/// A link that doesn't point at anything in the website.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// The file the link is in, relative to the output directory. e.g. blog/sleep.html
+    pub file: PathBuf,
+    /// The edit-me file that is published as that file, if there is one. e.g. edit-me/pages/all-blogs/blog/sleep.html
+    pub source: Option<PathBuf>,
+    /// The link, as it is written. e.g. ../shared/images/boot.jpg
+    pub link: String,
+    /// What is wrong with it. e.g. "there is no 'shared/images/boot.jpg'"
+    pub problem: String,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(formatter, "Broken link '{}' in '{}' (published as '{}'): {}", self.link, source.display(), self.file.display(), self.problem),
+            None => write!(formatter, "Broken link '{}' in '{}': {}", self.link, self.file.display(), self.problem),
+        }
+    }
+}
+
+/// What checking the links found.
+#[derive(Debug, Default)]
+pub struct LinkCheckSummary {
+    /// How many html / css files were checked.
+    pub files_checked: usize,
+    /// How many links to the website's own files were looked up.
+    pub links_checked: usize,
+    /// The links that weren't found, by file and then link.
+    pub broken: Vec<BrokenLink>,
+}
+
+/// What the links from the website's root start with, the longest first.
+/// e.g. ["https://user.github.io/repo/", "/repo/"] for a site at https://user.github.io/repo, or ["/"] for one at a domain's root
+/// (With a base path, "/" isn't the website's root: /about.html is another website's page on the same domain)
+pub fn root_urls(config: &SiteConfig) -> Vec<String> {
+    let mut root_urls = Vec::new();
+    if let Some(base_url) = &config.site.base_url {
+        root_urls.push(format!("{}/", base_url.trim_end_matches('/')));
+    }
+    let base_path = base_path(config);
+    if base_path.is_empty() {
+        root_urls.push("/".to_string());
+    } else {
+        root_urls.push(format!("{}/", base_path));
+    }
+    root_urls
+}
+
+/// Checks that every internal link of a built website's html and css points at something in it. (see the top of this file)
+///
+/// # Arguments
+/// * `fs` - Where the website is read from. e.g. &DiskFileSystem
+/// * `output_dir` - The built website. e.g. actual-website-do-not-edit
+/// * `published` - Each published file (relative to the output directory) and the edit-me file it comes from. (see BuildReport)
+/// * `root_urls` - What the links from the root start with. (see `root_urls`)
+///
+/// # Returns
+/// * The links checked, and the broken ones.
+/// * `Err` if a file couldn't be read.
+pub fn check_links(fs: &dyn FileSystem, output_dir: &Path, published: &BTreeMap<PathBuf, PathBuf>, root_urls: &[String]) -> Result<LinkCheckSummary> {
+    let mut summary = LinkCheckSummary::default();
+    // (the ids of each page a #fragment points at, read once)
+    let mut page_ids: HashMap<PathBuf, BTreeSet<String>> = HashMap::new();
+
+    let mut files = fs.walk_files(output_dir);
+    files.sort();
+    for file_path in files {
+        let extension = file_path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_string();
+        if extension != "html" && extension != "css" {
+            continue;
+        }
+        let Ok(relative_path) = file_path.strip_prefix(output_dir) else { continue };
+        let content = fs.read_to_string(&file_path).with_context(|| format!("Failed to read '{}'", file_path.display()))?;
+
+        let mut links = BTreeSet::new();
+        if extension == "html" {
+            map_html_links(&COMMENT_REGEX.replace_all(&content, ""), |link| {
+                links.insert(link.to_string());
+                None
+            });
+        } else {
+            map_css_urls(&content, |url| {
+                links.insert(url.to_string());
+                None
+            });
+        }
+        summary.files_checked += 1;
+
+        for link in links {
+            // (a url(#x) of css is an svg filter / mask, which isn't checked)
+            if extension == "css" && link.trim().starts_with('#') {
+                continue;
+            }
+            let Some(target) = internal_target(&link, relative_path, root_urls) else { continue };
+            summary.links_checked += 1;
+            let problem = match target {
+                Err(problem) => Some(problem),
+                Ok((path, fragment)) => match find_published_file(fs, output_dir, &path) {
+                    None => Some(format!("there is no '{}'", path)),
+                    Some(page) => fragment.filter(|fragment| !has_id(fs, &page, fragment, &mut page_ids)).map(|fragment| {
+                        format!("'{}' has nothing with the id '{}'", page.strip_prefix(output_dir).unwrap_or(&page).display(), fragment)
+                    }),
+                },
+            };
+            if let Some(problem) = problem {
+                summary.broken.push(BrokenLink { file: relative_path.to_path_buf(), source: source_of(published, relative_path), link, problem });
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Where a link points in the website: its path (relative to the root, "" for the home page) and #fragment,
+/// `Err` if it goes above the website's root (or is from the domain's root, but the website is under a base path),
+/// or None if it isn't to the website (or isn't checked).
+fn internal_target(link: &str, file: &Path, root_urls: &[String]) -> Option<Result<(String, Option<String>), String>> {
+    let link = link.trim();
+    if link.is_empty() || link.contains(['{', '}', '<']) {
+        return None;
+    }
+    let (without_fragment, fragment) = match link.split_once('#') {
+        Some((before, fragment)) => (before, Some(percent_decoded(fragment)).filter(|fragment| !fragment.is_empty() && fragment != "top")),
+        None => (link, None),
+    };
+    let path = without_fragment.split('?').next().unwrap_or_default();
+
+    let from_root = root_urls.iter().find_map(|root_url| path.strip_prefix(root_url.as_str()));
+    let path = match from_root {
+        Some(from_root) if !path.starts_with("//") => resolve_link_in_website(Path::new(""), &percent_decoded(from_root)),
+        // (another website, an e-mail address, data, ...)
+        _ if path.starts_with("//") || path.split('/').next().is_some_and(|first| first.contains(':')) => return None,
+        // (from the domain's root, without the base path: e.g. /about.html for a website at /repo/)
+        _ if path.starts_with('/') => {
+            let website_root = root_urls.last().map(String::as_str).unwrap_or("/");
+            return Some(Err(format!("it is outside the website, which is at '{}'", website_root)));
+        }
+        // (the page it is on)
+        _ if path.is_empty() => Some(file.to_string_lossy().replace('\\', "/")),
+        _ => resolve_link_in_website(file.parent().unwrap_or(Path::new("")), &percent_decoded(path)),
+    };
+    Some(match path {
+        Some(path) => Ok((path, fragment)),
+        None => Err("it goes above the website's root".to_string()),
+    })
+}
+
+/// The file a path of the website is served from: the file itself, a folder's index.html, or a page without its .html.
+fn find_published_file(fs: &dyn FileSystem, output_dir: &Path, path: &str) -> Option<PathBuf> {
+    let file_path = output_dir.join(path.trim_end_matches('/'));
+    let mut candidates = Vec::new();
+    if !path.is_empty() && !path.ends_with('/') {
+        candidates.push(file_path.clone());
+    }
+    candidates.push(file_path.join("index.html"));
+    if !path.ends_with('/') && file_path.extension().is_none() {
+        candidates.push(file_path.with_extension("html"));
+    }
+    candidates.into_iter().find(|candidate| fs.is_file(candidate))
+}
+
+/// True if a page has something with this id (or name).
+fn has_id(fs: &dyn FileSystem, page: &Path, id: &str, page_ids: &mut HashMap<PathBuf, BTreeSet<String>>) -> bool {
+    if page.extension().is_none_or(|extension| extension != "html") {
+        return true;
+    }
+    page_ids.entry(page.to_path_buf()).or_insert_with(|| {
+        let content = fs.read_to_string(page).unwrap_or_default();
+        ID_REGEX.captures_iter(&content)
+            .filter_map(|captures| captures.get(1).or(captures.get(2)).or(captures.get(3)).map(|id| id.as_str().to_string()))
+            .collect()
+    }).contains(id)
+}
+
+/// The edit-me file published as a file of the website (or, for an asset with a hash in its name, as the file without it).
+fn source_of(published: &BTreeMap<PathBuf, PathBuf>, file: &Path) -> Option<PathBuf> {
+    published.get(file).cloned().or_else(|| {
+        let original = strip_fingerprint(&file.file_name()?.to_string_lossy())?;
+        published.get(&file.with_file_name(original)).cloned()
+    })
+}
+
+/// A link with its %-escapes decoded. e.g. my%20photo.png -> my photo.png
+fn percent_decoded(link: &str) -> String {
+    let bytes = link.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%' && i + 2 < bytes.len())
+            .then(|| std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+            .flatten();
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
@@ -0,0 +1,227 @@
+use std::path::{Component, Path};
+use std::sync::LazyLock;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use crate::components::relative_path_to_root;
+use crate::config::{LinkSetting, SiteConfig};
+use crate::github_pages::base_path;
+use crate::vfs::FileSystem;
+
+// === LINK MODES ===
+// How the links to the website's own files (the <root>/ placeholders, and the relative links of the html and css) are written:
+//      links = "relative"      (the default) <root>/shared/global.css in blog/sleep.html -> ../shared/global.css
+//      links = "absolute"      -> /shared/global.css                       (with the base path, e.g. /repo/shared/global.css)
+//      links = "url"           -> https://example.com/shared/global.css    (from [site] base-url, which has to be set)
+// Relative links are worked out from where each file is actually published (so they are right at any depth, for pages
+// with permalinks, and for language versions). They work wherever the website is opened (even from the file system).
+// Absolute links / urls are the same from every page, which is what a page copied elsewhere (e.g. 404.html, or an
+// e-mail / rss version) needs.
+//
+// The build works with relative links (the bundling, critical css, responsive images and asset hashing all follow them),
+// and the html links (href / src / srcset / action / poster) and css url()s are made absolute once it is done.
+// <root>/ in a script is replaced with the absolute link straight away: a url in a script is relative to the page that
+// runs it, not to the script, so a relative one is only right for pages in the script's folder.
+
+/// Matches the attributes of html that hold links (quoted, or unquoted as the minifier can leave them).
+static HTML_LINK_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?i)(\s(href|src|srcset|action|poster)\s*=\s*)(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+))"#).unwrap());
+
+/// Matches the url()s of css.
+static CSS_URL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"url\(\s*(['"]?)([^'")]+)(['"]?)\s*\)"#).unwrap());
+
+/// How the links to the website's own files are written. (see the top of this file)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum LinkMode {
+    /// e.g. ../shared/global.css
+    #[default]
+    Relative,
+    /// e.g. /shared/global.css, or /repo/shared/global.css (the base path doesn't end in a /, and is "" for none)
+    Absolute { base_path: String },
+    /// e.g. https://example.com/shared/global.css (the base url doesn't end in a /)
+    Url { base_url: String },
+}
+
+impl LinkMode {
+    /// The link mode of a site's `links` setting. (Absolute links get the site's base path, see github_pages.rs)
+    pub fn from_setting(setting: Option<LinkSetting>, config: &SiteConfig) -> LinkMode {
+        match setting {
+            Some(LinkSetting::Absolute) => LinkMode::Absolute { base_path: base_path(config) },
+            Some(LinkSetting::Url) => LinkMode::Url { base_url: config.site.base_url.as_deref().unwrap_or_default().trim_end_matches('/').to_string() },
+            Some(LinkSetting::Relative) | None => LinkMode::Relative,
+        }
+    }
+
+    /// What a link from the website's root starts with, or None for relative links.
+    /// e.g. "/", "/repo/", "https://example.com/"
+    pub fn root_url(&self) -> Option<String> {
+        match self {
+            LinkMode::Relative => None,
+            LinkMode::Absolute { base_path } => Some(format!("{}/", base_path)),
+            LinkMode::Url { base_url } => Some(format!("{}/", base_url)),
+        }
+    }
+
+    /// What `<root>/` is replaced with in a file.
+    ///
+    /// # Arguments
+    /// * `file` - Where the file is published. e.g. actual-website-do-not-edit/blog/sleep.html
+    /// * `output_dir` - The website's root. e.g. actual-website-do-not-edit
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use websiteCompiler::links::LinkMode;
+    /// let file = Path::new("site/blog/social/ugly-people/index.html");
+    /// assert_eq!(LinkMode::Relative.root_prefix(file, Path::new("site")), "../../../");
+    /// assert_eq!(LinkMode::Absolute { base_path: "/repo".to_string() }.root_prefix(file, Path::new("site")), "/repo/");
+    /// ```
+    pub fn root_prefix(&self, file: &Path, output_dir: &Path) -> String {
+        self.root_url().unwrap_or_else(|| relative_path_to_root(file, output_dir))
+    }
+}
+
+/// True if a link is relative, and to a file of this website. (Not another website, an e-mail address, data, a link
+/// within the page, a link from the root, or a parameter that wasn't filled in)
+pub fn is_relative_link(link: &str) -> bool {
+    let is_external = link.split('/').next().is_some_and(|first| first.contains(':'));
+    !(link.is_empty() || link.starts_with(['/', '#']) || is_external || link.contains(['{', '}', '<']))
+}
+
+/// A relative link resolved against a folder of the website (keeping its ?query / #fragment), or None if it goes above the root.
+/// e.g. blog + ../about.css?v=2 -> about.css?v=2
+pub fn resolve_link_in_website(folder: &Path, link: &str) -> Option<String> {
+    let (path, suffix) = link.split_at(link.find(['?', '#']).unwrap_or(link.len()));
+    let mut parts: Vec<String> = Vec::new();
+    for component in folder.join(path).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => parts.pop().map(|_| ())?,
+            _ => {}
+        }
+    }
+    let trailing_slash = if path.ends_with('/') && !parts.is_empty() { "/" } else { "" };
+    Some(format!("{}{}{}", parts.join("/"), trailing_slash, suffix))
+}
+
+// ============================================================
+// FINDING / CHANGING THE LINKS
+// ============================================================
+
+/// Changes each link of some html (href / src / action / poster, and each url of a srcset), leaving the rest as it is.
+///
+/// # Arguments
+/// * `change` - Given a link, returns what it becomes, or None to leave it as it is.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::links::map_html_links;
+/// let html = r#"<img src="a.png" srcset="a-480.png 480w, a-960.png 960w"><a href=b.html>B</a>"#;
+/// assert_eq!(map_html_links(html, |link| Some(format!("/{}", link))), r#"<img src="/a.png" srcset="/a-480.png 480w, /a-960.png 960w"><a href=/b.html>B</a>"#);
+/// ```
+pub fn map_html_links(content: &str, mut change: impl FnMut(&str) -> Option<String>) -> String {
+    HTML_LINK_REGEX.replace_all(content, |captures: &Captures| {
+        let (value, quote) = match (captures.get(3), captures.get(4), captures.get(5)) {
+            (Some(value), _, _) => (value.as_str(), "\""),
+            (None, Some(value), _) => (value.as_str(), "'"),
+            (None, None, Some(value)) => (value.as_str(), ""),
+            _ => return captures[0].to_string(),
+        };
+        let changed = if captures[2].eq_ignore_ascii_case("srcset") {
+            // e.g. "a-480.png 480w, a-960.png 960w" (each url, then its width / density)
+            value.split(',').map(|candidate| {
+                let trimmed = candidate.trim_start();
+                let url_end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+                let (url, descriptor) = trimmed.split_at(url_end);
+                let leading = &candidate[..candidate.len() - trimmed.len()];
+                format!("{}{}{}", leading, change(url).unwrap_or_else(|| url.to_string()), descriptor)
+            }).collect::<Vec<String>>().join(",")
+        } else {
+            change(value).unwrap_or_else(|| value.to_string())
+        };
+        format!("{}{}{}{}", &captures[1], quote, changed, quote)
+    }).to_string()
+}
+
+/// Changes each url() of some css, leaving the rest as it is.
+///
+/// # Arguments
+/// * `change` - Given a url, returns what it becomes, or None to leave it as it is.
+pub fn map_css_urls(content: &str, mut change: impl FnMut(&str) -> Option<String>) -> String {
+    CSS_URL_REGEX.replace_all(content, |captures: &Captures| {
+        match change(&captures[2]) {
+            Some(url) => format!("url({}{}{})", &captures[1], url, &captures[3]),
+            None => captures[0].to_string(),
+        }
+    }).to_string()
+}
+
+/// The relative links of an html page made absolute (from the root), so they work from any url.
+///
+/// # Arguments
+/// * `page` - Where the page is published, relative to the website's root. e.g. blog/missing.html
+/// * `root_url` - What a link from the root starts with. e.g. "/repo/" (see `LinkMode::root_url`)
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use websiteCompiler::links::absolute_links_in_html;
+/// let html = r#"<link href="../index.css"><a href="sleep.html#tips">Sleep</a><a href="https://example.com">Elsewhere</a>"#;
+/// assert_eq!(
+///     absolute_links_in_html(html, Path::new("blog/missing.html"), "/repo/"),
+///     r#"<link href="/repo/index.css"><a href="/repo/blog/sleep.html#tips">Sleep</a><a href="https://example.com">Elsewhere</a>"#
+/// );
+/// ```
+pub fn absolute_links_in_html(content: &str, page: &Path, root_url: &str) -> String {
+    let folder = page.parent().unwrap_or(Path::new(""));
+    map_html_links(content, |link| absolute_link(folder, link, root_url))
+}
+
+/// The relative url()s of a css file made absolute (from the root). e.g. in shared/global.css, url(images/a.png) -> url(/shared/images/a.png)
+///
+/// # Arguments
+/// * `file` - Where the css is published, relative to the website's root. e.g. shared/global.css
+/// * `root_url` - What a link from the root starts with. e.g. "/"
+pub fn absolute_urls_in_css(content: &str, file: &Path, root_url: &str) -> String {
+    let folder = file.parent().unwrap_or(Path::new(""));
+    map_css_urls(content, |url| absolute_link(folder, url, root_url))
+}
+
+/// A relative link made absolute, or None if it isn't one (or goes above the root).
+fn absolute_link(folder: &Path, link: &str, root_url: &str) -> Option<String> {
+    if !is_relative_link(link) {
+        return None;
+    }
+    resolve_link_in_website(folder, link).map(|path| format!("{}{}", root_url, path))
+}
+
+/// Writes the links of a built website's html and css as the link mode has them. (Nothing to do for relative links)
+/// Links that are already absolute are left alone, so it can run over the output of an incremental build again.
+///
+/// # Arguments
+/// * `output_dir` - The built website. e.g. actual-website-do-not-edit
+/// * `mode` - How the links are written.
+///
+/// # Returns
+/// * How many files were changed.
+/// * `Err` if a file couldn't be read / written.
+pub fn apply_link_mode(fs: &dyn FileSystem, output_dir: &Path, mode: &LinkMode) -> Result<usize> {
+    let Some(root_url) = mode.root_url() else { return Ok(0) };
+    let mut files_changed = 0;
+
+    for file_path in fs.walk_files(output_dir) {
+        let extension = file_path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_string();
+        if extension != "html" && extension != "css" {
+            continue;
+        }
+        let Ok(relative_path) = file_path.strip_prefix(output_dir) else { continue };
+        let content = fs.read_to_string(&file_path).with_context(|| format!("Failed to read '{}'", file_path.display()))?;
+        let changed = match extension.as_str() {
+            "html" => absolute_links_in_html(&content, relative_path, &root_url),
+            _ => absolute_urls_in_css(&content, relative_path, &root_url),
+        };
+        if changed != content {
+            fs.write(&file_path, changed.as_bytes()).with_context(|| format!("Failed to write '{}'", file_path.display()))?;
+            files_changed += 1;
+        }
+    }
+    Ok(files_changed)
+}
@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::vfs::FileSystem;
+
+// === LOCALIZED SIBLING FILES ===
+// A whole page / blog post can be translated by adding a sibling file, with the language code before the extension:
+//      edit-me/pages/all-blogs/blog/social/ugly-people.md      <- default language version
+//      edit-me/pages/all-blogs/blog/social/ugly-people.jp.md   <- japanese version
+// Every language then gets published next to the default file as '<lang>-<name>', i.e. the same naming
+// used for the key based translations (en-index.html, jp-index.html, ...).
+// Languages which have no sibling file fall back to the default language version (for html, translated by its keys, see i18n).
+// Markdown versions are published as markdown (like every markdown file), sharing the default version's front matter.
+
+/// File types that can be localized with sibling files.
+pub const LOCALIZABLE_EXTENSIONS: [&str; 2] = ["html", "md"];
+
+/// Returns the language code of a localized sibling file, or None if it is a default language file.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use websiteCompiler::localization::localized_variant_language;
+/// let languages = vec!["en".to_string(), "jp".to_string()];
+/// assert_eq!(localized_variant_language(Path::new("blog/ugly-people.jp.md"), &languages), Some("jp".to_string()));
+/// assert_eq!(localized_variant_language(Path::new("blog/ugly-people.md"), &languages), None);
+/// ```
+pub fn localized_variant_language(path: &Path, language_codes: &[String]) -> Option<String> {
+    let extension = path.extension().and_then(|e| e.to_str())?;
+    if !LOCALIZABLE_EXTENSIONS.contains(&extension) {
+        return None;
+    }
+
+    // e.g. 'ugly-people.jp' -> 'jp'
+    let stem = path.file_stem().and_then(|s| s.to_str())?;
+    let (name, language_code) = stem.rsplit_once('.')?;
+    if name.is_empty() || !language_codes.iter().any(|code| code == language_code) {
+        return None;
+    }
+    Some(language_code.to_string())
+}
+
+/// Returns the default language sibling of a localized file.
+/// e.g. 'blog/ugly-people.jp.md' -> 'blog/ugly-people.md'
+pub fn default_language_path(variant_path: &Path) -> PathBuf {
+    let (Some(stem), Some(extension)) = (variant_path.file_stem().and_then(|s| s.to_str()), variant_path.extension().and_then(|e| e.to_str())) else {
+        return variant_path.to_path_buf();
+    };
+    match stem.rsplit_once('.') {
+        Some((name, _language_code)) => variant_path.with_file_name(format!("{}.{}", name, extension)),
+        None => variant_path.to_path_buf(),
+    }
+}
+
+/// Returns the path a localized sibling file would have for the given language.
+/// e.g. 'blog/ugly-people.md' + "jp" -> 'blog/ugly-people.jp.md'
+pub fn variant_path_for_language(default_path: &Path, language_code: &str) -> PathBuf {
+    let stem = default_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    match default_path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => default_path.with_file_name(format!("{}.{}.{}", stem, language_code, extension)),
+        None => default_path.with_file_name(format!("{}.{}", stem, language_code)),
+    }
+}
+
+/// Finds the localized sibling files that exist for a default language file.
+/// Returns a map of language code -> variant path. e.g. {"jp": "blog/ugly-people.jp.md"}
+pub fn find_localized_variants(fs: &dyn FileSystem, default_path: &Path, language_codes: &[String]) -> BTreeMap<String, PathBuf> {
+    let is_localizable = default_path.extension().and_then(|e| e.to_str()).is_some_and(|e| LOCALIZABLE_EXTENSIONS.contains(&e));
+    if !is_localizable {
+        return BTreeMap::new();
+    }
+
+    language_codes
+        .iter()
+        .map(|code| (code.clone(), variant_path_for_language(default_path, code)))
+        .filter(|(_, variant_path)| fs.is_file(variant_path))
+        .collect()
+}
+
+/// Returns the published file name of a language version, which sits next to the default language output.
+/// e.g. 'actual-website-do-not-edit/blog/ugly-people.html' + "jp" -> 'actual-website-do-not-edit/blog/jp-ugly-people.html'
+pub fn language_output_path(default_output_path: &Path, language_code: &str) -> PathBuf {
+    let file_name = default_output_path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+    default_output_path.with_file_name(format!("{}-{}", language_code, file_name))
+}
+
+/// Adds the rest of each file's localized group (the default language file and all of its variants) to the set.
+/// A change to one language version changes the links of every other version, so they all need recompiling.
+/// (The files are looked for in `fs`, see vfs.rs)
+pub fn expand_to_localized_groups(fs: &dyn FileSystem, files: &HashSet<String>, language_codes: &[String]) -> HashSet<String> {
+    let mut expanded = files.clone();
+
+    for file in files {
+        let path = Path::new(file);
+        let default_path = match localized_variant_language(path, language_codes) {
+            Some(_) => default_language_path(path),
+            None => path.to_path_buf(),
+        };
+        let variants = find_localized_variants(fs, &default_path, language_codes);
+        if variants.is_empty() {
+            continue;
+        }
+
+        if fs.is_file(&default_path) {
+            expanded.insert(default_path.to_string_lossy().to_string());
+        }
+        for variant_path in variants.values() {
+            expanded.insert(variant_path.to_string_lossy().to_string());
+        }
+    }
+
+    expanded
+}
+
+/// Returns the (hreflang, href) pairs linking every language version of a file together.
+/// Every language has a published version (its own variant, or the default language fallback),
+/// and 'x-default' points at the un-prefixed default file.
+///
+/// # Arguments
+/// * `default_file_name` - e.g. "ugly-people.html"
+/// * `language_codes` - e.g. ["en", "jp"]
+///
+/// # Returns
+/// e.g. [("x-default", "ugly-people.html"), ("en", "en-ugly-people.html"), ("jp", "jp-ugly-people.html")]
+pub fn alternate_language_links(default_file_name: &str, language_codes: &[String]) -> Vec<(String, String)> {
+    let mut sorted_codes: Vec<&String> = language_codes.iter().collect();
+    sorted_codes.sort();
+
+    let mut links = vec![("x-default".to_string(), default_file_name.to_string())];
+    for code in sorted_codes {
+        links.push((code.clone(), format!("{}-{}", code, default_file_name)));
+    }
+    links
+}
+
+// === SHARED METADATA ===
+// Markdown posts may start with a front matter block:
+//      ---
+//      title: Ugly people
+//      tags: social
+//      ---
+// Language versions share the default version's metadata; a variant only needs to list the keys it changes.
+
+const FRONT_MATTER_FENCE: &str = "---";
+
+/// Splits a leading front matter block off of some content.
+/// Returns the (key, value) pairs in order, and the remaining body. Content without front matter returns no pairs.
+pub fn split_front_matter(content: &str) -> (Vec<(String, String)>, &str) {
+    let mut lines = content.split_inclusive('\n');
+    let consumed_first_line = match lines.next() {
+        Some(first_line) if first_line.trim_end() == FRONT_MATTER_FENCE => first_line.len(),
+        _ => return (Vec::new(), content),
+    };
+
+    let mut metadata = Vec::new();
+    let mut consumed = consumed_first_line;
+    for line in lines {
+        consumed += line.len();
+        if line.trim_end() == FRONT_MATTER_FENCE {
+            return (metadata, &content[consumed..]);
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            metadata.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    // No closing fence; it was just a horizontal rule at the start of the file.
+    (Vec::new(), content)
+}
+
+/// Writes (key, value) pairs back out as a front matter block, followed by the body.
+pub fn join_front_matter(metadata: &[(String, String)], body: &str) -> String {
+    if metadata.is_empty() {
+        return body.to_string();
+    }
+
+    let mut output = format!("{}\n", FRONT_MATTER_FENCE);
+    for (key, value) in metadata {
+        output.push_str(&format!("{}: {}\n", key, value));
+    }
+    output.push_str(&format!("{}\n", FRONT_MATTER_FENCE));
+    output.push_str(body);
+    output
+}
+
+/// Builds a markdown language version: the default version's metadata, overridden by the variant's own
+/// metadata, with the language and the links to the other language versions filled in.
+///
+/// # Arguments
+/// * `default_content` - The default language file's content (only its front matter is used)
+/// * `variant_content` - The content being published, i.e. the variant, or the default content itself for fallbacks
+/// * `language_code` - The language of `variant_content`'s body
+/// * `alternates` - The output of `alternate_language_links`
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::localization::merge_shared_metadata;
+/// let alternates = vec![("x-default".to_string(), "a.md".to_string()), ("jp".to_string(), "jp-a.md".to_string())];
+/// let merged = merge_shared_metadata("---\ntags: social\n---\nhi\n", "---\ntitle: やあ\n---\nやあ\n", "jp", &alternates);
+/// assert_eq!(merged, "---\ntags: social\ntitle: やあ\nlang: jp\nalternates: x-default=a.md, jp=jp-a.md\n---\nやあ\n");
+/// ```
+pub fn merge_shared_metadata(default_content: &str, variant_content: &str, language_code: &str, alternates: &[(String, String)]) -> String {
+    let (mut metadata, _) = split_front_matter(default_content);
+    let (variant_metadata, body) = split_front_matter(variant_content);
+
+    let mut set_value = |key: &str, value: String| {
+        match metadata.iter_mut().find(|(existing_key, _)| existing_key == key) {
+            Some(entry) => entry.1 = value,
+            None => metadata.push((key.to_string(), value)),
+        }
+    };
+    for (key, value) in variant_metadata {
+        set_value(&key, value);
+    }
+    set_value("lang", language_code.to_string());
+    let alternates_value = alternates.iter().map(|(code, href)| format!("{}={}", code, href)).collect::<Vec<String>>().join(", ");
+    set_value("alternates", alternates_value);
+
+    join_front_matter(&metadata, body)
+}
+
+/// Sets the `lang` attribute of the `<html>` tag, e.g. `<html lang="en">` -> `<html lang="jp">`.
+/// Adds the attribute if the tag has none. Content without an `<html>` tag is returned unchanged.
+pub fn set_html_language(content: &str, language_code: &str) -> String {
+    let html_tag_regex = regex::Regex::new(r#"(?i)<html\b([^>]*)>"#).expect("Failed to compile html tag regex");
+    let lang_attribute_regex = regex::Regex::new(r#"(?i)\blang\s*=\s*("[^"]*"|'[^']*'|[^\s>]+)"#).expect("Failed to compile lang attribute regex");
+
+    html_tag_regex.replacen(content, 1, |caps: &regex::Captures| {
+        let attributes = &caps[1];
+        let lang_attribute = format!("lang=\"{}\"", language_code);
+        if lang_attribute_regex.is_match(attributes) {
+            format!("<html{}>", lang_attribute_regex.replace(attributes, regex::NoExpand(&lang_attribute)))
+        } else {
+            format!("<html {}{}>", lang_attribute, attributes)
+        }
+    }).to_string()
+}
+
+/// Inserts `<link rel="alternate" hreflang=".." href="..">` tags before `</head>` for each language version.
+/// Links which are already present are not added twice. Content without a `</head>` is returned unchanged.
+pub fn add_alternate_language_links(content: &str, alternates: &[(String, String)]) -> String {
+    let Some(head_pos) = content.find("</head>") else {
+        return content.to_string();
+    };
+
+    let mut head_inserts = String::new();
+    for (language_code, href) in alternates {
+        let link = format!("<link rel=\"alternate\" hreflang=\"{}\" href=\"{}\">\n", language_code, href);
+        if !content.contains(&link) {
+            head_inserts.push_str(&link);
+        }
+    }
+
+    let mut output = content.to_string();
+    output.insert_str(head_pos, &head_inserts);
+    output
+}
@@ -0,0 +1,290 @@
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
+use crate::vfs::FileSystem;
+
+// === MINIFICATION (PRODUCTION BUILDS) ===
+// Production builds strip the whitespace / comments that make the output readable:
+//      html -> whitespace collapsed (apart from in <pre> / <textarea>), comments removed, inline <style> minified
+//      css  -> minified (sass also writes it compressed in the first place)
+//      js   -> comments / indentation removed (the javascript that tsc emits, and any plain .js files)
+// Dev builds leave everything as authored.
+
+/// File types that get minified in production builds.
+pub const MINIFIABLE_EXTENSIONS: [&str; 3] = ["html", "css", "js"];
+
+/// The outcome of `minify_directory`.
+#[derive(Debug, Default)]
+pub struct MinifySummary {
+    pub files_minified: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    /// Files left as they are on purpose (e.g. because they have a source map)
+    pub files_skipped: usize,
+    /// Files that couldn't be minified (e.g. a syntax the minifier doesn't understand), with the reason.
+    /// They are left as they were.
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+/// Minifies html: collapses whitespace (keeping it in <pre> / <textarea>), removes comments,
+/// and minifies the css in <style> tags. (<script> tags are left alone; see `minify_js`)
+pub fn minify_html(html: &str) -> String {
+    let cfg = minify_html::Cfg {
+        // keep the output valid html, rather than the smallest the browsers will put up with
+        keep_closing_tags: true,
+        keep_html_and_head_opening_tags: true,
+        do_not_minify_doctype: true,
+        ensure_spec_compliant_unquoted_attribute_values: true,
+        keep_spaces_between_attributes: true,
+        minify_css: true,
+        ..minify_html::Cfg::default()
+    };
+    String::from_utf8_lossy(&minify_html::minify(html.as_bytes(), &cfg)).to_string()
+}
+
+/// Minifies a css file.
+pub fn minify_css(css: &str) -> Result<String> {
+    let mut stylesheet = StyleSheet::parse(css, ParserOptions::default()).map_err(|e| anyhow!("{}", e))?;
+    stylesheet.minify(MinifyOptions::default()).map_err(|e| anyhow!("{}", e))?;
+    let result = stylesheet.to_css(PrinterOptions { minify: true, ..PrinterOptions::default() }).map_err(|e| anyhow!("{}", e))?;
+    Ok(result.code)
+}
+
+/// Minifies a javascript file, conservatively: comments and indentation are removed, and runs of whitespace are collapsed.
+/// Line breaks are kept (as single newlines), since javascript's automatic semicolon insertion depends on them,
+/// and nothing is renamed / rewritten, so the output always behaves exactly like the input.
+/// (The javascript minifiers that rewrite code, e.g. turning `function f() {}` into `var f = () => {}`, change when a function can be called)
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::minify::minify_js;
+/// assert_eq!(minify_js("function f(a) {\n    // comment\n    return a   /  2; /* done */\n}\n").unwrap(), "function f(a) {\nreturn a / 2;\n}");
+/// ```
+pub fn minify_js(js: &str) -> Result<String> {
+    let chars: Vec<char> = js.chars().collect();
+    let mut output = String::with_capacity(js.len());
+    // One entry per template literal `${ ... }` that we are inside of: the depth of { } braces within it
+    let mut template_stack: Vec<usize> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        // Whitespace and comments: collapsed to a single newline / space
+        if c.is_whitespace() || (c == '/' && (next == Some('/') || next == Some('*'))) {
+            let mut has_newline = false;
+            while i < chars.len() {
+                let (c, next) = (chars[i], chars.get(i + 1).copied());
+                if c.is_whitespace() {
+                    has_newline |= c == '\n';
+                    i += 1;
+                } else if c == '/' && next == Some('/') {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                } else if c == '/' && next == Some('*') {
+                    let end = find_sequence(&chars, i + 2, &['*', '/']).ok_or_else(|| anyhow!("unterminated comment"))?;
+                    has_newline |= chars[i..end].contains(&'\n');
+                    i = end + 2;
+                } else {
+                    break;
+                }
+            }
+            if !output.is_empty() && i < chars.len() {
+                if has_newline {
+                    let trimmed_length = output.trim_end_matches(' ').len();
+                    output.truncate(trimmed_length);
+                    if !output.ends_with('\n') {
+                        output.push('\n');
+                    }
+                } else if !output.ends_with([' ', '\n']) {
+                    output.push(' ');
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '\'' | '"' => {
+                let end = find_string_end(&chars, i, c).ok_or_else(|| anyhow!("unterminated string"))?;
+                output.extend(&chars[i..=end]);
+                i = end + 1;
+            }
+            '`' => {
+                i = copy_template_literal(&chars, i + 1, &mut output, &mut template_stack)?;
+            }
+            '/' if is_regex_start(&output) => {
+                let end = find_regex_end(&chars, i).ok_or_else(|| anyhow!("unterminated regular expression"))?;
+                output.extend(&chars[i..=end]);
+                i = end + 1;
+            }
+            '{' => {
+                if let Some(depth) = template_stack.last_mut() {
+                    *depth += 1;
+                }
+                output.push(c);
+                i += 1;
+            }
+            '}' => {
+                match template_stack.last_mut() {
+                    // The end of a `${ ... }`, so back into the template literal
+                    Some(0) => {
+                        template_stack.pop();
+                        output.push(c);
+                        i = copy_template_literal(&chars, i + 1, &mut output, &mut template_stack)?;
+                    }
+                    Some(depth) => {
+                        *depth -= 1;
+                        output.push(c);
+                        i += 1;
+                    }
+                    None => {
+                        output.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            _ => {
+                output.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    Ok(output.trim_end().to_string())
+}
+
+/// Returns the index of the start of `sequence` in `chars`, searching from `from`.
+fn find_sequence(chars: &[char], from: usize, sequence: &[char]) -> Option<usize> {
+    (from..chars.len()).find(|&i| chars[i..].starts_with(sequence))
+}
+
+/// Returns the index of the closing quote of the string starting at `start`.
+fn find_string_end(chars: &[char], start: usize, quote: char) -> Option<usize> {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote => return Some(i),
+            '\n' => return None,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Copies template literal text (from just after the ` or }) up to and including the closing ` or the next `${`.
+/// Returns the index to carry on from.
+fn copy_template_literal(chars: &[char], start: usize, output: &mut String, template_stack: &mut Vec<usize>) -> Result<usize> {
+    if chars.get(start.wrapping_sub(1)) == Some(&'`') {
+        output.push('`');
+    }
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                output.extend(chars.get(i..i + 2).unwrap_or(&chars[i..]));
+                i += 2;
+            }
+            '`' => {
+                output.push('`');
+                return Ok(i + 1);
+            }
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                output.push_str("${");
+                template_stack.push(0);
+                return Ok(i + 2);
+            }
+            c => {
+                output.push(c);
+                i += 1;
+            }
+        }
+    }
+    Err(anyhow!("unterminated template literal"))
+}
+
+/// Returns true if a '/' following the output so far starts a regular expression (rather than being a division).
+fn is_regex_start(output: &str) -> bool {
+    let before = output.trim_end();
+    let Some(last) = before.chars().last() else { return true };
+    if "(,=:[!&|?{};+-*%<>~^".contains(last) {
+        return true;
+    }
+    // e.g. `return /a/.test(x)`
+    let last_word: String = before.chars().rev().take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$').collect::<Vec<_>>().into_iter().rev().collect();
+    ["return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case", "do", "else", "yield", "await"].contains(&last_word.as_str())
+}
+
+/// Returns the index of the closing '/' of the regular expression starting at `start`, including any flags.
+fn find_regex_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    let mut in_class = false;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '[' => { in_class = true; i += 1; }
+            ']' => { in_class = false; i += 1; }
+            '/' if !in_class => {
+                while chars.get(i + 1).is_some_and(|c| c.is_alphabetic()) {
+                    i += 1;
+                }
+                return Some(i);
+            }
+            '\n' => return None,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Minifies a single file (of `fs`) in place, based on its extension.
+///
+/// # Returns
+/// * The size of the file before and after, or None if it isn't a file type that gets minified.
+pub fn minify_file(fs: &dyn FileSystem, path: &Path) -> Result<Option<(usize, usize)>> {
+    let Some(extension) = path.extension().and_then(|e| e.to_str()).filter(|e| MINIFIABLE_EXTENSIONS.contains(e)) else {
+        return Ok(None);
+    };
+    let content = fs.read_to_string(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let minified = match extension {
+        "html" => minify_html(&content),
+        "css" => minify_css(&content)?,
+        _ => minify_js(&content)?,
+    };
+    if minified != content {
+        fs.write(path, minified.as_bytes()).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    }
+    Ok(Some((content.len(), minified.len())))
+}
+
+/// Minifies every html / css / js file in the output directory.
+/// A file that can't be minified is left as it was, and reported in the summary, rather than failing the build.
+///
+/// # Arguments
+/// * `fs` - Where the website is. e.g. &DiskFileSystem
+/// * `output_dir` - The compiled website, e.g. 'actual-website-do-not-edit'.
+/// * `skip` - Returns true for the files to leave as they are. (e.g. the files that have a source map, which would no
+///   longer match a minified file; see source_maps.rs)
+pub fn minify_directory(fs: &dyn FileSystem, output_dir: &Path, skip: impl Fn(&Path) -> bool) -> Result<MinifySummary> {
+    let mut summary = MinifySummary::default();
+    let files = fs.walk_files(output_dir);
+
+    for path in files {
+        if skip(&path) {
+            summary.files_skipped += 1;
+            continue;
+        }
+        match minify_file(fs, &path) {
+            Ok(Some((before, after))) => {
+                summary.files_minified += 1;
+                summary.bytes_before += before;
+                summary.bytes_after += after;
+            }
+            Ok(None) => {}
+            Err(e) => summary.failures.push((path, format!("{:#}", e))),
+        }
+    }
+    Ok(summary)
+}
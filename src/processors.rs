@@ -0,0 +1,879 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, info, warn};
+use regex::Regex;
+use crate::components::{add_reusable_javascript_components_to_content, compile_typescript_file, relative_path_to_root, replace_html_component_placeholders_in_content, replace_root_placeholder_in_content};
+use crate::config::PlaceholderSettings;
+use crate::i18n::TEXT_PLACEHOLDER_REGEX;
+use crate::links::LinkMode;
+use crate::localization::*;
+use crate::routing::Router;
+use crate::scss::{compile_scss_file_using, ScssCompiler};
+use crate::translations::TranslationsFile;
+use crate::typescript_modules::{guard_module_namespaces, is_typescript_module, link_typescript_modules_in_content, ModuleUsage};
+use crate::vfs::{CommandRunner, FileSystem};
+
+// === FILE PROCESSORS ===
+// Every edit-me file that needs (re)compiling is read once, put through each processor registered for its extension
+// (in memory, in the order they were registered), then written once into the output directory. The default processors are:
+//      scss          -> @use "<root>/..." paths made relative;                    compiled to css at the end of the build
+//      typescript    -> <r-name> shared code pasted in, imported modules linked in; compiled to js at the end of the build
+//      components    -> (html) <r-name> components expanded
+//      links         -> (html) links rewritten to where pages with permalinks are published (see routing.rs)
+//      root paths    -> (every file) <root>/ replaced with the relative path to the website's root
+//      blog          -> (html in the blog folder) the blog template put around the post
+//      localization  -> (html and markdown with language versions, e.g. ugly-people.jp.md) metadata shared, the versions linked together
+//      i18n          -> (html) the language versions of the page written, with its TEXT= keys translated
+// To handle another type of file (e.g. markdown, csv), implement `Processor` and register it.
+//
+// Files are written with an atomic rename (written to a temporary file next to them, then renamed over them), so a step
+// that fails, or a build that is stopped, never leaves a half-processed file in the output directory: it has either the
+// old version or the new one. Files that aren't text (e.g. images) are copied as they are, without any processors.
+//
+// A processor that compiles the copy into another file (scss -> css) names that file's extension; the compiling happens
+// after every file has been processed, so e.g. the scss partials a file @uses are ready by then.
+// Processors can also name the other edit-me files a file uses, so it is processed again when one of them changes.
+// Every file is read and written through the context's file system, and compilers are run through its command runner
+// (see vfs.rs), so a processor should use `context.fs` / `context.commands` rather than `std::fs` / `std::process`.
+
+/// The parts of the build that are the same for every file.
+pub struct ProcessContext<'a> {
+    /// e.g. edit-me
+    pub source_dir: &'a Path,
+    /// e.g. actual-website-do-not-edit
+    pub output_dir: &'a Path,
+    /// The page text of every language.
+    pub translations: &'a TranslationsFile,
+    /// The languages the website is in. e.g. ["en", "jp"]
+    pub language_codes: &'a [String],
+    /// Which exports of the typescript modules are used (so the unused ones can be left out).
+    pub module_usage: &'a ModuleUsage,
+    /// Where the files are read from and written to. e.g. &DiskFileSystem
+    pub fs: &'a dyn FileSystem,
+    /// What runs the compilers. e.g. &SystemCommandRunner
+    pub commands: &'a dyn CommandRunner,
+    /// Where each page is published (its permalink; see routing.rs).
+    pub router: &'a Router,
+}
+
+/// A file's content, as it goes through its processors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Document {
+    pub content: String,
+    /// Other files made from it (e.g. its language versions), written along with it, as they are when made.
+    pub extra_files: Vec<(PathBuf, String)>,
+}
+
+/// A file being processed, and where it goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    /// The edit-me file. e.g. edit-me/pages/index/index.ts
+    pub source_path: PathBuf,
+    /// Where its processed content is written in the output directory. e.g. actual-website-do-not-edit/index.ts
+    pub copy_path: PathBuf,
+    /// The published file: the compiled file, or the copy itself if nothing compiles it. e.g. actual-website-do-not-edit/index.js
+    pub output_path: PathBuf,
+    /// The language of a localized sibling file (e.g. "jp" for ugly-people.jp.md), or None.
+    pub variant_language: Option<String>,
+    /// The default language file of its group (the source file itself, unless it is a sibling file).
+    pub default_source_path: PathBuf,
+    /// Where the default language file is published. e.g. actual-website-do-not-edit/blog/social/ugly-people.md
+    pub default_output_path: PathBuf,
+    /// All sibling language files of its group. e.g. {"jp": edit-me/.../ugly-people.jp.md}
+    pub localized_variants: BTreeMap<String, PathBuf>,
+}
+
+impl SourceFile {
+    /// True if a processor compiles the copy into another file. (e.g. index.ts -> index.js)
+    pub fn is_compiled(&self) -> bool {
+        self.copy_path != self.output_path
+    }
+}
+
+/// Something that handles one or more types of file.
+///
+/// # Examples
+/// ```
+/// use anyhow::Result;
+/// use websiteCompiler::processors::{Document, ProcessContext, Processor, ProcessorRegistry, SourceFile};
+///
+/// /// Shouts every text file.
+/// struct Shout;
+///
+/// impl Processor for Shout {
+///     fn name(&self) -> &str { "shout" }
+///     fn input_extensions(&self) -> &[&str] { &["txt"] }
+///     fn process(&self, _file: &SourceFile, document: &mut Document, _context: &ProcessContext) -> Result<()> {
+///         document.content = document.content.to_uppercase();
+///         Ok(())
+///     }
+/// }
+///
+/// let mut processors = ProcessorRegistry::new();
+/// processors.register(Shout);
+/// assert_eq!(processors.processors_for("notes.txt".as_ref()).len(), 1);
+/// ```
+pub trait Processor: Send + Sync {
+    /// A short name, used in messages. e.g. "scss"
+    fn name(&self) -> &str;
+
+    /// The extensions (without the '.') of the files it handles. An empty list means every file.
+    fn input_extensions(&self) -> &[&str];
+
+    /// The extension of the file it compiles the copy into (e.g. Some("css") for scss), or None if the copy is published as it is.
+    /// Asked per file, since not every file of a type is compiled. (e.g. typescript modules are only linked into other files;
+    /// a processor that needs to read the file to know reads it from `context.fs`)
+    fn output_extension(&self, _source_path: &Path, _context: &ProcessContext) -> Option<&str> {
+        None
+    }
+
+    /// The other edit-me files the file uses, so it is processed again when one of them changes.
+    fn dependencies(&self, _source_path: &Path, _context: &ProcessContext) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Changes the file's content. (it is written to `file.copy_path` once every processor has run)
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()>;
+
+    /// The other files `process` writes for the file (its `extra_files`, e.g. its language versions), so where they go
+    /// is known before the build. (see `ProcessorRegistry::plan_outputs`)
+    fn extra_output_paths(&self, _file: &SourceFile, _context: &ProcessContext) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Compiles the processed file (`file.copy_path`) into `file.output_path`.
+    /// Only called for files it named an output extension for, once every file has been processed.
+    fn compile(&self, _file: &SourceFile, _context: &ProcessContext) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// How compiling one processor's files went.
+#[derive(Debug, Default)]
+pub struct CompileSummary {
+    /// The processor's name.
+    pub processor: String,
+    pub files_compiled: usize,
+    /// The files that couldn't be compiled, and why (e.g. the compiler's error message).
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+/// The processors of a build, in the order they run.
+#[derive(Default)]
+pub struct ProcessorRegistry {
+    processors: Vec<Box<dyn Processor>>,
+}
+
+// ============================================================
+// THE REGISTRY
+// ============================================================
+
+impl ProcessorRegistry {
+    /// An empty registry. (see `register`)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a processor, which runs after the ones already registered.
+    pub fn register<P: Processor + 'static>(&mut self, processor: P) {
+        self.processors.push(Box::new(processor));
+    }
+
+    /// Adds a processor that is already boxed. (see `register`)
+    pub fn register_boxed(&mut self, processor: Box<dyn Processor>) {
+        self.processors.push(processor);
+    }
+
+    /// The names of the registered processors, in the order they run.
+    pub fn names(&self) -> Vec<&str> {
+        self.processors.iter().map(|processor| processor.name()).collect()
+    }
+
+    /// The processors that handle a file, in the order they run.
+    pub fn processors_for(&self, path: &Path) -> Vec<&dyn Processor> {
+        self.processors.iter().map(|processor| processor.as_ref()).filter(|processor| handles(*processor, path)).collect()
+    }
+
+    /// The extension a file is compiled to (by the first of its processors that compiles it), or None if it isn't.
+    pub fn output_extension(&self, source_path: &Path, context: &ProcessContext) -> Option<&str> {
+        self.processors_for(source_path).into_iter().find_map(|processor| processor.output_extension(source_path, context))
+    }
+
+    /// Works out where an edit-me file goes, without touching the output directory.
+    ///
+    /// # Arguments
+    /// * `source_path` - e.g. edit-me/pages/all-blogs/blog/social/ugly-people.jp.md
+    /// * `context` - The build's directories, languages and permalinks.
+    ///
+    /// # Returns
+    /// * e.g. copy_path = output_path = actual-website-do-not-edit/blog/social/jp-ugly-people.md
+    /// * `Err` if the file isn't in the source directory.
+    pub fn plan_file(&self, source_path: &Path, context: &ProcessContext) -> Result<SourceFile> {
+        // Localized sibling files (e.g. ugly-people.jp.md) are published under their default file's name (e.g. jp-ugly-people.md)
+        let variant_language: Option<String> = localized_variant_language(source_path, context.language_codes);
+        let default_source_path: PathBuf = match variant_language {
+            Some(_) => default_language_path(source_path),
+            None => source_path.to_path_buf(),
+        };
+        let localized_variants: BTreeMap<String, PathBuf> = find_localized_variants(context.fs, &default_source_path, context.language_codes);
+
+        let path_relative_to_src: &Path = default_source_path.strip_prefix(context.source_dir).with_context(|| format!("Error stripping prefix '{}' from '{}'", context.source_dir.display(), source_path.display()))?;
+        let default_copy_path: PathBuf = context.output_dir.join(context.router.route(&output_relative_path(path_relative_to_src)));
+        let default_output_path: PathBuf = match self.output_extension(source_path, context) {
+            Some(extension) => default_copy_path.with_extension(extension),
+            None => default_copy_path.clone(),
+        };
+        let (copy_path, output_path) = match &variant_language {
+            Some(language_code) => (language_output_path(&default_copy_path, language_code), language_output_path(&default_output_path, language_code)),
+            None => (default_copy_path, default_output_path.clone()),
+        };
+
+        Ok(SourceFile { source_path: source_path.to_path_buf(), copy_path, output_path, variant_language, default_source_path, default_output_path, localized_variants })
+    }
+
+    /// Every file that building an edit-me file writes into the output directory: its copy, the file it is compiled to,
+    /// and the other files its processors make.
+    ///
+    /// # Examples
+    /// * edit-me/pages/index/index.html -> [actual-website-do-not-edit/index.html, actual-website-do-not-edit/en-index.html, actual-website-do-not-edit/jp-index.html]
+    pub fn planned_output_paths(&self, file: &SourceFile, context: &ProcessContext) -> Vec<PathBuf> {
+        let mut paths = vec![file.copy_path.clone()];
+        if file.is_compiled() {
+            paths.push(file.output_path.clone());
+        }
+        for processor in self.processors_for(&file.source_path) {
+            paths.extend(processor.extra_output_paths(file, context));
+        }
+        paths
+    }
+
+    /// Works out where every edit-me file goes, before anything is built, and makes sure no two files go to the same place.
+    /// (Pages are published without their page folder, so e.g. pages/a/index.html and pages/b/index.html would both be
+    /// index.html, and whichever was built last would win)
+    ///
+    /// # Arguments
+    /// * `source_paths` - Every edit-me file. e.g. [edit-me/pages/index/index.html, ...]
+    /// * `context` - The build's directories and languages.
+    ///
+    /// # Returns
+    /// * Each published file (relative to the output directory), and the edit-me file it comes from. e.g. {"jp-index.html": "edit-me/pages/index/index.html"}
+    /// * `Err` naming both edit-me files if two of them would be published as the same file (including their language versions).
+    pub fn plan_outputs(&self, source_paths: &[PathBuf], context: &ProcessContext) -> Result<BTreeMap<PathBuf, PathBuf>> {
+        let mut sources_by_output: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+        for source_path in source_paths {
+            let file = self.plan_file(source_path, context)?;
+            for output_path in self.planned_output_paths(&file, context) {
+                let output_path = output_path.strip_prefix(context.output_dir).map(Path::to_path_buf).unwrap_or(output_path);
+                match sources_by_output.get(&output_path) {
+                    Some(other_source_path) if other_source_path != source_path => {
+                        bail!("'{}' and '{}' would both be published as '{}' (pages are published without their page folder); rename or move one of them", other_source_path.display(), source_path.display(), output_path.display());
+                    }
+                    _ => {
+                        sources_by_output.insert(output_path, source_path.clone());
+                    }
+                }
+            }
+        }
+        Ok(sources_by_output)
+    }
+
+    /// Reads an edit-me file, runs its processors over it, and writes the result into the output directory.
+    /// (Compiling it, if it is compiled, is left to `compile_files`)
+    ///
+    /// # Arguments
+    /// * `source_path` - e.g. edit-me/pages/index/index.html
+    /// * `context` - The build's directories, languages, etc.
+    ///
+    /// # Returns
+    /// * Where the file went.
+    /// * `Err` if it couldn't be read or written, or one of its processors failed (in which case nothing is written).
+    pub fn process_file(&self, source_path: &Path, context: &ProcessContext) -> Result<SourceFile> {
+        let file = self.plan_file(source_path, context)?;
+        let bytes = context.fs.read(source_path).with_context(|| format!("Failed to read '{}'", source_path.display()))?;
+
+        match String::from_utf8(bytes) {
+            Ok(content) => {
+                let mut document = Document { content, extra_files: Vec::new() };
+                for processor in self.processors_for(source_path) {
+                    processor.process(&file, &mut document, context).with_context(|| format!("The {} processor failed on '{}'", processor.name(), source_path.display()))?;
+                }
+                write_file_atomically(context.fs, &file.copy_path, &document.content)?;
+                for (extra_path, extra_content) in &document.extra_files {
+                    write_file_atomically(context.fs, extra_path, extra_content)?;
+                    info!("Generated: {:?}", extra_path);
+                }
+            }
+            // Not valid UTF-8 → probably binary (like .ico), so it is copied as it is
+            Err(error) => write_file_atomically(context.fs, &file.copy_path, error.into_bytes())?,
+        }
+
+        // Delete the existing compiled file if it exists (so a failed compile doesn't leave the old one in place)
+        if file.is_compiled() && context.fs.exists(&file.output_path) {
+            let _ = context.fs.remove_file(&file.output_path);
+        }
+        Ok(file)
+    }
+
+    /// Compiles the processed files that are compiled (e.g. scss -> css), processor by processor.
+    ///
+    /// # Arguments
+    /// * `files` - The processed files (from `process_file`); the ones that aren't compiled are skipped.
+    /// * `context` - The build's directories, languages, etc.
+    ///
+    /// # Returns
+    /// * One summary per processor that had files to compile, in the order they run.
+    pub fn compile_files(&self, files: &[SourceFile], context: &ProcessContext) -> Vec<CompileSummary> {
+        let mut summaries = Vec::new();
+        for processor in &self.processors {
+            let files_to_compile: Vec<&SourceFile> = files.iter()
+                .filter(|file| file.is_compiled() && handles(processor.as_ref(), &file.source_path) && processor.output_extension(&file.source_path, context).is_some())
+                .collect();
+            if files_to_compile.is_empty() {
+                continue;
+            }
+
+            let mut summary = CompileSummary { processor: processor.name().to_string(), ..CompileSummary::default() };
+            for file in files_to_compile {
+                match processor.compile(file, context) {
+                    Ok(()) => summary.files_compiled += 1,
+                    Err(e) => summary.failures.push((file.source_path.clone(), format!("{:#}", e))),
+                }
+            }
+            summaries.push(summary);
+        }
+        summaries
+    }
+
+    /// Adds the edit-me files that use any of the given files (as their processors' `dependencies` say), and the files that
+    /// use those, and so on.
+    ///
+    /// # Arguments
+    /// * `files` - e.g. {"edit-me/shared/global.scss"}
+    /// * `context` - The build's directories, languages, etc.
+    ///
+    /// # Returns
+    /// * The files, plus the ones that depend on them. e.g. {"edit-me/shared/global.scss", "edit-me/pages/index/index.scss", ...}
+    pub fn with_dependents(&self, files: &HashSet<String>, context: &ProcessContext) -> HashSet<String> {
+        let dependencies: Vec<(String, HashSet<String>)> = context.fs.walk_files(context.source_dir)
+            .into_iter()
+            .map(|path| {
+                let uses: HashSet<String> = self.processors_for(&path).iter()
+                    .flat_map(|processor| processor.dependencies(&path, context))
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect();
+                (path.to_string_lossy().to_string(), uses)
+            })
+            .filter(|(_, uses)| !uses.is_empty())
+            .collect();
+
+        let mut affected: HashSet<String> = files.clone();
+        loop {
+            let before_count = affected.len();
+            for (file, uses) in &dependencies {
+                if !affected.contains(file) && !uses.is_disjoint(&affected) {
+                    affected.insert(file.clone());
+                }
+            }
+            if affected.len() == before_count {
+                return affected;
+            }
+        }
+    }
+}
+
+/// Writes a file in one go: to a temporary file next to it, which is then renamed over it.
+/// (so anything reading the file sees either the old version or the new one, never half of one)
+///
+/// # Arguments
+/// * `fs` - Where the file is written. e.g. &DiskFileSystem
+/// * `path` - The file to write. Its folder is created if it doesn't exist.
+/// * `contents` - What to write.
+pub fn write_file_atomically(fs: &dyn FileSystem, path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let file_name = path.file_name().ok_or_else(|| anyhow!("Not a file path: '{}'", path.display()))?;
+    if let Some(parent) = path.parent() {
+        fs.create_dir_all(parent).with_context(|| format!("Failed to create parent directory for '{}'", path.display()))?;
+    }
+    let temporary_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+    fs.write(&temporary_path, contents.as_ref()).with_context(|| format!("Failed to write '{}'", temporary_path.display()))?;
+    fs.rename(&temporary_path, path).with_context(|| format!("Failed to move '{}' to '{}'", temporary_path.display(), path.display())).inspect_err(|_| {
+        let _ = fs.remove_file(&temporary_path);
+    })
+}
+
+/// True if a processor handles the file, going by its extension.
+fn handles(processor: &dyn Processor, path: &Path) -> bool {
+    let extensions = processor.input_extensions();
+    extensions.is_empty() || path.extension().and_then(|s| s.to_str()).is_some_and(|extension| extensions.contains(&extension))
+}
+
+/// Where a file goes in the output directory: the same place as in the source directory, except that a page's own
+/// folder is dropped, so every page ends up at the root.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use websiteCompiler::processors::output_relative_path;
+///
+/// assert_eq!(output_relative_path(Path::new("pages/index/index.html")), Path::new("index.html"));
+/// assert_eq!(output_relative_path(Path::new("pages/all-blogs/blog/start-here.html")), Path::new("blog/start-here.html"));
+/// assert_eq!(output_relative_path(Path::new("shared/reusables/top-bar/top-bar.scss")), Path::new("shared/reusables/top-bar/top-bar.scss"));
+/// ```
+pub fn output_relative_path(path_relative_to_source: &Path) -> PathBuf {
+    static PAGES_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"pages/[^/]+/").unwrap());
+    PathBuf::from(PAGES_REGEX.replace(&path_relative_to_source.to_string_lossy(), "").as_ref())
+}
+
+// ============================================================
+// THE DEFAULT PROCESSORS
+// ============================================================
+
+/// scss: makes the `@use "<root>/..."` paths relative, then compiles the file to css.
+/// The other <root>s (e.g. of a url()) are left until the file is compiled, and are then made relative to the css file:
+/// a partial is pasted into every stylesheet that @uses it, and they can be at different depths.
+pub struct ScssProcessor {
+    /// e.g. "@use"
+    pub import_start: String,
+    /// e.g. "<root>"
+    pub root_placeholder: String,
+    pub compiler: ScssCompiler,
+    /// Write compressed (minified) css, for production builds.
+    pub compressed: bool,
+    /// Write a source map next to the css.
+    pub source_map: bool,
+}
+
+impl Processor for ScssProcessor {
+    fn name(&self) -> &str {
+        "scss"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &["scss"]
+    }
+
+    fn output_extension(&self, _source_path: &Path, _context: &ProcessContext) -> Option<&str> {
+        Some("css")
+    }
+
+    /// The edit-me files it @uses from the root. e.g. @use "<root>/shared/global.scss" -> edit-me/shared/global.scss
+    fn dependencies(&self, source_path: &Path, context: &ProcessContext) -> Vec<PathBuf> {
+        let Ok(content) = context.fs.read_to_string(source_path) else { return Vec::new() };
+        let import_regex = Regex::new(&format!(r#"{}\s+["']{}/([^"']+)["']"#, regex::escape(&self.import_start), regex::escape(&self.root_placeholder))).unwrap();
+        import_regex.captures_iter(&content)
+            .map(|captures| context.source_dir.join(&captures[1]))
+            .filter(|path| context.fs.is_file(path))
+            .collect()
+    }
+
+    /// Makes the <root> of each @use relative to the copy. (which is where the compiler looks for what it @uses)
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        let relative_root = relative_path_to_root(&file.copy_path, context.output_dir);
+        let import_regex = Regex::new(&format!(r#"({}\s+["']){}/"#, regex::escape(&self.import_start), regex::escape(&self.root_placeholder))).unwrap();
+        document.content = import_regex.replace_all(&document.content, format!("${{1}}{}", relative_root).as_str()).to_string();
+        Ok(())
+    }
+
+    /// Compiles the copy, then makes the rest of the <root>s (including those of the partials it @uses) relative to the css.
+    fn compile(&self, file: &SourceFile, context: &ProcessContext) -> Result<()> {
+        compile_scss_file_using(context.fs, context.commands, self.compiler, &file.copy_path.to_string_lossy(), &file.output_path.to_string_lossy(), self.compressed, self.source_map).map_err(|e| anyhow!(e))?;
+        let css = context.fs.read_to_string(&file.output_path).with_context(|| format!("Failed to read '{}'", file.output_path.display()))?;
+        let relative_root = relative_path_to_root(&file.output_path, context.output_dir);
+        let resolved = replace_root_placeholder_in_content(&css, &self.root_placeholder, &self.root_placeholder, &relative_root);
+        if resolved != css {
+            write_file_atomically(context.fs, &file.output_path, resolved)?;
+        }
+        Ok(())
+    }
+}
+
+/// typescript: pastes in the <r-name> shared code, links in the modules it imports, then compiles the file to javascript.
+/// (modules, i.e. typescript files that export things, are only ever linked into the files that import them, so aren't compiled)
+pub struct TypeScriptProcessor {
+    /// The folder of shared/ that <r-name> code is pasted from. e.g. "code"
+    pub shared_code_folder: String,
+    /// e.g. "<r-"
+    pub re_start: String,
+    /// e.g. ">"
+    pub re_end: String,
+    /// Write a source map next to the javascript.
+    pub source_map: bool,
+}
+
+impl Processor for TypeScriptProcessor {
+    fn name(&self) -> &str {
+        "typescript"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &["ts"]
+    }
+
+    /// "js", unless the file is a module (which is only linked into the files that import it).
+    fn output_extension(&self, source_path: &Path, context: &ProcessContext) -> Option<&str> {
+        match context.fs.read_to_string(source_path) {
+            Ok(source) if is_typescript_module(&source) => None,
+            _ => Some("js"),
+        }
+    }
+
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        document.content = add_reusable_javascript_components_to_content(context.fs, &document.content, context.output_dir, &self.shared_code_folder, &self.re_start, &self.re_end)?;
+        // Link in the modules it imports (e.g. import { x } from "<root>/shared/code/loader")
+        if let Some((linked_content, _)) = link_typescript_modules_in_content(context.fs, &document.content, &file.source_path, context.source_dir, context.module_usage)? {
+            document.content = linked_content;
+        }
+        Ok(())
+    }
+
+    fn compile(&self, file: &SourceFile, context: &ProcessContext) -> Result<()> {
+        compile_typescript_file(context.fs, context.commands, &file.copy_path.to_string_lossy(), &file.output_path.to_string_lossy(), self.source_map).map_err(|e| anyhow!(e))?;
+
+        // Each linked module only runs once, even if a page loads several scripts that link it in
+        let js = context.fs.read_to_string(&file.output_path).with_context(|| format!("Failed to read '{}'", file.output_path.display()))?;
+        let guarded = guard_module_namespaces(&js);
+        if guarded != js {
+            write_file_atomically(context.fs, &file.output_path, guarded)?;
+        }
+        Ok(())
+    }
+}
+
+/// html: expands the <r-name> reusable components.
+pub struct HtmlComponentsProcessor {
+    /// How the components are written. ([placeholders] of site.toml, e.g. <r-name title="..."> with {title} in the component)
+    pub placeholders: PlaceholderSettings,
+}
+
+impl Processor for HtmlComponentsProcessor {
+    fn name(&self) -> &str {
+        "components"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &["html"]
+    }
+
+    fn process(&self, _file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        document.content = replace_html_component_placeholders_in_content(context.fs, std::mem::take(&mut document.content), context.source_dir, &self.placeholders)?;
+        Ok(())
+    }
+}
+
+/// html: rewrites the internal links to where the files they point at are published, for pages with permalinks (see routing.rs).
+/// (Runs before the <root> placeholders are replaced, as the links of a page that was moved are written as <root>/ links)
+pub struct LinksProcessor {
+    /// e.g. "<root>"
+    pub root_placeholder: String,
+}
+
+impl Processor for LinksProcessor {
+    fn name(&self) -> &str {
+        "links"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &["html"]
+    }
+
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        // (where the file would have been published, which is what its relative links were written from)
+        let default_natural_path = output_relative_path(file.default_source_path.strip_prefix(context.source_dir).unwrap_or(&file.default_source_path));
+        let natural_path = match &file.variant_language {
+            Some(language_code) => language_output_path(&default_natural_path, language_code),
+            None => default_natural_path,
+        };
+        document.content = context.router.rewrite_links(&document.content, &natural_path, &self.root_placeholder);
+        Ok(())
+    }
+}
+
+/// Every file: replaces the <root> placeholders with the relative path to the root.
+/// i.e. changes <root>/shared/example.html in the file /blog/post.html to ../shared/example.html
+/// (Scripts get the link mode's absolute links instead, if it has them; see links.rs. And scss keeps its <root>s until it is
+/// compiled, as they are relative to the css they end up in; see `ScssProcessor`)
+pub struct RootPathsProcessor {
+    /// e.g. "<root>"
+    pub root_placeholder: String,
+    pub link_mode: LinkMode,
+}
+
+impl Processor for RootPathsProcessor {
+    fn name(&self) -> &str {
+        "root paths"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        if file.copy_path.extension().is_some_and(|extension| extension == "scss") {
+            return Ok(());
+        }
+        // (a url in a script is relative to the page running it, so only an absolute one works from every page)
+        let is_script = file.copy_path.extension().is_some_and(|extension| extension == "ts" || extension == "js");
+        let root = match self.link_mode.root_url() {
+            Some(root_url) if is_script => root_url,
+            _ => relative_path_to_root(&file.copy_path, context.output_dir),
+        };
+        document.content = replace_root_placeholder_in_content(&document.content, &self.root_placeholder, &self.root_placeholder, &root);
+        Ok(())
+    }
+}
+
+/// html in the blog folder (or one of its subfolders): puts the blog template (top + bottom) around the post.
+pub struct BlogProcessor {
+    /// Anything published in this folder is a blog post. e.g. actual-website-do-not-edit/blog
+    pub blog_output_folder: PathBuf,
+    /// e.g. edit-me/shared/reusables/template-blog-top/template-blog-top.html
+    pub template_top: PathBuf,
+    /// e.g. edit-me/shared/reusables/template-blog-bottom/template-blog-bottom.html
+    pub template_bottom: PathBuf,
+}
+
+impl Processor for BlogProcessor {
+    fn name(&self) -> &str {
+        "blog"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &["html"]
+    }
+
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        // e.g. copy_path may be actual-website-do-not-edit/blog/start-here.html or actual-website-do-not-edit/blog/gentle/waves.html
+        if file.copy_path.starts_with(&self.blog_output_folder) {
+            ensure_blog_file_boilerplate(context.fs, file, document, &self.template_top, &self.template_bottom)?;
+        }
+        Ok(())
+    }
+}
+
+/// Files with language versions (e.g. ugly-people.md + ugly-people.jp.md): shares the default version's metadata, and
+/// links every language version together.
+pub struct LocalizationProcessor {
+    /// The language of un-suffixed files. e.g. "en"
+    pub default_language: String,
+}
+
+impl Processor for LocalizationProcessor {
+    fn name(&self) -> &str {
+        "localization"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &LOCALIZABLE_EXTENSIONS
+    }
+
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        if file.localized_variants.is_empty() {
+            return Ok(());
+        }
+        publish_localized_versions(context.fs, file, document, context.language_codes, &self.default_language)
+    }
+
+    fn extra_output_paths(&self, file: &SourceFile, context: &ProcessContext) -> Vec<PathBuf> {
+        if file.localized_variants.is_empty() {
+            return Vec::new();
+        }
+        markdown_fallback_paths(file, context.language_codes).into_iter().map(|(_, path)| path).collect()
+    }
+}
+
+/// html: writes the page's version in each language, with its TEXT= keys translated.
+/// (languages with their own localized file are left to that file)
+pub struct I18nProcessor;
+
+impl Processor for I18nProcessor {
+    fn name(&self) -> &str {
+        "i18n"
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        &["html"]
+    }
+
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        match &file.variant_language {
+            Some(language_code) => {
+                document.content = generate_language_file(&document.content, context.translations, language_code);
+            }
+            None => {
+                let languages_with_own_file: HashSet<String> = file.localized_variants.keys().cloned().collect();
+                process_html_template_file_for_all_languages(file, document, context, &languages_with_own_file);
+            }
+        }
+        Ok(())
+    }
+
+    fn extra_output_paths(&self, file: &SourceFile, context: &ProcessContext) -> Vec<PathBuf> {
+        if file.variant_language.is_some() {
+            return Vec::new();
+        }
+        context.translations.keys()
+            .filter(|language_code| !file.localized_variants.contains_key(*language_code))
+            .filter_map(|language_code| localized_html_path(file, language_code, context))
+            .collect()
+    }
+}
+
+// ============================================================
+// GENERATING TRANSLATION FILES
+// ============================================================
+
+/// Process a single HTML template file for all available languages
+///
+/// @param `file` - The HTML template file being processed
+/// @param `document` - Its content; the localized HTML files are added to its extra files (e.g. jp-index.html next to index.html)
+/// @param `context` - Holds the map containing translations for different languages
+/// @param `languages_to_skip` - Languages which already have their own localized file (e.g. from ugly-people.jp.html)
+fn process_html_template_file_for_all_languages(file: &SourceFile, document: &mut Document, context: &ProcessContext, languages_to_skip: &HashSet<String>) {
+    debug!("\nPROCESSING HTML TEMPLATE FILE FOR ALL LANGUAGES");
+    let Some(html_base_filename) = file.copy_path.file_stem().and_then(|os_string: &std::ffi::OsStr| os_string.to_str()) else {
+        warn!("Invalid HTML filename: {:?}", file.copy_path);
+        return;
+    };
+    debug!("Html file basename: {}",html_base_filename);
+
+    // Generate a translated file for each available language
+    for target_language_code in context.translations.keys() {
+        if languages_to_skip.contains(target_language_code) {
+            continue;
+        }
+
+        let Some(localized_output_file_path) = localized_html_path(file, target_language_code, context) else {
+            continue;
+        };
+        let localized_content = generate_language_file(&document.content, context.translations, target_language_code);
+        document.extra_files.push((localized_output_file_path, localized_content));
+    }
+}
+
+
+/// Where the version of an HTML file in a language is written. e.g. actual-website-do-not-edit/index.html + "jp" -> actual-website-do-not-edit/jp-index.html
+fn localized_html_path(file: &SourceFile, language_code: &str, context: &ProcessContext) -> Option<PathBuf> {
+    let html_base_filename = file.copy_path.file_stem().and_then(|os_string: &std::ffi::OsStr| os_string.to_str())?;
+    let localized_output_filename = format!("{}-{}.html", language_code, html_base_filename);
+    Some(file.copy_path.parent().unwrap_or(context.output_dir).join(localized_output_filename))
+}
+
+/// Generate a language-specific version of an HTML file by replacing translation placeholders
+fn generate_language_file(original_html_content: &str, parsed_json: &TranslationsFile, target_language_code: &str) -> String {
+    let processed_html_content: String = TEXT_PLACEHOLDER_REGEX.replace_all(original_html_content, |regex_captures: &regex::Captures| {
+            let full_translation_key: &str = &regex_captures[1]; // e.g., "pages.index.title"
+
+            if let Some((page_name, variable_name)) = full_translation_key.split_once('.') {
+                // Try to get translation for this language
+                if let Some(translated_text) = get_translation_for_language_page_variable( parsed_json, target_language_code, page_name, variable_name) {
+                    format!("<div>{}</div>", translated_text)
+                } else {
+                    // Fallback to original key if translation not found
+                    format!("<div>{}</div>", full_translation_key)
+                }
+            } else {
+                // Invalid key format, keep original placeholder
+                format!("<div>TEXT={}</div>", full_translation_key)
+            }
+        }).to_string();
+    set_html_language(&processed_html_content, target_language_code)
+}
+
+
+/// Inline lookup function for maximum translation retrieval speed
+#[inline]
+fn get_translation_for_language_page_variable<'a>( translation_cache: &'a TranslationsFile, language_code: &str, page_name: &str, variable_name: &str) -> Option<&'a str> {
+    translation_cache.get(language_code)
+        .and_then(|language_map: &HashMap<String, HashMap<String, String>>| {
+            language_map.get(page_name)
+        })
+        .and_then(|page_variables_map: &HashMap<String, String>| {
+            page_variables_map.get(variable_name)
+        })
+        .map(|translation_string: &String| translation_string.as_str())
+}
+
+// ============================================================
+// BLOGS / LOCALIZED FILES
+// ============================================================
+
+/// The first line of the blog template, which marks a post that already has it.
+const BLOG_TEMPLATE_TOP_MARKER: &str = "<!--startofblogtemplatetop-->";
+
+fn ensure_blog_file_boilerplate(fs: &dyn FileSystem, file: &SourceFile, document: &mut Document, template_top: &Path, template_bottom: &Path) -> Result<()> {
+    // 1. CHECK: Does it have the marker?
+    let already_has_boilerplate = document.content
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .map(|line| line.trim().contains(BLOG_TEMPLATE_TOP_MARKER))
+        .unwrap_or(false);
+
+    if !already_has_boilerplate {
+        info!("--> Applying boilerplate: {:?}", file.copy_path);
+
+        // 2. Read templates with specific error messages
+        // (their paths are relative to the project root; see blog-template-top / blog-template-bottom in config.rs)
+        let top = fs.read_to_string(template_top)
+            .with_context(|| format!("MISSING TOP TEMPLATE: Checked path '{}'. (set blog-template-top in site.toml if it is somewhere else)", template_top.display()))?;
+
+        let bottom = fs.read_to_string(template_bottom)
+            .with_context(|| format!("MISSING BOTTOM TEMPLATE: Checked path '{}'. (set blog-template-bottom in site.toml if it is somewhere else)", template_bottom.display()))?;
+
+        document.content = format!("{}\n{}\n{}", top, document.content, bottom);
+    }
+
+    Ok(())
+}
+
+
+/// Finishes a file that belongs to a localized group (a default language file plus its sibling language files).
+///
+/// # Arguments
+/// * `fs` - Where the default language file is read from. e.g. &DiskFileSystem
+/// * `file` - The file being finished. e.g. its copy_path is actual-website-do-not-edit/blog/social/jp-ugly-people.md
+/// * `document` - Its content.
+/// * `language_codes` - The languages the website is in.
+/// * `default_language` - The language of the default language file. e.g. "en"
+///
+/// html versions get their `<html lang>` set, and `<link rel="alternate" hreflang>` tags to each other.
+/// (The languages without their own page fall back to the default page, translated by its keys; see `I18nProcessor`)
+/// Markdown versions share the default version's front matter, and link to each other through it.
+/// The default markdown file also makes the fallback versions of the languages that have no sibling file.
+fn publish_localized_versions(fs: &dyn FileSystem, file: &SourceFile, document: &mut Document, language_codes: &[String], default_language: &str) -> Result<()> {
+    let default_file_name = file.default_output_path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+    let alternates = alternate_language_links(&default_file_name, language_codes);
+    let language_code = file.variant_language.as_deref().unwrap_or(default_language);
+
+    match file.copy_path.extension().and_then(|s| s.to_str()) {
+        Some("html") => {
+            document.content = set_html_language(&add_alternate_language_links(&document.content, &alternates), language_code);
+        }
+        Some("md") => {
+            let default_content = fs.read_to_string(&file.default_source_path).with_context(|| format!("Failed to read the default language version of: {:?}", file.source_path))?;
+            document.content = merge_shared_metadata(&default_content, &document.content, language_code, &alternates);
+
+            // Languages without their own file fall back to the default language version
+            for (fallback_language, fallback_path) in markdown_fallback_paths(file, language_codes) {
+                debug!("No {} version, falling back to the default language: {:?}", fallback_language, fallback_path);
+                document.extra_files.push((fallback_path, document.content.clone()));
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// The fallback versions a default markdown file makes: one per language without its own file, with where it is written.
+/// (None for an html file, or a file that is a language's version itself)
+/// e.g. ugly-people.md (with only ugly-people.jp.md) -> [("en", actual-website-do-not-edit/blog/social/en-ugly-people.md)]
+fn markdown_fallback_paths(file: &SourceFile, language_codes: &[String]) -> Vec<(String, PathBuf)> {
+    if file.variant_language.is_some() || file.copy_path.extension().and_then(|s| s.to_str()) != Some("md") {
+        return Vec::new();
+    }
+    language_codes.iter()
+        .filter(|code| !file.localized_variants.contains_key(*code))
+        .map(|code| (code.clone(), language_output_path(&file.default_output_path, code)))
+        .collect()
+}
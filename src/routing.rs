@@ -0,0 +1,291 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{bail, Context, Result};
+use regex::{Captures, Regex};
+use crate::config::{SiteConfig, CONFIG_FILE_NAME};
+use crate::links::map_html_links;
+use crate::localization::{language_output_path, localized_variant_language};
+use crate::processors::output_relative_path;
+use crate::vfs::FileSystem;
+
+// === ROUTING (PERMALINKS) ===
+// By default a page is published where it is in edit-me, without its page folder (pages/about/about.html -> about.html).
+// A permalink pattern publishes pages somewhere else instead, e.g. at a "pretty" url that works without .html on any host:
+//      [permalinks]                              (in site.toml: a folder of the output -> the pattern of the pages in it)
+//      "blog" = "/blog/:category/:slug/"         blog/social/ugly-people.html -> blog/social/ugly-people/index.html
+//      "" = "/:slug/"                            (every other page) about.html -> about/index.html
+// A page can have its own, which wins over its folder's:      <meta name="permalink" content="/about-us/">
+// The parts of a pattern:
+//      :slug      -> the page's file name, without .html                       e.g. ugly-people
+//      :category  -> the folders between the pattern's folder and the page    e.g. social  (nothing, for a page right in it)
+//      :path      -> all of the page's folders                                 e.g. blog/social
+// A pattern ending in / publishes the page as that folder's index.html; one without an extension gets .html.
+// Only the html pages of edit-me/pages are routed, and a folder's pattern leaves its index.html pages where they are
+// (they already are their folder's url). A page's language versions go next to it (blog/social/ugly-people/jp-index.html).
+// Markdown files aren't pages: they are notes, published as they are, where they are (blog/sleep.md -> blog/sleep.md), so
+// :slug / :category don't apply to them, and their links aren't rewritten.
+//
+// Every internal link of the html files (href / src / srcset / action / poster, including <root>/ links and the link-path of
+// components) is rewritten to where the file it points at is published (by the links processor, see processors.rs), so
+// moving a page breaks no links. (links made by typescript aren't rewritten)
+
+/// The meta tag that gives a page its own permalink.
+static PERMALINK_META_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<meta\s+name\s*=\s*["']permalink["']\s+content\s*=\s*["']([^"']*)["']\s*/?>\n?"#).unwrap());
+
+/// Where every page is published.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Router {
+    /// The folders' patterns, the deepest folder first. e.g. [("blog", "/blog/:category/:slug/"), ("", "/:slug/")]
+    rules: Vec<(PathBuf, String)>,
+    /// Where each routed file would have been published -> where it is. (Relative to the output directory)
+    routes: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl Router {
+    /// A router with the [permalinks] patterns, which hasn't routed any pages yet.
+    ///
+    /// # Arguments
+    /// * `permalinks` - Each folder's pattern. e.g. {"blog": "/blog/:category/:slug/"}
+    ///
+    /// # Returns
+    /// * `Err` if a pattern has an unknown :part, or goes outside the website.
+    pub fn new(permalinks: &BTreeMap<String, String>) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (folder, pattern) in permalinks {
+            permalink_path(pattern, Path::new("a/b/page.html"), Path::new("")).with_context(|| format!("Invalid permalink pattern for \"{}\"", folder))?;
+            rules.push((PathBuf::from(folder.trim_matches('/')), pattern.clone()));
+        }
+        rules.sort_by_key(|(folder, _)| Reverse(folder.components().count()));
+        Ok(Router { rules, routes: BTreeMap::new() })
+    }
+
+    /// Works out where every page of a site is published, from its [permalinks] and the pages' own permalinks.
+    ///
+    /// # Arguments
+    /// * `fs` - Where the pages are read from. e.g. &DiskFileSystem
+    /// * `config` - The site's [permalinks] and folders.
+    /// * `language_codes` - The languages the website is in. e.g. ["en", "jp"]
+    ///
+    /// # Returns
+    /// * `Err` if a pattern is invalid.
+    pub fn for_pages(fs: &dyn FileSystem, config: &SiteConfig, language_codes: &[String]) -> Result<Self> {
+        let mut router = Router::new(&config.permalinks).with_context(|| format!("Invalid [permalinks] in {}", CONFIG_FILE_NAME))?;
+        let source_dir = &config.paths.source_dir;
+        for page in fs.walk_files(&source_dir.join("pages")) {
+            if page.extension().is_none_or(|extension| extension != "html") || localized_variant_language(&page, language_codes).is_some() {
+                continue;
+            }
+            let natural_path = output_relative_path(page.strip_prefix(source_dir).unwrap_or(&page));
+            let content = fs.read_to_string(&page).unwrap_or_default();
+            let folder_rule = router.folder_rule(&natural_path);
+            let folder = folder_rule.map(|(folder, _)| folder.to_path_buf()).unwrap_or_default();
+            let pattern = match (page_permalink(&content), folder_rule) {
+                (Some(own_pattern), _) => own_pattern.to_string(),
+                (None, Some((_, pattern))) if natural_path.file_name().is_some_and(|name| name != "index.html") => pattern.to_string(),
+                _ => continue,
+            };
+            let routed_path = permalink_path(&pattern, &natural_path, &folder).with_context(|| format!("Invalid permalink for '{}'", page.display()))?;
+            router.add_route(natural_path, routed_path, language_codes);
+        }
+        Ok(router)
+    }
+
+    /// Publishes a page somewhere else (and its language versions next to it).
+    ///
+    /// # Arguments
+    /// * `natural_path` - Where it would have been published. e.g. blog/social/ugly-people.html
+    /// * `routed_path` - Where it is published. e.g. blog/social/ugly-people/index.html
+    /// * `language_codes` - e.g. ["en", "jp"] (jp-ugly-people.html -> ugly-people/jp-index.html)
+    pub fn add_route(&mut self, natural_path: PathBuf, routed_path: PathBuf, language_codes: &[String]) {
+        if natural_path == routed_path {
+            return;
+        }
+        for code in language_codes {
+            self.routes.insert(language_output_path(&natural_path, code), language_output_path(&routed_path, code));
+        }
+        self.routes.insert(natural_path, routed_path);
+    }
+
+    /// Where a file is published. e.g. blog/social/ugly-people.html -> blog/social/ugly-people/index.html
+    /// (relative to the output directory; files that aren't routed stay where they are)
+    pub fn route(&self, natural_path: &Path) -> PathBuf {
+        self.routes.get(natural_path).cloned().unwrap_or_else(|| natural_path.to_path_buf())
+    }
+
+    /// Every routed file: where it would have been published -> where it is.
+    pub fn routes(&self) -> &BTreeMap<PathBuf, PathBuf> {
+        &self.routes
+    }
+
+    /// True if no file is routed (so every file is where it would have been).
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// The pattern of the deepest folder (with a pattern) that a page is in, and that folder.
+    fn folder_rule(&self, natural_path: &Path) -> Option<(&Path, &str)> {
+        let page_folder = natural_path.parent().unwrap_or(Path::new(""));
+        self.rules.iter().find(|(folder, _)| page_folder.starts_with(folder)).map(|(folder, pattern)| (folder.as_path(), pattern.as_str()))
+    }
+
+    /// Rewrites the internal links (href / src / srcset / action / poster) of an html file to where the files they point at
+    /// are published.
+    /// A link of a page that was moved is written as a <root>/ link, so it works from the page's new folder.
+    ///
+    /// # Arguments
+    /// * `content` - The html, before its <root> placeholders are replaced.
+    /// * `natural_path` - Where the file would have been published (what its relative links are relative to). e.g. about.html
+    /// * `root_placeholder` - e.g. "<root>"
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use websiteCompiler::routing::Router;
+    ///
+    /// let mut router = Router::default();
+    /// router.add_route(PathBuf::from("blog/sleep.html"), PathBuf::from("blog/sleep/index.html"), &[]);
+    /// router.add_route(PathBuf::from("about.html"), PathBuf::from("about/index.html"), &[]);
+    /// let html = r#"<a href="<root>/blog/sleep.html#tips">Sleep</a><link href="about.css">"#;
+    /// assert_eq!(router.rewrite_links(html, Path::new("about.html"), "<root>"), r#"<a href="<root>/blog/sleep/#tips">Sleep</a><link href="<root>/about.css">"#);
+    /// ```
+    pub fn rewrite_links(&self, content: &str, natural_path: &Path, root_placeholder: &str) -> String {
+        let content = PERMALINK_META_REGEX.replace_all(content, "");
+        if self.is_empty() {
+            return content.to_string();
+        }
+        let page_moved = self.routes.contains_key(natural_path);
+        let root_prefix = format!("{}/", root_placeholder);
+
+        map_html_links(&content, |link| self.rewrite_link(link, natural_path, page_moved, &root_prefix))
+    }
+
+    /// A link, rewritten (or None if it stays as it is).
+    fn rewrite_link(&self, link: &str, natural_path: &Path, page_moved: bool, root_prefix: &str) -> Option<String> {
+        // (other websites, e-mail addresses, data, links within the page, and parameters that weren't filled in)
+        let is_external = link.split('/').next().is_some_and(|first| first.contains(':')) || link.starts_with("//");
+        if link.is_empty() || link.starts_with('#') || is_external || link.contains(['{', '}']) {
+            return None;
+        }
+        let suffix_start = link.find(['?', '#']).unwrap_or(link.len());
+        let (path, suffix) = link.split_at(suffix_start);
+
+        let (prefix, target, relative) = if let Some(target) = path.strip_prefix(root_prefix) {
+            (root_prefix, PathBuf::from(target), false)
+        } else if let Some(target) = path.strip_prefix('/') {
+            ("/", PathBuf::from(target), false)
+        } else {
+            (root_prefix, resolve_in_website(natural_path.parent().unwrap_or(Path::new("")), path)?, true)
+        };
+
+        let url = match self.routes.get(&target) {
+            Some(routed_path) => url_path(routed_path),
+            // (a relative link of a page that was moved still needs writing from the website's root)
+            None if relative && page_moved => {
+                let url = target.to_string_lossy().replace('\\', "/");
+                if path.ends_with('/') && !url.is_empty() { format!("{}/", url) } else { url }
+            }
+            None => return None,
+        };
+        Some(format!("{}{}{}", prefix, url, suffix))
+    }
+}
+
+/// One line per routed file: "<where it would have been> -> <where it is>". (Saved between builds, to notice a change)
+impl fmt::Display for Router {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (natural_path, routed_path) in &self.routes {
+            writeln!(formatter, "{} -> {}", natural_path.display(), routed_path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a pattern publishes a page.
+///
+/// # Arguments
+/// * `pattern` - e.g. "/blog/:category/:slug/"
+/// * `natural_path` - Where the page would have been published. e.g. blog/social/ugly-people.html
+/// * `folder` - The folder the pattern is for (what :category is relative to). e.g. blog
+///
+/// # Returns
+/// * e.g. blog/social/ugly-people/index.html
+/// * `Err` if the pattern has an unknown :part, or goes outside the website.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use websiteCompiler::routing::permalink_path;
+///
+/// let page = Path::new("blog/social/ugly-people.html");
+/// assert_eq!(permalink_path("/blog/:category/:slug/", page, Path::new("blog")).unwrap(), Path::new("blog/social/ugly-people/index.html"));
+/// assert_eq!(permalink_path("/posts/:slug", page, Path::new("blog")).unwrap(), Path::new("posts/ugly-people.html"));
+/// assert_eq!(permalink_path("/:path/:slug/", Path::new("about.html"), Path::new("")).unwrap(), Path::new("about/index.html"));
+/// assert!(permalink_path("/:title/", page, Path::new("blog")).is_err());
+/// ```
+pub fn permalink_path(pattern: &str, natural_path: &Path, folder: &Path) -> Result<PathBuf> {
+    static PART_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r":([a-zA-Z_]+)").unwrap());
+    let page_folder = natural_path.parent().unwrap_or(Path::new(""));
+    let slug = natural_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let category = page_folder.strip_prefix(folder).unwrap_or(page_folder).to_string_lossy().to_string();
+    let path = page_folder.to_string_lossy().to_string();
+
+    let mut unknown_part = None;
+    let expanded = PART_REGEX.replace_all(pattern, |captures: &Captures| match &captures[1] {
+        "slug" => slug.clone(),
+        "category" => category.clone(),
+        "path" => path.clone(),
+        other => {
+            unknown_part.get_or_insert_with(|| other.to_string());
+            String::new()
+        }
+    });
+    if let Some(part) = unknown_part {
+        bail!("'{}' has an unknown part ':{}' (the parts are :slug, :category and :path)", pattern, part);
+    }
+
+    let mut routed_path = PathBuf::new();
+    for component in Path::new(expanded.as_ref()).components() {
+        match component {
+            Component::Normal(part) => routed_path.push(part),
+            Component::RootDir | Component::CurDir => {}
+            _ => bail!("'{}' goes outside the website", pattern),
+        }
+    }
+    if expanded.ends_with('/') || routed_path.as_os_str().is_empty() {
+        routed_path.push("index.html");
+    } else if routed_path.extension().is_none() {
+        routed_path.set_extension("html");
+    }
+    Ok(routed_path)
+}
+
+/// The permalink pattern a page gives itself, with `<meta name="permalink" content="...">`.
+pub fn page_permalink(content: &str) -> Option<&str> {
+    PERMALINK_META_REGEX.captures(content).and_then(|captures| captures.get(1)).map(|pattern| pattern.as_str())
+}
+
+/// The url of a published file (relative to the website's root), without index.html. e.g. blog/sleep/index.html -> blog/sleep/
+pub fn url_path(path: &Path) -> String {
+    let url = path.to_string_lossy().replace('\\', "/");
+    match url.strip_suffix("index.html") {
+        Some(folder) if folder.is_empty() || folder.ends_with('/') => folder.to_string(),
+        _ => url,
+    }
+}
+
+/// A relative link resolved against a folder of the website, or None if it goes above the website's root.
+/// e.g. blog + ../about.css -> about.css
+fn resolve_in_website(folder: &Path, link: &str) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in folder.join(link).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => resolved.pop().then_some(())?,
+            _ => {}
+        }
+    }
+    Some(resolved)
+}
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use crate::config::SiteConfig;
+use crate::site::{plan_site, COMPILATION_ORDER_FILE};
+use crate::vfs::FileSystem;
+
+// === SCAFFOLDING (the `new` command) ===
+// Makes the files of a new page, component or blog post, laid out the way the compiler expects them:
+//      new page about          -> edit-me/pages/about/about.html, about.scss, about.ts
+//      new component top-bar   -> edit-me/shared/reusables/top-bar/top-bar.html, top-bar.scss, top-bar.ts
+//                                 (and adds reusables/top-bar to the end of reusables-compilation-order.txt)
+//      new blog sleep          -> edit-me/pages/all-blogs/blog/sleep.md
+// (blog posts go in the first pages/<x>/blog folder there is, the blog folder being the [paths] blog-folder of site.toml)
+//
+// The files are made from the project's own templates when it has them ([paths] templates-dir, by default templates/):
+//      templates/page/__name__.html, __name__.scss, ...    (every file in the folder, with __name__ in its path replaced)
+//      templates/component/...
+//      templates/blog/__name__.md
+// In a template, {{name}} is replaced with the name (e.g. top-bar) and {{title}} with it as a title (e.g. Top Bar).
+// ({{ }} rather than { }, so the {param} placeholders of a component template are left alone)
+// A blog post without templates/blog/ is copied from the blog's blogs-notes-template.md, if there is one; otherwise
+// (and for pages / components without templates) the built-in templates below are used.
+//
+// Nothing that exists is overwritten, and a name is refused if anything it would be published as is already published from
+// another file, as the build works it out (see `plan_site` in site.rs): a page's folder is dropped in the output
+// (pages/<x>/about.html -> about.html), a page with a permalink is published there instead, and every html page is
+// published once per language too (en-about.html, jp-about.html, ...), so e.g. `new page jp-index` would clash with the index page.
+
+/// What `new` makes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaffoldKind {
+    Page,
+    Component,
+    Blog,
+}
+
+impl ScaffoldKind {
+    /// The kind with a name, as given on the command line. e.g. "component"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "page" => Some(ScaffoldKind::Page),
+            "component" => Some(ScaffoldKind::Component),
+            "blog" => Some(ScaffoldKind::Blog),
+            _ => None,
+        }
+    }
+
+    /// Its folder in the templates folder. e.g. component
+    fn template_folder(&self) -> &'static str {
+        match self {
+            ScaffoldKind::Page => "page",
+            ScaffoldKind::Component => "component",
+            ScaffoldKind::Blog => "blog",
+        }
+    }
+}
+
+/// What `new` made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaffolded {
+    /// The files made. e.g. [edit-me/shared/reusables/top-bar/top-bar.html, ...]
+    pub files: Vec<PathBuf>,
+    /// The compilation order file a new component was added to. e.g. edit-me/shared/reusables-compilation-order.txt
+    pub registered_in: Option<PathBuf>,
+}
+
+/// The page folder blog posts are made in, when the site doesn't have one yet.
+const DEFAULT_BLOG_PAGE_FOLDER: &str = "all-blogs";
+
+/// The notes a blog post starts from (in the blog's page folder), when there isn't a templates/blog/ folder.
+const BLOG_NOTES_TEMPLATE: &str = "blogs-notes-template.md";
+
+/// Replaced with the name in template file names. e.g. __name__.html -> top-bar.html
+const FILE_NAME_PLACEHOLDER: &str = "__name__";
+
+// The built-in templates of each kind (used when the project doesn't have its own)
+const PAGE_FILES: [(&str, &str); 3] = [
+    ("__name__.html", "<!DOCTYPE html>\n<html lang=\"en\">\n    <head>\n        <meta charset=\"UTF-8\">\n        <meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">\n        <title> {{title}} </title>\n\n        <script defer src=\"shared/global.js\"></script>\n\n        <!-- load stuff specific to this file -->\n        <link rel=stylesheet href=\"{{name}}.css\">\n        <script defer src=\"{{name}}.js\"></script>\n    </head>\n    <body>\n    </body>\n</html>\n"),
+    ("__name__.scss", "@use \"<root>/shared/global.scss\" as *;\n"),
+    ("__name__.ts", ""),
+];
+const COMPONENT_FILES: [(&str, &str); 3] = [
+    ("__name__.html", "<div class=\"{{name}}\">\n</div>\n"),
+    ("__name__.scss", "@use \"<root>/shared/global.scss\" as *;\n\n.{{name}} {\n}\n"),
+    ("__name__.ts", ""),
+];
+const BLOG_FILES: [(&str, &str); 1] = [("__name__.md", "# {{title}}\n")];
+
+/// Checks a name can be used for a page / component / blog post: lower case letters, digits and '-'. e.g. top-bar
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('-') || name.ends_with('-') {
+        bail!("'{}' can't be used as a name: it must start and end with a letter or digit", name);
+    }
+    if let Some(c) = name.chars().find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-')) {
+        bail!("'{}' can't be used as a name: '{}' isn't allowed (use lower case letters, digits and '-', e.g. top-bar)", name, c);
+    }
+    Ok(())
+}
+
+/// A name as a title. e.g. top-bar -> Top Bar
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::scaffold::title_from_name;
+/// assert_eq!(title_from_name("how-to-sleep-2"), "How To Sleep 2");
+/// ```
+pub fn title_from_name(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Makes a new page, component or blog post (and adds a new component to the compilation order).
+///
+/// # Arguments
+/// * `fs` - Where the files are made. e.g. &DiskFileSystem
+/// * `config` - The site.
+/// * `kind` - What to make.
+/// * `name` - Its name. e.g. top-bar
+///
+/// # Returns
+/// * The files made, and where a component was registered.
+/// * `Err` if the name isn't allowed, something with it already exists, or it would be published where another file is.
+pub fn scaffold(fs: &dyn FileSystem, config: &SiteConfig, kind: ScaffoldKind, name: &str) -> Result<Scaffolded> {
+    validate_name(name)?;
+    let folder = match kind {
+        ScaffoldKind::Page => config.paths.source_dir.join("pages").join(name),
+        ScaffoldKind::Component => config.paths.components_dir().join(name),
+        ScaffoldKind::Blog => blog_source_dir(fs, config),
+    };
+    let files: Vec<(PathBuf, Vec<u8>)> = template_files(fs, config, kind, name)?
+        .into_iter()
+        .map(|(relative, content)| (folder.join(relative), content))
+        .collect();
+
+    // (a folder that exists is someone else's page / component)
+    if kind != ScaffoldKind::Blog && fs.exists(&folder) {
+        bail!("'{}' already exists", folder.display());
+    }
+    if let Some((existing, _)) = files.iter().find(|(path, _)| fs.exists(path)) {
+        bail!("'{}' already exists", existing.display());
+    }
+    let check_published_paths = kind != ScaffoldKind::Component;
+    if check_published_paths {
+        plan_site(fs, config).context("The website can't be built as it is, so nothing was made")?;
+    }
+
+    let folder_existed = fs.is_dir(&folder);
+    for (path, content) in &files {
+        if let Some(parent) = path.parent() {
+            fs.create_dir_all(parent).with_context(|| format!("Failed to create '{}'", parent.display()))?;
+        }
+        fs.create_new(path, content).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    }
+    // (the website is planned as the build plans it, with the new files, which are removed again if they don't fit in)
+    if check_published_paths && let Err(e) = plan_site(fs, config) {
+        if folder_existed {
+            for (path, _) in &files {
+                let _ = fs.remove_file(path);
+            }
+        } else {
+            let _ = fs.remove_dir_all(&folder);
+        }
+        return Err(e.context(format!("Can't make '{}' here; pick another name", name)));
+    }
+    let registered_in = match kind {
+        ScaffoldKind::Component => Some(register_component(fs, config, name)?),
+        _ => None,
+    };
+    Ok(Scaffolded { files: files.into_iter().map(|(path, _)| path).collect(), registered_in })
+}
+
+/// The files a new page / component / blog post starts with, with the name put in: their paths (relative to its folder) and contents.
+fn template_files(fs: &dyn FileSystem, config: &SiteConfig, kind: ScaffoldKind, name: &str) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let fill_in = |text: &str| text.replace("{{name}}", name).replace("{{title}}", &title_from_name(name));
+    let fill_in_content = |content: Vec<u8>| match String::from_utf8(content) {
+        Ok(text) => fill_in(&text).into_bytes(),
+        // (e.g. an image, which is copied as it is)
+        Err(e) => e.into_bytes(),
+    };
+
+    // 1) The project's templates
+    let template_dir = config.paths.templates_dir.join(kind.template_folder());
+    if fs.is_dir(&template_dir) {
+        let mut files = Vec::new();
+        for path in fs.walk_files(&template_dir) {
+            let relative = path.strip_prefix(&template_dir).unwrap_or(&path).to_string_lossy().replace(FILE_NAME_PLACEHOLDER, name);
+            let content = fs.read(&path).with_context(|| format!("Failed to read the template '{}'", path.display()))?;
+            files.push((PathBuf::from(relative), fill_in_content(content)));
+        }
+        if files.is_empty() {
+            bail!("The template folder '{}' is empty", template_dir.display());
+        }
+        return Ok(files);
+    }
+
+    // 2) The blog's notes template
+    let blog_notes_template = blog_source_dir(fs, config).parent().map(|page_dir| page_dir.join(BLOG_NOTES_TEMPLATE));
+    if let Some(template_path) = blog_notes_template.filter(|path| kind == ScaffoldKind::Blog && fs.is_file(path)) {
+        let content = fs.read(&template_path).with_context(|| format!("Failed to read the template '{}'", template_path.display()))?;
+        return Ok(vec![(PathBuf::from(format!("{}.md", name)), fill_in_content(content))]);
+    }
+
+    // 3) The built-in ones
+    let built_in: &[(&str, &str)] = match kind {
+        ScaffoldKind::Page => &PAGE_FILES,
+        ScaffoldKind::Component => &COMPONENT_FILES,
+        ScaffoldKind::Blog => &BLOG_FILES,
+    };
+    Ok(built_in.iter().map(|(file_name, content)| (PathBuf::from(file_name.replace(FILE_NAME_PLACEHOLDER, name)), fill_in(content).into_bytes())).collect())
+}
+
+/// The folder blog posts go in: the first pages/<x>/<blog folder> there is. e.g. edit-me/pages/all-blogs/blog
+pub fn blog_source_dir(fs: &dyn FileSystem, config: &SiteConfig) -> PathBuf {
+    let pages_dir = config.paths.source_dir.join("pages");
+    fs.read_dir(&pages_dir)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|page_dir| page_dir.join(&config.paths.blog_folder))
+        .find(|blog_dir| fs.is_dir(blog_dir))
+        .unwrap_or_else(|| pages_dir.join(DEFAULT_BLOG_PAGE_FOLDER).join(&config.paths.blog_folder))
+}
+
+/// Adds a component to the end of the compilation order file (which is made if there isn't one), if it isn't already in it.
+///
+/// # Returns
+/// * The compilation order file. e.g. edit-me/shared/reusables-compilation-order.txt
+pub fn register_component(fs: &dyn FileSystem, config: &SiteConfig, name: &str) -> Result<PathBuf> {
+    let shared_dir = config.paths.shared_dir();
+    let order_file_path = shared_dir.join(COMPILATION_ORDER_FILE);
+    // e.g. reusables/top-bar
+    let components_folder = config.paths.components_dir().strip_prefix(&shared_dir).map(Path::to_path_buf).unwrap_or_else(|_| PathBuf::from("reusables"));
+    let entry = components_folder.join(name).to_string_lossy().replace('\\', "/");
+
+    let mut order = if fs.is_file(&order_file_path) {
+        fs.read_to_string(&order_file_path).with_context(|| format!("Failed to read '{}'", order_file_path.display()))?
+    } else {
+        String::new()
+    };
+    if order.lines().any(|line| line.trim() == entry) {
+        return Ok(order_file_path);
+    }
+    if !order.is_empty() && !order.ends_with('\n') {
+        order.push('\n');
+    }
+    order.push_str(&entry);
+    order.push('\n');
+    fs.create_dir_all(&shared_dir).with_context(|| format!("Failed to create '{}'", shared_dir.display()))?;
+    fs.write(&order_file_path, order.as_bytes()).with_context(|| format!("Failed to write '{}'", order_file_path.display()))?;
+    Ok(order_file_path)
+}
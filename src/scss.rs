@@ -0,0 +1,98 @@
+use std::path::Path;
+use std::sync::LazyLock;
+use log::info;
+use regex::Regex;
+use crate::components::{compile_scss_file, resolve_relative_reference};
+use crate::source_maps::SourceOrigins;
+use crate::vfs::{CommandRunner, FileSystem, GrassFileSystem};
+
+// === SCSS COMPILERS ===
+// scss is compiled either by the Dart Sass command line tool (`sass`), or by the compiler built into this program (the grass crate),
+// so the website can be built with nothing but a Rust toolchain installed.
+// Both understand everything the website's scss uses (@use / @import of the <root> paths, variables, nesting, mixins, ...), but:
+//      -> the built-in compiler doesn't write source maps (so with it, the css has none; see source_maps.rs)
+//      -> the built-in compiler follows Dart Sass closely, but isn't it, so rare differences in the css are possible
+// By default `sass` is used if it is installed, otherwise the built-in compiler. (`--scss-compiler` picks one)
+// Either way, errors name the edit-me file rather than the copy in the output directory that was actually compiled.
+
+/// Which compiler turns scss into css.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScssCompiler {
+    /// The compiler built into this program (grass).
+    BuiltIn,
+    /// The Dart Sass command line tool, `sass`.
+    Sass,
+}
+
+impl ScssCompiler {
+    /// `sass` if it is installed, otherwise the built-in compiler.
+    /// (Whether `sass` can be run is asked of `commands`, e.g. &SystemCommandRunner)
+    pub fn detect(commands: &dyn CommandRunner) -> Self {
+        if commands.is_available("sass") { ScssCompiler::Sass } else { ScssCompiler::BuiltIn }
+    }
+}
+
+/// Compiles a single scss file to a css file, with the built-in compiler.
+///
+/// # Arguments
+/// * `fs` - Where the files (and the ones they @use) are. e.g. &DiskFileSystem
+/// * `scss_source` - The path to the source scss file.
+/// * `css_output` - The path where the css file will be saved.
+/// * `compressed` - Write compressed (minified) css, for production builds.
+///
+/// # Returns
+/// * `Ok(())` if compilation is successful.
+/// * `Err(String)` with the error (including the file + line it is on) if the source file is not found or can't be compiled.
+pub fn compile_scss_file_built_in(fs: &dyn FileSystem, scss_source: &str, css_output: &str, compressed: bool) -> Result<(), String> {
+    if !fs.exists(Path::new(scss_source)) {
+        return Err(format!("warning: source scss file not found: {}", scss_source));
+    }
+    info!("compiling scss (built-in compiler): {} -> {}", scss_source, css_output);
+
+    if let Some(parent_dir) = Path::new(css_output).parent() {
+        fs.create_dir_all(parent_dir).map_err(|e| format!("failed to create output directory: {}", e))?;
+    }
+    let style = if compressed { grass::OutputStyle::Compressed } else { grass::OutputStyle::Expanded };
+    let grass_fs = GrassFileSystem(fs);
+    let css = grass::from_path(scss_source, &grass::Options::default().style(style).fs(&grass_fs))
+        .map_err(|e| format!("error compiling scss file: {}\n{}", scss_source, e))?;
+    fs.write(Path::new(css_output), css.as_bytes()).map_err(|e| format!("failed to write '{}': {}", css_output, e))
+}
+
+/// Compiles a single scss file to a css file, with the chosen compiler.
+///
+/// # Arguments
+/// * `fs` - Where the files are. e.g. &DiskFileSystem
+/// * `commands` - What runs `sass`. e.g. &SystemCommandRunner (the real sass reads and writes these paths on disk, whatever `fs` is)
+/// * `compiler` - The built-in compiler, or `sass`.
+/// * `scss_source` - The path to the source scss file.
+/// * `css_output` - The path where the css file will be saved.
+/// * `compressed` - Write compressed (minified) css, for production builds.
+/// * `source_map` - Write a source map next to the css (only `sass` can; the built-in compiler ignores this).
+pub fn compile_scss_file_using(fs: &dyn FileSystem, commands: &dyn CommandRunner, compiler: ScssCompiler, scss_source: &str, css_output: &str, compressed: bool, source_map: bool) -> Result<(), String> {
+    match compiler {
+        ScssCompiler::BuiltIn => compile_scss_file_built_in(fs, scss_source, css_output, compressed),
+        ScssCompiler::Sass => compile_scss_file(fs, commands, scss_source, css_output, compressed, source_map),
+    }
+}
+
+/// Changes the paths of the scss copies in the output directory that an error message mentions to the edit-me files they are copies of.
+/// (Replacing <root> doesn't change the number of lines, so the line numbers in the message still match)
+///
+/// # Arguments
+/// * `message` - e.g. "Error: expected ';'. ... ./actual-website-do-not-edit/pages/../index.scss:3:9"
+/// * `origins` - Where the output directory's files came from.
+pub fn point_errors_at_sources(message: &str, origins: &SourceOrigins) -> String {
+    static SCSS_PATH_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"[^\s:'"()]+\.s[ac]ss\b"#).unwrap());
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    SCSS_PATH_REGEX.replace_all(message, |captures: &regex::Captures| {
+        // (the built-in compiler puts "./" in front of every path, even absolute ones)
+        let text = captures[0].strip_prefix("./").filter(|rest| rest.starts_with('/')).unwrap_or(&captures[0]);
+        let path = resolve_relative_reference(Path::new("file"), text);
+        let path = path.strip_prefix(&current_dir).unwrap_or(&path);
+        match origins.source_of(path) {
+            Some(source) => source.to_string_lossy().to_string(),
+            None => captures[0].to_string(),
+        }
+    }).to_string()
+}
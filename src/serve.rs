@@ -0,0 +1,240 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use walkdir::WalkDir;
+use crate::site::BuildReport;
+
+// === THE DEVELOPMENT SERVER (the `serve` command) ===
+// Serves the output directory at http://localhost:<port>/<base path>, and rebuilds the website whenever an edit-me file
+// (or site.toml) changes:
+//      -> every WATCH_INTERVAL, the watched files / folders are checked for files that were added, removed or changed
+//      -> a change runs a normal (incremental) build, which swaps the new output in once it has all succeeded
+// Requests are answered from whatever output was published last, so a page is never served half built.
+// Links without .html (as GitHub Pages allows them) work too: /about -> about.html
+// A website with a base path (e.g. /repo, for GitHub Pages; see github_pages.rs) is served under it, as it will be
+// published: /repo/about.html, with / sent on to /repo/ (and a link that forgets the base path is a 404, as it will be).
+// Requests are answered by CONNECTION_THREADS threads; any more wait for one of them to be free.
+// (This is for looking at the website while working on it, so it only listens on localhost. The output directory and the
+// base path are the ones it was started with; changing them in site.toml needs the server restarting)
+
+/// How often the watched files are checked for changes.
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How many requests are answered at once.
+pub const CONNECTION_THREADS: usize = 8;
+
+/// How long a request has to arrive, before its connection is closed. (so a connection left open doesn't hold a thread)
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the output directory, rebuilding the website whenever a watched file changes. (Runs until the program is stopped)
+///
+/// # Arguments
+/// * `output_dir` - The website. e.g. actual-website-do-not-edit
+/// * `watched` - The files / folders watched for changes. e.g. [edit-me, site.toml]
+/// * `base_path` - What the website's urls start with, or "" for none. e.g. "/repo" (see `github_pages::base_path`)
+/// * `port` - e.g. 8000
+/// * `rebuild` - Runs a build. (e.g. `|| site.builder().build()`)
+///
+/// # Returns
+/// * `Err` if the port can't be listened on.
+pub fn serve(output_dir: &Path, watched: &[PathBuf], base_path: &str, port: u16, rebuild: impl FnMut() -> Result<BuildReport> + Send) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).with_context(|| format!("Failed to listen on port {} (is something else using it?)", port))?;
+    info!("Serving {} at http://localhost:{}{}/ (press Ctrl+C to stop)", output_dir.display(), port, base_path);
+
+    // (the connections wait here for one of the threads answering them)
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(CONNECTION_THREADS);
+    let receiver = Mutex::new(receiver);
+    thread::scope(|scope| {
+        scope.spawn(|| watch(watched, rebuild));
+        for _ in 0..CONNECTION_THREADS {
+            scope.spawn(|| loop {
+                let Ok(Ok(stream)) = receiver.lock().map(|receiver| receiver.recv()) else { return };
+                if let Err(e) = handle_request(stream, output_dir, base_path) {
+                    warn!("Warning: failed to answer a request: {}", e);
+                }
+            });
+        }
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT));
+                    if sender.send(stream).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("Warning: failed to accept a connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Rebuilds the website each time a watched file / folder changes.
+fn watch(watched: &[PathBuf], mut rebuild: impl FnMut() -> Result<BuildReport>) {
+    let mut last_state = source_state(watched);
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let state = source_state(watched);
+        if state == last_state {
+            continue;
+        }
+        last_state = state;
+
+        info!("\n🔁 {} changed; rebuilding...", watched.iter().map(|path| path.display().to_string()).collect::<Vec<String>>().join(" / "));
+        match rebuild() {
+            Ok(report) if report.has_errors() => warn!("⚠️ Rebuilt, with {} error(s) (see above)", report.errors().count()),
+            Ok(report) => info!("✅ Rebuilt in {:.2}s", report.total_time().as_secs_f64()),
+            Err(e) => error!("❌ Rebuild failed (still serving the last build): {:#}", e),
+        }
+    }
+}
+
+/// Every file of some files / folders, and when it was last changed, sorted. (Compared between checks, to notice any change)
+/// (A file that doesn't exist has no files, so creating it is a change too)
+pub fn source_state(watched: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    watched.iter()
+        .flat_map(|path| WalkDir::new(path).sort_by_file_name())
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .map(|e| (e.path().to_path_buf(), e.metadata().ok().and_then(|metadata| metadata.modified().ok())))
+        .collect()
+}
+
+/// Answers one request, with the file it asks for (or a 404).
+fn handle_request(stream: TcpStream, output_dir: &Path, base_path: &str) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // (the headers aren't used, but are read so the browser doesn't see the connection reset)
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or("/"));
+    let head_only = method == "HEAD";
+    if method != "GET" && !head_only {
+        return respond(&stream, "405 Method Not Allowed", "text/plain; charset=utf-8", b"Only GET and HEAD requests are supported\n", false);
+    }
+    // (the home page of a website with a base path is at /repo/, as it will be when it is published)
+    if !base_path.is_empty() && target.split(['?', '#']).next() == Some("/") {
+        let location = format!("{}/", base_path);
+        return respond_with(&stream, "302 Found", &format!("Location: {}\r\n", location), "text/plain; charset=utf-8", format!("The website is at {}\n", location).as_bytes(), head_only);
+    }
+    match resolve_request_path(output_dir, base_path, target) {
+        Some(path) => respond(&stream, "200 OK", content_type(&path), &fs::read(&path)?, head_only),
+        None => respond(&stream, "404 Not Found", "text/plain; charset=utf-8", format!("Not found: {}\n", target).as_bytes(), head_only),
+    }
+}
+
+/// Writes a response. (Nothing is cached, so a rebuilt file is always seen)
+fn respond(stream: &TcpStream, status: &str, content_type: &str, body: &[u8], head_only: bool) -> io::Result<()> {
+    respond_with(stream, status, "", content_type, body, head_only)
+}
+
+/// Writes a response with some more headers. (each ending in \r\n)
+fn respond_with(mut stream: &TcpStream, status: &str, headers: &str, content_type: &str, body: &[u8], head_only: bool) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n", status, headers, content_type, body.len())?;
+    if !head_only {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// The file a request is for: the url's path (after the base path) in the output directory, its index.html if it is a
+/// folder, or the .html file of that name if it has no extension. Paths that go outside the output directory, or don't
+/// start with the base path, are never served.
+///
+/// # Examples
+/// * "/" -> actual-website-do-not-edit/index.html
+/// * "/blog/sleep?lang=jp" -> actual-website-do-not-edit/blog/sleep.html
+/// * "/repo/blog/sleep" (with the base path "/repo") -> actual-website-do-not-edit/blog/sleep.html
+/// * "/../site.toml" -> None
+pub fn resolve_request_path(output_dir: &Path, base_path: &str, target: &str) -> Option<PathBuf> {
+    let url_path = target.split(['?', '#']).next().unwrap_or_default();
+    let url_path = match url_path.strip_prefix(base_path) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => return None,
+    };
+    let decoded = percent_decode(url_path);
+    let mut path = output_dir.to_path_buf();
+    for component in Path::new(decoded.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    if path.is_file() {
+        return Some(path);
+    }
+    let html_path = path.with_extension("html");
+    (path.extension().is_none() && html_path.is_file()).then_some(html_path)
+}
+
+/// Decodes the %XX escapes of a url. e.g. "/my%20page.html" -> "/my page.html"
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%').then(|| text.get(index + 1..index + 3)).flatten().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// The Content-Type of a file, going by its extension.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use websiteCompiler::serve::content_type;
+///
+/// assert_eq!(content_type(Path::new("index.html")), "text/html; charset=utf-8");
+/// assert_eq!(content_type(Path::new("images/logo.webp")), "image/webp");
+/// ```
+pub fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") | Some("map") => "application/json",
+        Some("txt") | Some("md") => "text/plain; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("otf") => "font/otf",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,938 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, error, info, warn};
+use crate::bundling::{bundle_component_assets, BundleMode, BUNDLE_DIRECTORY};
+use crate::components::*;
+use crate::config::*;
+use crate::critical_css::inline_critical_css_in_directory;
+use crate::fingerprint::{fingerprint_assets, ASSET_MANIFEST_FILE};
+use crate::github_pages::{base_path, prepare_for_github_pages};
+use crate::images::{make_images_responsive, ImageSettings};
+use crate::link_checker::{check_links, root_urls};
+use crate::links::{apply_link_mode, LinkMode};
+use crate::localization::expand_to_localized_groups;
+use crate::minify::minify_directory;
+use crate::processors::*;
+use crate::routing::Router;
+use crate::scss::{point_errors_at_sources, ScssCompiler};
+use crate::source_maps::{has_source_map, remove_source_maps, write_source_maps, SourceMapMode, SourceOrigins};
+use crate::staging::{working_directories, BuildLock, StagingDirectory};
+use crate::translations::*;
+use crate::typescript_modules::{collect_module_usage, typescript_files_to_relink, ModuleUsage};
+use crate::vfs::{CommandRunner, DiskFileSystem, FileSystem, SystemCommandRunner};
+
+// === SITES AND BUILDS ===
+// The library's way in: a `Site` is a project (its root and site.toml), and a `Builder` runs a build of it.
+//
+//      let site = Site::open(&DiskFileSystem, Path::new("my-website"))?;
+//      let report = site.builder().production(true).build()?;
+//      for diagnostic in &report.diagnostics { ... }
+//
+// A build goes through these stages (each one timed in the `BuildReport`):
+//      prepare       -> lock, staging directory, where every file goes (its permalink; failing if two go to the same place),
+//                       working out which files changed (and the files that use them)
+//      process       -> the changed files copied into the output, through their processors (components first, in order)
+//      compile       -> scss / typescript compiled
+//      source maps, images, bundling, critical css, minification, asset hashing, github pages (the ones turned on)
+//      publish       -> the staging directory swapped in as the output directory
+// Problems that don't stop the build (a file that doesn't compile, an image that can't be encoded, ...) are collected as
+// the report's diagnostics, as well as logged; anything else stops the build, leaving the output directory as it was.
+// (The library writes its progress through the `log` crate, so it prints nothing itself; the command line prints the log)
+//
+// `Builder::only` builds some of the files (and the files that use them), as if only they had changed.
+// `Builder::check` runs a whole build to find its problems, and throws it away (the output directory isn't touched).
+// `Builder::file_system` / `Builder::command_runner` run a build somewhere other than the disk, e.g. in memory in a test (see vfs.rs).
+
+// Files / folders of the project (relative to the project root)
+pub const LAST_COMPILE_TIME_FILE: &str = ".last_compiled";
+pub const IMAGE_CACHE_DIR: &str = ".image-cache"; // encoded image variants, kept between builds so unchanged images aren't re-encoded
+pub const SOURCE_MAP_DIRECTORY: &str = "source-maps"; // where `--source-maps hidden` puts the maps (not part of the website)
+pub const BUILD_LOCK_FILE: &str = ".build.lock"; // exists while a build is running, so two builds can't write the output at once
+pub const COMPILATION_ORDER_FILE: &str = "reusables-compilation-order.txt"; // (in the shared folder)
+pub const ROUTES_FILE: &str = ".routes"; // where the last build published each page with a permalink (see routing.rs)
+
+// --> Responsive images
+const RESPONSIVE_IMAGE_WIDTHS: [u32; 3] = [480, 960, 1600]; // widths (px) of the webp/avif versions generated for each <img>
+
+// --> Bundling
+const SHARED_BUNDLE_MIN_PAGES: usize = 3; // with `--bundle shared`, components used on at least this many pages go in the shared bundle
+
+// ============================================================
+// SITES
+// ============================================================
+
+/// A website project: its root folder and its configuration.
+#[derive(Debug, Clone)]
+pub struct Site {
+    config: SiteConfig,
+}
+
+impl Site {
+    /// Opens the project at a root folder, reading its site.toml (or using the default settings if it doesn't have one).
+    ///
+    /// # Arguments
+    /// * `fs` - Where site.toml is read from. e.g. &DiskFileSystem
+    /// * `root` - The project root. e.g. ../my-website
+    ///
+    /// # Returns
+    /// * `Err` if site.toml is invalid.
+    pub fn open(fs: &dyn FileSystem, root: &Path) -> Result<Self> {
+        Ok(Site { config: load_project(fs, root)? })
+    }
+
+    /// Opens the project a folder is in (the nearest folder, it or above it, with a site.toml or an edit-me folder).
+    /// (The root is kept relative to the working directory when it is inside it, so the paths logged stay short)
+    ///
+    /// # Arguments
+    /// * `fs` - Where to look for it. e.g. &DiskFileSystem
+    /// * `dir` - The project root, or any folder inside it. e.g. . or edit-me/pages
+    ///
+    /// # Returns
+    /// * `Err` if the folder doesn't exist, or site.toml is invalid.
+    pub fn discover(fs: &dyn FileSystem, dir: &Path) -> Result<Self> {
+        let current_dir = std::env::current_dir().context("Failed to get the working directory")?;
+        let start_dir = fs.canonicalize(dir).with_context(|| format!("The project folder '{}' doesn't exist", dir.display()))?;
+        let root = find_project_root(fs, &start_dir).unwrap_or(start_dir);
+        let root = root.strip_prefix(&current_dir).map(Path::to_path_buf).unwrap_or(root);
+
+        let site = Site::open(fs, &root)?;
+        let config_path = site.config.project_path(CONFIG_FILE_NAME);
+        if fs.is_file(&config_path) {
+            info!("Using the site configuration in {}", config_path.display());
+        } else {
+            info!("No {} in the project root ({}); using the default settings", CONFIG_FILE_NAME, if root.as_os_str().is_empty() { "." } else { root.to_str().unwrap_or_default() });
+        }
+        Ok(site)
+    }
+
+    /// A site from a configuration made in code. (Its paths are used as they are; see `SiteConfig::with_root`)
+    pub fn from_config(config: SiteConfig) -> Self {
+        Site { config }
+    }
+
+    /// The site's configuration (with its paths relative to the working directory).
+    pub fn config(&self) -> &SiteConfig {
+        &self.config
+    }
+
+    /// Starts a build, with the [build] settings of site.toml.
+    pub fn builder(&self) -> Builder<'_> {
+        Builder {
+            site: self,
+            settings: self.config.build.clone(),
+            fresh: false,
+            only: None,
+            processors: Vec::new(),
+            check: false,
+            fs: Arc::new(DiskFileSystem),
+            commands: Arc::new(SystemCommandRunner),
+        }
+    }
+
+    /// Builds the website, with the [build] settings of site.toml. (The same as `site.builder().build()`)
+    pub fn build(&self) -> Result<BuildReport> {
+        self.builder().build()
+    }
+
+    /// Deletes everything builds make: the output directory (and any staging directory left behind), the image cache,
+    /// the hidden source maps, and the last compile time (so the next build builds everything).
+    ///
+    /// (They are deleted from `fs`, e.g. &DiskFileSystem)
+    ///
+    /// # Returns
+    /// * The files / folders deleted.
+    /// * `Err` if a build is running.
+    pub fn clean(&self, fs: &dyn FileSystem) -> Result<Vec<PathBuf>> {
+        let _build_lock = BuildLock::acquire(fs, &self.config.project_path(BUILD_LOCK_FILE))?;
+        let output_dir = &self.config.paths.output_dir;
+        let mut paths = vec![output_dir.clone()];
+        paths.extend(working_directories(output_dir));
+        paths.extend([IMAGE_CACHE_DIR, SOURCE_MAP_DIRECTORY, LAST_COMPILE_TIME_FILE, ROUTES_FILE].map(|path| self.config.project_path(path)));
+
+        let mut removed = Vec::new();
+        for path in paths {
+            if fs.is_dir(&path) {
+                fs.remove_dir_all(&path).with_context(|| format!("Failed to delete '{}'", path.display()))?;
+            } else if fs.is_file(&path) {
+                fs.remove_file(&path).with_context(|| format!("Failed to delete '{}'", path.display()))?;
+            } else {
+                continue;
+            }
+            removed.push(path);
+        }
+        Ok(removed)
+    }
+
+    /// The reusable components: the folders of shared/reusables, sorted. e.g. ["back-button", "top-bar"]
+    /// (They are listed in `fs`, e.g. &DiskFileSystem)
+    pub fn components(&self, fs: &dyn FileSystem) -> Result<Vec<String>> {
+        let components_dir = self.config.paths.components_dir();
+        let entries = fs.read_dir(&components_dir).with_context(|| format!("Failed to read '{}'", components_dir.display()))?;
+        Ok(entries.into_iter().filter(|path| fs.is_dir(path)).filter_map(|path| path.file_name().map(|name| name.to_string_lossy().to_string())).collect())
+    }
+
+    /// The html pages (and blog posts) of the website, and where each one is published (with its permalink, and its
+    /// language versions), sorted. (see `Builder::plan`)
+    /// e.g. [("edit-me/pages/index/index.html", "en-index.html"), ("edit-me/pages/index/index.html", "index.html"), ...]
+    ///
+    /// # Arguments
+    /// * `fs` - Where the edit-me files are. e.g. &DiskFileSystem
+    ///
+    /// # Returns
+    /// * `Err` if there is no pages folder, a permalink is invalid, or two edit-me files would be published as the same file.
+    pub fn pages(&self, fs: &dyn FileSystem) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let pages_dir = self.config.paths.source_dir.join("pages");
+        if !fs.is_dir(&pages_dir) {
+            bail!("The pages folder '{}' doesn't exist", pages_dir.display());
+        }
+        let is_html = |path: &Path| path.extension().is_some_and(|extension| extension == "html");
+        let builder = self.builder();
+        let mut pages: Vec<(PathBuf, PathBuf)> = plan_outputs_of(fs, builder.commands.as_ref(), &self.config, &builder.options(), Vec::new())?
+            .into_iter()
+            .filter(|(output, source)| source.starts_with(&pages_dir) && is_html(source) && is_html(output))
+            .map(|(output, source)| (source, output))
+            .collect();
+        pages.sort();
+        Ok(pages)
+    }
+
+    /// The languages the website is translated into (the catalogs in page_text), sorted. e.g. ["en", "jp"]
+    /// (The catalogs are listed in `fs`, e.g. &DiskFileSystem)
+    pub fn languages(&self, fs: &dyn FileSystem) -> Result<Vec<String>> {
+        let catalog_files = find_catalog_files(fs, self.config.paths.page_text_dir())?;
+        let mut languages: Vec<String> = catalog_files.iter().filter_map(|path| catalog_language_code(path).map(str::to_string)).collect();
+        languages.sort();
+        languages.dedup();
+        Ok(languages)
+    }
+}
+
+// ============================================================
+// BUILD OPTIONS
+// ============================================================
+
+/// The options a build is run with (the build settings, with the defaults filled in).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildOptions {
+    pub fresh_run: bool,
+    pub github_pages: bool,
+    pub hash_assets: bool,
+    pub production: bool,
+    pub bundle_mode: Option<BundleMode>,
+    pub critical_css: bool,
+    pub check_links: bool,
+    pub source_maps: SourceMapMode,
+    pub scss_compiler: ScssCompiler,
+    pub link_mode: LinkMode,
+}
+
+impl BuildOptions {
+    /// Fills in the defaults of the settings that aren't set. (e.g. production builds have no source maps, unless asked for)
+    ///
+    /// # Arguments
+    /// * `settings` - The build settings. e.g. [build] of site.toml, with the profile and command line flags over it.
+    /// * `config` - The site. (for where hidden source maps go)
+    /// * `fresh_run` - Build everything, rather than just what changed.
+    /// * `commands` - What looks for the `sass` command, for `scss_compiler = "auto"`. e.g. &SystemCommandRunner
+    pub fn from_settings(settings: &BuildSettings, config: &SiteConfig, fresh_run: bool, commands: &dyn CommandRunner) -> Self {
+        let production = settings.production.unwrap_or(false);
+        BuildOptions {
+            fresh_run,
+            github_pages: settings.github_pages.unwrap_or(false),
+            hash_assets: settings.hash_assets.unwrap_or(false),
+            production,
+            critical_css: settings.critical_css.unwrap_or(false),
+            check_links: settings.check_links.unwrap_or(false),
+            bundle_mode: match settings.bundle {
+                Some(BundleSetting::Page) => Some(BundleMode::Page),
+                Some(BundleSetting::Shared) => Some(BundleMode::Shared { min_pages: SHARED_BUNDLE_MIN_PAGES }),
+                Some(BundleSetting::None) | None => None,
+            },
+            source_maps: match settings.source_maps {
+                Some(SourceMapSetting::External) => SourceMapMode::External,
+                Some(SourceMapSetting::Hidden) => SourceMapMode::Hidden(config.project_path(SOURCE_MAP_DIRECTORY)),
+                Some(SourceMapSetting::None) => SourceMapMode::None,
+                None if production => SourceMapMode::None,
+                None => SourceMapMode::External,
+            },
+            scss_compiler: match settings.scss_compiler {
+                Some(ScssCompilerSetting::BuiltIn) => ScssCompiler::BuiltIn,
+                Some(ScssCompilerSetting::Sass) => ScssCompiler::Sass,
+                Some(ScssCompilerSetting::Auto) | None => ScssCompiler::detect(commands),
+            },
+            link_mode: LinkMode::from_setting(settings.links, config),
+        }
+    }
+}
+
+// ============================================================
+// BUILD RESULTS
+// ============================================================
+
+/// How bad a problem is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Something was left out or left as it was (e.g. an image whose smaller versions couldn't be made).
+    Warning,
+    /// A file couldn't be built (e.g. scss with a syntax error).
+    Error,
+}
+
+/// A problem a build ran into, that didn't stop it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The file it is about, if it is about one. e.g. edit-me/pages/index/index.scss
+    pub file: Option<PathBuf>,
+    pub message: String,
+}
+
+/// What a build did.
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// e.g. actual-website-do-not-edit
+    pub output_dir: PathBuf,
+    /// The edit-me files that were built, and where they went (in the output directory).
+    pub outputs: Vec<SourceFile>,
+    /// Every file of the website (relative to the output directory), and the edit-me file it comes from; worked out
+    /// before the build, for every edit-me file (not only the ones that were built).
+    pub published: BTreeMap<PathBuf, PathBuf>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// How long each stage took, in the order they ran. e.g. ("compile", 1.2s)
+    pub timings: Vec<(String, Duration)>,
+}
+
+impl BuildReport {
+    /// The diagnostics that are errors.
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// True if any file couldn't be built.
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// How long the whole build took.
+    pub fn total_time(&self) -> Duration {
+        self.timings.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Logs a warning, and keeps it.
+    fn warn(&mut self, file: Option<&Path>, message: String) {
+        warn!("{}", message);
+        self.diagnostics.push(Diagnostic { severity: Severity::Warning, file: file.map(Path::to_path_buf), message });
+    }
+
+    /// Logs an error, and keeps it.
+    fn error(&mut self, file: Option<&Path>, message: String) {
+        error!("{}", message);
+        self.diagnostics.push(Diagnostic { severity: Severity::Error, file: file.map(Path::to_path_buf), message });
+    }
+
+    /// Records how long a stage took, and starts timing the next one.
+    fn record_stage(&mut self, stage: &str, started: &mut Instant) {
+        self.timings.push((stage.to_string(), started.elapsed()));
+        *started = Instant::now();
+    }
+}
+
+// ============================================================
+// BUILDING
+// ============================================================
+
+/// A build of a site, set up with its methods and run with `build`.
+/// Each setting starts as site.toml's [build] has it; the methods set them over that (as the command line flags do).
+pub struct Builder<'a> {
+    site: &'a Site,
+    settings: BuildSettings,
+    fresh: bool,
+    only: Option<Vec<PathBuf>>,
+    processors: Vec<Box<dyn Processor>>,
+    check: bool,
+    fs: Arc<dyn FileSystem>,
+    commands: Arc<dyn CommandRunner>,
+}
+
+impl<'a> Builder<'a> {
+    /// Uses a [profiles.name] section of site.toml (over [build]).
+    ///
+    /// # Returns
+    /// * `Err` if there isn't a profile with that name.
+    pub fn profile(mut self, name: &str) -> Result<Self> {
+        self.site.config.build_settings(Some(name))?;
+        if let Some(profile) = self.site.config.profiles.get(name) {
+            self.settings = self.settings.overridden_by(profile);
+        }
+        Ok(self)
+    }
+
+    /// Sets the settings that `overrides` sets. (e.g. the command line flags)
+    pub fn settings(mut self, overrides: &BuildSettings) -> Self {
+        self.settings = self.settings.overridden_by(overrides);
+        self
+    }
+
+    /// Builds everything from scratch, rather than just what changed.
+    pub fn fresh(mut self, fresh: bool) -> Self {
+        self.fresh = fresh;
+        self
+    }
+
+    /// Minifies the output (and leaves out the source maps, unless they are asked for).
+    pub fn production(mut self, production: bool) -> Self {
+        self.settings.production = Some(production);
+        self
+    }
+
+    /// Removes `.html` from the links, for GitHub Pages.
+    pub fn github_pages(mut self, github_pages: bool) -> Self {
+        self.settings.github_pages = Some(github_pages);
+        self
+    }
+
+    /// Adds a hash of the content to the asset file names.
+    pub fn hash_assets(mut self, hash_assets: bool) -> Self {
+        self.settings.hash_assets = Some(hash_assets);
+        self
+    }
+
+    /// Inlines each page's critical css.
+    pub fn critical_css(mut self, critical_css: bool) -> Self {
+        self.settings.critical_css = Some(critical_css);
+        self
+    }
+
+    /// Looks up every internal link of the built website, and reports the broken ones (as warnings). (see link_checker.rs)
+    pub fn check_links(mut self, check_links: bool) -> Self {
+        self.settings.check_links = Some(check_links);
+        self
+    }
+
+    /// How component css / js is bundled.
+    pub fn bundle(mut self, bundle: BundleSetting) -> Self {
+        self.settings.bundle = Some(bundle);
+        self
+    }
+
+    /// What compiles the scss.
+    pub fn scss_compiler(mut self, scss_compiler: ScssCompilerSetting) -> Self {
+        self.settings.scss_compiler = Some(scss_compiler);
+        self
+    }
+
+    /// Where the source maps go.
+    pub fn source_maps(mut self, source_maps: SourceMapSetting) -> Self {
+        self.settings.source_maps = Some(source_maps);
+        self
+    }
+
+    /// How the links to the website's own files are written. (see links.rs)
+    pub fn links(mut self, links: LinkSetting) -> Self {
+        self.settings.links = Some(links);
+        self
+    }
+
+    /// Builds only these edit-me files (and the files that use them), as if only they had changed.
+    /// e.g. ["edit-me/pages/index/index.html"]
+    pub fn only<I, P>(mut self, files: I) -> Self where I: IntoIterator<Item = P>, P: Into<PathBuf> {
+        self.only = Some(files.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Builds everything, to find the problems (in the report), without changing the output directory: the build is thrown away.
+    /// (No source maps are written, and the last compile time and the image cache are left as they are)
+    /// Its links are always checked, and a broken one is an error.
+    pub fn check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Adds a processor, which runs after the default ones. (see processors.rs)
+    pub fn processor<P: Processor + 'static>(mut self, processor: P) -> Self {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    /// Reads and writes the project's files with `fs`, rather than on disk. (e.g. a `MemoryFileSystem`; see vfs.rs)
+    /// The real sass / tsc still work on the disk, so give it a `command_runner` too (e.g. a `MockCommandRunner`), unless
+    /// the website has no typescript and uses the built-in scss compiler.
+    pub fn file_system<F: FileSystem + 'static>(mut self, fs: F) -> Self {
+        self.fs = Arc::new(fs);
+        self
+    }
+
+    /// Runs the compilers (tsc, sass) with `commands`, rather than as processes. (e.g. a `MockCommandRunner`; see vfs.rs)
+    pub fn command_runner<C: CommandRunner + 'static>(mut self, commands: C) -> Self {
+        self.commands = Arc::new(commands);
+        self
+    }
+
+    /// The options the build will run with.
+    pub fn options(&self) -> BuildOptions {
+        let mut options = BuildOptions::from_settings(&self.settings, &self.site.config, self.fresh || self.check, self.commands.as_ref());
+        if self.check {
+            options.source_maps = SourceMapMode::None;
+        }
+        options
+    }
+
+    /// Works out where every edit-me file is published, as the build would (with its permalink, language versions and
+    /// compiled files), without building anything.
+    ///
+    /// # Returns
+    /// * Each published file (relative to the output directory), and the edit-me file it comes from. (see `ProcessorRegistry::plan_outputs`)
+    /// * `Err` if a permalink is invalid, or two edit-me files would be published as the same file.
+    pub fn plan(self) -> Result<BTreeMap<PathBuf, PathBuf>> {
+        let options = self.options();
+        let Builder { site, processors: extra_processors, fs, commands, .. } = self;
+        plan_outputs_of(fs.as_ref(), commands.as_ref(), &site.config, &options, extra_processors)
+    }
+
+    /// Runs the build. Orchestrates finding, preparing, and compiling all necessary files.
+    ///
+    /// # Returns
+    /// * What was built, the problems that didn't stop the build, and how long each stage took.
+    /// * `Err` if the build failed (in which case the output directory is left as it was).
+    pub fn build(self) -> Result<BuildReport> {
+        let options = self.options();
+        let Builder { site, settings: _, fresh: _, only, processors: extra_processors, check, fs, commands } = self;
+        let (fs, commands): (&dyn FileSystem, &dyn CommandRunner) = (fs.as_ref(), commands.as_ref());
+        let config = &site.config;
+        let paths = &config.paths;
+        let placeholders = &config.placeholders;
+        let source_dir: &Path = &paths.source_dir;
+        let components_dir = paths.components_dir();
+        if only.is_some() && check {
+            bail!("A check builds the whole website, so it can't be of only some files");
+        }
+        if only.is_some() && options.fresh_run {
+            bail!("A build of only some files can't be a fresh build (the rest of the website would be missing from it)");
+        }
+        if matches!(&options.link_mode, LinkMode::Url { base_url } if base_url.is_empty()) {
+            bail!("Links written as urls (links = \"url\") need the website's address: set base-url in [site] of {}", CONFIG_FILE_NAME);
+        }
+        let mut report = BuildReport { output_dir: paths.output_dir.clone(), ..BuildReport::default() };
+        let mut stage_started = Instant::now();
+        info!("----------------------------------------------------------------------------------------------------------");
+        info!(" -- RUNNING LOGIC --");
+        info!("----------------------------------------------------------------------------------------------------------");
+
+        // Only one build at a time (released when this function returns)
+        let _build_lock = BuildLock::acquire(fs, &config.project_path(BUILD_LOCK_FILE))?;
+
+        // The build is written into a staging directory, which replaces the output directory only once it has all succeeded
+        // (so if the build fails, the output directory is left as it was, and the staging directory is deleted)
+        let staging = StagingDirectory::create(fs, &paths.output_dir, options.fresh_run)?;
+        let output_dir: &Path = staging.path();
+        info!("Building into {} (swapped in as {} once the build succeeds)", output_dir.display(), paths.output_dir.display());
+
+        let last_compile_time:u64 = if options.fresh_run {
+            info!("RUNNING WITH FRESH RUN!!!!!!!!!!!!!!!!!!!!!!!!!!");
+            // (the staging directory starts empty, rather than as a copy of the existing output dir)
+            let source_map_dir = config.project_path(SOURCE_MAP_DIRECTORY);
+            if !check && fs.exists(&source_map_dir) {
+                fs.remove_dir_all(&source_map_dir)?;
+            }
+            // reset last compile time
+            0
+        } else {
+            read_last_compile_time(fs, &config.project_path(LAST_COMPILE_TIME_FILE).to_string_lossy())
+        };
+
+        let newly_modified_files_set: HashSet<String> = match &only {
+            // (a build of only some files rebuilds just those, as if only they had changed)
+            Some(files) => files.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+            None => {
+                let all_files = files_in_source_dir(fs, &source_dir.to_string_lossy())?;
+                let newly_modified_files = only_modify_time_newer_than_last_compile_time(fs, &all_files, last_compile_time)?;
+                newly_modified_files.iter().map(|p| p.to_string_lossy().to_string()).collect()
+            }
+        };
+
+
+        // TRANSLATION HTML FILES: Load all translation files from the translations directory
+        info!("Loading translation files...");
+        let translations_files: TranslationsFile = load_translation_files_from_directory(fs, paths.page_text_dir())?;
+    
+        info!("Successfully loaded {} language translation files", translations_files.len());
+        for available_language_code in translations_files.keys() {
+            info!("  - Available language: {}", available_language_code);
+        }
+
+        // LOCALIZED FILES: If one language version of a page / blog post changed, all of its versions need relinking
+        let language_codes: Vec<String> = translations_files.keys().cloned().collect();
+
+        // ROUTING: Where each page is published (see routing.rs). If that changed since the last build, the links of any
+        // page may have, and the pages that moved are still at their old paths, so everything is built again from nothing
+        let router = Router::for_pages(fs, config, &language_codes)?;
+        let routes_file = config.project_path(ROUTES_FILE);
+        let routes_changed = fs.read_to_string(&routes_file).unwrap_or_default() != router.to_string();
+        let newly_modified_files_set: HashSet<String> = if routes_changed && !options.fresh_run && only.is_none() {
+            info!("The permalinks changed since the last build, so everything is built again");
+            staging.clear()?;
+            files_in_source_dir(fs, &source_dir.to_string_lossy())?.iter().map(|p| p.to_string_lossy().to_string()).collect()
+        } else {
+            newly_modified_files_set
+        };
+        let newly_modified_files_set: HashSet<String> = expand_to_localized_groups(fs, &newly_modified_files_set, &language_codes);
+        let mut all_files_that_are_to_be_compiled: HashSet<String> = get_all_files_that_need_recompiling(fs, &source_dir.to_string_lossy(), &placeholders.component_start, &placeholders.component_end, &newly_modified_files_set, &components_dir.to_string_lossy());
+
+        // TYPESCRIPT MODULES: If a module (or anything it imports) changed, every file importing modules needs relinking
+        all_files_that_are_to_be_compiled.extend(typescript_files_to_relink(fs, source_dir, &newly_modified_files_set));
+        let module_usage: ModuleUsage = collect_module_usage(fs, source_dir);
+
+        // PROCESSORS: what is done to each type of file (see processors.rs)
+        let keep_source_maps = options.source_maps != SourceMapMode::None;
+        let mut processors: ProcessorRegistry = default_processors(&options, config, output_dir, keep_source_maps);
+        for processor in extra_processors {
+            processors.register_boxed(processor);
+        }
+        let context = ProcessContext { source_dir, output_dir, translations: &translations_files, language_codes: &language_codes, module_usage: &module_usage, fs, commands, router: &router };
+
+        // OUTPUT PATHS: Where every file goes, worked out up front, so two files published as the same file stop the build
+        // (rather than whichever is built last silently winning)
+        report.published = processors.plan_outputs(&fs.walk_files(source_dir), &context)?;
+        info!("Planned the {} files of the website (no two edit-me files are published as the same file)", report.published.len());
+
+        // DEPENDENCIES: Files that use a changed file (e.g. scss that @uses a changed partial) need recompiling too
+        let all_files_that_are_to_be_compiled: HashSet<String> = processors.with_dependents(&all_files_that_are_to_be_compiled, &context);
+
+        // (0); Load the prioritized components list
+        let components_list = load_components_list(fs, &paths.shared_dir())?;
+        report.record_stage("prepare", &mut stage_started);
+
+        // (3) Compiles the components, in the order they should be compiled in.
+        debug!("\n<><><><><><><><><><><><> COMPILING THE *PRIORITIZED* REUSABLE COMPONENTS IN ORDER <><><><><><><><><><><><>");
+        // ---------------------
+        let mut compiled_components = HashSet::new();
+        let mut all_processed_files = Vec::new();
+
+        // a. Iterate through components in order of priority
+        for component_name in &components_list {
+            // e.g. "reusables/top-bar"
+            let component_dir = paths.shared_dir().join(component_name);
+
+            if !fs.is_dir(&component_dir) {
+                warn!("Warning: component directory not found: {}", component_dir.display());
+                continue;
+            }
+
+            // Iterate over all files in the component’s folder
+            for path in fs.read_dir(&component_dir)? {
+                // Skip subdirectories if you only want direct files in the component folder
+                if fs.is_dir(&path) {
+                    continue;
+                }
+
+                let component_path_str: String = path.to_string_lossy().to_string();
+
+                debug!("....................checking whether the following path is in the list of new components that need compiling: {}", &component_path_str);
+                if !all_files_that_are_to_be_compiled.contains(&component_path_str) {
+                    continue;
+                }
+
+                debug!("\t~~~~~~~");
+                debug!("\tCompiling (prioritized): {}", &component_path_str);
+                debug!("\t~~~~~~~");
+
+                let mut single_file_set: HashSet<String> = HashSet::new();
+                single_file_set.insert(component_path_str.clone());
+                all_processed_files.extend(compile_all(&single_file_set, &processors, &context)?);
+                compiled_components.insert(component_path_str);
+            }
+        }
+
+        // b. Compile any remaining components that weren't in the priority list (i.e. any of the standard pages // things not in the shared directory)
+        let new_everything_else_that_needs_compiling : HashSet<String> = get_non_prioritised_files_list(&all_files_that_are_to_be_compiled, &compiled_components);
+
+        debug!("<><><><><><><><><><><><> DONE <><><><><><><><><><><><>");
+        debug!("\n<><><><><><><><><><><><> COMPILING THE REGULAR COMPONENTS IN ORDER <><><><><><><><><><><><>");
+
+        // (4) run compile All on the newly_modified_pages
+        all_processed_files.extend(compile_all(&new_everything_else_that_needs_compiling, &processors, &context)?);
+
+        report.record_stage("process", &mut stage_started);
+
+        // ----------------------------------------------------------------------------------------
+        // --- Final Compilation Step ---
+        // ----------------------------------------------------------------------------------------
+
+        // --- compile scss / typescript (and anything else a processor compiles) ---
+        let origins = SourceOrigins::new(fs, source_dir, output_dir, &placeholders.component_start, &placeholders.component_end, &paths.shared_code_folder)?;
+        for compile_summary in processors.compile_files(&all_processed_files, &context) {
+            for (source_path, reason) in &compile_summary.failures {
+                report.error(Some(source_path), point_errors_at_sources(reason, &origins));
+            }
+            info!("{} of {} {} file(s) compiled.", compile_summary.files_compiled, compile_summary.files_compiled + compile_summary.failures.len(), compile_summary.processor);
+        }
+        report.record_stage("compile", &mut stage_started);
+
+        // --- source maps ---
+        // (pointed back at the edit-me files, or cleaned up if they aren't wanted)
+        if keep_source_maps {
+            let compiled_files: Vec<PathBuf> = all_processed_files.iter().filter(|file| file.is_compiled()).map(|file| file.output_path.clone()).collect();
+            let source_map_summary = write_source_maps(fs, &compiled_files, output_dir, &origins, &options.source_maps)?;
+            for (map_path, reason) in &source_map_summary.failures {
+                report.warn(Some(map_path), format!("Warning: could not point the source map '{}' at the edit-me files, so it was removed: {}", map_path.display(), reason));
+            }
+            info!("{} source map(s) written ({} line(s) the compiler added aren't mapped).", source_map_summary.maps_written, source_map_summary.lines_untraced);
+        } else {
+            remove_source_maps(fs, output_dir)?;
+        }
+        report.record_stage("source maps", &mut stage_started);
+
+        info!("✅ finished compiling modified files");
+
+        // --- responsive images ---
+        // (runs over the whole output, since a page that didn't change may use an image that did)
+        // (a check uses the image cache, but doesn't add to it: it leaves the project as it was)
+        let image_settings = ImageSettings { widths: RESPONSIVE_IMAGE_WIDTHS.to_vec(), read_only_cache: check, ..ImageSettings::default() };
+        let image_summary = make_images_responsive(fs, output_dir, &config.project_path(IMAGE_CACHE_DIR), &image_settings)?;
+        for (image_path, reason) in &image_summary.failures {
+            report.warn(Some(image_path), format!("Warning: could not generate the responsive versions of '{}': {}", image_path.display(), reason));
+        }
+        info!("{} image(s) made responsive ({} variant(s) encoded, {} from the cache); {} html file(s) updated.", image_summary.images_processed, image_summary.variants_encoded, image_summary.variants_from_cache, image_summary.html_files_rewritten);
+        report.record_stage("images", &mut stage_started);
+
+        // --- component css / js bundles ---
+        // (before the minification and hashing, so the bundles get minified / hashed like any other file)
+        if let Some(mode) = options.bundle_mode {
+            let compilation_order: Vec<String> = components_list.iter().map(|component| format!("shared/{}", component)).collect();
+            let bundle_summary = bundle_component_assets(fs, output_dir, mode, &compilation_order)?;
+            info!("{} page(s) bundled; {} bundle(s) in {}/{} ({} removed).", bundle_summary.pages_bundled, bundle_summary.bundles_written, paths.output_dir.display(), BUNDLE_DIRECTORY, bundle_summary.bundles_removed);
+            report.record_stage("bundling", &mut stage_started);
+        }
+
+        // --- critical css ---
+        // (after the bundling, so it is the bundles that get deferred)
+        if options.critical_css {
+            let critical_summary = inline_critical_css_in_directory(fs, output_dir)?;
+            for (css_path, reason) in &critical_summary.failures {
+                report.warn(Some(css_path), format!("Warning: could not work out the critical css of '{}', so it is loaded as normal: {}", css_path.display(), reason));
+            }
+            info!("Critical css inlined in {} page(s) ({} KB); {} stylesheet link(s) deferred.", critical_summary.pages_processed, critical_summary.bytes_inlined / 1024, critical_summary.stylesheets_deferred);
+            report.record_stage("critical css", &mut stage_started);
+        }
+
+        // --- minification ---
+        // (before the hashing, so the hashes are of the files as they are published)
+        if options.production {
+            // (files with a source map are left alone, so the map still matches them)
+            let minify_summary = minify_directory(fs, output_dir, |path| has_source_map(fs, path, output_dir, &options.source_maps))?;
+            for (file_path, reason) in &minify_summary.failures {
+                report.warn(Some(file_path), format!("Warning: could not minify '{}', so it is left as it was: {}", file_path.display(), reason));
+            }
+            info!("{} file(s) minified ({} KB -> {} KB); {} left as they are for their source maps.", minify_summary.files_minified, minify_summary.bytes_before / 1024, minify_summary.bytes_after / 1024, minify_summary.files_skipped);
+            report.record_stage("minification", &mut stage_started);
+        }
+
+        // --- content-hashed asset file names ---
+        // (after the images, so the generated image versions get hashed too)
+        if options.hash_assets {
+            let manifest = fingerprint_assets(fs, output_dir)?;
+            info!("{} asset(s) given hashed file names; see {}/{}", manifest.len(), paths.output_dir.display(), ASSET_MANIFEST_FILE);
+            report.record_stage("asset hashing", &mut stage_started);
+        }
+
+        // --- absolute links ---
+        // (after every stage that follows the relative links: the images, bundling, critical css and hashing)
+        if let Some(root_url) = options.link_mode.root_url() {
+            let files_changed = apply_link_mode(fs, output_dir, &options.link_mode)?;
+            info!("Links made absolute (from {}) in {} html / css file(s).", root_url, files_changed);
+            report.record_stage("links", &mut stage_started);
+        }
+
+        // --- APPLYING GITHUB PAGES COMPILATION OPTIONS ---
+        if options.github_pages {
+            info!("----------------------------------------------------------------------------------------------------------");
+            info!(" -- APPLYING GITHUB PAGES COMPILATION OPTIONS --");
+            info!("----------------------------------------------------------------------------------------------------------");
+            let summary = prepare_for_github_pages(fs, output_dir, config)?;
+            let special_files: Vec<String> = summary.special_files.iter().filter_map(|path| path.file_name().map(|name| name.to_string_lossy().to_string())).collect();
+            info!("{} file(s) changed for GitHub Pages (base path: '{}'); wrote {}", summary.files_changed, base_path(config), special_files.join(", "));
+            report.record_stage("github pages", &mut stage_started);
+        }
+    
+        // --- link checking ---
+        // (last, so it is the links as they are published that are looked up)
+        if options.check_links || check {
+            let link_summary = check_links(fs, output_dir, &report.published, &root_urls(config))?;
+            for broken_link in &link_summary.broken {
+                let file = broken_link.source.clone().unwrap_or_else(|| paths.output_dir.join(&broken_link.file));
+                match check {
+                    true => report.error(Some(&file), broken_link.to_string()),
+                    false => report.warn(Some(&file), format!("Warning: {}", broken_link)),
+                }
+            }
+            info!("{} link(s) in {} html / css file(s) checked; {} broken.", link_summary.links_checked, link_summary.files_checked, link_summary.broken.len());
+            report.record_stage("link check", &mut stage_started);
+        }
+
+        // Swap the finished build into place
+        // (the files' paths are changed from the staging directory to the output directory it becomes)
+        report.outputs = all_processed_files.into_iter().map(|file| moved_to_output_dir(file, output_dir, &paths.output_dir)).collect();
+        if check {
+            // (the staging directory is deleted when it is dropped, so the output directory is left as it was)
+            drop(staging);
+            report.record_stage("check", &mut stage_started);
+            info!("✅ checked ({} left as it was)", paths.output_dir.display());
+            return Ok(report);
+        }
+        staging.publish()?;
+        info!("✅ {} updated", paths.output_dir.display());
+
+        // Update the last compile time in the hidden file
+        // (not after a build of only some files, as the other changed files still need building)
+        if only.is_none() {
+            if !router.is_empty() {
+                fs.write(&routes_file, router.to_string().as_bytes()).with_context(|| format!("Failed to write '{}'", routes_file.display()))?;
+            } else if fs.exists(&routes_file) {
+                fs.remove_file(&routes_file).with_context(|| format!("Failed to remove '{}'", routes_file.display()))?;
+            }
+            update_last_compile_time(fs, SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64, &config.project_path(LAST_COMPILE_TIME_FILE).to_string_lossy())?;
+        }
+        report.record_stage("publish", &mut stage_started);
+
+        info!("done.");
+        Ok(report)
+    }
+}
+
+/// A built file, with its paths moved from the staging directory to the output directory.
+fn moved_to_output_dir(mut file: SourceFile, staging_dir: &Path, output_dir: &Path) -> SourceFile {
+    let move_path = |path: &Path| match path.strip_prefix(staging_dir) {
+        Ok(relative) => output_dir.join(relative),
+        Err(_) => path.to_path_buf(),
+    };
+    file.copy_path = move_path(&file.copy_path);
+    file.output_path = move_path(&file.output_path);
+    file.default_output_path = move_path(&file.default_output_path);
+    file
+}
+
+
+/// Given all modified files and the set of already compiled components,
+/// returns the "non-prioritised" files (everything else).
+///
+/// # Arguments
+/// * `all_modified_files` - All of the files that changed.
+/// * `compiled_components` - The files that were already compiled (prioritised).
+///
+/// # Returns
+/// * The files of `all_modified_files` that weren't already compiled.
+pub fn get_non_prioritised_files_list(all_modified_files: &HashSet<String>, compiled_components: &HashSet<String>) -> HashSet<String> {
+    debug!("-> filtering non-prioritised files");
+    all_modified_files.difference(compiled_components).cloned().collect()
+}
+
+
+/// Pre-processes a list of source files: copies them to the output directory, and runs each one's processors over the
+/// copy (injecting / replacing placeholders and components, etc.; see processors.rs).
+/// Returns where each file went; the ones that need a final compilation (TS and SCSS) are compiled later, by `compile_files`.
+/// This is the Rust version of the `compileAll` shell function.
+pub fn compile_all(source_files: &HashSet<String>, processors: &ProcessorRegistry, context: &ProcessContext) -> Result<Vec<SourceFile>> {
+    let mut processed_files = Vec::new();
+
+    // Iterate through each file path in the HashSet
+    for source_path_str in source_files {
+        // e.g. source_path=edit-me/pages/index/index.ts  //  edit-me/shared/reusables/top-bar/top-bar.scss
+
+        // Skip empty lines
+        if source_path_str.trim().is_empty() {
+            continue;
+        }
+
+        processed_files.push(processors.process_file(Path::new(source_path_str), context)?);
+        debug!("        _____________________________");
+    }
+
+    Ok(processed_files)
+}
+
+
+/// Works out where every edit-me file of a site is published, as a build with its [build] settings would (see
+/// `Builder::plan`), reading the files from `fs`. (e.g. for `new`, to find out if a new page would replace another)
+///
+/// # Returns
+/// * Each published file (relative to the output directory), and the edit-me file it comes from.
+/// * `Err` if two files would be published as the same file, or the permalinks / page text can't be read.
+pub fn plan_site(fs: &dyn FileSystem, config: &SiteConfig) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    // (what compiles the scss doesn't change where anything is published, so the `sass` command isn't looked for)
+    let settings = BuildSettings { scss_compiler: Some(ScssCompilerSetting::BuiltIn), ..config.build.clone() };
+    let options = BuildOptions::from_settings(&settings, config, false, &SystemCommandRunner);
+    plan_outputs_of(fs, &SystemCommandRunner, config, &options, Vec::new())
+}
+
+/// Plans the outputs of every edit-me file with the default processors (and any extra ones), with the site's routes.
+fn plan_outputs_of(fs: &dyn FileSystem, commands: &dyn CommandRunner, config: &SiteConfig, options: &BuildOptions, extra_processors: Vec<Box<dyn Processor>>) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    let (source_dir, output_dir) = (config.paths.source_dir.as_path(), config.paths.output_dir.as_path());
+    let translations = load_translation_files_from_directory(fs, config.paths.page_text_dir())?;
+    let language_codes: Vec<String> = translations.keys().cloned().collect();
+    let router = Router::for_pages(fs, config, &language_codes)?;
+    let module_usage = collect_module_usage(fs, source_dir);
+    let mut processors = default_processors(options, config, output_dir, options.source_maps != SourceMapMode::None);
+    for processor in extra_processors {
+        processors.register_boxed(processor);
+    }
+    let context = ProcessContext { source_dir, output_dir, translations: &translations, language_codes: &language_codes, module_usage: &module_usage, fs, commands, router: &router };
+    processors.plan_outputs(&fs.walk_files(source_dir), &context)
+}
+
+/// The processors every build runs, in order: what is done to each type of file as it is copied into the output directory.
+/// (A project that needs another type of file handled registers its own processor after these; see processors.rs)
+pub fn default_processors(options: &BuildOptions, config: &SiteConfig, output_dir: &Path, keep_source_maps: bool) -> ProcessorRegistry {
+    let paths = &config.paths;
+    let placeholders = &config.placeholders;
+    let mut processors = ProcessorRegistry::new();
+    processors.register(ScssProcessor { import_start: placeholders.scss_import_start.clone(), root_placeholder: placeholders.root.clone(), compiler: options.scss_compiler, compressed: options.production, source_map: keep_source_maps });
+    processors.register(TypeScriptProcessor { shared_code_folder: paths.shared_code_folder.clone(), re_start: placeholders.component_start.clone(), re_end: placeholders.component_end.clone(), source_map: keep_source_maps });
+    processors.register(HtmlComponentsProcessor { placeholders: placeholders.clone() });
+    processors.register(LinksProcessor { root_placeholder: placeholders.root.clone() });
+    processors.register(RootPathsProcessor { root_placeholder: placeholders.root.clone(), link_mode: options.link_mode.clone() });
+    processors.register(BlogProcessor { blog_output_folder: output_dir.join(&paths.blog_folder), template_top: paths.blog_template_top(), template_bottom: paths.blog_template_bottom() });
+    processors.register(LocalizationProcessor { default_language: config.site.default_language.clone() });
+    processors.register(I18nProcessor);
+    processors
+}
+
+
+/// Loads and validates the prioritized list of components from the compilation order file.
+fn load_components_list(fs: &dyn FileSystem, shared_dir: &Path) -> Result<Vec<String>> {
+    let order_file_path = shared_dir.join(COMPILATION_ORDER_FILE);
+    debug!("><><><><><><><><><><>< loading the ordered components list from {} ><><><><><><><><><><><", order_file_path.display());
+
+    if !fs.is_file(&order_file_path) {
+        return Err(anyhow!("Error: Compilation order file not found at: {}", order_file_path.display()));
+    }
+
+    // Get all valid directory paths relative to SHARED_DIR (no max depth)
+    let valid_dirs: HashSet<String> = fs.walk(shared_dir).into_iter().filter(|path| fs.is_dir(path)).map(|path| path.strip_prefix(shared_dir).unwrap().to_string_lossy().into_owned()).collect();
+
+    // Read file, clean lines, and validate
+    let file_content = fs.read_to_string(&order_file_path)?;
+    let mut components_list = Vec::new();
+    for line in file_content.lines() {
+        let trimmed_line = line.trim();
+
+        // Skip empty lines and comments
+        if trimmed_line.is_empty() || trimmed_line.starts_with('#') {
+            continue;
+        }
+
+        // Check if it's a valid directory
+        if valid_dirs.contains(trimmed_line) {
+            components_list.push(trimmed_line.to_string());
+        } else {
+            warn!("Valid component directories are: {:?}", valid_dirs);
+            return Err(anyhow!("Error: Invalid component name in order file: \"{}\"", trimmed_line));
+        }
+    }
+    
+    debug!("Validated components list:\n{:?}", &components_list);
+    debug!("><><><><><><><><><><>< done ><><><><><><><><><><><");
+    Ok(components_list)
+}
@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde_json::Value;
+use crate::components::resolve_relative_reference;
+use crate::processors::output_relative_path;
+use crate::typescript_modules::linked_module_paths;
+use crate::vfs::FileSystem;
+
+// === SOURCE MAPS ===
+// sass and tsc can write a source map next to each file they compile (e.g. index.js + index.js.map), but what they compile
+// are the copies in the output directory, after the compiler has changed them (<root> replaced, <r-name> code pasted in, modules linked in),
+// so their maps point at those copies. After compiling, each map is rewritten to point at the original files in edit-me:
+//      -> `sources` become paths to the edit-me files (relative to the map), with their content embedded (`sourcesContent`)
+//      -> each line of a copy is traced back to the file + line it came from (the copy's own source, a linked module, pasted shared code)
+//
+// Modes:
+//      external -> the map is next to its file, and linked from it with a sourceMappingURL comment (the dev default)
+//      hidden   -> the map is written to a separate directory, and not linked from the website (e.g. for an error tracker)
+//      none     -> no maps (the production default)
+// A file that has a map isn't minified (see minify.rs), since the map would no longer match it.
+
+/// Where source maps go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceMapMode {
+    /// Next to each compiled file, linked from it.
+    External,
+    /// In this directory (at the same path as the compiled file), not linked from it.
+    Hidden(PathBuf),
+    /// No source maps.
+    None,
+}
+
+/// The outcome of `write_source_maps`.
+#[derive(Debug, Default)]
+pub struct SourceMapSummary {
+    pub maps_written: usize,
+    /// Lines of the compiled copies that couldn't be traced back to an edit-me file (e.g. the namespace wrapped around a linked module).
+    pub lines_untraced: usize,
+    /// Maps that couldn't be rewritten, with the reason. (they are removed, rather than left pointing at the wrong files)
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+/// One segment of a source map's `mappings`: a column of the generated file, and the place in a source it came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mapping {
+    pub generated_column: i64,
+    pub original: Option<OriginalPosition>,
+}
+
+/// A position in one of a source map's `sources` (all 0 based).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OriginalPosition {
+    pub source: usize,
+    pub line: usize,
+    pub column: i64,
+    /// An index into the map's `names`.
+    pub name: Option<usize>,
+}
+
+// ===============================================================================================
+// THE MAPPINGS (base64 VLQ)
+// ===============================================================================================
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Decodes a source map's `mappings` into one list of segments per line of the generated file.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::source_maps::decode_mappings;
+/// let lines = decode_mappings("AAAA,IAAI;;AACA").unwrap();
+/// assert_eq!(lines.len(), 3);
+/// assert_eq!((lines[0][1].generated_column, lines[0][1].original.unwrap().column), (4, 4));
+/// assert_eq!(lines[2][0].original.unwrap().line, 1);
+/// ```
+pub fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Mapping>>> {
+    let (mut source, mut line, mut column, mut name) = (0i64, 0i64, 0i64, 0i64);
+    let mut lines = Vec::new();
+
+    for line_mappings in mappings.split(';') {
+        let mut generated_column = 0i64;
+        let mut segments = Vec::new();
+        for segment in line_mappings.split(',').filter(|segment| !segment.is_empty()) {
+            let values = decode_vlq(segment)?;
+            generated_column += values[0];
+            let original = match values.len() {
+                1 => None,
+                4 | 5 => {
+                    source += values[1];
+                    line += values[2];
+                    column += values[3];
+                    if values.len() == 5 {
+                        name += values[4];
+                    }
+                    if source < 0 || line < 0 {
+                        bail!("source map segment '{}' points before the start of a source", segment);
+                    }
+                    Some(OriginalPosition { source: source as usize, line: line as usize, column, name: (values.len() == 5).then_some(name as usize) })
+                }
+                _ => bail!("source map segment '{}' has {} fields", segment, values.len()),
+            };
+            segments.push(Mapping { generated_column, original });
+        }
+        lines.push(segments);
+    }
+    Ok(lines)
+}
+
+/// Encodes segments (one list per line of the generated file) as a source map's `mappings`.
+pub fn encode_mappings(lines: &[Vec<Mapping>]) -> String {
+    let (mut source, mut line, mut column, mut name) = (0i64, 0i64, 0i64, 0i64);
+    let mut encoded = String::new();
+
+    for (line_index, segments) in lines.iter().enumerate() {
+        if line_index > 0 {
+            encoded.push(';');
+        }
+        let mut generated_column = 0i64;
+        for (segment_index, segment) in segments.iter().enumerate() {
+            if segment_index > 0 {
+                encoded.push(',');
+            }
+            encode_vlq(segment.generated_column - generated_column, &mut encoded);
+            generated_column = segment.generated_column;
+            if let Some(original) = segment.original {
+                encode_vlq(original.source as i64 - source, &mut encoded);
+                encode_vlq(original.line as i64 - line, &mut encoded);
+                encode_vlq(original.column - column, &mut encoded);
+                (source, line, column) = (original.source as i64, original.line as i64, original.column);
+                if let Some(original_name) = original.name {
+                    encode_vlq(original_name as i64 - name, &mut encoded);
+                    name = original_name as i64;
+                }
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_vlq(segment: &str) -> Result<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0i64, 0);
+    for c in segment.bytes() {
+        let digit = BASE64_ALPHABET.iter().position(|&b| b == c).ok_or_else(|| anyhow!("'{}' isn't base64 (in source map segment '{}')", c as char, segment))? as i64;
+        value += (digit & 31) << shift;
+        if digit & 32 != 0 {
+            shift += 5;
+            continue;
+        }
+        values.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+        (value, shift) = (0, 0);
+    }
+    if shift != 0 {
+        bail!("source map segment '{}' ends part way through a value", segment);
+    }
+    Ok(values)
+}
+
+fn encode_vlq(value: i64, encoded: &mut String) {
+    let mut vlq = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+    loop {
+        let mut digit = vlq & 31;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 32;
+        }
+        encoded.push(BASE64_ALPHABET[digit as usize] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+// ===============================================================================================
+// TRACING LINES BACK TO THEIR SOURCE
+// ===============================================================================================
+
+/// Works out which file + line each line of a generated file came from, given the files its content was taken from.
+/// Lines are matched in order (longest common subsequence, ignoring indentation), one original file at a time;
+/// a changed line (e.g. with <root> replaced) between two matched lines of the same file is traced to the line between them.
+///
+/// # Arguments
+/// * `generated` - e.g. a page's typescript after the compiler pasted / linked code into it.
+/// * `originals` - The content of the files it was made from, most important first (e.g. the page's own source, then the linked modules).
+///
+/// # Returns
+/// * For each line of `generated`: the index into `originals` and the 0 based line in it, or None if it wasn't in any of them.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::source_maps::trace_line_origins;
+/// let generated = "const a = 1;\nnamespace ns {\nexport function f() {}\n}\nf();\n";
+/// let origins = trace_line_origins(generated, &["f();\n", "// a module\nexport function f() {}\n"]);
+/// assert_eq!(origins, [None, None, Some((1, 1)), None, Some((0, 0)), None]);
+/// ```
+pub fn trace_line_origins(generated: &str, originals: &[&str]) -> Vec<Option<(usize, usize)>> {
+    let generated_lines: Vec<&str> = generated.split('\n').map(str::trim).collect();
+    let mut origins: Vec<Option<(usize, usize)>> = vec![None; generated_lines.len()];
+
+    for (file_index, original) in originals.iter().enumerate() {
+        let original_lines: Vec<(usize, &str)> = original.split('\n').map(str::trim).enumerate().filter(|(_, line)| !line.is_empty()).collect();
+        let pending: Vec<(usize, &str)> = generated_lines.iter().copied().enumerate().filter(|(i, line)| origins[*i].is_none() && !line.is_empty()).collect();
+        for (generated_index, original_line) in longest_common_subsequence(&pending, &original_lines) {
+            origins[generated_index] = Some((file_index, original_line));
+        }
+    }
+
+    // Changed lines, between two lines that were traced to the same file the same distance apart
+    let traced: Vec<usize> = (0..origins.len()).filter(|i| origins[*i].is_some()).collect();
+    for pair in traced.windows(2) {
+        let (before, after) = (pair[0], pair[1]);
+        let (Some((file, before_line)), Some((after_file, after_line))) = (origins[before], origins[after]) else { continue };
+        if file == after_file && after_line > before_line && after_line - before_line == after - before {
+            for offset in 1..after - before {
+                origins[before + offset] = Some((file, before_line + offset));
+            }
+        }
+    }
+    origins
+}
+
+/// Pairs up equal lines of `a` and `b`, in order, as (a's line number, b's line number).
+fn longest_common_subsequence(a: &[(usize, &str)], b: &[(usize, &str)]) -> Vec<(usize, usize)> {
+    // Lines only in one of them can't be in the subsequence, so are left out of the (n x m) table
+    let in_b: std::collections::HashSet<&str> = b.iter().map(|(_, line)| *line).collect();
+    let a: Vec<&(usize, &str)> = a.iter().filter(|(_, line)| in_b.contains(line)).collect();
+    let in_a: std::collections::HashSet<&str> = a.iter().map(|(_, line)| *line).collect();
+    let b: Vec<&(usize, &str)> = b.iter().filter(|(_, line)| in_a.contains(line)).collect();
+
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i].1 == b[j].1 {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].1 == b[j].1 {
+            pairs.push((a[i].0, b[j].0));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+// ===============================================================================================
+// WHERE THE OUTPUT'S FILES CAME FROM
+// ===============================================================================================
+
+/// Knows which edit-me files each (pre-compilation) file in the output directory was made from.
+pub struct SourceOrigins {
+    source_dir: PathBuf,
+    /// e.g. "actual-website-do-not-edit/index.ts" -> "edit-me/pages/index/index.ts"
+    copies: HashMap<PathBuf, PathBuf>,
+    /// Matches the <r-name> tags that paste shared code into typescript files
+    pasted_code_regex: Regex,
+    shared_code_dir: PathBuf,
+}
+
+impl SourceOrigins {
+    /// Finds where the typescript / scss files in the source directory are copied to in the output directory.
+    /// (the same places as the build puts them: the pages/<page>/ part of a page's path is dropped, see `output_relative_path`)
+    ///
+    /// # Arguments
+    /// * `fs` - Where the source directory is. e.g. &DiskFileSystem
+    /// * `source_dir` - e.g. "edit-me".
+    /// * `output_dir` - e.g. "actual-website-do-not-edit".
+    /// * `re_start` / `re_end` - The start / end of the tags that paste in shared code, e.g. "<r-" and ">".
+    /// * `shared_code_folder` - The folder in shared/ the pasted code is in, e.g. "code".
+    pub fn new(fs: &dyn FileSystem, source_dir: &Path, output_dir: &Path, re_start: &str, re_end: &str, shared_code_folder: &str) -> Result<Self> {
+        let mut copies = HashMap::new();
+        for path in fs.walk_files(source_dir) {
+            let path = path.as_path();
+            if !path.extension().and_then(|e| e.to_str()).is_some_and(|e| e == "ts" || e == "scss") {
+                continue;
+            }
+            let relative = path.strip_prefix(source_dir).unwrap_or(path);
+            copies.insert(output_dir.join(output_relative_path(relative)), path.to_path_buf());
+        }
+        Ok(SourceOrigins {
+            source_dir: source_dir.to_path_buf(),
+            copies,
+            pasted_code_regex: Regex::new(&format!(r"{}([A-Za-z0-9_.-]+){}", regex::escape(re_start), regex::escape(re_end)))?,
+            shared_code_dir: source_dir.join("shared").join(shared_code_folder),
+        })
+    }
+
+    /// The edit-me file a file in the output directory is a copy of.
+    pub fn source_of(&self, copy: &Path) -> Option<&Path> {
+        self.copies.get(copy).map(PathBuf::as_path)
+    }
+
+    /// The edit-me files whose code is in a file in the output directory: the file it is a copy of,
+    /// then (for typescript) the modules linked into it and the shared code pasted into it with <r-name>.
+    /// (The edit-me files are read from `fs`, e.g. &DiskFileSystem)
+    pub fn original_files(&self, fs: &dyn FileSystem, copy: &Path, copy_content: &str) -> Vec<PathBuf> {
+        let Some(source) = self.source_of(copy) else {
+            return Vec::new();
+        };
+        let mut files = vec![source.to_path_buf()];
+        if source.extension().is_some_and(|e| e == "ts") {
+            files.extend(linked_module_paths(copy_content).into_iter().map(|path| self.source_dir.join(path)));
+            if let Ok(source_content) = fs.read_to_string(source) {
+                for captures in self.pasted_code_regex.captures_iter(&source_content) {
+                    let pasted = self.shared_code_dir.join(format!("{}.ts", &captures[1]));
+                    if fs.is_file(&pasted) && !files.contains(&pasted) {
+                        files.push(pasted);
+                    }
+                }
+            }
+        }
+        files
+    }
+}
+
+// ===============================================================================================
+// REWRITING THE MAPS
+// ===============================================================================================
+
+/// Removes the `sourceMappingURL` comment(s) from css / javascript.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::source_maps::strip_source_mapping_url;
+/// assert_eq!(strip_source_mapping_url("a();\n//# sourceMappingURL=index.js.map"), "a();\n");
+/// assert_eq!(strip_source_mapping_url(".a{}\n/*# sourceMappingURL=a.css.map */\n"), ".a{}\n");
+/// ```
+pub fn strip_source_mapping_url(code: &str) -> String {
+    static SOURCE_MAPPING_URL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^[ \t]*(?://[#@] sourceMappingURL=[^\n]*|/\*[#@] sourceMappingURL=[^\n]*?\*/)[ \t]*(?:\r?\n|$)").unwrap());
+    SOURCE_MAPPING_URL_REGEX.replace_all(code, "").to_string()
+}
+
+/// The source map that sass / tsc write next to a compiled file (e.g. index.js -> index.js.map).
+pub fn source_map_path(compiled_file: &Path) -> PathBuf {
+    let mut path = compiled_file.as_os_str().to_owned();
+    path.push(".map");
+    PathBuf::from(path)
+}
+
+/// Returns true if a compiled file in the output directory has a source map (so must be left as it is).
+/// (The map is looked for in `fs`, e.g. &DiskFileSystem)
+pub fn has_source_map(fs: &dyn FileSystem, compiled_file: &Path, output_dir: &Path, mode: &SourceMapMode) -> bool {
+    match mode {
+        SourceMapMode::External => fs.is_file(&source_map_path(compiled_file)),
+        SourceMapMode::Hidden(maps_dir) => compiled_file.strip_prefix(output_dir).is_ok_and(|relative| fs.is_file(&source_map_path(&maps_dir.join(relative)))),
+        SourceMapMode::None => false,
+    }
+}
+
+/// Rewrites a source map written by sass / tsc, so it points at the edit-me files instead of the copies in the output directory.
+///
+/// # Arguments
+/// * `fs` - Where the copies and the edit-me files are read from. e.g. &DiskFileSystem
+/// * `map_json` - The source map, as sass / tsc wrote it.
+/// * `map_path` - Where sass / tsc wrote it (its `sources` are relative to this).
+/// * `destination` - Where the rewritten map goes (its `sources` are made relative to this).
+/// * `origins` - Where the output directory's files came from.
+///
+/// # Returns
+/// * The rewritten map, and how many lines of the copies couldn't be traced back to an edit-me file.
+pub fn rewrite_source_map(fs: &dyn FileSystem, map_json: &str, map_path: &Path, destination: &Path, origins: &SourceOrigins) -> Result<(String, usize)> {
+    let mut map: Value = serde_json::from_str(map_json).context("Source map isn't valid json")?;
+    let source_root = map.get("sourceRoot").and_then(Value::as_str).unwrap_or("").to_string();
+    let sources: Vec<String> = map.get("sources").and_then(Value::as_array).ok_or_else(|| anyhow!("Source map has no sources"))?
+        .iter().map(|source| source.as_str().unwrap_or("").to_string()).collect();
+    let lines = decode_mappings(map.get("mappings").and_then(Value::as_str).unwrap_or(""))?;
+    let destination_dir = destination.parent().unwrap_or(Path::new(""));
+    let current_dir = std::env::current_dir().unwrap_or_default();
+
+    // 1) Where each of the old sources' lines came from
+    let mut new_sources: Vec<(String, Option<String>)> = Vec::new();
+    let mut source_index = |path: &Path, content: Option<String>| -> usize {
+        let relative = pathdiff::diff_paths(path, destination_dir).unwrap_or(path.to_path_buf()).to_string_lossy().replace('\\', "/");
+        new_sources.iter().position(|(source, _)| *source == relative).unwrap_or_else(|| {
+            new_sources.push((relative, content));
+            new_sources.len() - 1
+        })
+    };
+    let mut line_origins: Vec<Vec<Option<(usize, usize)>>> = Vec::new();
+    let mut untraced_lines = 0;
+    for source in &sources {
+        let url = format!("{}{}", source_root, source);
+        let copy = match url.strip_prefix("file://") {
+            Some(absolute) => { let path = PathBuf::from(absolute); path.strip_prefix(&current_dir).map(Path::to_path_buf).unwrap_or(path) }
+            None => resolve_relative_reference(map_path, &url),
+        };
+        let copy_content = fs.read_to_string(&copy).unwrap_or_default();
+        let original_files = origins.original_files(fs, &copy, &copy_content);
+        if original_files.is_empty() {
+            // Not from edit-me (e.g. one of sass's built in modules): kept, but relative to the new location
+            let index = source_index(&copy, None);
+            line_origins.push((0..copy_content.split('\n').count().max(1)).map(|line| Some((index, line))).collect());
+            continue;
+        }
+        let original_contents: Vec<String> = original_files.iter().map(|path| fs.read_to_string(path).unwrap_or_default()).collect();
+        let content_refs: Vec<&str> = original_contents.iter().map(String::as_str).collect();
+        let traced = trace_line_origins(&copy_content, &content_refs);
+        untraced_lines += traced.iter().zip(copy_content.split('\n')).filter(|(origin, line)| origin.is_none() && !line.trim().is_empty()).count();
+        let indexes: Vec<usize> = original_files.iter().zip(&original_contents).map(|(path, content)| source_index(path, Some(content.clone()))).collect();
+        line_origins.push(traced.into_iter().map(|origin| origin.map(|(file, line)| (indexes[file], line))).collect());
+    }
+
+    // 2) The mappings, pointing at the new sources
+    let remapped: Vec<Vec<Mapping>> = lines.iter().map(|segments| segments.iter().map(|segment| {
+        let original = segment.original.and_then(|original| {
+            let (source, line) = line_origins.get(original.source)?.get(original.line).copied()??;
+            Some(OriginalPosition { source, line, ..original })
+        });
+        Mapping { generated_column: segment.generated_column, original }
+    }).collect()).collect();
+
+    let object = map.as_object_mut().ok_or_else(|| anyhow!("Source map isn't a json object"))?;
+    object.remove("sourceRoot");
+    object.insert("sources".to_string(), Value::from(new_sources.iter().map(|(source, _)| source.clone()).collect::<Vec<_>>()));
+    object.insert("sourcesContent".to_string(), Value::from(new_sources.into_iter().map(|(_, content)| content.map_or(Value::Null, Value::from)).collect::<Vec<_>>()));
+    object.insert("mappings".to_string(), Value::from(encode_mappings(&remapped)));
+    Ok((serde_json::to_string(&map)?, untraced_lines))
+}
+
+/// Rewrites the source maps of freshly compiled files to point at the edit-me files, and puts them where the mode says.
+/// (files without a map, e.g. because they didn't compile, are skipped)
+///
+/// # Arguments
+/// * `fs` - Where the website is. e.g. &DiskFileSystem
+/// * `compiled_files` - The css / js files that were just compiled.
+/// * `output_dir` - The compiled website, e.g. 'actual-website-do-not-edit'.
+/// * `origins` - Where the output directory's files came from.
+/// * `mode` - External / Hidden (None removes the maps; see `remove_source_maps`).
+pub fn write_source_maps(fs: &dyn FileSystem, compiled_files: &[PathBuf], output_dir: &Path, origins: &SourceOrigins, mode: &SourceMapMode) -> Result<SourceMapSummary> {
+    let mut summary = SourceMapSummary::default();
+    for compiled_file in compiled_files {
+        let map_path = source_map_path(compiled_file);
+        let Ok(map_json) = fs.read_to_string(&map_path) else {
+            continue;
+        };
+        let destination = match mode {
+            SourceMapMode::Hidden(maps_dir) => source_map_path(&maps_dir.join(compiled_file.strip_prefix(output_dir).unwrap_or(compiled_file))),
+            _ => map_path.clone(),
+        };
+        let linked = match rewrite_source_map(fs, &map_json, &map_path, &destination, origins) {
+            Ok((rewritten, untraced_lines)) => {
+                if let Some(parent) = destination.parent() {
+                    fs.create_dir_all(parent).with_context(|| format!("Failed to create '{}'", parent.display()))?;
+                }
+                fs.write(&destination, rewritten.as_bytes()).with_context(|| format!("Failed to write '{}'", destination.display()))?;
+                summary.maps_written += 1;
+                summary.lines_untraced += untraced_lines;
+                destination == map_path
+            }
+            Err(e) => {
+                summary.failures.push((map_path.clone(), format!("{:#}", e)));
+                false
+            }
+        };
+        // A hidden map isn't linked from the website (and a map that couldn't be rewritten isn't kept)
+        if !linked {
+            fs.remove_file(&map_path).with_context(|| format!("Failed to delete '{}'", map_path.display()))?;
+            let code = fs.read_to_string(compiled_file).with_context(|| format!("Failed to read '{}'", compiled_file.display()))?;
+            let stripped = strip_source_mapping_url(&code);
+            if stripped != code {
+                fs.write(compiled_file, stripped.as_bytes()).with_context(|| format!("Failed to write '{}'", compiled_file.display()))?;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Removes every source map in the output directory (in `fs`), and the comments linking to them.
+///
+/// # Returns
+/// * The number of maps removed.
+pub fn remove_source_maps(fs: &dyn FileSystem, output_dir: &Path) -> Result<usize> {
+    let maps: Vec<PathBuf> = fs.walk_files(output_dir)
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "map"))
+        .collect();
+    for map_path in &maps {
+        fs.remove_file(map_path).with_context(|| format!("Failed to delete '{}'", map_path.display()))?;
+        let compiled_file = map_path.with_extension("");
+        if let Ok(code) = fs.read_to_string(&compiled_file) {
+            let stripped = strip_source_mapping_url(&code);
+            if stripped != code {
+                fs.write(&compiled_file, stripped.as_bytes()).with_context(|| format!("Failed to write '{}'", compiled_file.display()))?;
+            }
+        }
+    }
+    Ok(maps.len())
+}
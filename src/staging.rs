@@ -0,0 +1,261 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use anyhow::{bail, Context, Result};
+use log::info;
+use crate::vfs::FileSystem;
+
+// === STAGING BUILDS ===
+// A build is written into a staging directory next to the output directory (e.g. .actual-website-do-not-edit.staging),
+// which is only swapped into place once the whole build has succeeded:
+//      -> a fresh build starts from an empty staging directory
+//      -> an incremental build starts from a copy of the current output (as only the changed files are rebuilt)
+// So a build that fails or is stopped leaves the output directory exactly as the last successful build left it.
+// (The staging directory is at the same depth as the output directory, so the relative <root> paths are the same in both)
+//
+// Publishing is two renames (output -> .previous, then staging -> output), so for that moment there is no output directory:
+// a request `serve` (or another server) answers at that moment gets a 404, and a reload fixes it. And as an incremental build
+// starts from a copy of the whole output, its time grows with the size of the website, not just with what changed.
+//
+// A lock file makes sure only one build runs in a folder at a time (e.g. a rebuild by `serve` and a build run by hand).
+// It holds the build's process id, so the lock of a build that was killed (and couldn't remove it) is noticed and replaced
+// (by one build only, see `replace_stale_lock`).
+// (Both work on the build's file system, see vfs.rs)
+
+/// How long a lock file that doesn't have a process id in it yet is treated as held (its build is just starting).
+const NEW_LOCK_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Held for as long as a build runs; the lock file is removed when it is dropped.
+pub struct BuildLock<'a> {
+    fs: &'a dyn FileSystem,
+    path: PathBuf,
+}
+
+impl<'a> BuildLock<'a> {
+    /// Takes the build lock, by creating the lock file.
+    ///
+    /// # Arguments
+    /// * `fs` - Where the lock file is. e.g. &DiskFileSystem
+    /// * `path` - The lock file. e.g. .build.lock
+    ///
+    /// # Returns
+    /// * The lock, released when it is dropped.
+    /// * `Err` if another build that is still running holds it.
+    pub fn acquire(fs: &'a dyn FileSystem, path: &Path) -> Result<Self> {
+        loop {
+            match fs.create_new(path, std::process::id().to_string().as_bytes()) {
+                Ok(()) => {
+                    return Ok(BuildLock { fs, path: path.to_path_buf() });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    // (gone already: its build just finished)
+                    let Ok(contents) = fs.read_to_string(path) else { continue };
+                    let holder: Option<u32> = contents.trim().parse().ok();
+                    match holder {
+                        Some(process_id) if process_is_running(process_id) => {
+                            bail!("Another build (process {}) is already running here. Wait for it to finish, or if it isn't running, delete '{}'", process_id, path.display());
+                        }
+                        None if file_age(fs, path).is_some_and(|age| age < NEW_LOCK_GRACE_PERIOD) => {
+                            bail!("Another build is just starting here (see '{}')", path.display());
+                        }
+                        _ => {
+                            info!("Replacing the lock of a build that was stopped: {}", path.display());
+                            replace_stale_lock(fs, path, &contents)?;
+                            return Ok(BuildLock { fs, path: path.to_path_buf() });
+                        }
+                    }
+                }
+                Err(e) => return Err(e).with_context(|| format!("Failed to create the build lock '{}'", path.display())),
+            }
+        }
+    }
+
+    /// The lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for BuildLock<'_> {
+    fn drop(&mut self) {
+        let _ = self.fs.remove_file(&self.path);
+    }
+}
+
+impl std::fmt::Debug for BuildLock<'_> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.debug_struct("BuildLock").field("path", &self.path).finish()
+    }
+}
+
+/// Replaces the lock file of a build that was stopped with this build's. Only one build can be replacing it (the one that
+/// makes the takeover file next to it), and only if it is still the stopped build's; this build's lock is written to a
+/// temporary file that is renamed over it (so it is never missing or half written), then read back to make sure it is ours.
+///
+/// # Arguments
+/// * `path` - The lock file. e.g. .build.lock
+/// * `stale_contents` - What the stopped build's lock file has in it.
+///
+/// # Returns
+/// * `Err` if another build took the lock first, or the lock file couldn't be written.
+fn replace_stale_lock(fs: &dyn FileSystem, path: &Path, stale_contents: &str) -> Result<()> {
+    let process_id = std::process::id().to_string();
+    let takeover_path = with_suffix(path, "takeover");
+    match fs.create_new(&takeover_path, process_id.as_bytes()) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            bail!("Another build is replacing the lock of a build that was stopped. If no build is running, delete '{}'", takeover_path.display());
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to create '{}'", takeover_path.display())),
+    }
+
+    let replaced = (|| {
+        if fs.read_to_string(path).ok().as_deref() != Some(stale_contents) {
+            bail!("Another build has just started here (see '{}')", path.display());
+        }
+        let new_lock_path = with_suffix(path, &process_id);
+        fs.write(&new_lock_path, process_id.as_bytes()).with_context(|| format!("Failed to write '{}'", new_lock_path.display()))?;
+        fs.rename(&new_lock_path, path).with_context(|| format!("Failed to replace the stale build lock '{}'", path.display()))?;
+        if fs.read_to_string(path).ok().as_deref() != Some(process_id.as_str()) {
+            bail!("Another build took the lock '{}' first", path.display());
+        }
+        Ok(())
+    })();
+    let _ = fs.remove_file(&takeover_path);
+    replaced
+}
+
+/// A file's path with a suffix added to its name. e.g. .build.lock -> .build.lock.takeover
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// True if a process with the id is running. (If that can't be told, it is assumed to be)
+fn process_is_running(process_id: u32) -> bool {
+    let proc_dir = Path::new("/proc");
+    if proc_dir.is_dir() {
+        return proc_dir.join(process_id.to_string()).exists();
+    }
+    Command::new("kill").arg("-0").arg(process_id.to_string()).output().map(|output| output.status.success()).unwrap_or(true)
+}
+
+/// How long ago a file was last changed.
+fn file_age(fs: &dyn FileSystem, path: &Path) -> Option<Duration> {
+    let modified = fs.modified(path).ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+/// The directory a build is written into, before being swapped into place with `publish`.
+/// If it is dropped without being published (i.e. the build failed), it is deleted, leaving the output directory as it was.
+pub struct StagingDirectory<'a> {
+    fs: &'a dyn FileSystem,
+    output_dir: PathBuf,
+    path: PathBuf,
+    published: bool,
+}
+
+impl<'a> StagingDirectory<'a> {
+    /// Makes the staging directory for a build. (Any left behind by a build that was stopped is deleted first)
+    ///
+    /// # Arguments
+    /// * `fs` - Where the directories are. e.g. &DiskFileSystem
+    /// * `output_dir` - e.g. actual-website-do-not-edit
+    /// * `fresh` - Start from nothing, rather than from a copy of the output directory. (Copying is the whole output, every time)
+    ///
+    /// # Returns
+    /// * The staging directory. e.g. .actual-website-do-not-edit.staging
+    pub fn create(fs: &'a dyn FileSystem, output_dir: &Path, fresh: bool) -> Result<Self> {
+        let path = sibling_directory(output_dir, "staging");
+        if fs.exists(&path) {
+            fs.remove_dir_all(&path).with_context(|| format!("Failed to remove the old staging directory '{}'", path.display()))?;
+        }
+        if !fresh && fs.is_dir(output_dir) {
+            copy_directory(fs, output_dir, &path)?;
+        }
+        fs.create_dir_all(&path).with_context(|| format!("Failed to create the staging directory '{}'", path.display()))?;
+        Ok(StagingDirectory { fs, output_dir: output_dir.to_path_buf(), path, published: false })
+    }
+
+    /// Where the build is written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Empties the staging directory, so the build starts from nothing. (e.g. when an incremental build finds it can't be one)
+    pub fn clear(&self) -> Result<()> {
+        self.fs.remove_dir_all(&self.path).with_context(|| format!("Failed to empty the staging directory '{}'", self.path.display()))?;
+        self.fs.create_dir_all(&self.path).with_context(|| format!("Failed to create the staging directory '{}'", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Swaps the staging directory into place as the output directory. (The previous output is deleted)
+    /// If the swap fails, the previous output is put back.
+    /// The output directory doesn't exist between the two renames of the swap. (see the top of this file)
+    pub fn publish(mut self) -> Result<()> {
+        let fs = self.fs;
+        let previous = sibling_directory(&self.output_dir, "previous");
+        if fs.exists(&previous) {
+            fs.remove_dir_all(&previous).with_context(|| format!("Failed to remove '{}'", previous.display()))?;
+        }
+        if fs.exists(&self.output_dir) {
+            fs.rename(&self.output_dir, &previous).with_context(|| format!("Failed to move '{}' out of the way", self.output_dir.display()))?;
+        }
+        if let Err(e) = fs.rename(&self.path, &self.output_dir) {
+            if fs.exists(&previous) {
+                let _ = fs.rename(&previous, &self.output_dir);
+            }
+            return Err(e).with_context(|| format!("Failed to move '{}' to '{}'", self.path.display(), self.output_dir.display()));
+        }
+        self.published = true;
+
+        if fs.exists(&previous) {
+            fs.remove_dir_all(&previous).with_context(|| format!("Failed to remove the previous output '{}'", previous.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StagingDirectory<'_> {
+    fn drop(&mut self) {
+        if !self.published {
+            let _ = self.fs.remove_dir_all(&self.path);
+        }
+    }
+}
+
+impl std::fmt::Debug for StagingDirectory<'_> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.debug_struct("StagingDirectory").field("output_dir", &self.output_dir).field("path", &self.path).field("published", &self.published).finish()
+    }
+}
+
+/// The directories a build makes next to the output directory (normally gone once it finishes, but left behind by a build that was killed).
+/// e.g. actual-website-do-not-edit -> [.actual-website-do-not-edit.staging, .actual-website-do-not-edit.previous]
+pub fn working_directories(output_dir: &Path) -> [PathBuf; 2] {
+    [sibling_directory(output_dir, "staging"), sibling_directory(output_dir, "previous")]
+}
+
+/// A hidden directory next to another one. e.g. actual-website-do-not-edit -> .actual-website-do-not-edit.staging
+fn sibling_directory(dir: &Path, suffix: &str) -> PathBuf {
+    let name = dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    dir.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// Copies a directory and everything in it.
+fn copy_directory(fs: &dyn FileSystem, from: &Path, to: &Path) -> Result<()> {
+    fs.create_dir_all(to).with_context(|| format!("Failed to create '{}'", to.display()))?;
+    for path in fs.walk(from) {
+        let relative = path.strip_prefix(from).unwrap_or(&path);
+        let destination = to.join(relative);
+        if fs.is_dir(&path) {
+            fs.create_dir_all(&destination).with_context(|| format!("Failed to create '{}'", destination.display()))?;
+        } else {
+            fs.copy(&path, &destination).with_context(|| format!("Failed to copy '{}' to '{}'", path.display(), destination.display()))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use crate::vfs::FileSystem;
+
+// ============================================================
+// LOADING TRANSLATION FILES
+// ============================================================
+// Each language has one catalog in the page_text directory, e.g. en.json / jp.toml / ie.yaml.
+// Whatever the format, a catalog must be pages of keys of text:
+//      { "index": { "learn-more": "Learn more", ... }, ... }
+// Anything else (a page that isn't a table, text that isn't a string, a key used twice, ...) is an error,
+// pointing at the file, line and key path.
+
+
+// Object to represent the contents of the language files. e.g. en.json's contents
+// language code -> page name -> key -> text
+pub type TranslationsFile = HashMap<String, HashMap<String, HashMap<String, String>>>;
+
+// A single language's catalog. page name -> key -> text
+pub type LanguageCatalog = HashMap<String, HashMap<String, String>>;
+
+/// The file formats a catalog can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CatalogFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl CatalogFormat {
+    /// Picks the format from a file's extension. e.g. "en.toml" -> Toml. None if it isn't a catalog.
+    pub fn from_path(path: &Path) -> Option<CatalogFormat> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Some(CatalogFormat::Json),
+            Some("toml") => Some(CatalogFormat::Toml),
+            Some("yaml") | Some("yml") => Some(CatalogFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// File extensions recognised as translation catalogs
+pub const CATALOG_EXTENSIONS: [&str; 4] = ["json", "toml", "yaml", "yml"];
+
+
+// ============================================================
+// ERRORS
+// ============================================================
+
+/// What was wrong with a translation file.
+#[derive(Debug)]
+pub enum TranslationErrorKind {
+    Io(std::io::Error),
+    Syntax(String),                              // not valid JSON / TOML / YAML
+    Schema(String),                              // valid, but not pages of keys of text (includes duplicate keys)
+    InvalidFileName,                             // no language code in the file name
+    DuplicateLanguage { other_file: PathBuf },   // e.g. both en.json and en.toml
+}
+
+/// An error in a translation file, with as much of its location as is known.
+#[derive(Debug)]
+pub struct TranslationError {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub key_path: Option<String>, // e.g. "index.learn-more"
+    pub kind: TranslationErrorKind,
+}
+
+impl TranslationError {
+    fn new(file: &Path, kind: TranslationErrorKind) -> TranslationError {
+        TranslationError { file: file.to_path_buf(), line: None, column: None, key_path: None, kind }
+    }
+}
+
+impl fmt::Display for TranslationError {
+    // e.g. edit-me/shared/page_text/en.json:12:9: index.learn-more: invalid type: integer `3`, expected a string
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": ")?;
+        if let Some(key_path) = &self.key_path {
+            write!(f, "{}: ", key_path)?;
+        }
+        match &self.kind {
+            TranslationErrorKind::Io(error) => write!(f, "failed to read translation file: {}", error),
+            TranslationErrorKind::Syntax(message) => write!(f, "syntax error: {}", message),
+            TranslationErrorKind::Schema(message) => write!(f, "{}", message),
+            TranslationErrorKind::InvalidFileName => write!(f, "invalid translation file name; expected <language code>.json / .toml / .yaml"),
+            TranslationErrorKind::DuplicateLanguage { other_file } => write!(f, "this language already has a translation file: {}", other_file.display()),
+        }
+    }
+}
+
+impl std::error::Error for TranslationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            TranslationErrorKind::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// All of the errors found while loading the translation files.
+#[derive(Debug)]
+pub struct TranslationErrors(pub Vec<TranslationError>);
+
+impl fmt::Display for TranslationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} error(s) in the translation files:", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TranslationErrors {}
+
+
+// ============================================================
+// THE SCHEMA
+// ============================================================
+
+/// A map which refuses duplicate keys, and keys that can't be used in a `page.key` reference.
+/// (serde_json / serde_yaml_ng would otherwise silently keep the last of two duplicate keys.)
+struct StrictMap<V>(HashMap<String, V>);
+
+/// Page names and keys: letters, digits, '-' and '_' (no '.', since references are written "page.key")
+pub fn is_valid_catalog_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for StrictMap<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrictMapVisitor<V>(PhantomData<V>);
+
+        impl<'de, V: Deserialize<'de>> Visitor<'de> for StrictMapVisitor<V> {
+            type Value = StrictMap<V>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a table of translation keys")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+                let mut map = HashMap::with_capacity(access.size_hint().unwrap_or(0));
+                while let Some(key) = access.next_key::<String>()? {
+                    if !is_valid_catalog_key(&key) {
+                        return Err(de::Error::custom(format!("invalid key {:?}; keys may only contain letters, digits, '-' and '_'", key)));
+                    }
+                    if map.contains_key(&key) {
+                        return Err(de::Error::custom(format!("duplicate key {:?}", key)));
+                    }
+                    let value = access.next_value::<V>()?;
+                    map.insert(key, value);
+                }
+                Ok(StrictMap(map))
+            }
+        }
+
+        deserializer.deserialize_map(StrictMapVisitor(PhantomData))
+    }
+}
+
+// pages -> keys -> text
+type CatalogSchema = StrictMap<StrictMap<String>>;
+
+fn into_language_catalog(schema: CatalogSchema) -> LanguageCatalog {
+    schema.0.into_iter().map(|(page, keys)| (page, keys.0)).collect()
+}
+
+// serde_path_to_error's path, e.g. "index.learn-more". None for the top level.
+fn key_path(path: &serde_path_to_error::Path) -> Option<String> {
+    let path = path.to_string();
+    if path.is_empty() || path == "." { None } else { Some(path) }
+}
+
+/// The line and column (both 1-based, the column counted in characters) of a byte offset into some content.
+/// (Used to point at the problems of the translation catalogs, and of site.toml)
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::translations::line_and_column;
+/// assert_eq!(line_and_column("a = 1\nb = \"é\" x", 15), (2, 9));
+/// ```
+pub fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rfind('\n').map_or(before.chars().count(), |newline| before[newline + 1..].chars().count()) + 1;
+    (line, column)
+}
+
+/// Parses and validates a single catalog's content.
+///
+/// # Arguments
+/// * `file` - The file the content came from (used in errors), e.g. edit-me/shared/page_text/en.json
+/// * `content` - The file's content
+/// * `format` - The format to parse it as
+pub fn parse_catalog(file: &Path, content: &str, format: CatalogFormat) -> Result<LanguageCatalog, TranslationError> {
+    match format {
+        CatalogFormat::Json => {
+            let to_error = |path: Option<String>, inner: serde_json::Error| {
+                let kind = if inner.is_syntax() || inner.is_eof() { TranslationErrorKind::Syntax(strip_json_position(&inner.to_string())) } else { TranslationErrorKind::Schema(strip_json_position(&inner.to_string())) };
+                TranslationError { file: file.to_path_buf(), line: Some(inner.line()), column: Some(inner.column()), key_path: path, kind }
+            };
+            let mut deserializer = serde_json::Deserializer::from_str(content);
+            let schema = serde_path_to_error::deserialize::<_, CatalogSchema>(&mut deserializer).map_err(|error| {
+                let path = key_path(error.path());
+                to_error(path, error.into_inner())
+            })?;
+            deserializer.end().map_err(|error| to_error(None, error))?;
+            Ok(into_language_catalog(schema))
+        }
+        CatalogFormat::Toml => {
+            let deserializer = toml::Deserializer::new(content);
+            serde_path_to_error::deserialize::<_, CatalogSchema>(deserializer).map(into_language_catalog).map_err(|error| {
+                let path = key_path(error.path());
+                let inner = error.into_inner();
+                let (line, column) = match inner.span() {
+                    Some(span) => { let (l, c) = line_and_column(content, span.start); (Some(l), Some(c)) }
+                    None => (None, None),
+                };
+                let message = inner.message().to_string();
+                let kind = if path.is_none() && !message.starts_with("duplicate key") && !message.starts_with("invalid type") { TranslationErrorKind::Syntax(message) } else { TranslationErrorKind::Schema(message) };
+                TranslationError { file: file.to_path_buf(), line, column, key_path: path, kind }
+            })
+        }
+        CatalogFormat::Yaml => {
+            let deserializer = serde_yaml_ng::Deserializer::from_str(content);
+            serde_path_to_error::deserialize::<_, CatalogSchema>(deserializer).map(into_language_catalog).map_err(|error| {
+                let path = key_path(error.path());
+                let inner = error.into_inner();
+                let location = inner.location();
+                let message = strip_yaml_position(&inner.to_string());
+                let kind = if message.contains("did not find expected") || message.contains("while parsing") || message.contains("while scanning") { TranslationErrorKind::Syntax(message) } else { TranslationErrorKind::Schema(message) };
+                TranslationError { file: file.to_path_buf(), line: location.as_ref().map(|l| l.line()), column: location.as_ref().map(|l| l.column()), key_path: path, kind }
+            })
+        }
+    }
+}
+
+// serde_json puts " at line X column Y" on the end of its messages; we show the location ourselves
+fn strip_json_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+// serde_yaml_ng puts "path: " on the front and " at line X column Y" on the end of its messages
+fn strip_yaml_position(message: &str) -> String {
+    let message = match message.rfind(" at line ") {
+        Some(index) => &message[..index],
+        None => message,
+    };
+    match message.split_once(": ") {
+        Some((prefix, rest)) if !prefix.contains(' ') => rest.to_string(),
+        _ => message.to_string(),
+    }
+}
+
+/// Reads and validates a single catalog file, picking the format from its extension.
+/// (It is read from `fs`, e.g. &DiskFileSystem)
+pub fn load_catalog_file(fs: &dyn FileSystem, path: &Path) -> Result<LanguageCatalog, TranslationError> {
+    let format = CatalogFormat::from_path(path).ok_or_else(|| TranslationError::new(path, TranslationErrorKind::InvalidFileName))?;
+    let content = fs.read_to_string(path).map_err(|error| TranslationError::new(path, TranslationErrorKind::Io(error)))?;
+    parse_catalog(path, &content, format)
+}
+
+/// Lists the catalog files in a directory, e.g. [page_text/en.json, page_text/jp.toml]
+/// Sub-directories (e.g. page_text/source-text/) are not looked in.
+/// (The directory is listed in `fs`, e.g. &DiskFileSystem)
+pub fn find_catalog_files<P: AsRef<Path>>(fs: &dyn FileSystem, translation_directory_path: P) -> Result<Vec<PathBuf>, TranslationError> {
+    let directory = translation_directory_path.as_ref();
+    let directory_entries = fs.read_dir(directory).map_err(|error| TranslationError::new(directory, TranslationErrorKind::Io(error)))?;
+
+    let mut catalog_file_paths: Vec<PathBuf> = directory_entries
+        .into_iter()
+        .filter(|path| fs.is_file(path) && CatalogFormat::from_path(path).is_some())
+        .collect();
+    catalog_file_paths.sort();
+    Ok(catalog_file_paths)
+}
+
+/// Returns the catalog file of a language, in whichever format it's written. e.g. (page_text, "jp") -> page_text/jp.toml
+/// (It is looked for in `fs`, e.g. &DiskFileSystem)
+pub fn find_catalog_file(fs: &dyn FileSystem, translation_directory_path: &Path, language_code: &str) -> Option<PathBuf> {
+    CATALOG_EXTENSIONS.iter()
+        .map(|extension| translation_directory_path.join(format!("{}.{}", language_code, extension)))
+        .find(|path| fs.is_file(path))
+}
+
+/// The language code of a catalog file. e.g. "page_text/en.json" -> "en"
+pub fn catalog_language_code(path: &Path) -> Option<&str> {
+    path.file_stem().and_then(|stem| stem.to_str()).filter(|code| is_valid_catalog_key(code))
+}
+
+/// Load all translation files from a directory containing language files like en.json, es.toml, jp.yaml
+/// (They are read from `fs`, e.g. &DiskFileSystem)
+pub fn load_translation_files_from_directory<P: AsRef<Path>>(fs: &dyn FileSystem, translation_directory_path: P) -> Result<TranslationsFile, TranslationErrors> {
+    let catalog_file_paths = find_catalog_files(fs, translation_directory_path).map_err(|error| TranslationErrors(vec![error]))?;
+    load_translation_files_from_paths(fs, &catalog_file_paths)
+}
+
+/// Load translations from specific catalog file paths.
+/// Every file is checked, and all of the errors found are returned together.
+/// (They are read from `fs`, e.g. &DiskFileSystem)
+pub fn load_translation_files_from_paths<P: AsRef<Path>>(fs: &dyn FileSystem, catalog_file_paths: &[P]) -> Result<TranslationsFile, TranslationErrors> {
+    let mut translation_cache_map: TranslationsFile = HashMap::with_capacity(catalog_file_paths.len());
+    let mut language_files: HashMap<String, PathBuf> = HashMap::new();
+    let mut errors: Vec<TranslationError> = Vec::new();
+
+    for catalog_file_path in catalog_file_paths {
+        let catalog_file_path: &Path = catalog_file_path.as_ref();
+
+        // Extract language code from filename (e.g., "en.json" -> "en")
+        let Some(language_code) = catalog_language_code(catalog_file_path) else {
+            errors.push(TranslationError::new(catalog_file_path, TranslationErrorKind::InvalidFileName));
+            continue;
+        };
+        if let Some(other_file) = language_files.get(language_code) {
+            errors.push(TranslationError::new(catalog_file_path, TranslationErrorKind::DuplicateLanguage { other_file: other_file.clone() }));
+            continue;
+        }
+        language_files.insert(language_code.to_string(), catalog_file_path.to_path_buf());
+
+        match load_catalog_file(fs, catalog_file_path) {
+            Ok(language_catalog) => { translation_cache_map.insert(language_code.to_string(), language_catalog); }
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if errors.is_empty() { Ok(translation_cache_map) } else { Err(TranslationErrors(errors)) }
+}
@@ -0,0 +1,856 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use crate::components::resolve_relative_reference;
+use crate::vfs::FileSystem;
+
+// === TYPESCRIPT MODULES (import / export) ===
+// Shared code can be imported with standard import statements, instead of pasting it in with <r-name>:
+//      import { setDynamicElementsContent } from "<root>/shared/code/dynamicContentLoader";
+// A .ts file with `export`s is a module: it isn't compiled by itself, but linked into every file that imports it.
+// Before tsc runs, each imported module (and the modules it imports) is put at the top of the importing file, in its own namespace:
+//      namespace __module_shared_code_dynamicContentLoader { export async function setDynamicElementsContent() {...} }
+//      import setDynamicElementsContent = __module_shared_code_dynamicContentLoader.setDynamicElementsContent;
+// so its code doesn't share the page's global scope, and tsc still type checks everything together.
+//
+// Tree-shaking: only the exports that some file in the website imports are kept (and whatever they use).
+// Running once per page: after tsc, each module's namespace is guarded, so when several scripts on a page include the
+// same module (e.g. two components that both use the dynamic content loader), only the first copy runs; the others re-use it.
+//
+// Supported: `import {a, b as c}`, `import d`, `import * as ns`, `import "x"` (side effects only), and `export` on declarations,
+// `export default`, and `export {a, b as c}`. Paths are relative ("./x", "../x") or from the root ("<root>/shared/code/x").
+// Re-exports (`export ... from`) and npm packages aren't supported.
+
+/// The prefix of the namespaces modules are linked into.
+pub const MODULE_NAMESPACE_PREFIX: &str = "__module_";
+
+/// The start of an import path that is relative to the root of the website.
+const ROOT_SPECIFIER_PREFIX: &str = "<root>/";
+
+/// The name a default export is given in its module's namespace, when it doesn't have one.
+const DEFAULT_EXPORT_NAME: &str = "__default";
+
+/// One name brought in by an import statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportBinding {
+    /// `import { imported as local }`
+    Named { imported: String, local: String },
+    /// `import local from ...`
+    Default(String),
+    /// `import * as local from ...`
+    Namespace(String),
+}
+
+/// An import statement. (a side-effect only import, `import "x";`, has no bindings)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportStatement {
+    pub specifier: String,
+    pub bindings: Vec<ImportBinding>,
+}
+
+/// Which of a module's exports are imported anywhere in the website.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportUsage {
+    All,
+    /// The imported export names ("default" for the default export).
+    Names(BTreeSet<String>),
+}
+
+/// The usage of every imported module (by source path), across the whole website.
+pub type ModuleUsage = BTreeMap<PathBuf, ExportUsage>;
+
+// ===============================================================================================
+// SPLITTING A FILE INTO STATEMENTS
+// ===============================================================================================
+
+/// Splits typescript source into its top-level statements, returned as byte ranges that together cover the whole source.
+/// Each statement includes the comments / blank lines before it, and the rest of the line it ends on.
+///
+/// This isn't a full parser: it follows strings, comments, template literals, regular expressions and brackets, and ends a statement at
+/// a top-level `;`, at the `}` closing a function / class / if / ... block, or at a line break followed by a new declaration
+/// (for code without semicolons).
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::typescript_modules::split_statements;
+/// let source = "const a = 1\nfunction f() { return '}'; }\nf();\n";
+/// let statements: Vec<&str> = split_statements(source).into_iter().map(|(start, end)| &source[start..end]).collect();
+/// assert_eq!(statements, ["const a = 1\n", "function f() { return '}'; }\n", "f();\n"]);
+/// ```
+pub fn split_statements(source: &str) -> Vec<(usize, usize)> {
+    let bytes = source.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut depth: usize = 0;
+    // The depth each `${` of a template literal that we are inside of was opened at
+    let mut template_depths: Vec<usize> = Vec::new();
+    let mut last_significant = b';';
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied();
+        match c {
+            b'/' if next == Some(b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if next == Some(b'*') => {
+                i = source[i + 2..].find("*/").map_or(bytes.len(), |offset| i + 2 + offset + 2);
+                continue;
+            }
+            b'\'' | b'"' => {
+                i = skip_string(bytes, i);
+                last_significant = c;
+                continue;
+            }
+            b'`' => {
+                let (end, opened_interpolation) = skip_template(bytes, i + 1);
+                i = end;
+                if opened_interpolation {
+                    depth += 1;
+                    template_depths.push(depth);
+                }
+                last_significant = b'`';
+                continue;
+            }
+            b'/' if is_regex_start(last_significant, &source[start..i]) => {
+                i = skip_regex(bytes, i);
+                // A regular expression is a value (its closing '/' isn't an operator)
+                last_significant = b'0';
+                continue;
+            }
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' => depth = depth.saturating_sub(1),
+            b'}' => {
+                if template_depths.last() == Some(&depth) {
+                    // The end of a `${ ... }`, so back into the template literal
+                    template_depths.pop();
+                    depth -= 1;
+                    let (end, opened_interpolation) = skip_template(bytes, i + 1);
+                    i = end;
+                    if opened_interpolation {
+                        depth += 1;
+                        template_depths.push(depth);
+                    }
+                    last_significant = b'`';
+                    continue;
+                }
+                depth = depth.saturating_sub(1);
+                if depth == 0 && is_block_statement(&source[start..=i]) && !continues_after_block(&source[i + 1..]) {
+                    i = end_of_line(bytes, i + 1);
+                    statements.push((start, i));
+                    start = i;
+                    last_significant = b';';
+                    continue;
+                }
+            }
+            b';' if depth == 0 => {
+                i = end_of_line(bytes, i + 1);
+                statements.push((start, i));
+                start = i;
+                last_significant = b';';
+                continue;
+            }
+            b'\n' if depth == 0 => {
+                let has_content = !strip_leading_comments(&source[start..i]).trim().is_empty();
+                if has_content && !b"=+-*/%&|^<>!?:,.([{".contains(&last_significant) && starts_with_declaration(&source[i + 1..]) {
+                    statements.push((start, i + 1));
+                    start = i + 1;
+                    last_significant = b';';
+                }
+            }
+            _ => {}
+        }
+        if !c.is_ascii_whitespace() {
+            last_significant = c;
+        }
+        i += 1;
+    }
+    if start < bytes.len() {
+        statements.push((start, bytes.len()));
+    }
+    statements
+}
+
+/// Returns the index just after the end of the line (if there is only whitespace between `end` and it), otherwise `end`.
+/// (so a statement takes the rest of the line it ends on with it)
+fn end_of_line(bytes: &[u8], end: usize) -> usize {
+    let mut i = end;
+    while i < bytes.len() && matches!(bytes[i], b' ' | b'\t' | b'\r') {
+        i += 1;
+    }
+    match bytes.get(i) {
+        Some(b'\n') => i + 1,
+        None => i,
+        _ => end,
+    }
+}
+
+/// Returns the index just after the string starting at `start`.
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Skips template literal text, from just after the ` or }, up to just after the closing ` or the next `${`.
+/// Returns the index to carry on from, and whether it stopped at a `${`.
+fn skip_template(bytes: &[u8], start: usize) -> (usize, bool) {
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => return (i + 1, false),
+            b'$' if bytes.get(i + 1) == Some(&b'{') => return (i + 2, true),
+            _ => i += 1,
+        }
+    }
+    (bytes.len(), false)
+}
+
+/// Returns the index just after the regular expression (and its flags) starting at `start`.
+fn skip_regex(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    let mut in_class = false;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'[' => { in_class = true; i += 1; }
+            b']' => { in_class = false; i += 1; }
+            b'/' if !in_class => {
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                return i;
+            }
+            b'\n' => return i,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Returns true if a '/' here starts a regular expression (rather than being a division).
+fn is_regex_start(last_significant: u8, statement_so_far: &str) -> bool {
+    if b"(,=:[!&|?{};+-*%<>~^".contains(&last_significant) {
+        return true;
+    }
+    let last_word: String = statement_so_far.trim_end().chars().rev().take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$').collect::<Vec<_>>().into_iter().rev().collect();
+    ["return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case", "do", "else", "yield", "await"].contains(&last_word.as_str())
+}
+
+/// Returns the code with any comments / whitespace at the start removed.
+fn strip_leading_comments(code: &str) -> &str {
+    let mut code = code.trim_start();
+    loop {
+        if let Some(rest) = code.strip_prefix("//") {
+            code = rest.find('\n').map_or("", |index| &rest[index..]).trim_start();
+        } else if let Some(rest) = code.strip_prefix("/*") {
+            code = rest.find("*/").map_or("", |index| &rest[index + 2..]).trim_start();
+        } else {
+            return code;
+        }
+    }
+}
+
+/// Returns true if the statement is one that ends at the `}` of its block (a function, class, if, ...), rather than at a `;`.
+fn is_block_statement(statement: &str) -> bool {
+    static BLOCK_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+        r"^(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?:function|class|interface|enum|const\s+enum|namespace|module|global|if|for|while|switch|try|do)\b|^\{"
+    ).unwrap());
+    BLOCK_REGEX.is_match(strip_leading_comments(statement))
+}
+
+/// Returns true if the code after a block's closing `}` carries on the same statement (e.g. `} else {`).
+fn continues_after_block(rest: &str) -> bool {
+    static CONTINUATION_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?:else|catch|finally|while)\b|^[.,)\]?:]").unwrap());
+    CONTINUATION_REGEX.is_match(strip_leading_comments(rest))
+}
+
+/// Returns true if the code starts with a new declaration / import / export / block statement
+/// (so a line break before it ends the previous statement).
+fn starts_with_declaration(rest: &str) -> bool {
+    static DECLARATION_START_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+        r"^(?:import|export|const|let|var|function|async\s+function|class|abstract\s+class|interface|type\s+[A-Za-z_$][\w$]*\s*[=<]|enum|declare|namespace|if|for|while|switch|try|do)\b"
+    ).unwrap());
+    DECLARATION_START_REGEX.is_match(strip_leading_comments(rest))
+}
+
+// ===============================================================================================
+// IMPORTS / EXPORTS
+// ===============================================================================================
+
+/// Parses an import statement (comments before it are allowed), or returns None if it isn't one.
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::typescript_modules::{parse_import_statement, ImportBinding};
+/// let statement = parse_import_statement("import load, { a, b as c } from \"<root>/shared/code/x\";").unwrap();
+/// assert_eq!(statement.specifier, "<root>/shared/code/x");
+/// assert_eq!(statement.bindings, [
+///     ImportBinding::Default("load".to_string()),
+///     ImportBinding::Named { imported: "a".to_string(), local: "a".to_string() },
+///     ImportBinding::Named { imported: "b".to_string(), local: "c".to_string() },
+/// ]);
+/// ```
+pub fn parse_import_statement(statement: &str) -> Option<ImportStatement> {
+    static IMPORT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?s)^import\s+(?:type\s+)?(.*?)\s*from\s*["']([^"']+)["']\s*;?\s*$|^import\s*["']([^"']+)["']\s*;?\s*$"#).unwrap());
+    let captures = IMPORT_REGEX.captures(strip_leading_comments(statement).trim_end())?;
+    if let Some(specifier) = captures.get(3) {
+        return Some(ImportStatement { specifier: specifier.as_str().to_string(), bindings: Vec::new() });
+    }
+
+    let mut bindings = Vec::new();
+    let mut clause = captures[1].trim();
+    // A default import comes first, e.g. `load, { a }`
+    if !clause.starts_with('{') && !clause.starts_with('*') {
+        let (default, rest) = clause.split_once(',').unwrap_or((clause, ""));
+        bindings.push(ImportBinding::Default(default.trim().to_string()));
+        clause = rest.trim();
+    }
+    if let Some(namespace) = clause.strip_prefix('*') {
+        bindings.push(ImportBinding::Namespace(namespace.trim().strip_prefix("as")?.trim().to_string()));
+    } else if let Some(names) = clause.strip_prefix('{').and_then(|names| names.strip_suffix('}')) {
+        for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let name = name.strip_prefix("type ").unwrap_or(name).trim();
+            let (imported, local) = match name.split_once(" as ") {
+                Some((imported, local)) => (imported.trim(), local.trim()),
+                None => (name, name),
+            };
+            bindings.push(match imported {
+                "default" => ImportBinding::Default(local.to_string()),
+                _ => ImportBinding::Named { imported: imported.to_string(), local: local.to_string() },
+            });
+        }
+    }
+    Some(ImportStatement { specifier: captures[2].to_string(), bindings })
+}
+
+/// The kinds of top-level statement that matter for linking.
+#[derive(Debug)]
+enum Statement {
+    Import(ImportStatement),
+    /// A declaration of a single name (function, class, const, interface, ...)
+    Declaration { name: String, exported: bool, default: bool },
+    /// `export { local as exported, ... }`
+    ExportList(Vec<(String, String)>),
+    /// Anything else (code that runs, or declarations that can't be told apart, e.g. `const {a, b} = ...`), which is always kept.
+    Other,
+}
+
+/// Works out what kind of statement a statement is.
+fn classify_statement(statement: &str) -> Result<Statement> {
+    static DECLARATION_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+        r"^(export\s+)?(default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(function\b\s*\*?\s*|(?:class|interface|type|const\s+enum|enum|const|let|var|namespace)\s+)([A-Za-z_$][\w$]*)?"
+    ).unwrap());
+    static EXPORT_LIST_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)^export\s+(?:type\s+)?\{([^}]*)\}\s*(from\b)?").unwrap());
+    let code = strip_leading_comments(statement);
+
+    if code.starts_with("import") && let Some(import) = parse_import_statement(code) {
+        return Ok(Statement::Import(import));
+    }
+    if code.starts_with("export *") {
+        bail!("re-exports (`export * from ...`) aren't supported: {}", code.lines().next().unwrap_or(""));
+    }
+    if let Some(captures) = EXPORT_LIST_REGEX.captures(code) {
+        if captures.get(2).is_some() {
+            bail!("re-exports (`export {{ ... }} from ...`) aren't supported: {}", code.lines().next().unwrap_or(""));
+        }
+        let names = captures[1].split(',').map(str::trim).filter(|name| !name.is_empty()).map(|name| {
+            let name = name.strip_prefix("type ").unwrap_or(name).trim();
+            match name.split_once(" as ") {
+                Some((local, exported)) => (local.trim().to_string(), exported.trim().to_string()),
+                None => (name.to_string(), name.to_string()),
+            }
+        }).collect();
+        return Ok(Statement::ExportList(names));
+    }
+
+    if let Some(captures) = DECLARATION_REGEX.captures(code) {
+        let (exported, default) = (captures.get(1).is_some(), captures.get(2).is_some());
+        let keyword = captures[3].trim();
+        let is_variable = ["const", "let", "var"].contains(&keyword);
+        match captures.get(4) {
+            // e.g. `const a = 1, b = 2` declares more than one name
+            Some(_) if is_variable && has_top_level_comma(&code[captures.get(0).unwrap().end()..]) => return Ok(Statement::Other),
+            Some(name) => return Ok(Statement::Declaration { name: name.as_str().to_string(), exported, default }),
+            None if default && (keyword.starts_with("function") || keyword == "class") => {
+                return Ok(Statement::Declaration { name: DEFAULT_EXPORT_NAME.to_string(), exported, default });
+            }
+            None => {}
+        }
+    }
+    if code.starts_with("export default") {
+        return Ok(Statement::Declaration { name: DEFAULT_EXPORT_NAME.to_string(), exported: true, default: true });
+    }
+    Ok(Statement::Other)
+}
+
+/// Returns true if there is a ',' outside of any brackets (and strings) in some code.
+fn has_top_level_comma(code: &str) -> bool {
+    let bytes = code.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' | b'"' | b'`' => { i = skip_string(bytes, i); continue; }
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            b',' if depth == 0 => return true,
+            b';' if depth == 0 => return false,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Returns every identifier-like word in some code (including in its strings / comments; it only has to not miss any).
+fn referenced_names(code: &str) -> HashSet<&str> {
+    static IDENTIFIER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Za-z_$][\w$]*").unwrap());
+    IDENTIFIER_REGEX.find_iter(code).map(|m| m.as_str()).collect()
+}
+
+/// Returns true if a typescript file is a module (it has exports), rather than a script that runs on a page.
+pub fn is_typescript_module(source: &str) -> bool {
+    static EXPORT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^export\b").unwrap());
+    EXPORT_REGEX.is_match(source)
+}
+
+// ===============================================================================================
+// RESOLVING IMPORT PATHS
+// ===============================================================================================
+
+/// Finds the source file an import path refers to.
+///
+/// # Arguments
+/// * `fs` - Where the module is looked for. e.g. &DiskFileSystem
+/// * `specifier` - The path in the import statement, e.g. "<root>/shared/code/dynamicContentLoader" or "./helpers".
+/// * `importer` - The source file the import is in, e.g. "edit-me/pages/index/index.ts".
+/// * `source_dir` - The source directory, e.g. "edit-me".
+///
+/// # Returns
+/// * The module's source file, e.g. "edit-me/shared/code/dynamicContentLoader.ts".
+pub fn resolve_module_specifier(fs: &dyn FileSystem, specifier: &str, importer: &Path, source_dir: &Path) -> Result<PathBuf> {
+    let base = if let Some(from_root) = specifier.strip_prefix(ROOT_SPECIFIER_PREFIX) {
+        // (pages are moved about in the output, so only shared files are at the same place in both)
+        if !from_root.starts_with("shared/") {
+            bail!("'{}' (in '{}'): only files in shared/ can be imported from <root>", specifier, importer.display());
+        }
+        source_dir.join(from_root)
+    } else if specifier.starts_with("./") || specifier.starts_with("../") {
+        resolve_relative_reference(importer, specifier)
+    } else {
+        bail!("'{}' (in '{}') isn't a relative path or a <root>/shared/... path; packages aren't supported", specifier, importer.display());
+    };
+
+    let file_name = base.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let candidates = [
+        base.clone(),
+        base.with_extension("ts"),
+        base.with_file_name(format!("{}.ts", file_name)),
+        base.join("index.ts"),
+    ];
+    candidates.into_iter()
+        .find(|candidate| candidate.extension().is_some_and(|e| e == "ts") && fs.is_file(candidate))
+        .ok_or_else(|| anyhow!("could not find the module '{}' imported in '{}'", specifier, importer.display()))
+}
+
+/// Returns the name of the namespace a module is linked into, e.g. "__module_shared_code_dynamicContentLoader".
+pub fn module_namespace(module_path: &Path, source_dir: &Path) -> String {
+    let relative = module_path.strip_prefix(source_dir).unwrap_or(module_path).with_extension("");
+    let name: String = relative.to_string_lossy().chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    format!("{}{}", MODULE_NAMESPACE_PREFIX, name)
+}
+
+/// Returns every .ts file in a directory, with its content.
+fn typescript_files(fs: &dyn FileSystem, source_dir: &Path) -> Vec<(PathBuf, String)> {
+    fs.walk_files(source_dir)
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ts") && !path.to_string_lossy().ends_with(".d.ts"))
+        .filter_map(|path| fs.read_to_string(&path).ok().map(|content| (path, content)))
+        .collect()
+}
+
+/// Returns the import statements of some source.
+fn import_statements(source: &str) -> Vec<ImportStatement> {
+    split_statements(source).into_iter().filter_map(|(start, end)| {
+        let code = strip_leading_comments(&source[start..end]);
+        if code.starts_with("import") { parse_import_statement(code) } else { None }
+    }).collect()
+}
+
+/// Works out which exports of each module are imported, by any file in the website. (see `ExportUsage`)
+/// Imports that can't be resolved are skipped here; they are reported when the file with them is linked.
+///
+/// # Arguments
+/// * `fs` - Where the edit-me files are read from. e.g. &DiskFileSystem
+/// * `source_dir` - The source directory, e.g. "edit-me".
+pub fn collect_module_usage(fs: &dyn FileSystem, source_dir: &Path) -> ModuleUsage {
+    let mut usage = ModuleUsage::new();
+    for (path, source) in typescript_files(fs, source_dir) {
+        for import in import_statements(&source) {
+            let Ok(module_path) = resolve_module_specifier(fs, &import.specifier, &path, source_dir) else { continue };
+            let module_usage = usage.entry(module_path).or_insert_with(|| ExportUsage::Names(BTreeSet::new()));
+            for binding in &import.bindings {
+                match (binding, &mut *module_usage) {
+                    (_, ExportUsage::All) => {}
+                    (ImportBinding::Namespace(_), _) => *module_usage = ExportUsage::All,
+                    (ImportBinding::Default(_), ExportUsage::Names(names)) => { names.insert("default".to_string()); }
+                    (ImportBinding::Named { imported, .. }, ExportUsage::Names(names)) => { names.insert(imported.clone()); }
+                }
+            }
+        }
+    }
+    usage
+}
+
+/// Returns the typescript files that need re-linking (so recompiling) because a file they import, directly or not, changed.
+/// As tree-shaking depends on what every file imports, when any file that imports modules (or any module) changes,
+/// every file that imports modules is re-linked.
+///
+/// # Arguments
+/// * `fs` - Where the edit-me files are read from. e.g. &DiskFileSystem
+/// * `source_dir` - The source directory, e.g. "edit-me".
+/// * `modified_files` - The changed source files, e.g. "edit-me/shared/code/dynamicContentLoader.ts".
+pub fn typescript_files_to_relink(fs: &dyn FileSystem, source_dir: &Path, modified_files: &HashSet<String>) -> HashSet<String> {
+    let files = typescript_files(fs, source_dir);
+    let mut modules: HashSet<PathBuf> = HashSet::new();
+    let mut importers: Vec<&PathBuf> = Vec::new();
+    for (path, source) in &files {
+        let imports = import_statements(source);
+        if !imports.is_empty() {
+            importers.push(path);
+        }
+        modules.extend(imports.iter().filter_map(|import| resolve_module_specifier(fs, &import.specifier, path, source_dir).ok()));
+    }
+
+    let any_changed = modified_files.iter().any(|file| {
+        let path = PathBuf::from(file);
+        modules.contains(&path) || importers.contains(&&path)
+    });
+    if !any_changed {
+        return HashSet::new();
+    }
+    importers.into_iter()
+        .filter(|path| !files.iter().any(|(file, source)| file == *path && is_typescript_module(source)))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+// ===============================================================================================
+// LINKING
+// ===============================================================================================
+
+/// A module that has been linked into the file being linked.
+struct LinkedModule {
+    namespace: String,
+    /// The name of its default export in its namespace, if it has one.
+    default_name: Option<String>,
+}
+
+/// Returns the lines that replace an import statement: one import alias per binding, e.g. `import a = __module_x.a;`.
+fn import_aliases(import: &ImportStatement, module: &LinkedModule, exported_locals: &HashSet<String>) -> Result<String> {
+    let mut aliases = String::new();
+    for binding in &import.bindings {
+        let (local, target) = match binding {
+            ImportBinding::Named { imported, local } => (local, format!("{}.{}", module.namespace, imported)),
+            ImportBinding::Namespace(local) => (local, module.namespace.clone()),
+            ImportBinding::Default(local) => {
+                let default_name = module.default_name.as_ref().ok_or_else(|| anyhow!("'{}' has no default export", import.specifier))?;
+                (local, format!("{}.{}", module.namespace, default_name))
+            }
+        };
+        let export = if exported_locals.contains(local) { "export " } else { "" };
+        aliases.push_str(&format!("{}import {} = {};\n", export, local, target));
+    }
+    Ok(aliases)
+}
+
+/// Links a module into a namespace, keeping only the statements that are needed for its used exports.
+///
+/// # Arguments
+/// * `source` - The module's source.
+/// * `usage` - Which of its exports are used.
+/// * `namespace` - The namespace to put it in.
+/// * `imported_modules` - The modules it imports, by import path (already linked).
+fn link_module(source: &str, usage: &ExportUsage, namespace: &str, imported_modules: &HashMap<String, LinkedModule>, display_path: &str) -> Result<(String, Option<String>)> {
+    let ranges = split_statements(source);
+    let mut statements = Vec::new();
+    for (start, end) in &ranges {
+        statements.push((&source[*start..*end], classify_statement(&source[*start..*end])?));
+    }
+
+    // Names, and the statements that declare / import them
+    let mut declarers: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut exported_locals: HashSet<String> = HashSet::new();
+    let mut renamed_exports: Vec<(String, String)> = Vec::new();
+    let mut default_name = None;
+    for (index, (_, statement)) in statements.iter().enumerate() {
+        match statement {
+            Statement::Declaration { name, default, .. } => {
+                declarers.entry(name.as_str()).or_default().push(index);
+                if *default {
+                    default_name = Some(name.clone());
+                }
+            }
+            Statement::Import(import) => {
+                for binding in &import.bindings {
+                    let (ImportBinding::Named { local, .. } | ImportBinding::Default(local) | ImportBinding::Namespace(local)) = binding;
+                    declarers.entry(local.as_str()).or_default().push(index);
+                }
+            }
+            Statement::ExportList(names) => {
+                for (local, exported) in names {
+                    if local == exported {
+                        exported_locals.insert(local.clone());
+                    } else {
+                        renamed_exports.push((local.clone(), exported.clone()));
+                    }
+                }
+            }
+            Statement::Other => {}
+        }
+    }
+
+    // 1) What is needed from the outside: the used exports, and any code that runs
+    let is_used = |name: &str| match usage {
+        ExportUsage::All => true,
+        ExportUsage::Names(names) => names.contains(name),
+    };
+    let mut needed_names: Vec<String> = Vec::new();
+    let mut keep = vec![false; statements.len()];
+    for (index, (_, statement)) in statements.iter().enumerate() {
+        match statement {
+            Statement::Declaration { name, exported, default } => {
+                let export_name = if *default { "default" } else { name.as_str() };
+                if (*exported || exported_locals.contains(name)) && is_used(export_name) {
+                    needed_names.push(name.clone());
+                }
+            }
+            Statement::Import(import) if import.bindings.is_empty() => keep[index] = true,
+            Statement::Other => keep[index] = true,
+            _ => {}
+        }
+    }
+    for local in &exported_locals {
+        if is_used(local) {
+            needed_names.push(local.clone());
+        }
+    }
+    for (local, exported) in &renamed_exports {
+        if is_used(exported) {
+            needed_names.push(local.clone());
+        }
+    }
+
+    // 2) ... and everything those use, and so on
+    let mut checked: HashSet<usize> = HashSet::new();
+    let mut to_check: Vec<usize> = keep.iter().enumerate().filter(|(_, kept)| **kept).map(|(index, _)| index).collect();
+    for name in &needed_names {
+        to_check.extend(declarers.get(name.as_str()).into_iter().flatten());
+    }
+    while let Some(index) = to_check.pop() {
+        if !checked.insert(index) {
+            continue;
+        }
+        keep[index] = true;
+        for name in referenced_names(statements[index].0) {
+            to_check.extend(declarers.get(name).into_iter().flatten().filter(|declarer| !checked.contains(declarer)));
+        }
+    }
+
+    // 3) Write out the kept statements, as namespace members
+    let mut body = String::new();
+    for (index, (text, statement)) in statements.iter().enumerate() {
+        if !keep[index] {
+            continue;
+        }
+        match statement {
+            Statement::Import(import) => {
+                let module = imported_modules.get(&import.specifier).ok_or_else(|| anyhow!("'{}' wasn't linked", import.specifier))?;
+                body.push_str(&import_aliases(import, module, &exported_locals)?);
+            }
+            Statement::Declaration { name, exported, default } => {
+                let leading = &text[..text.len() - strip_leading_comments(text).len()];
+                let code = strip_leading_comments(text);
+                body.push_str(leading);
+                if *default {
+                    body.push_str(&default_export_as_named(code, name));
+                } else if !exported && exported_locals.contains(name) {
+                    body.push_str("export ");
+                    body.push_str(code);
+                } else {
+                    body.push_str(code);
+                }
+            }
+            Statement::ExportList(_) => {}
+            Statement::Other => body.push_str(text),
+        }
+    }
+    for (local, exported) in &renamed_exports {
+        if is_used(exported) {
+            body.push_str(&format!("export import {} = {};\n", exported, local));
+        }
+    }
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+
+    let linked = format!("// ===== module: {} (linked in by the compiler) =====\nnamespace {} {{\n{}}}\n", display_path, namespace, body);
+    Ok((linked, default_name))
+}
+
+/// Turns `export default function f() {}` / `export default class {}` / `export default <expression>;` into a named export.
+fn default_export_as_named(code: &str, name: &str) -> String {
+    let rest = code.trim_start_matches("export").trim_start().trim_start_matches("default").trim_start();
+    if name == DEFAULT_EXPORT_NAME {
+        for keyword in ["async function", "function", "class"] {
+            if let Some(after) = rest.strip_prefix(keyword) {
+                return format!("export {} {}{}", keyword, DEFAULT_EXPORT_NAME, after);
+            }
+        }
+        return format!("export const {} = {}", DEFAULT_EXPORT_NAME, rest);
+    }
+    format!("export {}", rest)
+}
+
+/// Links the modules a typescript file imports into it (see the top of this file).
+/// Files that are modules themselves, or that don't import anything, are left alone.
+///
+/// # Arguments
+/// * `fs` - Where the file and the modules are. e.g. &DiskFileSystem
+/// * `ts_file` - The file to link, in the output directory (it is changed in place, before being compiled).
+/// * `source_file` - The file it was copied from (its relative imports are relative to this).
+/// * `source_dir` - The source directory, e.g. "edit-me".
+/// * `usage` - What every module has imported from it (see `collect_module_usage`).
+///
+/// # Returns
+/// * The number of modules linked in.
+pub fn link_typescript_modules(fs: &dyn FileSystem, ts_file: &Path, source_file: &Path, source_dir: &Path, usage: &ModuleUsage) -> Result<usize> {
+    let content = fs.read_to_string(ts_file).with_context(|| format!("Failed to read '{}'", ts_file.display()))?;
+    match link_typescript_modules_in_content(fs, &content, source_file, source_dir, usage)? {
+        Some((new_content, modules_linked)) => {
+            fs.write(ts_file, new_content.as_bytes()).with_context(|| format!("Failed to write '{}'", ts_file.display()))?;
+            Ok(modules_linked)
+        }
+        None => Ok(0),
+    }
+}
+
+/// Links the modules some typescript imports into it, in a file's content (in memory); what processors.rs runs.
+/// (The modules are read from `fs`, e.g. &DiskFileSystem)
+///
+/// # Returns
+/// * The linked content and the number of modules linked in, or None if there was nothing to link.
+pub fn link_typescript_modules_in_content(fs: &dyn FileSystem, content: &str, source_file: &Path, source_dir: &Path, usage: &ModuleUsage) -> Result<Option<(String, usize)>> {
+    if is_typescript_module(content) {
+        return Ok(None);
+    }
+    let ranges = split_statements(content);
+    let imports: Vec<(usize, usize, ImportStatement)> = ranges.iter()
+        .filter_map(|(start, end)| parse_import_statement(&content[*start..*end]).map(|import| (*start, *end, import)))
+        .collect();
+    if imports.is_empty() {
+        return Ok(None);
+    }
+
+    // The modules, in dependency order (each one after the modules it imports)
+    let mut linked: HashMap<PathBuf, LinkedModule> = HashMap::new();
+    let mut module_code = String::new();
+    let mut entry_modules: HashMap<String, PathBuf> = HashMap::new();
+    for (_, _, import) in &imports {
+        let module_path = resolve_module_specifier(fs, &import.specifier, source_file, source_dir)?;
+        link_module_tree(fs, &module_path, source_dir, usage, &mut linked, &mut Vec::new(), &mut module_code)?;
+        entry_modules.insert(import.specifier.clone(), module_path);
+    }
+
+    // The file itself, with its imports swapped for aliases
+    let mut new_content = module_code;
+    let mut last_end = 0;
+    for (start, end, import) in &imports {
+        new_content.push_str(&content[last_end..*start]);
+        let code = strip_leading_comments(&content[*start..*end]);
+        new_content.push_str(&content[*start..*end - code.len()]);
+        new_content.push_str(&import_aliases(import, &linked[&entry_modules[&import.specifier]], &HashSet::new())?);
+        last_end = *end;
+    }
+    new_content.push_str(&content[last_end..]);
+
+    Ok(Some((new_content, linked.len())))
+}
+
+/// Links a module, after the modules it imports, adding their code to `module_code`.
+fn link_module_tree(fs: &dyn FileSystem, module_path: &Path, source_dir: &Path, usage: &ModuleUsage, linked: &mut HashMap<PathBuf, LinkedModule>, stack: &mut Vec<PathBuf>, module_code: &mut String) -> Result<()> {
+    if linked.contains_key(module_path) {
+        return Ok(());
+    }
+    if stack.iter().any(|path| path == module_path) {
+        let cycle: Vec<String> = stack.iter().chain(std::iter::once(&module_path.to_path_buf())).map(|path| path.display().to_string()).collect();
+        bail!("circular imports aren't supported: {}", cycle.join(" -> "));
+    }
+    stack.push(module_path.to_path_buf());
+
+    let source = fs.read_to_string(module_path).with_context(|| format!("Failed to read module '{}'", module_path.display()))?;
+    let mut imported_modules: HashMap<String, LinkedModule> = HashMap::new();
+    for import in import_statements(&source) {
+        let imported_path = resolve_module_specifier(fs, &import.specifier, module_path, source_dir)?;
+        link_module_tree(fs, &imported_path, source_dir, usage, linked, stack, module_code)?;
+        let module = &linked[&imported_path];
+        imported_modules.insert(import.specifier.clone(), LinkedModule { namespace: module.namespace.clone(), default_name: module.default_name.clone() });
+    }
+
+    let namespace = module_namespace(module_path, source_dir);
+    let module_usage = usage.get(module_path).cloned().unwrap_or(ExportUsage::All);
+    let display_path = module_path.strip_prefix(source_dir).unwrap_or(module_path).to_string_lossy().replace('\\', "/");
+    let (code, default_name) = link_module(&source, &module_usage, &namespace, &imported_modules, &display_path)
+        .with_context(|| format!("Failed to link module '{}'", module_path.display()))?;
+    module_code.push_str(&code);
+    module_code.push('\n');
+
+    stack.pop();
+    linked.insert(module_path.to_path_buf(), LinkedModule { namespace, default_name });
+    Ok(())
+}
+
+/// Makes each linked module run only once per page: a module's namespace is only filled in if an earlier script hasn't already.
+/// (run on the javascript tsc writes, where each namespace is `var ns; (function (ns) { ... })(ns || (ns = {}));`)
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::typescript_modules::guard_module_namespaces;
+/// let js = "var __module_x;\n(function (__module_x) {\n    __module_x.a = 1;\n})(__module_x || (__module_x = {}));\n";
+/// assert_eq!(guard_module_namespaces(js), "var __module_x;\n__module_x || (function (__module_x) {\n    __module_x.a = 1;\n})(__module_x || (__module_x = {}));\n");
+/// ```
+pub fn guard_module_namespaces(js: &str) -> String {
+    let namespace_regex = Regex::new(&format!(r"(?m)^(\s*)\(function \(({}[A-Za-z0-9_]+)\) \{{", MODULE_NAMESPACE_PREFIX)).unwrap();
+    namespace_regex.replace_all(js, "$1$2 || (function ($2) {").to_string()
+}
+
+/// Lists the modules that `link_typescript_modules` linked into a typescript file, as paths relative to the source directory.
+/// (so the linked code can be traced back to where it came from; see source_maps.rs)
+///
+/// # Examples
+/// ```
+/// use websiteCompiler::typescript_modules::linked_module_paths;
+/// let ts = "// ===== module: shared/code/loader.ts (linked in by the compiler) =====\nnamespace __module_shared_code_loader {\n}\n";
+/// assert_eq!(linked_module_paths(ts), ["shared/code/loader.ts"]);
+/// ```
+pub fn linked_module_paths(ts: &str) -> Vec<String> {
+    static MODULE_HEADER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^// ===== module: (.+) \(linked in by the compiler\) =====$").unwrap());
+    MODULE_HEADER_REGEX.captures_iter(ts).map(|captures| captures[1].to_string()).collect()
+}
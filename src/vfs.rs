@@ -0,0 +1,505 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+// === FILE SYSTEMS AND COMMANDS ===
+// A build reads and writes its files through a `FileSystem`, and runs the external tools (sass, tsc) through a `CommandRunner`:
+//      DiskFileSystem      -> the real files (what the command line uses)
+//      MemoryFileSystem    -> files kept in memory, so a test can build a whole website without touching the disk
+//      SystemCommandRunner -> runs the real programs
+//      MockCommandRunner   -> pretends to be them, with a handler per program (e.g. a "tsc" that writes a .js file)
+// The in-memory file system behaves like a disk one: writing a file whose folder doesn't exist fails, renaming a folder
+// moves everything in it, and so on, so a build that works in memory works on disk too.
+// The real programs (SystemCommandRunner) only see the disk, though: sass and tsc read and write the paths they are given
+// there, whatever the `FileSystem` is. So a MemoryFileSystem build is only hermetic with a MockCommandRunner (or the built-in
+// scss compiler and no typescript); to run the real tools, build in a temporary folder on disk instead.
+//
+// e.g. a test that builds a website with a pretend `tsc`:
+//      let files = MemoryFileSystem::new();
+//      files.add_file("edit-me/pages/index/index.html", "<r-top-bar>");
+//      let tsc_files = files.clone();
+//      let commands = MockCommandRunner::new().on("tsc", move |args| { tsc_files.add_file(...); CommandOutput::success("") });
+
+// ============================================================
+// FILE SYSTEMS
+// ============================================================
+
+/// Where a build's files are read from and written to.
+/// (The methods are the `std::fs` functions of the same names, apart from `walk`)
+pub trait FileSystem: Send + Sync {
+    /// Reads a whole file.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Writes a whole file, replacing it if it exists. (Its folder must exist)
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Creates a file that mustn't already exist. (`ErrorKind::AlreadyExists` if it does)
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Moves a file or a folder (and everything in it).
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// When a file was last changed.
+    fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+
+    /// The paths of the files and folders in a folder, sorted.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// The path with no `.`, `..` (or, on disk, symbolic links) in it. (`Err` if it doesn't exist)
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Reads a whole file as text. (`ErrorKind::InvalidData` if it isn't UTF-8)
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.write(to, &self.read(from)?)
+    }
+
+    /// A file's size, in bytes.
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        self.read(path).map(|contents| contents.len() as u64)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.is_file(path) || self.is_dir(path)
+    }
+
+    /// Every file and folder inside a folder (and inside the folders in it, and so on), sorted, with each folder before
+    /// what is in it. (Empty if the folder doesn't exist)
+    fn walk(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for path in self.read_dir(dir).unwrap_or_default() {
+            let is_dir = self.is_dir(&path);
+            found.push(path.clone());
+            if is_dir {
+                found.extend(self.walk(&path));
+            }
+        }
+        found
+    }
+
+    /// Every file inside a folder (and inside the folders in it, and so on), sorted.
+    fn walk_files(&self, dir: &Path) -> Vec<PathBuf> {
+        self.walk(dir).into_iter().filter(|path| self.is_file(path)).collect()
+    }
+}
+
+/// The real files on disk.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskFileSystem;
+
+impl FileSystem for DiskFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::OpenOptions::new().write(true).create_new(true).open(path)?.write_all(contents)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<PathBuf>>>()?;
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn walk(&self, dir: &Path) -> Vec<PathBuf> {
+        WalkDir::new(dir).min_depth(1).sort_by_file_name().into_iter().filter_map(Result::ok).map(|entry| entry.into_path()).collect()
+    }
+}
+
+/// A file or folder of a `MemoryFileSystem`.
+#[derive(Debug, Clone)]
+enum MemoryEntry {
+    File { contents: Vec<u8>, modified: SystemTime },
+    Dir,
+}
+
+/// Files kept in memory. (Cloning it gives another handle to the same files, e.g. for a `MockCommandRunner` handler to write to)
+/// Relative paths and absolute paths are kept apart, as if the working directory were somewhere else entirely.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+    entries: Arc<Mutex<BTreeMap<PathBuf, MemoryEntry>>>,
+}
+
+impl MemoryFileSystem {
+    /// An empty file system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file (and the folders it is in), replacing it if it exists.
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) {
+        let path = normalize(path.as_ref());
+        if let Some(parent) = path.parent() {
+            let _ = self.create_dir_all(parent);
+        }
+        self.entries.lock().unwrap().insert(path, MemoryEntry::File { contents: contents.as_ref().to_vec(), modified: SystemTime::now() });
+    }
+
+    /// Every file's path, sorted.
+    pub fn file_paths(&self) -> Vec<PathBuf> {
+        self.entries.lock().unwrap().iter().filter(|(_, entry)| matches!(entry, MemoryEntry::File { .. })).map(|(path, _)| path.clone()).collect()
+    }
+
+    /// Sets when a file was last changed. (e.g. to make it older than the last build)
+    pub fn set_modified(&self, path: impl AsRef<Path>, modified: SystemTime) -> io::Result<()> {
+        match self.entries.lock().unwrap().get_mut(&normalize(path.as_ref())) {
+            Some(MemoryEntry::File { modified: file_modified, .. }) => {
+                *file_modified = modified;
+                Ok(())
+            }
+            _ => Err(not_found(path.as_ref())),
+        }
+    }
+
+    /// True if the path's folder exists. (The top of the file system always does)
+    fn parent_exists(entries: &BTreeMap<PathBuf, MemoryEntry>, path: &Path) -> bool {
+        match path.parent() {
+            None => true,
+            Some(parent) if parent.as_os_str().is_empty() || parent == Path::new("/") => true,
+            Some(parent) => matches!(entries.get(parent), Some(MemoryEntry::Dir)),
+        }
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.entries.lock().unwrap().get(&normalize(path)) {
+            Some(MemoryEntry::File { contents, .. }) => Ok(contents.clone()),
+            Some(MemoryEntry::Dir) => Err(io::Error::new(ErrorKind::IsADirectory, format!("'{}' is a directory", path.display()))),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let path = normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        if matches!(entries.get(&path), Some(MemoryEntry::Dir)) {
+            return Err(io::Error::new(ErrorKind::IsADirectory, format!("'{}' is a directory", path.display())));
+        }
+        if !Self::parent_exists(&entries, &path) {
+            return Err(not_found(&path));
+        }
+        entries.insert(path, MemoryEntry::File { contents: contents.to_vec(), modified: SystemTime::now() });
+        Ok(())
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        if self.exists(path) {
+            return Err(io::Error::new(ErrorKind::AlreadyExists, format!("'{}' already exists", path.display())));
+        }
+        self.write(path, contents)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        for dir in path.ancestors().filter(|dir| !dir.as_os_str().is_empty() && *dir != Path::new("/")) {
+            match entries.get(dir) {
+                Some(MemoryEntry::File { .. }) => return Err(io::Error::new(ErrorKind::AlreadyExists, format!("'{}' is a file", dir.display()))),
+                Some(MemoryEntry::Dir) => {}
+                None => { entries.insert(dir.to_path_buf(), MemoryEntry::Dir); }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&path) {
+            Some(MemoryEntry::File { .. }) => { entries.remove(&path); Ok(()) }
+            Some(MemoryEntry::Dir) => Err(io::Error::new(ErrorKind::IsADirectory, format!("'{}' is a directory", path.display()))),
+            None => Err(not_found(&path)),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        if !matches!(entries.get(&path), Some(MemoryEntry::Dir)) {
+            return Err(not_found(&path));
+        }
+        entries.retain(|entry_path, _| !entry_path.starts_with(&path));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (normalize(from), normalize(to));
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&from) {
+            return Err(not_found(&from));
+        }
+        if !Self::parent_exists(&entries, &to) {
+            return Err(not_found(&to));
+        }
+        match entries.get(&to) {
+            Some(MemoryEntry::Dir) if entries.keys().any(|path| path.starts_with(&to) && *path != to) => {
+                return Err(io::Error::new(ErrorKind::DirectoryNotEmpty, format!("'{}' isn't empty", to.display())));
+            }
+            _ => {}
+        }
+        entries.retain(|path, _| !path.starts_with(&to));
+        let moved: Vec<(PathBuf, MemoryEntry)> = entries.iter().filter(|(path, _)| path.starts_with(&from)).map(|(path, entry)| (path.clone(), entry.clone())).collect();
+        for (path, entry) in moved {
+            entries.remove(&path);
+            let relative = path.strip_prefix(&from).unwrap_or(Path::new(""));
+            entries.insert(if relative.as_os_str().is_empty() { to.clone() } else { to.join(relative) }, entry);
+        }
+        Ok(())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        matches!(self.entries.lock().unwrap().get(&normalize(path)), Some(MemoryEntry::File { .. }))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        path.as_os_str().is_empty() || path == Path::new("/") || matches!(self.entries.lock().unwrap().get(&path), Some(MemoryEntry::Dir))
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        match self.entries.lock().unwrap().get(&normalize(path)) {
+            Some(MemoryEntry::File { modified, .. }) => Ok(*modified),
+            Some(MemoryEntry::Dir) => Ok(SystemTime::UNIX_EPOCH),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let dir = normalize(path);
+        if !self.is_dir(&dir) {
+            return Err(not_found(&dir));
+        }
+        let entries = self.entries.lock().unwrap();
+        // (keeping the path as it was asked for, e.g. ./edit-me/pages -> ./edit-me/pages/index)
+        Ok(entries.keys().filter(|entry_path| entry_path.parent() == Some(dir.as_path()) || (dir.as_os_str().is_empty() && entry_path.parent() == Some(Path::new(""))))
+            .map(|entry_path| path.join(entry_path.file_name().unwrap_or_default()))
+            .collect())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        if !self.exists(path) {
+            return Err(not_found(path));
+        }
+        Ok(normalize(path))
+    }
+}
+
+/// The path without any `.` in it, and with each `..` taking away the folder before it. e.g. ./a/b/../c -> a/c
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => { normalized.pop(); }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(ErrorKind::NotFound, format!("'{}' doesn't exist", path.display()))
+}
+
+// ============================================================
+// COMMANDS
+// ============================================================
+
+/// What a program printed, and whether it succeeded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    /// A run that succeeded, printing `stdout`.
+    pub fn success(stdout: &str) -> Self {
+        CommandOutput { success: true, stdout: stdout.to_string(), stderr: String::new() }
+    }
+
+    /// A run that failed, printing `stderr`.
+    pub fn failure(stderr: &str) -> Self {
+        CommandOutput { success: false, stdout: String::new(), stderr: stderr.to_string() }
+    }
+}
+
+/// Runs the external programs a build uses. (e.g. sass, tsc)
+pub trait CommandRunner: Send + Sync {
+    /// Runs a program and waits for it to finish.
+    ///
+    /// # Returns
+    /// * What it printed, and whether it succeeded.
+    /// * `Err` if it couldn't be run at all. (e.g. it isn't installed)
+    fn run(&self, program: &str, args: &[String]) -> io::Result<CommandOutput>;
+
+    /// True if the program can be run.
+    fn is_available(&self, program: &str) -> bool {
+        self.run(program, &["--version".to_string()]).is_ok_and(|output| output.success)
+    }
+}
+
+/// Runs the real programs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemCommandRunner;
+
+impl CommandRunner for SystemCommandRunner {
+    fn run(&self, program: &str, args: &[String]) -> io::Result<CommandOutput> {
+        let output = Command::new(program).args(args).output()?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+}
+
+/// A shared runner, e.g. one a test keeps a handle on (to look at its `calls`) while a build uses it.
+impl<C: CommandRunner + ?Sized> CommandRunner for Arc<C> {
+    fn run(&self, program: &str, args: &[String]) -> io::Result<CommandOutput> {
+        self.as_ref().run(program, args)
+    }
+
+    fn is_available(&self, program: &str) -> bool {
+        self.as_ref().is_available(program)
+    }
+}
+
+/// What a `MockCommandRunner` does when a program is run, given its arguments.
+type CommandHandler = Box<dyn Fn(&[String]) -> CommandOutput + Send + Sync>;
+
+/// Pretends to be the external programs: each program it knows has a handler, and anything else isn't installed.
+/// Every run is recorded, so a test can check what was run.
+#[derive(Default)]
+pub struct MockCommandRunner {
+    handlers: HashMap<String, CommandHandler>,
+    calls: Mutex<Vec<(String, Vec<String>)>>,
+}
+
+impl MockCommandRunner {
+    /// A runner that knows no programs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a program, and what it does when it is run. e.g. .on("tsc", |args| CommandOutput::success(""))
+    pub fn on(mut self, program: &str, handler: impl Fn(&[String]) -> CommandOutput + Send + Sync + 'static) -> Self {
+        self.handlers.insert(program.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Every run so far: the program, and its arguments.
+    pub fn calls(&self) -> Vec<(String, Vec<String>)> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl CommandRunner for MockCommandRunner {
+    fn run(&self, program: &str, args: &[String]) -> io::Result<CommandOutput> {
+        self.calls.lock().unwrap().push((program.to_string(), args.to_vec()));
+        match self.handlers.get(program) {
+            Some(handler) => Ok(handler(args)),
+            None => Err(io::Error::new(ErrorKind::NotFound, format!("{}: command not found", program))),
+        }
+    }
+
+    fn is_available(&self, program: &str) -> bool {
+        self.handlers.contains_key(program)
+    }
+}
+
+// ============================================================
+// THE SCSS COMPILER'S FILES
+// ============================================================
+
+/// Lets the built-in scss compiler (grass) read the files it @uses from a `FileSystem`.
+pub struct GrassFileSystem<'a>(pub &'a dyn FileSystem);
+
+impl std::fmt::Debug for GrassFileSystem<'_> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("GrassFileSystem")
+    }
+}
+
+impl grass::Fs for GrassFileSystem<'_> {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.0.is_dir(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.0.is_file(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.0.read(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.0.canonicalize(path)
+    }
+}
@@ -0,0 +1,128 @@
+use websiteCompiler::bundling::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// Writes a tiny site: three components (signpost is used by top-bar, so it comes first in the compilation order) and some pages.
+fn write_site(site: &Path) {
+    for (name, css) in [("signpost", ".signpost { background: url(images/arrow.png); }"), ("top-bar", ".top-bar { color: red; }"), ("footer", ".footer { color: blue; }")] {
+        fs::create_dir_all(site.join("shared/reusables").join(name)).unwrap();
+        fs::write(site.join(format!("shared/reusables/{0}/{0}.css", name)), css).unwrap();
+        fs::write(site.join(format!("shared/reusables/{0}/{0}.js", name)), format!("console.log('{}')", name)).unwrap();
+    }
+    let page = |components: &[&str], root: &str| -> String {
+        let mut tags = String::new();
+        for component in components {
+            tags.push_str(&format!("<link rel=\"stylesheet\" href=\"{0}shared/reusables/{1}/{1}.css\">\n", root, component));
+            tags.push_str(&format!("<script defer src=\"{0}shared/reusables/{1}/{1}.js\"></script>\n", root, component));
+        }
+        format!("<html><head>\n<link rel=\"stylesheet\" href=\"{}shared/global.css\">\n{}</head><body></body></html>", root, tags)
+    };
+    fs::create_dir_all(site.join("blog")).unwrap();
+    fs::write(site.join("index.html"), page(&["top-bar", "signpost", "footer"], "")).unwrap();
+    fs::write(site.join("about.html"), page(&["top-bar", "signpost"], "")).unwrap();
+    fs::write(site.join("blog/post.html"), page(&["top-bar", "signpost", "top-bar"], "../")).unwrap();
+}
+
+fn compilation_order() -> Vec<String> {
+    ["shared/reusables/signpost", "shared/reusables/top-bar", "shared/reusables/footer"].iter().map(|s| s.to_string()).collect()
+}
+
+// ===================================================================
+// # === Tests for `find_bundleable_tags` / `sort_into_dependency_order` ===
+// ===================================================================
+
+#[test]
+fn test_find_bundleable_tags() {
+    let site = Path::new("site");
+    let html = "<link rel=\"stylesheet\" href=\"../shared/reusables/a/a.css\">\n<link rel=\"stylesheet\" href=\"post.css\">\n<script defer src=\"../bundles/1a2b3c4d.js\"></script>\n<script defer src=\"https://example.com/shared/reusables/a/a.js\"></script>";
+    let tags = find_bundleable_tags(html, &site.join("blog/post.html"), site);
+
+    assert_eq!(tags.len(), 2);
+    assert_eq!((tags[0].kind, tags[0].path.as_str()), (BundleKind::Css, "shared/reusables/a/a.css"));
+    assert_eq!(&html[tags[0].start..tags[0].end], "<link rel=\"stylesheet\" href=\"../shared/reusables/a/a.css\">\n");
+    assert_eq!((tags[1].kind, tags[1].path.as_str()), (BundleKind::Js, "bundles/1a2b3c4d.js"));
+}
+
+#[test]
+fn test_sort_into_dependency_order() {
+    let mut files: Vec<String> = ["shared/reusables/unlisted/unlisted.css", "shared/reusables/footer/footer.css", "shared/reusables/signpost/signpost.css"].iter().map(|s| s.to_string()).collect();
+    sort_into_dependency_order(&mut files, &compilation_order());
+    assert_eq!(files, ["shared/reusables/signpost/signpost.css", "shared/reusables/footer/footer.css", "shared/reusables/unlisted/unlisted.css"]);
+}
+
+// ===================================================================
+// # === Tests for `bundle_component_assets` ===
+// ===================================================================
+
+#[test]
+fn test_bundle_per_page() {
+    let tmp = tempdir().unwrap();
+    let site = tmp.path();
+    write_site(site);
+
+    let summary = bundle_component_assets(&DiskFileSystem, site, BundleMode::Page, &compilation_order()).unwrap();
+    assert_eq!(summary.pages_bundled, 3);
+    // index has its own set; about and blog/post use the same set (the duplicate top-bar tag doesn't count), so share bundles
+    assert_eq!(summary.bundles_written, 4);
+
+    let html = fs::read_to_string(site.join("blog/post.html")).unwrap();
+    assert!(!html.contains("shared/reusables"), "{}", html);
+    assert!(html.contains("<link rel=\"stylesheet\" href=\"../shared/global.css\">"), "{}", html);
+    let css_name = bundle_name(&["shared/reusables/signpost/signpost.css".to_string(), "shared/reusables/top-bar/top-bar.css".to_string()], BundleKind::Css);
+    assert!(html.contains(&format!("<link rel=\"stylesheet\" href=\"../bundles/{}\">\n<script defer src=\"../bundles/", css_name)), "{}", html);
+
+    // Dependency order, and the url()s still point at the component's files
+    let css = fs::read_to_string(site.join("bundles").join(&css_name)).unwrap();
+    let signpost = css.find(".signpost { background: url(../shared/reusables/signpost/images/arrow.png); }").unwrap();
+    assert!(signpost < css.find(".top-bar").unwrap(), "{}", css);
+
+    // Running again changes nothing
+    let about = fs::read_to_string(site.join("about.html")).unwrap();
+    bundle_component_assets(&DiskFileSystem, site, BundleMode::Page, &compilation_order()).unwrap();
+    assert_eq!(fs::read_to_string(site.join("about.html")).unwrap(), about);
+    assert_eq!(fs::read_to_string(site.join("bundles").join(&css_name)).unwrap(), css);
+}
+
+#[test]
+fn test_bundle_shared_and_incremental_rebuild() {
+    let tmp = tempdir().unwrap();
+    let site = tmp.path();
+    write_site(site);
+
+    bundle_component_assets(&DiskFileSystem, site, BundleMode::Shared { min_pages: 3 }, &compilation_order()).unwrap();
+
+    // top-bar and signpost are on all three pages, so shared; the footer is only on the index page
+    let shared_css = fs::read_to_string(site.join("bundles/shared.css")).unwrap();
+    assert!(shared_css.contains(".top-bar") && shared_css.contains(".signpost") && !shared_css.contains(".footer"), "{}", shared_css);
+    let index = fs::read_to_string(site.join("index.html")).unwrap();
+    let footer_css = bundle_name(&["shared/reusables/footer/footer.css".to_string()], BundleKind::Css);
+    assert!(index.contains(&format!("href=\"bundles/shared.css\">\n<link rel=\"stylesheet\" href=\"bundles/{}\">", footer_css)), "{}", index);
+    let about = fs::read_to_string(site.join("about.html")).unwrap();
+    assert!(about.contains("bundles/shared.css") && !about.contains(&footer_css), "{}", about);
+
+    // A component changes and only one page is recompiled (so only it has the component tags again);
+    // the bundles every page uses are still rebuilt
+    write_site_page_only(site);
+    fs::write(site.join("shared/reusables/top-bar/top-bar.css"), ".top-bar { color: green; }").unwrap();
+    bundle_component_assets(&DiskFileSystem, site, BundleMode::Shared { min_pages: 3 }, &compilation_order()).unwrap();
+    let shared_css = fs::read_to_string(site.join("bundles/shared.css")).unwrap();
+    assert!(shared_css.contains("color: green") && !shared_css.contains("console.log"), "{}", shared_css);
+    assert_eq!(fs::read_to_string(site.join("about.html")).unwrap(), about);
+
+    // Switching back to per page bundles removes the shared ones (and the index page's footer-only ones)
+    let summary = bundle_component_assets(&DiskFileSystem, site, BundleMode::Page, &compilation_order()).unwrap();
+    assert_eq!(summary.bundles_removed, 4);
+    assert!(!site.join("bundles/shared.css").exists());
+    assert!(!fs::read_to_string(site.join("about.html")).unwrap().contains("shared.css"));
+}
+
+/// Re-writes only the index page, as an incremental build that recompiled just it would.
+fn write_site_page_only(site: &Path) {
+    let about = fs::read_to_string(site.join("about.html")).unwrap();
+    let post = fs::read_to_string(site.join("blog/post.html")).unwrap();
+    write_site(site);
+    fs::write(site.join("about.html"), about).unwrap();
+    fs::write(site.join("blog/post.html"), post).unwrap();
+}
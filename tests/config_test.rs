@@ -0,0 +1,204 @@
+use websiteCompiler::config::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `parse_config` ===
+// ===================================================================
+
+#[test]
+fn test_parse_config_defaults() {
+    // An empty file (or no file) means the settings this website has always used
+    let config = parse_config("").unwrap();
+    assert_eq!(config, SiteConfig::default());
+    assert_eq!(config.paths.source_dir, Path::new("edit-me"));
+    assert_eq!(config.paths.output_dir, Path::new("actual-website-do-not-edit"));
+    assert_eq!(config.paths.shared_dir(), Path::new("edit-me/shared"));
+    assert_eq!(config.paths.components_dir(), Path::new("edit-me/shared/reusables"));
+    assert_eq!(config.paths.page_text_dir(), Path::new("edit-me/shared/page_text"));
+    assert_eq!(config.paths.blog_template_top(), Path::new("edit-me/shared/reusables/template-blog-top/template-blog-top.html"));
+    assert_eq!(config.placeholders.root, "<root>");
+    assert_eq!(config.placeholders.component_start, "<r-");
+    assert_eq!(config.site.default_language, "en");
+    assert_eq!(config.site.base_url, None);
+    assert_eq!(config.build, BuildSettings::default());
+}
+
+#[test]
+fn test_parse_config_overrides() {
+    let config = parse_config(r#"
+[site]
+base-url = "https://example.com"
+default-language = "jp"
+
+[paths]
+source-dir = "src"
+output-dir = "build/site"
+
+[placeholders]
+root = "{{root}}"
+component-start = "<x-"
+
+[build]
+hash-assets = true
+bundle = "shared"
+scss-compiler = "built-in"
+"#).unwrap();
+    assert_eq!(config.site.base_url.as_deref(), Some("https://example.com"));
+    assert_eq!(config.site.default_language, "jp");
+    assert_eq!(config.paths.output_dir, Path::new("build/site"));
+    // (the folders inside the source directory follow it)
+    assert_eq!(config.paths.components_dir(), Path::new("src/shared/reusables"));
+    assert_eq!(config.paths.page_text_dir(), Path::new("src/shared/page_text"));
+    assert_eq!(config.placeholders.root, "{{root}}");
+    assert_eq!(config.placeholders.component_start, "<x-");
+    assert_eq!(config.placeholders.component_end, ">");
+    assert_eq!(config.build.hash_assets, Some(true));
+    assert_eq!(config.build.production, None);
+    assert_eq!(config.build.bundle, Some(BundleSetting::Shared));
+    assert_eq!(config.build.scss_compiler, Some(ScssCompilerSetting::BuiltIn));
+}
+
+#[test]
+fn test_parse_config_unknown_key() {
+    let error = parse_config("[paths]\nsource-dir = \"src\"\noutptu-dir = \"public\"\n").unwrap_err();
+    assert!(error.starts_with("3:1:"), "{}", error);
+    assert!(error.contains("unknown field `outptu-dir`"), "{}", error);
+    assert!(error.contains("(at paths.outptu-dir)") || error.contains("(at paths)"), "{}", error);
+
+    let error = parse_config("[sitee]\nbase-url = \"https://example.com\"\n").unwrap_err();
+    assert!(error.contains("unknown field `sitee`"), "{}", error);
+}
+
+#[test]
+fn test_parse_config_invalid_value() {
+    let error = parse_config("[build]\nbundle = \"everything\"\n").unwrap_err();
+    assert!(error.starts_with("2:"), "{}", error);
+    assert!(error.contains("everything"), "{}", error);
+    assert!(error.contains("build.bundle"), "{}", error);
+
+    let error = parse_config("[build]\nproduction = \"yes\"\n").unwrap_err();
+    assert!(error.contains("build.production"), "{}", error);
+
+    assert!(parse_config("[paths\n").is_err());
+}
+
+// ===================================================================
+// # === Tests for `SiteConfig::build_settings` ===
+// ===================================================================
+
+#[test]
+fn test_build_settings_profiles() {
+    let config = parse_config(r#"
+[build]
+critical-css = true
+source-maps = "hidden"
+
+[profiles.release]
+production = true
+source-maps = "none"
+"#).unwrap();
+
+    // No profile: just [build]
+    let settings = config.build_settings(None).unwrap();
+    assert_eq!(settings.production, None);
+    assert_eq!(settings.source_maps, Some(SourceMapSetting::Hidden));
+
+    // A profile's settings replace [build]'s, and anything it doesn't set is left as [build] has it
+    let settings = config.build_settings(Some("release")).unwrap();
+    assert_eq!(settings.production, Some(true));
+    assert_eq!(settings.source_maps, Some(SourceMapSetting::None));
+    assert_eq!(settings.critical_css, Some(true));
+
+    // (the command line flags are applied over that the same way)
+    let flags = BuildSettings { critical_css: Some(false), ..BuildSettings::default() };
+    assert_eq!(settings.overridden_by(&flags).critical_css, Some(false));
+    assert_eq!(settings.overridden_by(&flags).production, Some(true));
+
+    let error = config.build_settings(Some("staging")).unwrap_err();
+    assert!(error.to_string().contains("staging"));
+    assert!(error.to_string().contains("release"));
+}
+
+// ===================================================================
+// # === Tests for `load_config` ===
+// ===================================================================
+
+#[test]
+fn test_load_config() {
+    let tmp = tempdir().unwrap();
+    fs::write(tmp.path().join(CONFIG_FILE_NAME), "[paths]\noutput-dir = \"public\"\n").unwrap();
+
+    let config = load_config(&DiskFileSystem, &tmp.path().join(CONFIG_FILE_NAME)).unwrap();
+    assert_eq!(config.paths.output_dir, Path::new("public"));
+}
+
+#[test]
+fn test_load_config_errors_name_the_file() {
+    let tmp = tempdir().unwrap();
+    let path = tmp.path().join(CONFIG_FILE_NAME);
+    fs::write(&path, "[site]\nbase_url = \"https://example.com\"\n").unwrap();
+    let error = load_config(&DiskFileSystem, &path).unwrap_err().to_string();
+    assert!(error.starts_with(&format!("{}:2:1:", path.display())), "{}", error);
+    assert!(error.contains("base_url"), "{}", error);
+
+    // (the column is counted in characters, as it is for the translation catalogs)
+    fs::write(&path, "[site]\ndefault-language = \"日本\" x\n").unwrap();
+    let error = load_config(&DiskFileSystem, &path).unwrap_err().to_string();
+    assert!(error.starts_with(&format!("{}:2:25:", path.display())), "{}", error);
+
+    assert!(load_config(&DiskFileSystem, &tmp.path().join("missing.toml")).is_err());
+}
+
+// ===================================================================
+// # === Tests for `find_project_root` / `load_project` ===
+// ===================================================================
+
+#[test]
+fn test_find_project_root() {
+    let tmp = tempdir().unwrap();
+    let site = tmp.path().join("my-website");
+    let nested = site.join("edit-me/pages/blog");
+    fs::create_dir_all(&nested).unwrap();
+
+    // A site without a site.toml: the folder its edit-me folder is in
+    assert_eq!(find_project_root(&DiskFileSystem, &nested), Some(site.clone()));
+    assert_eq!(find_project_root(&DiskFileSystem, &site), Some(site.clone()));
+
+    // A site.toml marks the root, even if its source folder is called something else
+    let other = tmp.path().join("other-website");
+    fs::create_dir_all(other.join("src/pages")).unwrap();
+    fs::write(other.join(CONFIG_FILE_NAME), "[paths]\nsource-dir = \"src\"\n").unwrap();
+    assert_eq!(find_project_root(&DiskFileSystem, &other.join("src/pages")), Some(other.clone()));
+}
+
+#[test]
+fn test_load_project() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("my-website");
+    fs::create_dir_all(&root).unwrap();
+
+    // No site.toml: the default settings, relative to the root
+    let config = load_project(&DiskFileSystem, &root).unwrap();
+    assert_eq!(config.root, root);
+    assert_eq!(config.paths.source_dir, root.join("edit-me"));
+    assert_eq!(config.paths.output_dir, root.join("actual-website-do-not-edit"));
+    assert_eq!(config.paths.components_dir(), root.join("edit-me/shared/reusables"));
+    assert_eq!(config.paths.blog_template_bottom(), root.join("edit-me/shared/reusables/template-blog-bottom/template-blog-bottom.html"));
+    assert_eq!(config.project_path(".last_compiled"), root.join(".last_compiled"));
+    // (the blog folder is in the output directory, so it stays relative to that)
+    assert_eq!(config.paths.blog_folder, Path::new("blog"));
+
+    // The paths in site.toml are relative to the root too (absolute ones are left as they are)
+    fs::write(root.join(CONFIG_FILE_NAME), "[paths]\noutput-dir = \"build/site\"\npage-text-dir = \"text\"\nblog-template-top = \"/templates/top.html\"\n").unwrap();
+    let config = load_project(&DiskFileSystem, &root).unwrap();
+    assert_eq!(config.paths.output_dir, root.join("build/site"));
+    assert_eq!(config.paths.page_text_dir(), root.join("text"));
+    assert_eq!(config.paths.blog_template_top(), Path::new("/templates/top.html"));
+
+    // An empty root leaves the paths relative to the working directory, as they are written
+    let config = load_project(&DiskFileSystem, Path::new("")).unwrap();
+    assert_eq!(config.paths.source_dir, Path::new("edit-me"));
+}
@@ -0,0 +1,69 @@
+use websiteCompiler::critical_css::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `extract_critical_css` ===
+// ===================================================================
+
+#[test]
+fn test_extract_critical_css() {
+    let names = MarkupNames::from_html(r#"<body><div class="top-bar" data-open><p>Hi</p></div></body>"#);
+    let css = r#"
+@import url("other.css");
+@font-face { font-family: Dyslexic; src: url(fonts/dyslexic.otf); }
+body { margin: 0; }
+.top-bar, .footer { color: red; }
+.footer { color: blue; }
+.top-bar[data-open] p::first-line { font-weight: bold; }
+@media (max-width: 600px) { .top-bar { color: green; } .signpost { color: green; } }
+@media print { .signpost { display: none; } }
+"#;
+    assert_eq!(
+        extract_critical_css(css, &names).unwrap(),
+        "@font-face{font-family:Dyslexic;src:url(fonts/dyslexic.otf)}body{margin:0}.top-bar,.footer{color:red}.top-bar[data-open] p:first-line{font-weight:700}@media (width<=600px){.top-bar{color:green}}"
+    );
+    // (e.g. scss that wasn't compiled)
+    assert!(extract_critical_css(".a { $x: 1; }\n& .b { color: red; }", &names).is_err());
+}
+
+// ===================================================================
+// # === Tests for `inline_critical_css` ===
+// ===================================================================
+
+#[test]
+fn test_inline_critical_css() {
+    let tmp = tempdir().unwrap();
+    let site = tmp.path();
+    fs::create_dir_all(site.join("blog")).unwrap();
+    fs::write(site.join("shared.css"), ".post { background: url(images/bg.png); } .unused { color: red; }").unwrap();
+    fs::write(site.join("blog/post.css"), ".title { color: blue; }").unwrap();
+    fs::write(site.join("blog/late.css"), ".late { color: blue; }").unwrap();
+    let html = r#"<html><head>
+<link rel="stylesheet" href="../shared.css">
+<link rel=stylesheet href="post.css">
+<link rel="stylesheet" href="late.css" media="print" onload="this.media='all'">
+<noscript><link rel="stylesheet" href="late.css"></noscript>
+</head><body><div class="post"><h1 class="title">Hi</h1></div></body></html>"#;
+    let page_path = site.join("blog/post.html");
+
+    let (new_html, deferred, _, failures) = inline_critical_css(&DiskFileSystem, html, &page_path, site);
+    assert!(failures.is_empty());
+    assert_eq!(deferred, 2);
+    assert_eq!(new_html, r#"<html><head>
+<style data-critical-css>.post{background:url(../images/bg.png)}.title{color:#00f}</style>
+<link rel="stylesheet" href="../shared.css" media="print" onload="this.media='all'" data-critical-css>
+<noscript data-critical-css><link rel="stylesheet" href="../shared.css"></noscript>
+<link rel="stylesheet" href="post.css" media="print" onload="this.media='all'" data-critical-css>
+<noscript data-critical-css><link rel="stylesheet" href="post.css"></noscript>
+<link rel="stylesheet" href="late.css" media="print" onload="this.media='all'">
+<noscript><link rel="stylesheet" href="late.css"></noscript>
+</head><body><div class="post"><h1 class="title">Hi</h1></div></body></html>"#);
+
+    // Doing it again (e.g. after a stylesheet changes) starts from the restored page, so nothing is added twice
+    fs::write(site.join("blog/post.css"), ".title { color: green; }").unwrap();
+    let (newer_html, _, _, _) = inline_critical_css(&DiskFileSystem, &new_html, &page_path, site);
+    assert_eq!(newer_html, new_html.replace("color:#00f", "color:green"));
+    assert_eq!(restore_deferred_stylesheets(&newer_html), html.replace("rel=stylesheet", "rel=\"stylesheet\""));
+}
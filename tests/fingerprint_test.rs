@@ -0,0 +1,73 @@
+use websiteCompiler::fingerprint::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `rewrite_asset_references` ===
+// ===================================================================
+
+#[test]
+fn test_rewrite_asset_references() {
+    let manifest: AssetManifest = [
+        ("shared/global.css", "shared/global.11111111.css"),
+        ("shared/images/boot.png", "shared/images/boot.22222222.png"),
+        ("shared/images/boot-480w.webp", "shared/images/boot-480w.33333333.webp"),
+    ].iter().map(|(a, b)| (a.to_string(), b.to_string())).collect();
+    let output_dir = Path::new("site");
+
+    let html = r#"<link rel="stylesheet" href="../shared/global.css?v=1"><source srcset="../shared/images/boot-480w.webp 480w, ../shared/images/boot.png 960w">
+<img src="/shared/images/boot.png"><img src="../shared/images/other.png"><a href="https://example.com/shared/global.css">x</a>"#;
+    let rewritten = rewrite_asset_references(html, &output_dir.join("blog/post.html"), output_dir, &manifest);
+
+    assert_eq!(rewritten, r#"<link rel="stylesheet" href="../shared/global.11111111.css?v=1"><source srcset="../shared/images/boot-480w.33333333.webp 480w, ../shared/images/boot.22222222.png 960w">
+<img src="/shared/images/boot.22222222.png"><img src="../shared/images/other.png"><a href="https://example.com/shared/global.css">x</a>"#);
+
+    // An out of date fingerprint is updated
+    let css = "body { background: url('images/boot.99999999.png'); }";
+    let rewritten = rewrite_asset_references(css, &output_dir.join("shared/global.css"), output_dir, &manifest);
+    assert_eq!(rewritten, "body { background: url('images/boot.22222222.png'); }");
+}
+
+// ===================================================================
+// # === Tests for `fingerprint_assets` ===
+// ===================================================================
+
+#[test]
+fn test_fingerprint_assets() {
+    let tmp = tempdir().unwrap();
+    let site = tmp.path();
+    fs::create_dir_all(site.join("shared/images")).unwrap();
+    fs::write(site.join("shared/images/boot.png"), "png bytes").unwrap();
+    fs::write(site.join("shared/global.css"), "body { background: url(images/boot.png); }").unwrap();
+    fs::write(site.join("shared/global.js"), r#"const css = "shared/global.css";"#).unwrap();
+    fs::write(site.join("index.html"), r#"<link href="shared/global.css"><script src="shared/global.js"></script><img src="shared/images/boot.png">"#).unwrap();
+
+    let manifest = fingerprint_assets(&DiskFileSystem, site).unwrap();
+    assert_eq!(manifest.len(), 3);
+
+    // The css refers to the hashed image, and its own hash is of that rewritten content
+    let image_name = manifest["shared/images/boot.png"].clone();
+    let css_name = manifest["shared/global.css"].clone();
+    let css = fs::read_to_string(site.join(&css_name)).unwrap();
+    assert_eq!(css, format!("body {{ background: url(images/{}); }}", image_name.rsplit('/').next().unwrap()));
+    assert_eq!(css_name, format!("shared/global.{}.css", content_fingerprint(css.as_bytes())));
+
+    let html = fs::read_to_string(site.join("index.html")).unwrap();
+    assert_eq!(html, format!(r#"<link href="{}"><script src="{}"></script><img src="{}">"#, css_name, manifest["shared/global.js"], image_name));
+
+    let written_manifest: AssetManifest = serde_json::from_str(&fs::read_to_string(site.join(ASSET_MANIFEST_FILE)).unwrap()).unwrap();
+    assert_eq!(written_manifest, manifest);
+
+    // The image changes: it gets a new name, the old copy goes, and everything that refers to it is updated
+    fs::write(site.join("shared/images/boot.png"), "new png bytes").unwrap();
+    let new_manifest = fingerprint_assets(&DiskFileSystem, site).unwrap();
+    assert_ne!(new_manifest["shared/images/boot.png"], image_name);
+    assert_ne!(new_manifest["shared/global.css"], css_name);
+    assert!(!site.join(&image_name).exists());
+    assert!(!site.join(&css_name).exists());
+    let html = fs::read_to_string(site.join("index.html")).unwrap();
+    assert!(html.contains(&new_manifest["shared/images/boot.png"]), "{}", html);
+    assert!(html.contains(&new_manifest["shared/global.css"]), "{}", html);
+}
@@ -0,0 +1,141 @@
+use websiteCompiler::config::SiteConfig;
+use websiteCompiler::github_pages::*;
+use websiteCompiler::vfs::{CommandOutput, FileSystem, MemoryFileSystem, MockCommandRunner};
+use std::path::{Path, PathBuf};
+
+/// A built website in memory.
+fn make_output() -> MemoryFileSystem {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("out/index.html", "<a href=\"about.html\">About</a><link href=\"/index.css\"><img src=\"images/logo.png\">");
+    fs.add_file("out/index.css", "body { background: url(/images/bg.png); } .logo { background: url('images/logo.png'); }");
+    fs.add_file("out/blog/missing.html", "<link href=\"../index.css\"><a href=\"../index.html#top\">Home</a><a href=\"https://example.com\">Elsewhere</a>");
+    fs
+}
+
+/// A configuration with [github-pages] settings.
+fn config_with(base_path: Option<&str>, cname: Option<&str>, not_found_page: Option<&str>) -> SiteConfig {
+    let mut config = SiteConfig::default();
+    config.github_pages.base_path = base_path.map(str::to_string);
+    config.github_pages.cname = cname.map(str::to_string);
+    config.github_pages.not_found_page = not_found_page.map(PathBuf::from);
+    config
+}
+
+// ===================================================================
+// # === Tests for `base_path` ===
+// ===================================================================
+
+#[test]
+fn test_base_path() {
+    assert_eq!(base_path(&config_with(None, None, None)), "");
+    assert_eq!(base_path(&config_with(Some("repo/"), None, None)), "/repo");
+    assert_eq!(base_path(&config_with(Some("/"), None, None)), "");
+
+    // (by default, the path of the site's address)
+    let mut config = config_with(None, None, None);
+    config.site.base_url = Some("https://user.github.io/repo/".to_string());
+    assert_eq!(base_path(&config), "/repo");
+    config.site.base_url = Some("https://www.example.com".to_string());
+    assert_eq!(base_path(&config), "");
+}
+
+// ===================================================================
+// # === Tests for `prepare_for_github_pages` ===
+// ===================================================================
+
+#[test]
+fn test_prepare_for_github_pages() {
+    let fs = make_output();
+    let config = config_with(Some("/repo"), Some("www.example.com"), Some("blog/missing.html"));
+    let summary = prepare_for_github_pages(&fs, Path::new("out"), &config).unwrap();
+    let special_files: Vec<&str> = summary.special_files.iter().map(|path| path.to_str().unwrap()).collect();
+    assert_eq!(special_files, vec!["out/.nojekyll", "out/404.html", "out/CNAME"]);
+    assert_eq!(fs.read_to_string(Path::new("out/CNAME")).unwrap(), "www.example.com\n");
+
+    // .html taken off the links, and the base path put in front of the links from the root (in html and css)
+    assert_eq!(fs.read_to_string(Path::new("out/index.html")).unwrap(), "<a href=\"about\">About</a><link href=\"/repo/index.css\"><img src=\"images/logo.png\">");
+    assert_eq!(fs.read_to_string(Path::new("out/index.css")).unwrap(), "body { background: url(/repo/images/bg.png); } .logo { background: url('images/logo.png'); }");
+
+    // The not-found page's links work from any url
+    assert_eq!(fs.read_to_string(Path::new("out/404.html")).unwrap(), "<link href=\"/repo/index.css\"><a href=\"/repo/index#top\">Home</a><a href=\"https://example.com\">Elsewhere</a>");
+
+    // Doing it again (an incremental build starts from the last one) changes nothing
+    let summary = prepare_for_github_pages(&fs, Path::new("out"), &config).unwrap();
+    assert_eq!(summary.files_changed, 0);
+    assert!(!fs.read_to_string(Path::new("out/index.css")).unwrap().contains("/repo/repo"));
+}
+
+#[test]
+fn test_prepare_for_github_pages_not_found_page() {
+    // Without a not-found page, a plain one is written (linking to the home page)
+    let fs = make_output();
+    prepare_for_github_pages(&fs, Path::new("out"), &config_with(None, None, None)).unwrap();
+    assert!(fs.read_to_string(Path::new("out/404.html")).unwrap().contains("<a href=\"/\">"));
+    assert!(!fs.exists(Path::new("out/CNAME")));
+    assert!(fs.is_file(Path::new("out/.nojekyll")));
+
+    // A not-found page that isn't in the website
+    let error = prepare_for_github_pages(&fs, Path::new("out"), &config_with(None, None, Some("not-found.html"))).unwrap_err();
+    assert!(error.to_string().contains("not-found.html"), "{}", error);
+}
+
+#[test]
+fn test_prepare_for_github_pages_only_changes_links_of_the_website() {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("out/index.html", concat!(
+        "<a href=\"about.html#team\">Team</a><a href=\"/blog/sleep.html?v=2\">Sleep</a>",
+        "<a href=\"https://example.com/page.html\">Elsewhere</a><a href=\"//cdn.example.com/page.html\">CDN</a>",
+    ));
+    prepare_for_github_pages(&fs, Path::new("out"), &config_with(None, None, None)).unwrap();
+
+    // .html is taken off the website's own pages (keeping the #fragment / ?query), but not off other websites' pages
+    assert_eq!(fs.read_to_string(Path::new("out/index.html")).unwrap(), concat!(
+        "<a href=\"about#team\">Team</a><a href=\"/blog/sleep?v=2\">Sleep</a>",
+        "<a href=\"https://example.com/page.html\">Elsewhere</a><a href=\"//cdn.example.com/page.html\">CDN</a>",
+    ));
+}
+
+// ===================================================================
+// # === Tests for `deploy` ===
+// ===================================================================
+
+#[test]
+fn test_deploy() {
+    let fs = make_output();
+    fs.add_file("pages-repo/.git/HEAD", "ref: refs/heads/main\n");
+    fs.add_file("pages-repo/old-page.html", "old");
+    let git = MockCommandRunner::new().on("git", |args| match args[2].as_str() {
+        "status" => CommandOutput::success(" M index.html\n"),
+        _ => CommandOutput::success(""),
+    });
+
+    let deployment = deploy(&fs, &git, Path::new("out"), Path::new("pages-repo"), "Publish").unwrap();
+    assert_eq!(deployment.files_copied, 3);
+    assert!(deployment.committed);
+    // (the website replaces what was there, apart from .git)
+    assert!(fs.is_file(Path::new("pages-repo/.git/HEAD")));
+    assert!(!fs.exists(Path::new("pages-repo/old-page.html")));
+    assert_eq!(fs.read(Path::new("pages-repo/blog/missing.html")).unwrap(), fs.read(Path::new("out/blog/missing.html")).unwrap());
+
+    let commands: Vec<String> = git.calls().iter().map(|(_, args)| args.join(" ")).collect();
+    assert_eq!(commands, vec!["-C pages-repo add --all", "-C pages-repo status --porcelain", "-C pages-repo commit --quiet --message Publish"]);
+
+    // Nothing to commit
+    let unchanged = MockCommandRunner::new().on("git", |_| CommandOutput::success(""));
+    assert!(!deploy(&fs, &unchanged, Path::new("out"), Path::new("pages-repo"), "Publish").unwrap().committed);
+
+    // A failed commit, a folder that isn't a checkout, and a checkout that has the project in it
+    let failing = MockCommandRunner::new().on("git", |args| match args[2].as_str() {
+        "commit" => CommandOutput::failure("Author identity unknown"),
+        "status" => CommandOutput::success(" M index.html\n"),
+        _ => CommandOutput::success(""),
+    });
+    let error = deploy(&fs, &failing, Path::new("out"), Path::new("pages-repo"), "Publish").unwrap_err();
+    assert!(error.to_string().contains("Author identity unknown"), "{}", error);
+    fs.add_file("not-a-repo/file.txt", "");
+    assert!(deploy(&fs, &unchanged, Path::new("out"), Path::new("not-a-repo"), "Publish").unwrap_err().to_string().contains("isn't a git checkout"));
+    fs.add_file("site/.git/HEAD", "");
+    fs.add_file("site/out/index.html", "");
+    assert!(deploy(&fs, &unchanged, Path::new("site/out"), Path::new("site"), "Publish").is_err());
+    assert!(fs.is_file(Path::new("site/out/index.html")));
+}
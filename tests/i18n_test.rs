@@ -0,0 +1,194 @@
+use websiteCompiler::i18n::*;
+use websiteCompiler::config::SiteConfig;
+use websiteCompiler::vfs::{DiskFileSystem, MemoryFileSystem};
+use std::path::Path;
+use serde_json::json;
+use std::fs;
+use tempfile::tempdir;
+
+fn catalog(value: serde_json::Value) -> Catalog {
+    match value {
+        serde_json::Value::Object(map) => map,
+        _ => panic!("test catalogs must be objects"),
+    }
+}
+
+// ===================================================================
+// # === Tests for `extract_translation_references` ===
+// ===================================================================
+
+// Scenario: `index.html` uses the `buy-box` component, which uses the `price` component.
+// The components have no data-page, so their keys should be credited to the index page.
+#[test]
+fn test_extract_follows_components_to_pages() {
+    let tmp = tempdir().unwrap();
+    let source_dir = tmp.path().join("edit-me");
+    let components_dir = source_dir.join("shared/reusables");
+    fs::create_dir_all(source_dir.join("pages/index")).unwrap();
+    fs::create_dir_all(components_dir.join("buy-box")).unwrap();
+    fs::create_dir_all(components_dir.join("price")).unwrap();
+
+    fs::write(source_dir.join("pages/index/index.html"), r#"<body data-page="index"><h1 add-text-section="welcome">  Hello
+        there </h1><r-buy-box><div>TEXT=products.title</div><div>TEXT=products.nav-title</div></body>"#).unwrap();
+    fs::write(components_dir.join("buy-box/buy-box.html"), r#"<button add-text-section="buy-now">Buy now</button><r-price>"#).unwrap();
+    fs::write(components_dir.join("price/price.html"), r#"<span add-text-section="price-label"></span><span add-text-section="">x</span>"#).unwrap();
+
+    let references = extract_translation_references(&DiskFileSystem, &source_dir, &components_dir, "<r-", ">").unwrap();
+    let found: Vec<(String, String, String)> = references.iter().map(|r| (r.page.clone(), r.key.clone(), r.source_text.clone())).collect();
+
+    assert_eq!(found, vec![
+        ("index".to_string(), "buy-now".to_string(), "Buy now".to_string()),
+        ("index".to_string(), "price-label".to_string(), "".to_string()),
+        ("index".to_string(), "welcome".to_string(), "Hello there".to_string()),
+        ("products".to_string(), "nav-title".to_string(), "".to_string()),
+        ("products".to_string(), "title".to_string(), "".to_string()),
+    ]);
+}
+
+#[test]
+fn test_update_catalog_adds_missing_and_reports_unused() {
+    let mut en = catalog(json!({
+        "index": { "welcome": "Welcome!", "old-key": "Old" },
+        "shared": { "buy-now": "Buy now" }
+    }));
+    let references = vec![
+        TranslationReference { page: "index".into(), key: "welcome".into(), source_text: "Hello".into(), file: "a.html".into() },
+        TranslationReference { page: "index".into(), key: "buy-now".into(), source_text: "Buy now".into(), file: "a.html".into() },
+        TranslationReference { page: "index".into(), key: "learn-more".into(), source_text: "Learn more".into(), file: "a.html".into() },
+    ];
+
+    let update = update_catalog(&mut en, &references);
+
+    assert_eq!(update.added, vec!["index.learn-more".to_string()]);
+    assert_eq!(update.unused, vec!["index.old-key".to_string()]); // shared.buy-now counts as used
+    assert_eq!(catalog_text(&en, "index", "learn-more"), Some("Learn more"));
+    assert_eq!(catalog_text(&en, "index", "welcome"), Some("Welcome!")); // existing text is never overwritten
+}
+
+#[test]
+fn test_catalogs_in_a_file_system() {
+    let files = MemoryFileSystem::new();
+    let page_text_dir = Path::new("edit-me/shared/page_text");
+    files.add_file(page_text_dir.join("jp.toml"), "[index]\ntitle = \"ようこそ\"\n");
+
+    // A catalog is found in whichever format it is written, and a new language gets a json one
+    assert_eq!(catalog_path(&files, page_text_dir, "jp"), page_text_dir.join("jp.toml"));
+    assert_eq!(catalog_path(&files, page_text_dir, "ie"), page_text_dir.join("ie.json"));
+
+    let mut jp = read_catalog(&files, &page_text_dir.join("jp.toml")).unwrap();
+    set_catalog_text(&mut jp, "index", "buy-now", "今すぐ購入");
+    write_catalog(&files, &page_text_dir.join("jp.toml"), &jp).unwrap();
+    let written = read_catalog(&files, &page_text_dir.join("jp.toml")).unwrap();
+    assert_eq!(catalog_text(&written, "index", "title"), Some("ようこそ"));
+    assert_eq!(catalog_text(&written, "index", "buy-now"), Some("今すぐ購入"));
+    assert!(read_catalog(&files, &page_text_dir.join("ie.json")).unwrap().is_empty());
+}
+
+// ===================================================================
+// # === Tests for stale detection and PO / XLIFF round trips ===
+// ===================================================================
+
+#[test]
+fn test_stale_translations() {
+    let en = catalog(json!({ "index": { "learn-more": "Learn more about it", "buy-now": "Buy now", "new": "New" } }));
+    let jp = catalog(json!({ "index": { "learn-more": "詳しく見る", "buy-now": "今すぐ購入" } }));
+    let record = catalog(json!({ "index": { "learn-more": "Learn more", "buy-now": "Buy now" } }));
+
+    assert_eq!(find_stale_translations(&en, &jp, &record), vec!["index.learn-more".to_string()]);
+
+    let units = build_translation_units(&en, &jp, &record);
+    assert_eq!(units.len(), 3);
+    assert_eq!(units[0].stale_source.as_deref(), Some("Learn more"));
+    assert_eq!(units[2].target, ""); // "new" has no translation yet
+}
+
+#[test]
+fn test_po_round_trip() {
+    let units = vec![
+        TranslationUnit { context: "index.desc".into(), source: "● Stiff sole\n● \"buffalo\" leather".into(), target: "● 硬いソール\n● バッファロー".into(), stale_source: None },
+        TranslationUnit { context: "index.learn-more".into(), source: "Learn more about it".into(), target: "詳しく見る".into(), stale_source: Some("Learn more".into()) },
+        TranslationUnit { context: "index.new".into(), source: "New".into(), target: "".into(), stale_source: None },
+    ];
+
+    let po = write_po(&units, "en", "jp");
+    assert!(po.contains("#, fuzzy\n#| msgid \"Learn more\"\nmsgctxt \"index.learn-more\""));
+
+    let imported = parse_po(&po).unwrap();
+    assert_eq!(imported.language_code.as_deref(), Some("jp"));
+    assert_eq!(imported.units.len(), 3);
+    assert_eq!(imported.units[0], ImportedUnit { context: "index.desc".into(), source: units[0].source.clone(), target: units[0].target.clone(), needs_review: false });
+    assert!(imported.units[1].needs_review);
+    assert!(!imported.units[2].needs_review);
+}
+
+#[test]
+fn test_xliff_round_trip() {
+    let units = vec![
+        TranslationUnit { context: "index.a&b".into(), source: "Fish & <chips>".into(), target: "魚".into(), stale_source: None },
+        TranslationUnit { context: "index.learn-more".into(), source: "Learn more about it".into(), target: "詳しく見る".into(), stale_source: Some("Learn more".into()) },
+    ];
+
+    let xliff = write_xliff(&units, "en", "jp");
+    let imported = parse_xliff(&xliff).unwrap();
+
+    assert_eq!(imported.language_code.as_deref(), Some("jp"));
+    assert_eq!(imported.units[0], ImportedUnit { context: "index.a&b".into(), source: "Fish & <chips>".into(), target: "魚".into(), needs_review: false });
+    assert!(imported.units[1].needs_review);
+}
+
+#[test]
+fn test_apply_imported_units_records_source_text() {
+    let en = catalog(json!({ "index": { "learn-more": "Learn more about it", "buy-now": "Buy now" } }));
+    let mut jp = Catalog::new();
+    let mut record = Catalog::new();
+    let units = vec![
+        ImportedUnit { context: "index.buy-now".into(), source: "Buy now".into(), target: "今すぐ購入".into(), needs_review: false },
+        ImportedUnit { context: "index.learn-more".into(), source: "Learn more".into(), target: "詳しく見る".into(), needs_review: false },
+        ImportedUnit { context: "index.gone".into(), source: "Gone".into(), target: "消えた".into(), needs_review: false },
+    ];
+
+    let summary = apply_imported_units(&mut jp, &mut record, &en, &units);
+
+    assert_eq!(summary.imported, 2);
+    assert_eq!(summary.outdated, vec!["index.learn-more".to_string()]);
+    assert_eq!(summary.unknown_keys, vec!["index.gone".to_string()]);
+    assert_eq!(catalog_text(&jp, "index", "buy-now"), Some("今すぐ購入"));
+    // The translation of the old english text is kept, but shows up as stale
+    assert_eq!(find_stale_translations(&en, &jp, &record), vec!["index.learn-more".to_string()]);
+}
+
+// ===================================================================
+// # === Tests for the i18n command (extract / export / import) ===
+// ===================================================================
+
+#[test]
+fn test_extract_export_import() {
+    let tmp = tempdir().unwrap();
+    let config = SiteConfig::default().with_root(tmp.path());
+    let page_text_dir = config.paths.page_text_dir();
+    fs::create_dir_all(config.paths.source_dir.join("pages/index")).unwrap();
+    fs::create_dir_all(&page_text_dir).unwrap();
+    fs::write(config.paths.source_dir.join("pages/index/index.html"), r#"<body data-page="index"><p add-text-section="buy-now">Buy now</p></body>"#).unwrap();
+    fs::write(page_text_dir.join("en.json"), r#"{ "index": { "old": "Old" } }"#).unwrap();
+    fs::write(page_text_dir.join("jp.json"), "{}").unwrap();
+
+    // extract: the new key is added to en.json, and jp is missing both
+    let extracted = extract_translations(&DiskFileSystem, &config).unwrap();
+    assert_eq!(extracted.added, vec!["index.buy-now".to_string()]);
+    assert_eq!(extracted.unused, vec!["index.old".to_string()]);
+    assert_eq!(extracted.languages, vec![LanguageStatus { language_code: "jp".to_string(), missing: 2, stale: Vec::new() }]);
+    assert_eq!(catalog_text(&read_catalog(&DiskFileSystem, &page_text_dir.join("en.json")).unwrap(), "index", "buy-now"), Some("Buy now"));
+
+    // export: page_text/jp.po, for the translator
+    let exported = export_translations(&DiskFileSystem, &config, "jp", ExchangeFormat::Po, None).unwrap();
+    assert_eq!(exported, ExportReport { path: page_text_dir.join("jp.po"), keys: 2, missing: 2, stale: 0 });
+
+    // import: the translated file goes back into jp.json (and never into the default language)
+    let translated = fs::read_to_string(&exported.path).unwrap().replacen("msgid \"Buy now\"\nmsgstr \"\"", "msgid \"Buy now\"\nmsgstr \"今すぐ購入\"", 1);
+    fs::write(&exported.path, translated).unwrap();
+    let imported = import_translations(&DiskFileSystem, &config, &exported.path, None).unwrap();
+    assert_eq!(imported.language_code, "jp");
+    assert_eq!(imported.summary.imported, 1);
+    assert_eq!(catalog_text(&read_catalog(&DiskFileSystem, &imported.catalog_path).unwrap(), "index", "buy-now"), Some("今すぐ購入"));
+    assert!(import_translations(&DiskFileSystem, &config, &exported.path, Some("en")).is_err());
+}
@@ -0,0 +1,119 @@
+use websiteCompiler::images::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+fn small_settings() -> ImageSettings {
+    ImageSettings { widths: vec![16, 32, 64], formats: vec![VariantFormat::Avif, VariantFormat::Webp], quality: 50, default_sizes: "100vw".to_string(), read_only_cache: false }
+}
+
+fn write_test_png(path: &Path, width: u32, height: u32) {
+    let image = image::RgbImage::from_fn(width, height, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]));
+    image.save(path).unwrap();
+}
+
+// ===================================================================
+// # === Tests for `process_image` ===
+// ===================================================================
+
+#[test]
+fn test_process_image_generates_variants_and_uses_cache() {
+    let tmp = tempdir().unwrap();
+    let image_path = tmp.path().join("boot.png");
+    let cache_dir = tmp.path().join(".image-cache");
+    write_test_png(&image_path, 48, 24);
+
+    let (image, encoded) = process_image(&DiskFileSystem, &image_path, &cache_dir, &small_settings()).unwrap();
+    assert_eq!((image.width, image.height), (48, 24));
+    assert_eq!(encoded, 6); // 16, 32 and the original 48 wide, in both formats
+    assert_eq!(image.variants[0], ImageVariant { path: tmp.path().join("boot-16w.avif"), width: 16, format: VariantFormat::Avif });
+    assert_eq!(image::image_dimensions(tmp.path().join("boot-32w.webp")).unwrap(), (32, 16));
+
+    // The second time around, nothing is encoded, and deleted variants are restored from the cache
+    fs::remove_file(tmp.path().join("boot-48w.webp")).unwrap();
+    let (_, encoded) = process_image(&DiskFileSystem, &image_path, &cache_dir, &small_settings()).unwrap();
+    assert_eq!(encoded, 0);
+    assert!(tmp.path().join("boot-48w.webp").is_file());
+
+    // Changing the image changes its hash, so it is encoded again
+    write_test_png(&image_path, 40, 24);
+    let (_, encoded) = process_image(&DiskFileSystem, &image_path, &cache_dir, &small_settings()).unwrap();
+    assert_eq!(encoded, 6);
+}
+
+#[test]
+fn test_process_image_with_read_only_cache() {
+    let tmp = tempdir().unwrap();
+    let image_path = tmp.path().join("boot.png");
+    let cache_dir = tmp.path().join(".image-cache");
+    write_test_png(&image_path, 48, 24);
+    let read_only = ImageSettings { read_only_cache: true, ..small_settings() };
+
+    // The variants are encoded straight into the website, and the cache isn't made
+    let (image, encoded) = process_image(&DiskFileSystem, &image_path, &cache_dir, &read_only).unwrap();
+    assert_eq!(encoded, 6);
+    assert!(image.variants.iter().all(|variant| variant.path.is_file()));
+    assert!(!cache_dir.exists());
+
+    // (but the variants already in it are used)
+    process_image(&DiskFileSystem, &image_path, &cache_dir, &small_settings()).unwrap();
+    let (_, encoded) = process_image(&DiskFileSystem, &image_path, &cache_dir, &read_only).unwrap();
+    assert_eq!(encoded, 0);
+}
+
+// ===================================================================
+// # === Tests for rewriting <img> tags ===
+// ===================================================================
+
+#[test]
+fn test_rewrite_img_tags() {
+    let image = ProcessedImage {
+        width: 1200,
+        height: 900,
+        variants: vec![
+            ImageVariant { path: PathBuf::from("x"), width: 480, format: VariantFormat::Avif },
+            ImageVariant { path: PathBuf::from("x"), width: 1200, format: VariantFormat::Avif },
+            ImageVariant { path: PathBuf::from("x"), width: 480, format: VariantFormat::Webp },
+        ],
+    };
+    let html = r#"<div><img class="item-images" src="../shared/boot.jpg" alt="Boot" loading="lazy">
+<img src="https://example.com/a.jpg"><picture><img src="../shared/boot.jpg"></picture><img src="../shared/boot.jpg" srcset="a.jpg 1x"></div>"#;
+
+    let resolve = |src: &str| if src == "../shared/boot.jpg" { Some(image.clone()) } else { None };
+    let rewritten = rewrite_img_tags(html, resolve, "100vw");
+
+    assert_eq!(rewritten, r#"<div><picture><source type="image/avif" srcset="../shared/boot-480w.avif 480w, ../shared/boot-1200w.avif 1200w" sizes="100vw"><source type="image/webp" srcset="../shared/boot-480w.webp 480w" sizes="100vw"><img class="item-images" src="../shared/boot.jpg" alt="Boot" loading="lazy" width="1200" height="900"></picture>
+<img src="https://example.com/a.jpg"><picture><img src="../shared/boot.jpg"></picture><img src="../shared/boot.jpg" srcset="a.jpg 1x"></div>"#);
+
+    // Running it again changes nothing
+    assert_eq!(rewrite_img_tags(&rewritten, resolve, "100vw"), rewritten);
+}
+
+#[test]
+fn test_make_images_responsive() {
+    let tmp = tempdir().unwrap();
+    let site = tmp.path().join("site");
+    fs::create_dir_all(site.join("shared/images")).unwrap();
+    fs::create_dir_all(site.join("blog")).unwrap();
+    write_test_png(&site.join("shared/images/boot.png"), 48, 24);
+    fs::write(site.join("blog/post.html"), r#"<img src="../shared/images/boot.png?v=1" sizes="50vw"><img src="{img-file}"><img src="../shared/images/missing.png">"#).unwrap();
+
+    let summary = make_images_responsive(&DiskFileSystem, &site, &tmp.path().join("cache"), &small_settings()).unwrap();
+
+    assert_eq!(summary.images_processed, 1);
+    assert_eq!(summary.html_files_rewritten, 1);
+    assert!(summary.failures.is_empty());
+    let html = fs::read_to_string(site.join("blog/post.html")).unwrap();
+    assert!(html.contains(r#"srcset="../shared/images/boot-16w.webp 16w, ../shared/images/boot-32w.webp 32w, ../shared/images/boot-48w.webp 48w" sizes="50vw""#), "{}", html);
+    assert!(html.contains(r#"width="48" height="24""#), "{}", html);
+    assert!(html.ends_with(r#"<img src="{img-file}"><img src="../shared/images/missing.png">"#));
+}
+
+#[test]
+fn test_resolve_image_source() {
+    assert_eq!(resolve_image_source(Path::new("site/blog/post.html"), "../shared/boot.jpg#top"), PathBuf::from("site/shared/boot.jpg"));
+    assert_eq!(resolve_image_source(Path::new("site/index.html"), "./shared/boot.jpg"), PathBuf::from("site/shared/boot.jpg"));
+    assert!(!is_local_image_source("//cdn.example.com/a.jpg"));
+    assert!(!is_local_image_source("data:image/png;base64,AAAA"));
+}
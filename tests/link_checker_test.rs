@@ -0,0 +1,99 @@
+use websiteCompiler::config::SiteConfig;
+use websiteCompiler::link_checker::*;
+use websiteCompiler::vfs::MemoryFileSystem;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A built website in memory, and where its pages came from.
+fn make_output() -> (MemoryFileSystem, BTreeMap<PathBuf, PathBuf>) {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("out/index.html", concat!(
+        "<link rel=\"stylesheet\" href=\"index.3f9a1c2b.css\">",
+        "<a href=\"about\">About</a>",                            // (GitHub Pages serves about.html at /about)
+        "<a href=\"blog/\">Blog</a>",
+        "<a href=\"about.html#team\">Team</a>",
+        "<a href=\"#top\">Top</a><a href=\"#intro\">Intro</a><h2 id=\"intro\">Hi</h2>",
+        "<img src=\"shared/images/my%20photo.png\" srcset=\"shared/images/my%20photo-480w.png 480w\">",
+        "<a href=\"https://example.com/missing.html\">Elsewhere</a><a href=\"mailto:a@example.com\">Mail</a><a href=\"{link-path}\">Unfilled</a>",
+        "<!-- <a href=\"old-page.html\">Old</a> -->",
+    ));
+    fs.add_file("out/about.html", "<section id='team'></section><a href=\"/repo/blog/sleep.html\">Sleep</a>");
+    fs.add_file("out/blog/index.html", "<a href=\"sleep.html#tips\">Sleep</a><a href=\"../../outside.html\">Outside</a>");
+    fs.add_file("out/blog/sleep.html", "<img src=\"../shared/images/boot.jpg\"><a href=\"https://user.github.io/repo/about.html\">About</a>");
+    fs.add_file("out/index.3f9a1c2b.css", "body { background: url(shared/images/bg.png); filter: url(#blur); }");
+    fs.add_file("out/shared/images/my photo.png", "png");
+    fs.add_file("out/shared/images/my photo-480w.png", "png");
+
+    let published = [("index.html", "pages/index/index.html"), ("index.css", "pages/index/index.scss"), ("blog/sleep.html", "pages/all-blogs/blog/sleep.html")]
+        .into_iter()
+        .map(|(output, source)| (PathBuf::from(output), Path::new("edit-me").join(source)))
+        .collect();
+    (fs, published)
+}
+
+// ===================================================================
+// # === Tests for `root_urls` ===
+// ===================================================================
+
+#[test]
+fn test_root_urls() {
+    let mut config = SiteConfig::default();
+    assert_eq!(root_urls(&config), vec!["/"]);
+    config.site.base_url = Some("https://user.github.io/repo/".to_string());
+    // (with a base path, "/" is the domain's root rather than the website's)
+    assert_eq!(root_urls(&config), vec!["https://user.github.io/repo/", "/repo/"]);
+    config.site.base_url = Some("https://example.com".to_string());
+    assert_eq!(root_urls(&config), vec!["https://example.com/", "/"]);
+}
+
+// ===================================================================
+// # === Tests for `check_links` ===
+// ===================================================================
+
+#[test]
+fn test_check_links() {
+    let (fs, published) = make_output();
+    let root_urls = vec!["https://user.github.io/repo/".to_string(), "/repo/".to_string()];
+    let summary = check_links(&fs, Path::new("out"), &published, &root_urls).unwrap();
+    assert_eq!(summary.files_checked, 5);
+
+    let broken: Vec<(&str, &str, &str)> = summary.broken.iter().map(|link| (link.file.to_str().unwrap(), link.link.as_str(), link.problem.as_str())).collect();
+    assert_eq!(broken, vec![
+        ("blog/index.html", "../../outside.html", "it goes above the website's root"),
+        ("blog/index.html", "sleep.html#tips", "'blog/sleep.html' has nothing with the id 'tips'"),
+        ("blog/sleep.html", "../shared/images/boot.jpg", "there is no 'shared/images/boot.jpg'"),
+        ("index.3f9a1c2b.css", "shared/images/bg.png", "there is no 'shared/images/bg.png'"),
+    ]);
+
+    // Each is reported with the edit-me file it comes from (an asset's hash is ignored)
+    assert_eq!(summary.broken[0].source, None);
+    assert_eq!(summary.broken[2].source.as_deref(), Some(Path::new("edit-me/pages/all-blogs/blog/sleep.html")));
+    assert_eq!(summary.broken[3].source.as_deref(), Some(Path::new("edit-me/pages/index/index.scss")));
+    assert_eq!(summary.broken[2].to_string(), "Broken link '../shared/images/boot.jpg' in 'edit-me/pages/all-blogs/blog/sleep.html' (published as 'blog/sleep.html'): there is no 'shared/images/boot.jpg'");
+}
+
+#[test]
+fn test_check_links_without_base_path() {
+    // A link with a base path the website doesn't have is broken
+    let (fs, published) = make_output();
+    let summary = check_links(&fs, Path::new("out"), &published, &["/".to_string()]).unwrap();
+    assert!(summary.broken.iter().any(|link| link.link == "/repo/blog/sleep.html" && link.problem == "there is no 'repo/blog/sleep.html'"));
+    // (and a url of the website is another website's)
+    assert!(!summary.broken.iter().any(|link| link.link.starts_with("https://")));
+}
+
+#[test]
+fn test_check_links_outside_base_path() {
+    // A link from the domain's root, without the base path, goes to another website on the same domain
+    let fs = MemoryFileSystem::new();
+    fs.add_file("out/index.html", "<a href=\"/about.html\">About</a><a href=\"/repo/about.html\">About</a>");
+    fs.add_file("out/about.html", "<a href=\"/\">Home</a>");
+    let root_urls = vec!["https://user.github.io/repo/".to_string(), "/repo/".to_string()];
+    let summary = check_links(&fs, Path::new("out"), &BTreeMap::new(), &root_urls).unwrap();
+
+    let broken: Vec<(&str, &str, &str)> = summary.broken.iter().map(|link| (link.file.to_str().unwrap(), link.link.as_str(), link.problem.as_str())).collect();
+    assert_eq!(broken, vec![
+        ("about.html", "/", "it is outside the website, which is at '/repo/'"),
+        ("index.html", "/about.html", "it is outside the website, which is at '/repo/'"),
+    ]);
+}
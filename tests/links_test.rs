@@ -0,0 +1,103 @@
+use websiteCompiler::config::{LinkSetting, SiteConfig};
+use websiteCompiler::links::*;
+use websiteCompiler::vfs::{FileSystem, MemoryFileSystem};
+use std::path::Path;
+
+// ===================================================================
+// # === Tests for `LinkMode` ===
+// ===================================================================
+
+#[test]
+fn test_link_mode_from_setting() {
+    let mut config = SiteConfig::default();
+    assert_eq!(LinkMode::from_setting(None, &config), LinkMode::Relative);
+    assert_eq!(LinkMode::from_setting(Some(LinkSetting::Absolute), &config), LinkMode::Absolute { base_path: String::new() });
+
+    // (the base path, and the url, come from the site's address)
+    config.site.base_url = Some("https://user.github.io/repo/".to_string());
+    assert_eq!(LinkMode::from_setting(Some(LinkSetting::Absolute), &config).root_url().unwrap(), "/repo/");
+    assert_eq!(LinkMode::from_setting(Some(LinkSetting::Url), &config).root_url().unwrap(), "https://user.github.io/repo/");
+}
+
+#[test]
+fn test_root_prefix() {
+    let output_dir = Path::new("build/site");
+    assert_eq!(LinkMode::Relative.root_prefix(Path::new("build/site/index.html"), output_dir), "");
+    assert_eq!(LinkMode::Relative.root_prefix(Path::new("build/site/blog/jp-sleep.html"), output_dir), "../");
+    assert_eq!(LinkMode::Relative.root_prefix(Path::new("build/site/blog/social/ugly-people/index.html"), output_dir), "../../../");
+    assert_eq!(LinkMode::Absolute { base_path: String::new() }.root_prefix(Path::new("build/site/blog/sleep.html"), output_dir), "/");
+    assert_eq!(LinkMode::Url { base_url: "https://example.com".to_string() }.root_prefix(Path::new("build/site/blog/sleep.html"), output_dir), "https://example.com/");
+}
+
+// ===================================================================
+// # === Tests for the links of html / css ===
+// ===================================================================
+
+#[test]
+fn test_is_relative_link() {
+    for link in ["about.html", "../shared/global.css?v=2", "images/a.png#x", "./"] {
+        assert!(is_relative_link(link), "{}", link);
+    }
+    for link in ["", "#top", "/about", "//cdn.example.com/a.js", "https://example.com", "mailto:a@example.com", "data:image/png;base64,AA", "{link-path}", "<root>/a.css"] {
+        assert!(!is_relative_link(link), "{}", link);
+    }
+}
+
+#[test]
+fn test_resolve_link_in_website() {
+    assert_eq!(resolve_link_in_website(Path::new("blog"), "../about.css?v=2").unwrap(), "about.css?v=2");
+    assert_eq!(resolve_link_in_website(Path::new("blog/social"), "./#top").unwrap(), "blog/social/#top");
+    assert_eq!(resolve_link_in_website(Path::new(""), "./").unwrap(), "");
+    assert!(resolve_link_in_website(Path::new("blog"), "../../outside.png").is_none());
+}
+
+#[test]
+fn test_absolute_links_in_html() {
+    let html = concat!(
+        "<link rel=\"stylesheet\" href=\"../shared/global.css\">",
+        "<img src='images/a.png' srcset=\"images/a-480.png 480w,images/a-960.png 960w, /b.png 2x\">",
+        "<video poster=cover.jpg></video>",
+        "<form action=\"send\"></form>",
+        "<a href=\"#top\">Top</a><a href=\"mailto:a@example.com\">Mail</a><a href=\"/repo/about\">About</a>",
+    );
+    assert_eq!(absolute_links_in_html(html, Path::new("blog/post.html"), "/repo/"), concat!(
+        "<link rel=\"stylesheet\" href=\"/repo/shared/global.css\">",
+        "<img src='/repo/blog/images/a.png' srcset=\"/repo/blog/images/a-480.png 480w,/repo/blog/images/a-960.png 960w, /b.png 2x\">",
+        "<video poster=/repo/blog/cover.jpg></video>",
+        "<form action=\"/repo/blog/send\"></form>",
+        "<a href=\"#top\">Top</a><a href=\"mailto:a@example.com\">Mail</a><a href=\"/repo/about\">About</a>",
+    ));
+}
+
+#[test]
+fn test_absolute_urls_in_css() {
+    let css = ".a { background: url(\"images/a.png\"); } .b { background: url(../fonts/b.woff2) } .c { background: url(data:image/png;base64,AA); }";
+    assert_eq!(absolute_urls_in_css(css, Path::new("shared/global.css"), "https://example.com/"),
+        ".a { background: url(\"https://example.com/shared/images/a.png\"); } .b { background: url(https://example.com/fonts/b.woff2) } .c { background: url(data:image/png;base64,AA); }");
+}
+
+// ===================================================================
+// # === Tests for `apply_link_mode` ===
+// ===================================================================
+
+#[test]
+fn test_apply_link_mode() {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("out/index.html", "<a href=\"blog/sleep.html\">Sleep</a>");
+    fs.add_file("out/blog/sleep.html", "<a href=\"../index.html\">Home</a>");
+    fs.add_file("out/index.css", "body { background: url(images/bg.png); }");
+    fs.add_file("out/index.js", "const page = \"blog/sleep.html\";");
+
+    // Relative links are left as they are
+    assert_eq!(apply_link_mode(&fs, Path::new("out"), &LinkMode::Relative).unwrap(), 0);
+
+    let mode = LinkMode::Absolute { base_path: "/repo".to_string() };
+    assert_eq!(apply_link_mode(&fs, Path::new("out"), &mode).unwrap(), 3);
+    assert_eq!(fs.read_to_string(Path::new("out/blog/sleep.html")).unwrap(), "<a href=\"/repo/index.html\">Home</a>");
+    assert_eq!(fs.read_to_string(Path::new("out/index.css")).unwrap(), "body { background: url(/repo/images/bg.png); }");
+    // (only html and css: the links in a script are relative to the page running it, so it can't be changed afterwards)
+    assert_eq!(fs.read_to_string(Path::new("out/index.js")).unwrap(), "const page = \"blog/sleep.html\";");
+
+    // Doing it again (an incremental build starts from the last one) changes nothing
+    assert_eq!(apply_link_mode(&fs, Path::new("out"), &mode).unwrap(), 0);
+}
@@ -0,0 +1,106 @@
+use websiteCompiler::localization::*;
+use websiteCompiler::vfs::MemoryFileSystem;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+fn languages() -> Vec<String> {
+    vec!["en".to_string(), "ie".to_string(), "jp".to_string()]
+}
+
+// ===================================================================
+// # === Tests for pairing localized sibling files ===
+// ===================================================================
+
+#[test]
+fn test_localized_variant_language() {
+    let languages = languages();
+    assert_eq!(localized_variant_language(Path::new("blog/ugly-people.jp.html"), &languages), Some("jp".to_string()));
+    assert_eq!(localized_variant_language(Path::new("blog/fun-days.ie.html"), &languages), Some("ie".to_string()));
+    assert_eq!(localized_variant_language(Path::new("blog/notes.jp.md"), &languages), Some("jp".to_string()));
+
+    // Not a variant: default file, unknown language, non-localizable type, or a name that just contains dots
+    assert_eq!(localized_variant_language(Path::new("blog/ugly-people.html"), &languages), None);
+    assert_eq!(localized_variant_language(Path::new("blog/ugly-people.fr.html"), &languages), None);
+    assert_eq!(localized_variant_language(Path::new("shared/global.jp.scss"), &languages), None);
+    assert_eq!(localized_variant_language(Path::new("blog/i.e..html"), &languages), None);
+}
+
+#[test]
+fn test_default_and_variant_paths() {
+    assert_eq!(default_language_path(Path::new("blog/ugly-people.jp.html")), PathBuf::from("blog/ugly-people.html"));
+    assert_eq!(variant_path_for_language(Path::new("blog/ugly-people.html"), "jp"), PathBuf::from("blog/ugly-people.jp.html"));
+    assert_eq!(language_output_path(Path::new("out/blog/ugly-people.html"), "jp"), PathBuf::from("out/blog/jp-ugly-people.html"));
+}
+
+#[test]
+fn test_find_variants_and_expand_groups() {
+    let fs = MemoryFileSystem::new();
+    let default_file = PathBuf::from("blog/ugly-people.html");
+    let jp_file = PathBuf::from("blog/ugly-people.jp.html");
+    let lonely_file = PathBuf::from("blog/fun-days.html");
+    fs.add_file(&default_file, "hello");
+    fs.add_file(&jp_file, "konnichiwa");
+    fs.add_file(&lonely_file, "no translations");
+    fs.add_file("blog/notes.md", "notes");
+    fs.add_file("blog/notes.ie.md", "notes");
+
+    let variants = find_localized_variants(&fs, &default_file, &languages());
+    assert_eq!(variants.len(), 1);
+    assert_eq!(variants.get("jp"), Some(&jp_file));
+    assert_eq!(find_localized_variants(&fs, Path::new("blog/notes.md"), &languages()).get("ie"), Some(&PathBuf::from("blog/notes.ie.md")));
+
+    // Only the japanese version was modified, but the default file needs relinking too
+    let modified: HashSet<String> = ["blog/ugly-people.jp.html", "blog/fun-days.html"].into_iter().map(String::from).collect();
+    let expanded = expand_to_localized_groups(&fs, &modified, &languages());
+    assert_eq!(expanded.len(), 3);
+    assert!(expanded.contains("blog/ugly-people.html"));
+}
+
+// ===================================================================
+// # === Tests for linking the language versions ===
+// ===================================================================
+
+#[test]
+fn test_merge_shared_metadata() {
+    let default_content = "---\ntitle: Ugly people\ntags: social\n---\nbeing ugly...\n";
+    let variant_content = "---\ntitle: 醜い人々\n---\n醜いこと...\n";
+    let alternates = alternate_language_links("ugly-people.md", &["en".to_string(), "jp".to_string()]);
+
+    let merged = merge_shared_metadata(default_content, variant_content, "jp", &alternates);
+    let (metadata, body) = split_front_matter(&merged);
+
+    assert_eq!(body, "醜いこと...\n");
+    assert!(metadata.contains(&("title".to_string(), "醜い人々".to_string())));
+    assert!(metadata.contains(&("tags".to_string(), "social".to_string()))); // shared from the default version
+    assert!(metadata.contains(&("lang".to_string(), "jp".to_string())));
+    assert!(metadata.contains(&("alternates".to_string(), "x-default=ugly-people.md, en=en-ugly-people.md, jp=jp-ugly-people.md".to_string())));
+}
+
+#[test]
+fn test_split_front_matter_ignores_unclosed_rule() {
+    let content = "---\njust a horizontal rule\n";
+    let (metadata, body) = split_front_matter(content);
+    assert!(metadata.is_empty());
+    assert_eq!(body, content);
+
+    // (and a variant without front matter still gets the shared metadata)
+    let merged = merge_shared_metadata("---\ntags: social\n---\nhi\n", "konnichiwa\n", "jp", &[]);
+    assert_eq!(merged, "---\ntags: social\nlang: jp\nalternates: \n---\nkonnichiwa\n");
+}
+
+#[test]
+fn test_html_language_and_alternate_links() {
+    let html = "<html lang=\"en\">\n<head><title>x</title></head><body></body></html>";
+    let alternates = alternate_language_links("fun-days.html", &["jp".to_string()]);
+
+    let localized = add_alternate_language_links(&set_html_language(html, "jp"), &alternates);
+    assert!(localized.starts_with("<html lang=\"jp\">"));
+    assert!(localized.contains("<link rel=\"alternate\" hreflang=\"x-default\" href=\"fun-days.html\">\n"));
+    assert!(localized.contains("<link rel=\"alternate\" hreflang=\"jp\" href=\"jp-fun-days.html\">\n</head>"));
+
+    // Running it twice does not duplicate the links
+    assert_eq!(add_alternate_language_links(&localized, &alternates), localized);
+
+    // A tag without a lang attribute gets one
+    assert_eq!(set_html_language("<html><body></body></html>", "ie"), "<html lang=\"ie\"><body></body></html>");
+}
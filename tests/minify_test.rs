@@ -0,0 +1,74 @@
+use websiteCompiler::minify::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for minifying html / css / js ===
+// ===================================================================
+
+#[test]
+fn test_minify_html_keeps_pre_and_textarea() {
+    let html = r#"<!DOCTYPE html>
+<html>
+<head>
+    <!--startofblogtemplatetop-->
+    <title>  Boots  </title>
+    <style>
+        .a  {  color : red ; }
+    </style>
+</head>
+<body>
+    <div class="x">
+        Hello    there
+    </div>
+    <pre>  keep
+    this  </pre>
+    <textarea>  and
+  this</textarea>
+</body>
+</html>
+"#;
+    let minified = minify_html(html);
+
+    assert!(!minified.contains("startofblogtemplatetop"), "{}", minified);
+    assert!(minified.contains("<pre>  keep\n    this  </pre>"), "{}", minified);
+    assert!(minified.contains("<textarea>  and\n  this</textarea>"), "{}", minified);
+    assert!(minified.contains(".a{color:red}"), "{}", minified);
+    assert!(minified.contains("Hello there"), "{}", minified);
+    assert!(minified.len() < html.len());
+}
+
+#[test]
+fn test_minify_css_and_js() {
+    assert_eq!(minify_css("/* comment */\n.top-bar {\n    color: #ff0000;\n    margin: 0px 0px;\n}\n").unwrap(), ".top-bar{color:red;margin:0}");
+    assert_eq!(minify_css(".a { color: red; ").unwrap(), ".a{color:red}"); // an unclosed block is valid css
+
+    // Nothing is renamed / rewritten, and line breaks stay (automatic semicolon insertion relies on them)
+    let js = "document.addEventListener(\"DOMContentLoaded\", setDynamicElementsContent);\n\nfunction setDynamicElementsContent() {\n    // comment\n    let a = 1\n    let b = a\n    return a + b;\n}\n";
+    assert_eq!(minify_js(js).unwrap(), "document.addEventListener(\"DOMContentLoaded\", setDynamicElementsContent);\nfunction setDynamicElementsContent() {\nlet a = 1\nlet b = a\nreturn a + b;\n}");
+
+    // Strings, template literals and regular expressions are copied exactly
+    let js = "const s = '  // not a comment  ';\nconst t = `line one\n    ${ {a: `  ${x}  `}.a }  /* kept */`;\nconst r = /\\/\\/[/]  x/g.test(s) / 2;";
+    assert_eq!(minify_js(js).unwrap(), js);
+
+    assert!(minify_js("const s = 'unterminated").is_err());
+}
+
+#[test]
+fn test_minify_directory() {
+    let tmp = tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("shared")).unwrap();
+    fs::write(tmp.path().join("index.html"), "<p>\n    hi\n</p>\n").unwrap();
+    fs::write(tmp.path().join("shared/global.css"), "body {\n    margin: 0;\n}\n").unwrap();
+    fs::write(tmp.path().join("shared/broken.js"), "let s = `unterminated").unwrap();
+    fs::write(tmp.path().join("shared/code.ts"), "let  a = 1;\n").unwrap();
+
+    let summary = minify_directory(&DiskFileSystem, tmp.path(), |_| false).unwrap();
+
+    assert_eq!(summary.files_minified, 2);
+    assert_eq!(summary.failures.len(), 1);
+    assert_eq!(fs::read_to_string(tmp.path().join("shared/global.css")).unwrap(), "body{margin:0}");
+    assert_eq!(fs::read_to_string(tmp.path().join("shared/broken.js")).unwrap(), "let s = `unterminated");
+    assert_eq!(fs::read_to_string(tmp.path().join("shared/code.ts")).unwrap(), "let  a = 1;\n");
+}
@@ -0,0 +1,319 @@
+use websiteCompiler::processors::*;
+use websiteCompiler::routing::Router;
+use websiteCompiler::scss::ScssCompiler;
+use websiteCompiler::translations::TranslationsFile;
+use websiteCompiler::typescript_modules::ModuleUsage;
+use websiteCompiler::vfs::{DiskFileSystem, FileSystem, MemoryFileSystem, SystemCommandRunner};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+/// Turns a csv file into an html table. (compiled, like scss -> css)
+struct CsvProcessor;
+
+impl Processor for CsvProcessor {
+    fn name(&self) -> &str { "csv" }
+    fn input_extensions(&self) -> &[&str] { &["csv"] }
+    fn output_extension(&self, _source_path: &Path, _context: &ProcessContext) -> Option<&str> { Some("html") }
+
+    // (the header row is marked while processing; the rest is done when compiling)
+    fn process(&self, _file: &SourceFile, document: &mut Document, _context: &ProcessContext) -> Result<()> {
+        document.content.insert(0, '*');
+        Ok(())
+    }
+
+    fn compile(&self, file: &SourceFile, _context: &ProcessContext) -> Result<()> {
+        let content = fs::read_to_string(&file.copy_path)?;
+        if content.contains("broken") {
+            return Err(anyhow!("can't read row 2"));
+        }
+        let rows: Vec<String> = content.lines().map(|line| match line.strip_prefix('*') {
+            Some(header) => format!("<tr><th>{}</th></tr>", header.replace(',', "</th><th>")),
+            None => format!("<tr><td>{}</td></tr>", line.replace(',', "</td><td>")),
+        }).collect();
+        Ok(fs::write(&file.output_path, format!("<table>{}</table>", rows.concat()))?)
+    }
+}
+
+/// Pastes in the text files named on `include:` lines.
+struct IncludeProcessor;
+
+impl Processor for IncludeProcessor {
+    fn name(&self) -> &str { "include" }
+    fn input_extensions(&self) -> &[&str] { &["txt"] }
+
+    fn dependencies(&self, source_path: &Path, context: &ProcessContext) -> Vec<PathBuf> {
+        fs::read_to_string(source_path).unwrap_or_default().lines()
+            .filter_map(|line| line.strip_prefix("include: "))
+            .map(|included| context.source_dir.join(included))
+            .collect()
+    }
+
+    fn process(&self, _file: &SourceFile, _document: &mut Document, _context: &ProcessContext) -> Result<()> {
+        Ok(())
+    }
+}
+
+// ===================================================================
+// # === Tests for `ProcessorRegistry` ===
+// ===================================================================
+
+#[test]
+fn test_custom_processor() {
+    let tmp = tempdir().unwrap();
+    let source_dir = tmp.path().join("edit-me");
+    let output_dir = tmp.path().join("out");
+    fs::create_dir_all(source_dir.join("pages/prices")).unwrap();
+    fs::write(source_dir.join("pages/prices/prices.csv"), "item,price\ntea,2\ncake,3\n").unwrap();
+    fs::write(source_dir.join("pages/prices/broken.csv"), "item,price\nbroken\n").unwrap();
+    fs::write(source_dir.join("pages/prices/notes.md"), "# Notes\n").unwrap();
+
+    let translations = TranslationsFile::new();
+    let module_usage = ModuleUsage::default();
+    let context = ProcessContext { source_dir: &source_dir, output_dir: &output_dir, translations: &translations, language_codes: &[], module_usage: &module_usage, fs: &DiskFileSystem, commands: &SystemCommandRunner, router: &Router::default() };
+    let mut processors = ProcessorRegistry::new();
+    processors.register(CsvProcessor);
+    assert_eq!(processors.names(), vec!["csv"]);
+    assert_eq!(processors.output_extension(Path::new("a.csv"), &context), Some("html"));
+    assert!(processors.processors_for(Path::new("a.md")).is_empty());
+
+    // Pages end up at the root, with the name of the file they are compiled to
+    let planned = processors.plan_file(&source_dir.join("pages/prices/prices.csv"), &context).unwrap();
+    assert_eq!(planned.copy_path, output_dir.join("prices.csv"));
+    assert_eq!(planned.output_path, output_dir.join("prices.html"));
+    assert!(planned.is_compiled());
+    assert!(!output_dir.exists());
+
+    // Processing runs the processors over the file + writes it; compiling is left for later
+    let files: Vec<SourceFile> = ["prices.csv", "broken.csv", "notes.md"].iter()
+        .map(|name| processors.process_file(&source_dir.join("pages/prices").join(name), &context).unwrap())
+        .collect();
+    assert_eq!(fs::read_to_string(output_dir.join("prices.csv")).unwrap(), "*item,price\ntea,2\ncake,3\n");
+    assert_eq!(fs::read_to_string(output_dir.join("notes.md")).unwrap(), "# Notes\n");
+    assert!(!files[2].is_compiled());
+    assert!(!output_dir.join("prices.html").exists());
+
+    let summaries = processors.compile_files(&files, &context);
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].processor, "csv");
+    assert_eq!(summaries[0].files_compiled, 1);
+    assert_eq!(summaries[0].failures, vec![(source_dir.join("pages/prices/broken.csv"), "can't read row 2".to_string())]);
+    assert_eq!(fs::read_to_string(output_dir.join("prices.html")).unwrap(), "<table><tr><th>item</th><th>price</th></tr><tr><td>tea</td><td>2</td></tr><tr><td>cake</td><td>3</td></tr></table>");
+}
+
+#[test]
+fn test_with_dependents() {
+    let tmp = tempdir().unwrap();
+    let source_dir = tmp.path().join("edit-me");
+    fs::create_dir_all(source_dir.join("shared")).unwrap();
+    fs::create_dir_all(source_dir.join("pages/index")).unwrap();
+    fs::write(source_dir.join("shared/global.scss"), "$main-colour: red;\n").unwrap();
+    fs::write(source_dir.join("pages/index/index.scss"), "@use \"<root>/shared/global.scss\" as *;\n").unwrap();
+    fs::write(source_dir.join("pages/index/other.scss"), "body { margin: 0; }\n").unwrap();
+    // a.txt <- b.txt <- c.txt
+    fs::write(source_dir.join("shared/a.txt"), "a\n").unwrap();
+    fs::write(source_dir.join("shared/b.txt"), "include: shared/a.txt\n").unwrap();
+    fs::write(source_dir.join("pages/index/c.txt"), "include: shared/b.txt\n").unwrap();
+
+    let translations = TranslationsFile::new();
+    let module_usage = ModuleUsage::default();
+    let context = ProcessContext { source_dir: &source_dir, output_dir: &tmp.path().join("out"), translations: &translations, language_codes: &[], module_usage: &module_usage, fs: &DiskFileSystem, commands: &SystemCommandRunner, router: &Router::default() };
+    let mut processors = ProcessorRegistry::new();
+    processors.register(ScssProcessor { import_start: "@use".to_string(), root_placeholder: "<root>".to_string(), compiler: ScssCompiler::BuiltIn, compressed: false, source_map: false });
+    processors.register(IncludeProcessor);
+
+    let path = |relative: &str| source_dir.join(relative).to_string_lossy().to_string();
+    let changed: HashSet<String> = [path("shared/global.scss"), path("shared/a.txt")].into_iter().collect();
+    let expected: HashSet<String> = ["shared/global.scss", "pages/index/index.scss", "shared/a.txt", "shared/b.txt", "pages/index/c.txt"].iter().map(|relative| path(relative)).collect();
+    assert_eq!(processors.with_dependents(&changed, &context), expected);
+
+    let changed: HashSet<String> = [path("pages/index/other.scss")].into_iter().collect();
+    assert_eq!(processors.with_dependents(&changed, &context), changed);
+}
+
+/// Makes a copy of each text file in every language, then fails on the ones that say so.
+struct LanguagesProcessor;
+
+impl Processor for LanguagesProcessor {
+    fn name(&self) -> &str { "languages" }
+    fn input_extensions(&self) -> &[&str] { &[] }
+
+    fn process(&self, file: &SourceFile, document: &mut Document, context: &ProcessContext) -> Result<()> {
+        for language_code in context.language_codes {
+            let path = file.copy_path.with_file_name(format!("{}-{}", language_code, file.copy_path.file_name().unwrap().to_string_lossy()));
+            document.extra_files.push((path, format!("({}) {}", language_code, document.content)));
+        }
+        if document.content.contains("fail") {
+            return Err(anyhow!("told to fail"));
+        }
+        document.content.push_str("done\n");
+        Ok(())
+    }
+}
+
+#[test]
+fn test_process_file_writes_once() {
+    let tmp = tempdir().unwrap();
+    let source_dir = tmp.path().join("edit-me");
+    let output_dir = tmp.path().join("out");
+    fs::create_dir_all(source_dir.join("shared")).unwrap();
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(source_dir.join("shared/ok.txt"), "ok\n").unwrap();
+    fs::write(source_dir.join("shared/fail.txt"), "fail\n").unwrap();
+    fs::write(source_dir.join("shared/icon.ico"), [0u8, 159, 146, 150]).unwrap();
+
+    let translations = TranslationsFile::new();
+    let module_usage = ModuleUsage::default();
+    let language_codes = vec!["jp".to_string()];
+    let context = ProcessContext { source_dir: &source_dir, output_dir: &output_dir, translations: &translations, language_codes: &language_codes, module_usage: &module_usage, fs: &DiskFileSystem, commands: &SystemCommandRunner, router: &Router::default() };
+    let mut processors = ProcessorRegistry::new();
+    processors.register(LanguagesProcessor);
+
+    processors.process_file(&source_dir.join("shared/ok.txt"), &context).unwrap();
+    assert_eq!(fs::read_to_string(output_dir.join("shared/ok.txt")).unwrap(), "ok\ndone\n");
+    assert_eq!(fs::read_to_string(output_dir.join("shared/jp-ok.txt")).unwrap(), "(jp) ok\n");
+
+    // A failed step leaves the previous build's file as it was, and writes none of the extra files
+    fs::write(output_dir.join("shared/fail.txt"), "previous build\n").unwrap();
+    let error = processors.process_file(&source_dir.join("shared/fail.txt"), &context).unwrap_err();
+    assert!(format!("{:#}", error).contains("The languages processor failed on"));
+    assert_eq!(fs::read_to_string(output_dir.join("shared/fail.txt")).unwrap(), "previous build\n");
+    assert!(!output_dir.join("shared/jp-fail.txt").exists());
+
+    // Files that aren't text are copied as they are
+    processors.process_file(&source_dir.join("shared/icon.ico"), &context).unwrap();
+    assert_eq!(fs::read(output_dir.join("shared/icon.ico")).unwrap(), vec![0u8, 159, 146, 150]);
+
+    // (no temporary files are left behind)
+    let mut written: Vec<String> = fs::read_dir(output_dir.join("shared")).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+    written.sort();
+    assert_eq!(written, vec!["fail.txt", "icon.ico", "jp-ok.txt", "ok.txt"]);
+}
+
+#[test]
+fn test_plan_outputs() {
+    let files = MemoryFileSystem::new();
+    files.add_file("edit-me/pages/index/index.html", "<p>Hi</p>");
+    files.add_file("edit-me/pages/all-blogs/blog/sleep.html", "<p>Sleep</p>");
+    files.add_file("edit-me/pages/all-blogs/blog/sleep.jp.html", "<p>睡眠</p>");
+    files.add_file("edit-me/pages/all-blogs/blog/notes.md", "# Notes");
+    files.add_file("edit-me/shared/prices.csv", "name,price");
+
+    let translations: TranslationsFile = ["en", "jp"].iter().map(|code| (code.to_string(), HashMap::new())).collect();
+    let module_usage = ModuleUsage::default();
+    let language_codes = vec!["en".to_string(), "jp".to_string()];
+    let (source_dir, output_dir) = (Path::new("edit-me"), Path::new("out"));
+    let context = ProcessContext { source_dir, output_dir, translations: &translations, language_codes: &language_codes, module_usage: &module_usage, fs: &files, commands: &SystemCommandRunner, router: &Router::default() };
+    let mut processors = ProcessorRegistry::new();
+    processors.register(CsvProcessor);
+    processors.register(LocalizationProcessor { default_language: "en".to_string() });
+    processors.register(I18nProcessor);
+
+    // Every file a build would write, including the language versions and the compiled files
+    let published = processors.plan_outputs(&files.walk_files(source_dir), &context).unwrap();
+    let paths: Vec<&str> = published.keys().map(|path| path.to_str().unwrap()).collect();
+    assert_eq!(paths, vec!["blog/en-sleep.html", "blog/jp-sleep.html", "blog/notes.md", "blog/sleep.html", "en-index.html", "index.html", "jp-index.html", "shared/prices.csv", "shared/prices.html"]);
+    assert_eq!(published[Path::new("blog/jp-sleep.html")], Path::new("edit-me/pages/all-blogs/blog/sleep.jp.html"));
+    assert_eq!(published[Path::new("blog/en-sleep.html")], Path::new("edit-me/pages/all-blogs/blog/sleep.html"));
+    // (nothing is written)
+    assert!(!files.exists(output_dir));
+
+    // Two pages with the same name, in different page folders
+    files.add_file("edit-me/pages/index-with-search/index.html", "<p>Search</p>");
+    let error = processors.plan_outputs(&files.walk_files(source_dir), &context).unwrap_err().to_string();
+    assert!(error.contains("'edit-me/pages/index/index.html' and 'edit-me/pages/index-with-search/index.html' would both be published as 'index.html'"), "{}", error);
+    files.remove_file(Path::new("edit-me/pages/index-with-search/index.html")).unwrap();
+
+    // A real file with the name of a page's language version
+    files.add_file("edit-me/pages/about/jp-index.html", "<p>About</p>");
+    let error = processors.plan_outputs(&files.walk_files(source_dir), &context).unwrap_err().to_string();
+    assert!(error.contains("'edit-me/pages/about/jp-index.html' and 'edit-me/pages/index/index.html' would both be published as 'jp-index.html'"), "{}", error);
+}
+
+#[test]
+fn test_localized_versions() {
+    let files = MemoryFileSystem::new();
+    files.add_file("edit-me/pages/all-blogs/blog/sleep.html", "<html lang=\"fr\"><head></head><p>Sleep</p></html>");
+    files.add_file("edit-me/pages/all-blogs/blog/sleep.jp.html", "<html><head></head><p>睡眠</p></html>");
+    files.add_file("edit-me/pages/all-blogs/blog/notes.md", "---\ntitle: Notes\ntags: sleep\n---\n# Notes\n");
+    files.add_file("edit-me/pages/all-blogs/blog/notes.jp.md", "---\ntitle: ノート\n---\n# ノート\n");
+
+    let translations: TranslationsFile = ["en", "ie", "jp"].iter().map(|code| (code.to_string(), HashMap::new())).collect();
+    let module_usage = ModuleUsage::default();
+    let language_codes = vec!["en".to_string(), "ie".to_string(), "jp".to_string()];
+    let (source_dir, output_dir) = (Path::new("edit-me"), Path::new("out"));
+    let context = ProcessContext { source_dir, output_dir, translations: &translations, language_codes: &language_codes, module_usage: &module_usage, fs: &files, commands: &SystemCommandRunner, router: &Router::default() };
+    let mut processors = ProcessorRegistry::new();
+    processors.register(LocalizationProcessor { default_language: "en".to_string() });
+    processors.register(I18nProcessor);
+    for source_path in files.walk_files(source_dir) {
+        processors.process_file(&source_path, &context).unwrap();
+    }
+
+    // Every language gets a version (the ones without their own file fall back to the default one), of the same type
+    let mut published: Vec<String> = files.walk_files(&output_dir.join("blog")).iter().map(|path| path.strip_prefix("out/blog").unwrap().to_string_lossy().to_string()).collect();
+    published.sort();
+    assert_eq!(published, vec!["en-notes.md", "en-sleep.html", "ie-notes.md", "ie-sleep.html", "jp-notes.md", "jp-sleep.html", "notes.md", "sleep.html"]);
+
+    // ... in its language, linked to the others
+    let alternates = concat!(
+        "<link rel=\"alternate\" hreflang=\"x-default\" href=\"sleep.html\">\n",
+        "<link rel=\"alternate\" hreflang=\"en\" href=\"en-sleep.html\">\n",
+        "<link rel=\"alternate\" hreflang=\"ie\" href=\"ie-sleep.html\">\n",
+        "<link rel=\"alternate\" hreflang=\"jp\" href=\"jp-sleep.html\">\n",
+    );
+    let read = |name: &str| files.read_to_string(&output_dir.join("blog").join(name)).unwrap();
+    assert_eq!(read("jp-sleep.html"), format!("<html lang=\"jp\"><head>{}</head><p>睡眠</p></html>", alternates));
+    assert_eq!(read("ie-sleep.html"), format!("<html lang=\"ie\"><head>{}</head><p>Sleep</p></html>", alternates));
+    assert_eq!(read("sleep.html"), format!("<html lang=\"en\"><head>{}</head><p>Sleep</p></html>", alternates));
+
+    // Markdown versions share the default version's front matter, and link to each other through it
+    let alternates = "alternates: x-default=notes.md, en=en-notes.md, ie=ie-notes.md, jp=jp-notes.md\n";
+    assert_eq!(read("jp-notes.md"), format!("---\ntitle: ノート\ntags: sleep\nlang: jp\n{}---\n# ノート\n", alternates));
+    assert_eq!(read("notes.md"), format!("---\ntitle: Notes\ntags: sleep\nlang: en\n{}---\n# Notes\n", alternates));
+    assert_eq!(read("ie-notes.md"), read("notes.md"));
+
+    // ... and the fallbacks are planned before the build
+    let planned = processors.plan_outputs(&files.walk_files(source_dir), &context).unwrap();
+    assert!(planned.contains_key(Path::new("blog/ie-notes.md")));
+}
+
+#[test]
+fn test_translated_text() {
+    let files = MemoryFileSystem::new();
+    files.add_file("edit-me/pages/index/index.html", "<html><div>TEXT=index.nav-title</div><div>TEXT=index.title</div><div>TEXT=index.missing</div></html>");
+
+    // (keys can have hyphens, as they do where they are found for the catalogs, see i18n.rs)
+    let text: HashMap<String, String> = [("nav-title", "ナビ"), ("title", "ホーム")].iter().map(|(key, text)| (key.to_string(), text.to_string())).collect();
+    let translations: TranslationsFile = [("jp".to_string(), [("index".to_string(), text)].into_iter().collect())].into_iter().collect();
+    let module_usage = ModuleUsage::default();
+    let language_codes = vec!["jp".to_string()];
+    let (source_dir, output_dir) = (Path::new("edit-me"), Path::new("out"));
+    let context = ProcessContext { source_dir, output_dir, translations: &translations, language_codes: &language_codes, module_usage: &module_usage, fs: &files, commands: &SystemCommandRunner, router: &Router::default() };
+    let mut processors = ProcessorRegistry::new();
+    processors.register(I18nProcessor);
+    processors.process_file(Path::new("edit-me/pages/index/index.html"), &context).unwrap();
+
+    assert_eq!(files.read_to_string(Path::new("out/jp-index.html")).unwrap(), "<html lang=\"jp\"><div>ナビ</div><div>ホーム</div><div>index.missing</div></html>");
+}
+
+// ===================================================================
+// # === Tests for `write_file_atomically` ===
+// ===================================================================
+
+#[test]
+fn test_write_file_atomically() {
+    let tmp = tempdir().unwrap();
+    let path = tmp.path().join("a/b/page.html");
+    write_file_atomically(&DiskFileSystem, &path, "first").unwrap();
+    write_file_atomically(&DiskFileSystem, &path, "second").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+    assert_eq!(fs::read_dir(tmp.path().join("a/b")).unwrap().count(), 1);
+
+    // A folder where the file should be: nothing is written, and the temporary file is cleaned up
+    fs::create_dir_all(tmp.path().join("a/b/folder.html/inside")).unwrap();
+    assert!(write_file_atomically(&DiskFileSystem, &tmp.path().join("a/b/folder.html"), "text").is_err());
+    assert_eq!(fs::read_dir(tmp.path().join("a/b")).unwrap().count(), 2);
+}
@@ -0,0 +1,126 @@
+use websiteCompiler::config::SiteConfig;
+use websiteCompiler::routing::*;
+use websiteCompiler::vfs::MemoryFileSystem;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A site in memory, with some pages.
+fn make_site(permalinks: &[(&str, &str)]) -> (MemoryFileSystem, SiteConfig) {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("edit-me/pages/index/index.html", "<p>Home</p>");
+    fs.add_file("edit-me/pages/about/about.html", "<p>About</p>");
+    fs.add_file("edit-me/pages/about/about.css", "p {}");
+    fs.add_file("edit-me/pages/contact/contact.html", "<head><meta name=\"permalink\" content=\"/get-in-touch/\"></head>");
+    fs.add_file("edit-me/pages/all-blogs/blog/sleep.html", "<p>Sleep</p>");
+    fs.add_file("edit-me/pages/all-blogs/blog/sleep.jp.html", "<p>睡眠</p>");
+    fs.add_file("edit-me/pages/all-blogs/blog/social/ugly-people.html", "<p>Ugly people</p>");
+    fs.add_file("edit-me/pages/all-blogs/blog/social/notes.md", "# Notes");
+    let config = SiteConfig { permalinks: permalinks.iter().map(|(folder, pattern)| (folder.to_string(), pattern.to_string())).collect(), ..SiteConfig::default() };
+    (fs, config)
+}
+
+// ===================================================================
+// # === Tests for `permalink_path` ===
+// ===================================================================
+
+#[test]
+fn test_permalink_path() {
+    let page = Path::new("blog/social/relationships/activities.html");
+    assert_eq!(permalink_path("/blog/:category/:slug/", page, Path::new("blog")).unwrap(), Path::new("blog/social/relationships/activities/index.html"));
+    assert_eq!(permalink_path("/:path/:slug/", page, Path::new("")).unwrap(), Path::new("blog/social/relationships/activities/index.html"));
+    assert_eq!(permalink_path("/posts/:slug.html", page, Path::new("blog")).unwrap(), Path::new("posts/activities.html"));
+
+    // A page right in the pattern's folder has no category (and no empty folder is made for it)
+    assert_eq!(permalink_path("/blog/:category/:slug/", Path::new("blog/sleep.html"), Path::new("blog")).unwrap(), Path::new("blog/sleep/index.html"));
+    // "/" is the home page
+    assert_eq!(permalink_path("/", Path::new("home.html"), Path::new("")).unwrap(), Path::new("index.html"));
+
+    // Unknown parts, and patterns that go outside the website
+    assert!(permalink_path("/:year/:slug/", page, Path::new("")).unwrap_err().to_string().contains(":year"));
+    assert!(permalink_path("/../:slug/", page, Path::new("")).is_err());
+}
+
+#[test]
+fn test_url_path() {
+    assert_eq!(url_path(Path::new("blog/sleep/index.html")), "blog/sleep/");
+    assert_eq!(url_path(Path::new("index.html")), "");
+    assert_eq!(url_path(Path::new("blog/sleep.html")), "blog/sleep.html");
+    assert_eq!(url_path(Path::new("blog/not-an-index.html")), "blog/not-an-index.html");
+}
+
+// ===================================================================
+// # === Tests for `Router` ===
+// ===================================================================
+
+#[test]
+fn test_router_for_pages() {
+    let languages = vec!["jp".to_string()];
+
+    // No permalinks: only the pages that give themselves one move
+    let (fs, config) = make_site(&[]);
+    let router = Router::for_pages(&fs, &config, &languages).unwrap();
+    assert_eq!(router.route(Path::new("contact.html")), Path::new("get-in-touch/index.html"));
+    assert_eq!(router.route(Path::new("about.html")), Path::new("about.html"));
+
+    // A folder's pattern, with a catch-all one for every other page
+    let (fs, config) = make_site(&[("blog", "/blog/:category/:slug/"), ("", "/:slug/")]);
+    let router = Router::for_pages(&fs, &config, &languages).unwrap();
+    let routes: Vec<(&str, &str)> = router.routes().iter().map(|(from, to)| (from.to_str().unwrap(), to.to_str().unwrap())).collect();
+    assert_eq!(routes, vec![
+        ("about.html", "about/index.html"),
+        ("blog/jp-sleep.html", "blog/sleep/jp-index.html"),
+        ("blog/sleep.html", "blog/sleep/index.html"),
+        ("blog/social/jp-ugly-people.html", "blog/social/ugly-people/jp-index.html"),
+        ("blog/social/ugly-people.html", "blog/social/ugly-people/index.html"),
+        ("contact.html", "get-in-touch/index.html"),
+        ("jp-about.html", "about/jp-index.html"),
+        ("jp-contact.html", "get-in-touch/jp-index.html"),
+    ]);
+    // (the home page, the css and the markdown stay where they are)
+    assert_eq!(router.route(Path::new("index.html")), Path::new("index.html"));
+    assert_eq!(router.route(Path::new("about.css")), Path::new("about.css"));
+    assert_eq!(router.route(Path::new("blog/social/notes.md")), Path::new("blog/social/notes.md"));
+
+    // An invalid pattern names its folder
+    let (fs, config) = make_site(&[("blog", "/blog/:date/")]);
+    let error = format!("{:#}", Router::for_pages(&fs, &config, &languages).unwrap_err());
+    assert!(error.contains("[permalinks]") && error.contains("\"blog\"") && error.contains(":date"), "{}", error);
+}
+
+#[test]
+fn test_rewrite_links() {
+    let mut router = Router::new(&BTreeMap::new()).unwrap();
+    router.add_route(PathBuf::from("about.html"), PathBuf::from("about/index.html"), &[]);
+    router.add_route(PathBuf::from("blog/social/ugly-people.html"), PathBuf::from("blog/social/ugly-people/index.html"), &[]);
+
+    // A page that moved: every relative link is written from the root, so it still works from the page's new folder
+    let about = concat!(
+        "<meta name=\"permalink\" content=\"/about/\">\n",
+        "<link rel=\"stylesheet\" href=\"about.css?v=2\">",
+        "<a href=\"blog/social/ugly-people.html#top\">Ugly people</a>",
+        "<a href=\"<root>/index.html\">Home</a>",
+        "<a href=\"https://example.com/about.html\">Elsewhere</a>",
+        "<a href=\"mailto:hello@example.com\">Mail</a>",
+        "<a href=\"#team\">Team</a>",
+        "<img src=\"../outside.png\">",
+        "<img srcset=\"photo-480.png 480w, photo-960.png 960w\"><video poster='intro.jpg'></video>",
+    );
+    assert_eq!(router.rewrite_links(about, Path::new("about.html"), "<root>"), concat!(
+        "<link rel=\"stylesheet\" href=\"<root>/about.css?v=2\">",
+        "<a href=\"<root>/blog/social/ugly-people/#top\">Ugly people</a>",
+        "<a href=\"<root>/index.html\">Home</a>",
+        "<a href=\"https://example.com/about.html\">Elsewhere</a>",
+        "<a href=\"mailto:hello@example.com\">Mail</a>",
+        "<a href=\"#team\">Team</a>",
+        "<img src=\"../outside.png\">",
+        "<img srcset=\"<root>/photo-480.png 480w, <root>/photo-960.png 960w\"><video poster='<root>/intro.jpg'></video>",
+    ));
+
+    // A page that didn't move: only the links to pages that did change (including <root>/ and absolute ones)
+    let index = "<a href='about.html'>About</a><a href=\"<root>/blog/social/ugly-people.html\">Ugly</a><a href=\"/about.html\">About</a><a href=\"index.css\">";
+    assert_eq!(router.rewrite_links(index, Path::new("index.html"), "<root>"), "<a href='<root>/about/'>About</a><a href=\"<root>/blog/social/ugly-people/\">Ugly</a><a href=\"/about/\">About</a><a href=\"index.css\">");
+
+    // (every attribute that holds a link, quoted or not)
+    let form = "<form action=about.html><a href=blog/social/ugly-people.html>Ugly</a></form>";
+    assert_eq!(router.rewrite_links(form, Path::new("index.html"), "<root>"), "<form action=<root>/about/><a href=<root>/blog/social/ugly-people/>Ugly</a></form>");
+}
@@ -0,0 +1,133 @@
+use websiteCompiler::config::SiteConfig;
+use websiteCompiler::scaffold::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// A site with one page, one component (in the compilation order) and english / japanese text.
+fn make_site(root: &Path) -> SiteConfig {
+    let files = [
+        ("edit-me/pages/index/index.html", "<p>Home</p>\n"),
+        ("edit-me/pages/all-blogs/blog/social/ugly-people.md", "# Ugly people\n"),
+        ("edit-me/shared/reusables/greeting/greeting.html", "<p>Hello!</p>\n"),
+        ("edit-me/shared/reusables-compilation-order.txt", "reusables/greeting"),
+        ("edit-me/shared/page_text/en.json", "{}\n"),
+        ("edit-me/shared/page_text/jp.json", "{}\n"),
+    ];
+    for (path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    SiteConfig::default().with_root(root)
+}
+
+// ===================================================================
+// # === Tests for `validate_name` ===
+// ===================================================================
+
+#[test]
+fn test_validate_name() {
+    assert!(validate_name("top-bar").is_ok());
+    assert!(validate_name("page2").is_ok());
+    assert!(validate_name("").is_err());
+    assert!(validate_name("-top").is_err());
+    assert!(validate_name("Top-Bar").is_err());
+    assert!(validate_name("top_bar").is_err());
+    assert!(validate_name("../top").is_err());
+}
+
+// ===================================================================
+// # === Tests for `scaffold` ===
+// ===================================================================
+
+#[test]
+fn test_scaffold_built_in_templates() {
+    let tmp = tempdir().unwrap();
+    let config = make_site(tmp.path());
+    let source_dir = tmp.path().join("edit-me");
+
+    // A page: its folder, with the files named after it
+    let scaffolded = scaffold(&DiskFileSystem, &config, ScaffoldKind::Page, "about-us").unwrap();
+    assert_eq!(scaffolded.files, vec![source_dir.join("pages/about-us/about-us.html"), source_dir.join("pages/about-us/about-us.scss"), source_dir.join("pages/about-us/about-us.ts")]);
+    assert_eq!(scaffolded.registered_in, None);
+    let html = fs::read_to_string(&scaffolded.files[0]).unwrap();
+    assert!(html.contains("<link rel=stylesheet href=\"about-us.css\">"), "{}", html);
+    assert!(html.contains("<title> About Us </title>"), "{}", html);
+
+    // A component, which is added to the end of the compilation order
+    let scaffolded = scaffold(&DiskFileSystem, &config, ScaffoldKind::Component, "top-bar").unwrap();
+    assert_eq!(scaffolded.files[0], source_dir.join("shared/reusables/top-bar/top-bar.html"));
+    assert!(fs::read_to_string(&scaffolded.files[1]).unwrap().contains(".top-bar {"));
+    let order_file = source_dir.join("shared/reusables-compilation-order.txt");
+    assert_eq!(scaffolded.registered_in, Some(order_file.clone()));
+    assert_eq!(fs::read_to_string(&order_file).unwrap(), "reusables/greeting\nreusables/top-bar\n");
+
+    // A blog post, in the blog folder there is
+    let scaffolded = scaffold(&DiskFileSystem, &config, ScaffoldKind::Blog, "sleep").unwrap();
+    assert_eq!(scaffolded.files, vec![source_dir.join("pages/all-blogs/blog/sleep.md")]);
+    assert_eq!(fs::read_to_string(&scaffolded.files[0]).unwrap(), "# Sleep\n");
+
+    // Nothing is overwritten
+    fs::write(source_dir.join("pages/about-us/about-us.html"), "mine").unwrap();
+    let error = scaffold(&DiskFileSystem, &config, ScaffoldKind::Page, "about-us").unwrap_err();
+    assert!(error.to_string().contains("already exists"));
+    assert_eq!(fs::read_to_string(source_dir.join("pages/about-us/about-us.html")).unwrap(), "mine");
+    assert!(scaffold(&DiskFileSystem, &config, ScaffoldKind::Blog, "sleep").is_err());
+}
+
+#[test]
+fn test_scaffold_project_templates() {
+    let tmp = tempdir().unwrap();
+    let config = make_site(tmp.path());
+    let source_dir = tmp.path().join("edit-me");
+
+    // The project's own templates, with the name put in (and a component's {params} left alone)
+    fs::create_dir_all(tmp.path().join("templates/component/images")).unwrap();
+    fs::write(tmp.path().join("templates/component/__name__.html"), "<div class=\"{{name}}\">{title}</div>\n").unwrap();
+    fs::write(tmp.path().join("templates/component/images/icon.png"), [0x89, 0x50, 0xff]).unwrap();
+    let scaffolded = scaffold(&DiskFileSystem, &config, ScaffoldKind::Component, "card").unwrap();
+    assert_eq!(scaffolded.files, vec![source_dir.join("shared/reusables/card/card.html"), source_dir.join("shared/reusables/card/images/icon.png")]);
+    assert_eq!(fs::read_to_string(&scaffolded.files[0]).unwrap(), "<div class=\"card\">{title}</div>\n");
+    assert_eq!(fs::read(&scaffolded.files[1]).unwrap(), vec![0x89, 0x50, 0xff]);
+
+    // A blog post is copied from the blog's notes template
+    fs::write(source_dir.join("pages/all-blogs/blogs-notes-template.md"), "# {{title}}\n- Tags\n").unwrap();
+    let scaffolded = scaffold(&DiskFileSystem, &config, ScaffoldKind::Blog, "deep-sleep").unwrap();
+    assert_eq!(fs::read_to_string(&scaffolded.files[0]).unwrap(), "# Deep Sleep\n- Tags\n");
+}
+
+#[test]
+fn test_scaffold_refuses_published_path_collisions() {
+    let tmp = tempdir().unwrap();
+    let config = make_site(tmp.path());
+
+    // pages/<x>/ is dropped in the output, so a page in another folder with the same name would replace index.html
+    fs::create_dir_all(tmp.path().join("edit-me/pages/other")).unwrap();
+    fs::write(tmp.path().join("edit-me/pages/other/contact.html"), "<p>Contact</p>\n").unwrap();
+    let error = scaffold(&DiskFileSystem, &config, ScaffoldKind::Page, "contact").unwrap_err();
+    assert!(format!("{:#}", error).contains("edit-me/pages/other/contact.html"), "{:#}", error);
+    assert!(!tmp.path().join("edit-me/pages/contact").exists());
+
+    // ... as would one named like a language version of a page (jp-index.html)
+    let error = scaffold(&DiskFileSystem, &config, ScaffoldKind::Page, "jp-index").unwrap_err();
+    assert!(format!("{:#}", error).contains("jp-index.html"), "{:#}", error);
+
+    // ... or one where another page has its permalink (get-in-touch.html is published as contact.html)
+    fs::remove_file(tmp.path().join("edit-me/pages/other/contact.html")).unwrap();
+    fs::write(tmp.path().join("edit-me/pages/other/get-in-touch.html"), "<head><meta name=\"permalink\" content=\"/contact.html\"></head>\n").unwrap();
+    let error = scaffold(&DiskFileSystem, &config, ScaffoldKind::Page, "contact").unwrap_err();
+    assert!(format!("{:#}", error).contains("get-in-touch.html"), "{:#}", error);
+    assert!(!tmp.path().join("edit-me/pages/contact").exists());
+
+    // Blog posts are published without their page folder too (both are blog/sleep.md, as markdown notes are published as they are)
+    fs::create_dir_all(tmp.path().join("edit-me/pages/more-blogs/blog")).unwrap();
+    fs::write(tmp.path().join("edit-me/pages/more-blogs/blog/sleep.md"), "# Sleep\n").unwrap();
+    let error = scaffold(&DiskFileSystem, &config, ScaffoldKind::Blog, "sleep").unwrap_err();
+    assert!(format!("{:#}", error).contains("more-blogs/blog/sleep.md"), "{:#}", error);
+    assert!(!tmp.path().join("edit-me/pages/all-blogs/blog/sleep.md").exists());
+    // (an html page of the same name is published as blog/sleep.html, which is another file)
+    fs::rename(tmp.path().join("edit-me/pages/more-blogs/blog/sleep.md"), tmp.path().join("edit-me/pages/more-blogs/blog/sleep.html")).unwrap();
+    assert!(scaffold(&DiskFileSystem, &config, ScaffoldKind::Blog, "sleep").is_ok());
+}
@@ -0,0 +1,54 @@
+use websiteCompiler::scss::*;
+use websiteCompiler::source_maps::SourceOrigins;
+use websiteCompiler::vfs::{DiskFileSystem, SystemCommandRunner};
+use std::fs;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `compile_scss_file_built_in` ===
+// ===================================================================
+
+#[test]
+fn test_compile_scss_file_built_in() {
+    let tmp = tempdir().unwrap();
+    let site = tmp.path();
+    fs::create_dir_all(site.join("shared/reusables/top-bar")).unwrap();
+    // What the <root> rewriting leaves: @use of a path relative to the file
+    fs::write(site.join("shared/global.scss"), "$main-colour: #ff0000;\n@mixin centred { margin: 0 auto; }\n").unwrap();
+    fs::write(site.join("shared/reusables/top-bar/top-bar.scss"), "@use \"../../global.scss\" as *;\n\n.top-bar {\n    color: $main-colour;\n    .logo { @include centred; }\n}\n").unwrap();
+
+    let source = site.join("shared/reusables/top-bar/top-bar.scss");
+    let output = site.join("shared/reusables/top-bar/top-bar.css");
+    compile_scss_file_using(&DiskFileSystem, &SystemCommandRunner, ScssCompiler::BuiltIn, source.to_str().unwrap(), output.to_str().unwrap(), false, true).unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), ".top-bar {\n  color: #ff0000;\n}\n.top-bar .logo {\n  margin: 0 auto;\n}\n");
+
+    compile_scss_file_built_in(&DiskFileSystem, source.to_str().unwrap(), output.to_str().unwrap(), true).unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), ".top-bar{color:red}.top-bar .logo{margin:0 auto}");
+
+    assert!(compile_scss_file_built_in(&DiskFileSystem, site.join("missing.scss").to_str().unwrap(), output.to_str().unwrap(), false).is_err());
+}
+
+// ===================================================================
+// # === Tests for `point_errors_at_sources` ===
+// ===================================================================
+
+#[test]
+fn test_point_errors_at_sources() {
+    let tmp = tempdir().unwrap();
+    let source_dir = tmp.path().join("edit-me");
+    let output_dir = tmp.path().join("out");
+    fs::create_dir_all(source_dir.join("pages/index")).unwrap();
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(source_dir.join("pages/index/index.scss"), ".a {\n    color: $missing;\n}\n").unwrap();
+    fs::copy(source_dir.join("pages/index/index.scss"), output_dir.join("index.scss")).unwrap();
+
+    let copy = output_dir.join("index.scss");
+    let error = compile_scss_file_built_in(&DiskFileSystem, copy.to_str().unwrap(), output_dir.join("index.css").to_str().unwrap(), false).unwrap_err();
+    assert!(error.contains("Undefined variable"), "{}", error);
+
+    let origins = SourceOrigins::new(&DiskFileSystem, &source_dir, &output_dir, "<r-", ">", "code").unwrap();
+    let pointed = point_errors_at_sources(&error, &origins);
+    let source = source_dir.join("pages/index/index.scss").to_string_lossy().to_string();
+    assert!(pointed.contains(&format!("{}:2:12", source)), "{}", pointed);
+    assert!(!pointed.contains(&copy.to_string_lossy().to_string()), "{}", pointed);
+}
@@ -0,0 +1,75 @@
+use websiteCompiler::serve::*;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `resolve_request_path` ===
+// ===================================================================
+
+#[test]
+fn test_resolve_request_path() {
+    let tmp = tempdir().unwrap();
+    let output_dir = tmp.path().join("out");
+    fs::create_dir_all(output_dir.join("blog")).unwrap();
+    fs::write(output_dir.join("index.html"), "home").unwrap();
+    fs::write(output_dir.join("blog/index.html"), "blog").unwrap();
+    fs::write(output_dir.join("blog/my post.html"), "post").unwrap();
+    fs::write(output_dir.join("blog/sleep.html"), "sleep").unwrap();
+    fs::write(tmp.path().join("secret.txt"), "secret").unwrap();
+
+    // Folders are their index.html
+    assert_eq!(resolve_request_path(&output_dir, "", "/"), Some(output_dir.join("index.html")));
+    assert_eq!(resolve_request_path(&output_dir, "", "/blog/"), Some(output_dir.join("blog/index.html")));
+
+    // The query / fragment are ignored, and escapes decoded
+    assert_eq!(resolve_request_path(&output_dir, "", "/blog/sleep.html?lang=jp#top"), Some(output_dir.join("blog/sleep.html")));
+    assert_eq!(resolve_request_path(&output_dir, "", "/blog/my%20post.html"), Some(output_dir.join("blog/my post.html")));
+
+    // Links without .html (GitHub Pages style)
+    assert_eq!(resolve_request_path(&output_dir, "", "/blog/sleep"), Some(output_dir.join("blog/sleep.html")));
+
+    // Missing files, and anything outside the output directory
+    assert_eq!(resolve_request_path(&output_dir, "", "/missing.html"), None);
+    assert_eq!(resolve_request_path(&output_dir, "", "/../secret.txt"), None);
+    assert_eq!(resolve_request_path(&output_dir, "", "/%2e%2e/secret.txt"), None);
+
+    // A website with a base path is served under it (and a link without it is broken, as it will be when published)
+    assert_eq!(resolve_request_path(&output_dir, "/repo", "/repo/"), Some(output_dir.join("index.html")));
+    assert_eq!(resolve_request_path(&output_dir, "/repo", "/repo/blog/sleep"), Some(output_dir.join("blog/sleep.html")));
+    assert_eq!(resolve_request_path(&output_dir, "/repo", "/blog/sleep.html"), None);
+    assert_eq!(resolve_request_path(&output_dir, "/repo", "/repository/blog/sleep.html"), None);
+}
+
+#[test]
+fn test_content_type() {
+    assert_eq!(content_type(Path::new("shared/global.css")), "text/css; charset=utf-8");
+    assert_eq!(content_type(Path::new("index.JS")), "text/javascript; charset=utf-8");
+    assert_eq!(content_type(Path::new("fonts/a.woff2")), "font/woff2");
+    assert_eq!(content_type(Path::new("download")), "application/octet-stream");
+}
+
+// ===================================================================
+// # === Tests for `source_state` ===
+// ===================================================================
+
+#[test]
+fn test_source_state() {
+    let tmp = tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("pages")).unwrap();
+    fs::write(tmp.path().join("pages/index.html"), "a").unwrap();
+    let watched = [tmp.path().join("pages"), tmp.path().join("site.toml")];
+    let state = source_state(&watched);
+    assert_eq!(state.len(), 1);
+    assert_eq!(source_state(&watched), state);
+
+    // Added / removed files are changes
+    fs::write(tmp.path().join("pages/about.html"), "b").unwrap();
+    assert_ne!(source_state(&watched), state);
+    fs::remove_file(tmp.path().join("pages/about.html")).unwrap();
+    assert_eq!(source_state(&watched), state);
+
+    // (so is a watched file, e.g. site.toml, being created)
+    fs::write(tmp.path().join("site.toml"), "[build]\n").unwrap();
+    assert_eq!(source_state(&watched).len(), 2);
+}
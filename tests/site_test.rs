@@ -0,0 +1,386 @@
+use websiteCompiler::config::*;
+use websiteCompiler::site::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// A small website: one page using one component, and one scss file.
+fn make_site(root: &Path) {
+    let files = [
+        ("edit-me/pages/index/index.html", "<html><body><r-greeting><a href=\"<root>/about.html\">about</a></body></html>\n"),
+        ("edit-me/pages/about/about.html", "<html><body><p>About</p></body></html>\n"),
+        ("edit-me/shared/reusables/greeting/greeting.html", "<p>Hello!</p>\n"),
+        ("edit-me/shared/reusables-compilation-order.txt", "reusables/greeting\n"),
+        ("edit-me/shared/page_text/en.json", "{}\n"),
+        ("edit-me/shared/global.scss", "$colour: red;\nbody { color: $colour; }\n"),
+    ];
+    for (path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    fs::write(root.join(CONFIG_FILE_NAME), "[build]\nscss-compiler = \"built-in\"\n").unwrap();
+}
+
+// ===================================================================
+// # === Tests for `Site` ===
+// ===================================================================
+
+#[test]
+fn test_site_open() {
+    let tmp = tempdir().unwrap();
+    make_site(tmp.path());
+
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+    assert_eq!(site.config().root, tmp.path());
+    assert_eq!(site.config().paths.output_dir, tmp.path().join("actual-website-do-not-edit"));
+
+    // (found from inside the project too)
+    let site = Site::discover(&DiskFileSystem, &tmp.path().join("edit-me/pages")).unwrap();
+    assert_eq!(fs::canonicalize(&site.config().root).unwrap(), fs::canonicalize(tmp.path()).unwrap());
+
+    // The options start as site.toml's [build] has them, with the methods' settings over them
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+    let options = site.builder().options();
+    assert!(!options.production);
+    assert_eq!(options.scss_compiler, websiteCompiler::scss::ScssCompiler::BuiltIn);
+    let options = site.builder().production(true).fresh(true).options();
+    assert!(options.production && options.fresh_run);
+    assert_eq!(options.source_maps, websiteCompiler::source_maps::SourceMapMode::None);
+    assert!(site.builder().profile("release").is_err());
+}
+
+// ===================================================================
+// # === Tests for `Builder::build` ===
+// ===================================================================
+
+#[test]
+fn test_build() {
+    let tmp = tempdir().unwrap();
+    make_site(tmp.path());
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+    let output_dir = tmp.path().join("actual-website-do-not-edit");
+
+    let report = site.builder().fresh(true).build().unwrap();
+    assert_eq!(report.output_dir, output_dir);
+    assert!(!report.has_errors(), "{:?}", report.diagnostics);
+
+    // Components expanded, <root> made relative, scss compiled
+    let index = fs::read_to_string(output_dir.join("index.html")).unwrap();
+    assert!(index.contains("<p>Hello!</p>"), "{}", index);
+    assert!(index.contains("<a href=\"about.html\">"), "{}", index);
+    assert!(fs::read_to_string(output_dir.join("shared/global.css")).unwrap().contains("color: red"));
+
+    // The outputs are where they ended up (the output directory, not the staging directory it was built in)
+    let index_output = report.outputs.iter().find(|file| file.source_path.ends_with("pages/index/index.html")).unwrap();
+    assert_eq!(index_output.copy_path, output_dir.join("index.html"));
+    assert!(report.outputs.iter().all(|file| file.copy_path.starts_with(&output_dir)));
+
+    // Every stage was timed
+    let stages: Vec<&str> = report.timings.iter().map(|(stage, _)| stage.as_str()).collect();
+    assert_eq!(stages.first(), Some(&"prepare"));
+    assert!(stages.contains(&"compile"));
+    assert_eq!(stages.last(), Some(&"publish"));
+
+    // The compiler's own files are in the project root
+    assert!(tmp.path().join(LAST_COMPILE_TIME_FILE).is_file());
+    assert!(!tmp.path().join(BUILD_LOCK_FILE).exists());
+}
+
+#[test]
+fn test_build_only_some_files() {
+    let tmp = tempdir().unwrap();
+    make_site(tmp.path());
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+    let output_dir = tmp.path().join("actual-website-do-not-edit");
+    site.builder().fresh(true).build().unwrap();
+    let last_compiled = fs::read_to_string(tmp.path().join(LAST_COMPILE_TIME_FILE)).unwrap();
+
+    // Only the page asked for is rebuilt
+    fs::write(tmp.path().join("edit-me/pages/about/about.html"), "<html><body><p>About us</p></body></html>\n").unwrap();
+    fs::write(tmp.path().join("edit-me/pages/index/index.html"), "<html><body><p>Changed</p></body></html>\n").unwrap();
+    let report = site.builder().only([tmp.path().join("edit-me/pages/about/about.html")]).build().unwrap();
+    assert_eq!(report.outputs.len(), 1);
+    assert!(fs::read_to_string(output_dir.join("about.html")).unwrap().contains("About us"));
+    assert!(fs::read_to_string(output_dir.join("index.html")).unwrap().contains("Hello!"));
+    // (and the rest of the changed files are still built by the next normal build)
+    assert_eq!(fs::read_to_string(tmp.path().join(LAST_COMPILE_TIME_FILE)).unwrap(), last_compiled);
+
+    // Which can't be a fresh build
+    assert!(site.builder().fresh(true).only([tmp.path().join("edit-me/pages/about/about.html")]).build().is_err());
+}
+
+#[test]
+fn test_build_diagnostics() {
+    let tmp = tempdir().unwrap();
+    make_site(tmp.path());
+    fs::write(tmp.path().join("edit-me/shared/broken.scss"), "body { color: $undefined; }\n").unwrap();
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+
+    // A file that doesn't compile doesn't stop the build, but is reported
+    let report = site.builder().fresh(true).build().unwrap();
+    assert!(report.has_errors());
+    let error = report.errors().next().unwrap();
+    assert_eq!(error.severity, Severity::Error);
+    assert_eq!(error.file.as_deref(), Some(tmp.path().join("edit-me/shared/broken.scss").as_path()));
+    assert!(tmp.path().join("actual-website-do-not-edit/index.html").is_file());
+}
+
+#[test]
+fn test_check() {
+    let tmp = tempdir().unwrap();
+    make_site(tmp.path());
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+    let output_dir = tmp.path().join("actual-website-do-not-edit");
+
+    // Everything is built (and the problems reported), but nothing is written (not even the image cache)
+    fs::write(tmp.path().join("edit-me/shared/broken.scss"), "body { color: $undefined; }\n").unwrap();
+    fs::create_dir_all(tmp.path().join("edit-me/shared/images")).unwrap();
+    image::RgbImage::new(8, 8).save(tmp.path().join("edit-me/shared/images/team.png")).unwrap();
+    fs::write(tmp.path().join("edit-me/pages/about/about.html"), "<p>About</p><img src=\"<root>/shared/images/team.png\">\n").unwrap();
+    let report = site.builder().check(true).build().unwrap();
+    assert!(report.has_errors());
+    assert!(report.outputs.len() > 1);
+    assert!(!output_dir.exists());
+    assert!(!tmp.path().join(LAST_COMPILE_TIME_FILE).exists());
+    assert!(!tmp.path().join(".actual-website-do-not-edit.staging").exists());
+    assert!(!tmp.path().join(IMAGE_CACHE_DIR).exists());
+
+    // (an existing output is left as it was)
+    fs::remove_file(tmp.path().join("edit-me/shared/broken.scss")).unwrap();
+    site.build().unwrap();
+    fs::write(tmp.path().join("edit-me/pages/about/about.html"), "<p>Changed</p>\n").unwrap();
+    let report = site.builder().check(true).build().unwrap();
+    assert!(!report.has_errors(), "{:?}", report.diagnostics);
+    assert!(fs::read_to_string(output_dir.join("about.html")).unwrap().contains("About"));
+
+    // A broken link is an error of a check (naming the edit-me file it is in), and only a warning of a build that checks links
+    let page = tmp.path().join("edit-me/pages/about/about.html");
+    fs::write(&page, "<p>About</p><img src=\"<root>/shared/images/team.jpg\">\n").unwrap();
+    let report = site.builder().check(true).build().unwrap();
+    let errors: Vec<_> = report.errors().collect();
+    assert_eq!(errors.len(), 2, "{:?}", errors); // (about.html and en-about.html)
+    assert_eq!(errors[0].file.as_deref(), Some(page.as_path()));
+    assert!(errors[0].message.contains("there is no 'shared/images/team.jpg'"), "{}", errors[0].message);
+    let report = site.builder().check_links(true).build().unwrap();
+    assert!(!report.has_errors() && report.diagnostics.len() == 2, "{:?}", report.diagnostics);
+}
+
+#[test]
+fn test_clean() {
+    let tmp = tempdir().unwrap();
+    make_site(tmp.path());
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+    site.build().unwrap();
+    fs::create_dir_all(tmp.path().join(".actual-website-do-not-edit.staging")).unwrap();
+
+    let removed = site.clean(&DiskFileSystem).unwrap();
+    assert!(removed.contains(&tmp.path().join("actual-website-do-not-edit")));
+    assert!(removed.contains(&tmp.path().join(".actual-website-do-not-edit.staging")));
+    assert!(removed.contains(&tmp.path().join(LAST_COMPILE_TIME_FILE)));
+    assert!(!tmp.path().join("actual-website-do-not-edit").exists());
+    assert!(tmp.path().join("edit-me/pages/index/index.html").is_file());
+    assert!(site.clean(&DiskFileSystem).unwrap().is_empty());
+}
+
+#[test]
+fn test_list() {
+    let tmp = tempdir().unwrap();
+    make_site(tmp.path());
+    fs::create_dir_all(tmp.path().join("edit-me/pages/all-blogs/blog")).unwrap();
+    fs::write(tmp.path().join("edit-me/pages/all-blogs/blog/sleep.md"), "# Sleep\n").unwrap();
+    fs::write(tmp.path().join("edit-me/pages/all-blogs/notes-template.md"), "notes\n").unwrap();
+    fs::write(tmp.path().join("edit-me/shared/page_text/jp.json"), "{}\n").unwrap();
+    let site = Site::open(&DiskFileSystem, tmp.path()).unwrap();
+
+    assert_eq!(site.components(&DiskFileSystem).unwrap(), vec!["greeting"]);
+    // (every html page, where the build publishes it; the markdown notes aren't pages)
+    let pages: Vec<String> = site.pages(&DiskFileSystem).unwrap().iter().map(|(_, output)| output.to_string_lossy().to_string()).collect();
+    assert_eq!(pages, vec!["about.html", "en-about.html", "jp-about.html", "en-index.html", "index.html", "jp-index.html"]);
+    assert!(site.builder().plan().unwrap().contains_key(Path::new("blog/sleep.md")));
+    assert_eq!(site.languages(&DiskFileSystem).unwrap(), vec!["en", "jp"]);
+}
+
+#[test]
+fn test_build_in_memory() {
+    use std::sync::Arc;
+    use websiteCompiler::vfs::*;
+
+    // The whole website (and the build) in memory, with a pretend tsc
+    let files = MemoryFileSystem::new();
+    let root = Path::new("site");
+    files.add_file(root.join("edit-me/pages/index/index.html"), "<html><body><r-greeting><script src=\"index.js\"></script></body></html>\n");
+    files.add_file(root.join("edit-me/pages/index/index.ts"), "const message: string = 'hi';\n");
+    files.add_file(root.join("edit-me/shared/reusables/greeting/greeting.html"), "<p>Hello!</p>\n");
+    files.add_file(root.join("edit-me/shared/reusables-compilation-order.txt"), "reusables/greeting\n");
+    files.add_file(root.join("edit-me/shared/page_text/en.json"), "{}\n");
+    files.add_file(root.join("edit-me/shared/global.scss"), "@use 'colours';\nbody { color: colours.$main; }\n");
+    files.add_file(root.join("edit-me/shared/_colours.scss"), "$main: red;\n");
+    let tsc_files = files.clone();
+    let commands = Arc::new(MockCommandRunner::new().on("tsc", move |args| {
+        let js_output = &args[args.iter().position(|arg| arg == "--outfile").unwrap() + 1];
+        tsc_files.add_file(js_output, "const message = 'hi';\n");
+        CommandOutput::success("")
+    }));
+
+    let mut config = SiteConfig::default().with_root(root);
+    config.build.scss_compiler = Some(ScssCompilerSetting::BuiltIn);
+    let site = Site::from_config(config);
+    let report = site.builder().fresh(true).file_system(files.clone()).command_runner(Arc::clone(&commands)).build().unwrap();
+    assert!(!report.has_errors(), "{:?}", report.diagnostics);
+
+    // Built, without touching the disk
+    let output_dir = root.join("actual-website-do-not-edit");
+    assert!(!output_dir.exists());
+    let index = files.read_to_string(&output_dir.join("index.html")).unwrap();
+    assert!(index.contains("<p>Hello!</p>"), "{}", index);
+    assert!(files.read_to_string(&output_dir.join("shared/global.css")).unwrap().contains("color: red"));
+    assert!(files.is_file(&output_dir.join("index.js")));
+    assert!(files.is_file(&root.join(LAST_COMPILE_TIME_FILE)));
+    assert!(!files.exists(&root.join(BUILD_LOCK_FILE)));
+    assert!(!files.exists(Path::new("site/.actual-website-do-not-edit.staging")));
+
+    // tsc was run once, for the one typescript file
+    let calls = commands.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].0, "tsc");
+    assert!(calls[0].1.iter().any(|arg| arg.ends_with("index.ts")));
+    assert_eq!(report.published[Path::new("index.js")], root.join("edit-me/pages/index/index.ts"));
+
+    // Two pages that would be published as the same file stop the build, before anything is built
+    files.add_file(root.join("edit-me/pages/home/index.html"), "<p>Home</p>\n");
+    let error = site.builder().fresh(true).file_system(files.clone()).command_runner(Arc::clone(&commands)).build().unwrap_err();
+    assert!(format!("{:#}", error).contains("'site/edit-me/pages/home/index.html' and 'site/edit-me/pages/index/index.html' would both be published as 'index.html'"), "{:#}", error);
+    assert!(files.read_to_string(&output_dir.join("index.html")).unwrap().contains("<p>Hello!</p>"));
+    assert_eq!(commands.calls().len(), 1);
+}
+
+#[test]
+fn test_build_with_permalinks() {
+    use websiteCompiler::vfs::*;
+
+    let files = MemoryFileSystem::new();
+    let root = Path::new("site");
+    files.add_file(root.join("edit-me/pages/index/index.html"), "<html><body><r-post-link link-path=\"notes/sleep.html\"><a href=\"about.html\">About</a></body></html>\n");
+    files.add_file(root.join("edit-me/pages/about/about.html"), "<html><head><meta name=\"permalink\" content=\"/about-us/\"><link href=\"about.css\"></head><body><a href=\"<root>/index.html\">Home</a></body></html>\n");
+    files.add_file(root.join("edit-me/pages/about/about.css"), "p { color: red; }\n");
+    files.add_file(root.join("edit-me/pages/all-notes/notes/sleep.html"), "<html><body><a href=\"../about.html\">About</a></body></html>\n");
+    files.add_file(root.join("edit-me/shared/reusables/post-link/post-link.html"), "<a class=\"post\" href=\"<root>/{link-path}\">Read</a>\n");
+    files.add_file(root.join("edit-me/shared/reusables-compilation-order.txt"), "reusables/post-link\n");
+    files.add_file(root.join("edit-me/shared/page_text/en.json"), "{}\n");
+
+    let mut config = SiteConfig::default().with_root(root);
+    config.permalinks.insert("notes".to_string(), "/notes/:slug/".to_string());
+    let site = Site::from_config(config.clone());
+    let build = |site: &Site| site.builder().file_system(files.clone()).command_runner(MockCommandRunner::new()).build();
+    let report = build(&site).unwrap();
+    assert!(!report.has_errors(), "{:?}", report.diagnostics);
+
+    // The pages are published at their permalinks (with their language versions next to them)...
+    let output_dir = root.join("actual-website-do-not-edit");
+    let about = files.read_to_string(&output_dir.join("about-us/index.html")).unwrap();
+    let sleep = files.read_to_string(&output_dir.join("notes/sleep/index.html")).unwrap();
+    let index = files.read_to_string(&output_dir.join("index.html")).unwrap();
+    assert!(files.is_file(&output_dir.join("about-us/en-index.html")));
+    assert!(!files.exists(&output_dir.join("about.html")) && !files.exists(&output_dir.join("notes/sleep.html")));
+    assert_eq!(report.published[Path::new("notes/sleep/index.html")], root.join("edit-me/pages/all-notes/notes/sleep.html"));
+
+    // ... and every link to them (or from them) is rewritten to match, including the link-path of a component
+    assert!(index.contains("<a class=\"post\" href=\"notes/sleep/\">") && index.contains("<a href=\"about-us/\">"), "{}", index);
+    assert!(about.contains("<link href=\"../about.css\">") && about.contains("<a href=\"../index.html\">") && !about.contains("permalink"), "{}", about);
+    assert!(sleep.contains("<a href=\"../../about-us/\">"), "{}", sleep);
+    assert_eq!(files.read_to_string(&root.join(ROUTES_FILE)).unwrap().lines().count(), 4);
+
+    // Changing the permalinks rebuilds everything, so nothing is left at the old paths
+    config.permalinks.insert("notes".to_string(), "/:slug.html".to_string());
+    let site = Site::from_config(config.clone());
+    build(&site).unwrap();
+    assert!(files.is_file(&output_dir.join("sleep.html")));
+    assert!(!files.exists(&output_dir.join("notes/sleep")));
+    assert!(files.read_to_string(&output_dir.join("index.html")).unwrap().contains("href=\"sleep.html\""));
+
+    // A permalink that is another page's path stops the build
+    files.add_file(root.join("edit-me/pages/sleep/sleep.html"), "<p>zzz</p>\n");
+    let error = build(&site).unwrap_err();
+    assert!(format!("{:#}", error).contains("would both be published as 'sleep.html'"), "{:#}", error);
+}
+
+#[test]
+fn test_build_scss_partial_at_two_depths() {
+    use websiteCompiler::vfs::*;
+
+    // A partial's <root> is worked out from each stylesheet it is pasted into, not from the partial
+    let files = MemoryFileSystem::new();
+    let root = Path::new("site");
+    files.add_file(root.join("edit-me/pages/index/index.html"), "<html><head><link rel=\"stylesheet\" href=\"<root>/shared/global.css\"></head><body><r-top-bar></body></html>\n");
+    files.add_file(root.join("edit-me/shared/_fonts.scss"), "@font-face { font-family: Body; src: url('<root>/shared/fonts/body.woff2'); }\n");
+    files.add_file(root.join("edit-me/shared/global.scss"), "@use '<root>/shared/fonts';\nbody { background: url(\"<root>/shared/images/bg.png\"); }\n");
+    files.add_file(root.join("edit-me/shared/reusables/top-bar/top-bar.scss"), "@use '<root>/shared/fonts';\nnav { color: red; }\n");
+    files.add_file(root.join("edit-me/shared/reusables/top-bar/top-bar.html"), "<nav><link rel=\"stylesheet\" href=\"<root>/shared/reusables/top-bar/top-bar.css\"></nav>\n");
+    files.add_file(root.join("edit-me/shared/fonts/body.woff2"), "font");
+    files.add_file(root.join("edit-me/shared/images/bg.png"), "png");
+    files.add_file(root.join("edit-me/shared/reusables-compilation-order.txt"), "reusables/top-bar\n");
+    files.add_file(root.join("edit-me/shared/page_text/en.json"), "{}\n");
+
+    let mut config = SiteConfig::default().with_root(root);
+    config.build.scss_compiler = Some(ScssCompilerSetting::BuiltIn);
+    let site = Site::from_config(config);
+    let report = site.builder().file_system(files.clone()).command_runner(MockCommandRunner::new()).fresh(true).check_links(true).build().unwrap();
+    assert!(report.diagnostics.is_empty(), "{:?}", report.diagnostics);
+
+    let output_dir = root.join("actual-website-do-not-edit");
+    let global = files.read_to_string(&output_dir.join("shared/global.css")).unwrap();
+    let top_bar = files.read_to_string(&output_dir.join("shared/reusables/top-bar/top-bar.css")).unwrap();
+    assert!(global.contains("url(\"../shared/fonts/body.woff2\")") && global.contains("url(\"../shared/images/bg.png\")"), "{}", global);
+    assert!(top_bar.contains("url(\"../../../shared/fonts/body.woff2\")"), "{}", top_bar);
+    assert!(!files.read_to_string(&output_dir.join("shared/_fonts.scss")).unwrap_or_default().contains("../"));
+}
+
+#[test]
+fn test_build_with_absolute_links() {
+    use websiteCompiler::vfs::*;
+
+    let files = MemoryFileSystem::new();
+    let root = Path::new("site");
+    files.add_file(root.join("edit-me/pages/index/index.html"), "<html><head><link rel=\"stylesheet\" href=\"<root>/shared/global.css\"></head><body><a href=\"notes/sleep.html#tips\">Sleep</a><a href=\"https://example.com\">Elsewhere</a></body></html>\n");
+    files.add_file(root.join("edit-me/pages/index/index.js"), "const logo = \"<root>/shared/images/logo.png\";\n");
+    files.add_file(root.join("edit-me/pages/all-notes/notes/sleep.html"), "<html><body><img src=\"<root>/shared/images/logo.png\"><a href=\"../index.html\">Home</a></body></html>\n");
+    files.add_file(root.join("edit-me/shared/global.css"), "body { background: url(images/logo.png); }\n");
+    files.add_file(root.join("edit-me/shared/images/logo.png"), "png");
+    files.add_file(root.join("edit-me/shared/reusables-compilation-order.txt"), "");
+    files.add_file(root.join("edit-me/shared/page_text/en.json"), "{}\n");
+
+    let mut config = SiteConfig::default().with_root(root);
+    config.site.base_url = Some("https://user.github.io/repo/".to_string());
+    config.permalinks.insert("notes".to_string(), "/notes/:slug/".to_string());
+    let site = Site::from_config(config.clone());
+    let build = |links: LinkSetting| site.builder().file_system(files.clone()).command_runner(MockCommandRunner::new()).fresh(true).links(links).build();
+    let output_dir = root.join("actual-website-do-not-edit");
+    let read = |path: &str| files.read_to_string(&output_dir.join(path)).unwrap();
+
+    // Relative links are worked out from where each file is published (the page with a permalink is two folders down)
+    let report = build(LinkSetting::Relative).unwrap();
+    assert!(!report.has_errors(), "{:?}", report.diagnostics);
+    assert!(read("notes/sleep/index.html").contains("<img src=\"../../shared/images/logo.png\"><a href=\"../../index.html\">"), "{}", read("notes/sleep/index.html"));
+    assert!(read("index.html").contains("href=\"shared/global.css\""));
+
+    // Absolute links have the base path, in the html, the css and the scripts
+    build(LinkSetting::Absolute).unwrap();
+    let (index, sleep) = (read("index.html"), read("notes/sleep/index.html"));
+    assert!(index.contains("href=\"/repo/shared/global.css\"") && index.contains("href=\"/repo/notes/sleep/#tips\"") && index.contains("href=\"https://example.com\""), "{}", index);
+    assert!(sleep.contains("<img src=\"/repo/shared/images/logo.png\"><a href=\"/repo/index.html\">"), "{}", sleep);
+    assert_eq!(read("shared/global.css"), "body { background: url(/repo/shared/images/logo.png); }\n");
+    assert_eq!(read("index.js"), "const logo = \"/repo/shared/images/logo.png\";\n");
+
+    // Urls start with the base url
+    build(LinkSetting::Url).unwrap();
+    assert!(read("notes/sleep/index.html").contains("<img src=\"https://user.github.io/repo/shared/images/logo.png\">"));
+    assert_eq!(read("index.js"), "const logo = \"https://user.github.io/repo/shared/images/logo.png\";\n");
+
+    // ... which has to be set
+    config.site.base_url = None;
+    let site = Site::from_config(config);
+    let error = site.builder().file_system(files.clone()).command_runner(MockCommandRunner::new()).links(LinkSetting::Url).build().unwrap_err();
+    assert!(error.to_string().contains("base-url"), "{}", error);
+}
@@ -0,0 +1,106 @@
+use websiteCompiler::source_maps::*;
+use websiteCompiler::typescript_modules::{collect_module_usage, link_typescript_modules};
+use websiteCompiler::vfs::DiskFileSystem;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `decode_mappings` / `encode_mappings` ===
+// ===================================================================
+
+#[test]
+fn test_mappings_round_trip() {
+    let mappings = "AAAA,SAASA,CAAC;;IAEAC,aAAa;AAChB,gBAAgB";
+    let lines = decode_mappings(mappings).unwrap();
+    assert_eq!(lines[2][0], Mapping { generated_column: 4, original: Some(OriginalPosition { source: 0, line: 2, column: 10, name: Some(1) }) });
+    assert_eq!(encode_mappings(&lines), mappings);
+    assert!(decode_mappings("AA!A").is_err());
+}
+
+// ===================================================================
+// # === Tests for `write_source_maps` ===
+// ===================================================================
+
+/// A site with a page that imports a module, compiled as far as the copy of its typescript in the output directory
+/// (and a map for it, as tsc would write: each line of the javascript maps to the same line of the copy).
+fn write_site(site: &Path) -> (PathBuf, PathBuf) {
+    let source_dir = site.join("edit-me");
+    let output_dir = site.join("out");
+    fs::create_dir_all(source_dir.join("pages/index")).unwrap();
+    fs::create_dir_all(source_dir.join("shared/code")).unwrap();
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(source_dir.join("shared/code/loader.ts"), "export function load() {\n    return 1;\n}\n").unwrap();
+    fs::write(source_dir.join("pages/index/index.ts"), "import { load } from \"<root>/shared/code/loader\";\n\nload();\nconsole.log(\"<root>/x\");\nload();\n").unwrap();
+
+    let copy = output_dir.join("index.ts");
+    fs::copy(source_dir.join("pages/index/index.ts"), &copy).unwrap();
+    link_typescript_modules(&DiskFileSystem, &copy, &source_dir.join("pages/index/index.ts"), &source_dir, &collect_module_usage(&DiskFileSystem, &source_dir)).unwrap();
+    fs::write(&copy, fs::read_to_string(&copy).unwrap().replace("<root>", ".")).unwrap();
+
+    let line_count = fs::read_to_string(&copy).unwrap().lines().count();
+    let lines: Vec<Vec<Mapping>> = (0..line_count).map(|line| vec![Mapping { generated_column: 0, original: Some(OriginalPosition { source: 0, line, column: 0, name: None }) }]).collect();
+    fs::write(output_dir.join("index.js"), "// compiled\n//# sourceMappingURL=index.js.map\n").unwrap();
+    fs::write(output_dir.join("index.js.map"), format!(r#"{{"version":3,"file":"index.js","sourceRoot":"","sources":["index.ts"],"names":[],"mappings":"{}"}}"#, encode_mappings(&lines))).unwrap();
+    (source_dir, output_dir)
+}
+
+/// The edit-me file + line each line of the copy is mapped to.
+fn mapped_lines(map: &Value) -> Vec<Option<(String, usize)>> {
+    let sources: Vec<&str> = map["sources"].as_array().unwrap().iter().map(|s| s.as_str().unwrap()).collect();
+    decode_mappings(map["mappings"].as_str().unwrap()).unwrap().iter()
+        .map(|segments| segments.first().and_then(|segment| segment.original).map(|original| (sources[original.source].to_string(), original.line)))
+        .collect()
+}
+
+#[test]
+fn test_write_source_maps() {
+    let tmp = tempdir().unwrap();
+    let (source_dir, output_dir) = write_site(tmp.path());
+    let origins = SourceOrigins::new(&DiskFileSystem, &source_dir, &output_dir, "<r-", ">", "code").unwrap();
+    assert_eq!(origins.source_of(&output_dir.join("index.ts")), Some(source_dir.join("pages/index/index.ts").as_path()));
+
+    let summary = write_source_maps(&DiskFileSystem, &[output_dir.join("index.js")], &output_dir, &origins, &SourceMapMode::External).unwrap();
+    assert_eq!(summary.maps_written, 1);
+    assert!(summary.failures.is_empty(), "{:?}", summary.failures);
+
+    let map: Value = serde_json::from_str(&fs::read_to_string(output_dir.join("index.js.map")).unwrap()).unwrap();
+    assert_eq!(map["sources"], serde_json::json!(["../edit-me/pages/index/index.ts", "../edit-me/shared/code/loader.ts"]));
+    assert_eq!(map["sourcesContent"][1], "export function load() {\n    return 1;\n}\n");
+    assert!(map.get("sourceRoot").is_none());
+
+    let copy = fs::read_to_string(output_dir.join("index.ts")).unwrap();
+    let mapped = mapped_lines(&map);
+    let line_of = |text: &str| copy.lines().position(|line| line.contains(text)).unwrap();
+    let page = |line: usize| Some(("../edit-me/pages/index/index.ts".to_string(), line));
+    assert_eq!(mapped[line_of("return 1;")], Some(("../edit-me/shared/code/loader.ts".to_string(), 1)));
+    assert_eq!(mapped[line_of("namespace __module_")], None);
+    // The <root> replaced line is between two unchanged ones
+    assert_eq!(mapped[line_of("console.log")], page(3));
+    assert_eq!(mapped[copy.lines().count() - 1], page(4));
+    // The link to the map is kept
+    assert!(fs::read_to_string(output_dir.join("index.js")).unwrap().contains("sourceMappingURL=index.js.map"));
+    assert!(has_source_map(&DiskFileSystem, &output_dir.join("index.js"), &output_dir, &SourceMapMode::External));
+}
+
+#[test]
+fn test_hidden_and_removed_source_maps() {
+    let tmp = tempdir().unwrap();
+    let (source_dir, output_dir) = write_site(tmp.path());
+    let origins = SourceOrigins::new(&DiskFileSystem, &source_dir, &output_dir, "<r-", ">", "code").unwrap();
+    let maps_dir = tmp.path().join("source-maps");
+    let mode = SourceMapMode::Hidden(maps_dir.clone());
+
+    write_source_maps(&DiskFileSystem, &[output_dir.join("index.js")], &output_dir, &origins, &mode).unwrap();
+    assert!(!output_dir.join("index.js.map").exists());
+    assert_eq!(fs::read_to_string(output_dir.join("index.js")).unwrap(), "// compiled\n");
+    let map: Value = serde_json::from_str(&fs::read_to_string(maps_dir.join("index.js.map")).unwrap()).unwrap();
+    assert_eq!(map["sources"][0], "../edit-me/pages/index/index.ts");
+    assert!(has_source_map(&DiskFileSystem, &output_dir.join("index.js"), &output_dir, &mode));
+
+    // Turning them off
+    let (_, output_dir) = write_site(tmp.path());
+    assert_eq!(remove_source_maps(&DiskFileSystem, &output_dir).unwrap(), 1);
+    assert_eq!(fs::read_to_string(output_dir.join("index.js")).unwrap(), "// compiled\n");
+}
@@ -0,0 +1,93 @@
+use websiteCompiler::staging::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `BuildLock` ===
+// ===================================================================
+
+#[test]
+fn test_build_lock() {
+    let tmp = tempdir().unwrap();
+    let lock_path = tmp.path().join(".build.lock");
+
+    // Held: a second build can't take it
+    let lock = BuildLock::acquire(&DiskFileSystem, &lock_path).unwrap();
+    assert_eq!(fs::read_to_string(&lock_path).unwrap(), std::process::id().to_string());
+    let error = BuildLock::acquire(&DiskFileSystem, &lock_path).unwrap_err();
+    assert!(error.to_string().contains("Another build"));
+
+    // Released when dropped
+    drop(lock);
+    assert!(!lock_path.exists());
+
+    // The lock of a build that was stopped (its process isn't running any more) is replaced
+    fs::write(&lock_path, u32::MAX.to_string()).unwrap();
+    let lock = BuildLock::acquire(&DiskFileSystem, &lock_path).unwrap();
+    assert_eq!(lock.path(), lock_path);
+    assert_eq!(fs::read_to_string(&lock_path).unwrap(), std::process::id().to_string());
+    // (with nothing left next to it)
+    drop(lock);
+    assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+
+    // ... by one build only: while another is replacing it, it is left alone
+    fs::write(&lock_path, u32::MAX.to_string()).unwrap();
+    fs::write(tmp.path().join(".build.lock.takeover"), "1").unwrap();
+    let error = BuildLock::acquire(&DiskFileSystem, &lock_path).unwrap_err();
+    assert!(error.to_string().contains("Another build is replacing"), "{}", error);
+    assert_eq!(fs::read_to_string(&lock_path).unwrap(), u32::MAX.to_string());
+}
+
+// ===================================================================
+// # === Tests for `StagingDirectory` ===
+// ===================================================================
+
+#[test]
+fn test_staging_directory() {
+    let tmp = tempdir().unwrap();
+    let output_dir = tmp.path().join("site");
+    fs::create_dir_all(output_dir.join("blog")).unwrap();
+    fs::write(output_dir.join("index.html"), "old index").unwrap();
+    fs::write(output_dir.join("blog/post.html"), "old post").unwrap();
+
+    // An incremental build starts from a copy of the output
+    let staging = StagingDirectory::create(&DiskFileSystem, &output_dir, false).unwrap();
+    assert_eq!(staging.path(), tmp.path().join(".site.staging"));
+    assert_eq!(fs::read_to_string(staging.path().join("blog/post.html")).unwrap(), "old post");
+    fs::write(staging.path().join("index.html"), "new index").unwrap();
+    // (nothing changes until it is published)
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), "old index");
+    staging.publish().unwrap();
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), "new index");
+    assert_eq!(fs::read_to_string(output_dir.join("blog/post.html")).unwrap(), "old post");
+
+    // A fresh build starts from nothing
+    let staging = StagingDirectory::create(&DiskFileSystem, &output_dir, true).unwrap();
+    assert_eq!(fs::read_dir(staging.path()).unwrap().count(), 0);
+    fs::write(staging.path().join("index.html"), "fresh index").unwrap();
+    staging.publish().unwrap();
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), "fresh index");
+    assert!(!output_dir.join("blog").exists());
+
+    // A failed build (dropped without being published) leaves the output as it was
+    {
+        let staging = StagingDirectory::create(&DiskFileSystem, &output_dir, true).unwrap();
+        fs::write(staging.path().join("index.html"), "half built").unwrap();
+    }
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), "fresh index");
+
+    // (nothing but the output directory is left behind)
+    let names: Vec<String> = fs::read_dir(tmp.path()).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+    assert_eq!(names, vec!["site"]);
+}
+
+#[test]
+fn test_staging_directory_without_output() {
+    let tmp = tempdir().unwrap();
+    let output_dir = tmp.path().join("site");
+    let staging = StagingDirectory::create(&DiskFileSystem, &output_dir, false).unwrap();
+    fs::write(staging.path().join("index.html"), "first build").unwrap();
+    staging.publish().unwrap();
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), "first build");
+}
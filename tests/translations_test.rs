@@ -0,0 +1,97 @@
+use websiteCompiler::translations::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `parse_catalog` ===
+// ===================================================================
+
+#[test]
+fn test_parse_catalog_all_formats() {
+    let json = r#"{ "index": { "learn-more": "Learn more", "buy-now": "Buy now" } }"#;
+    let toml = "[index]\nlearn-more = \"Learn more\"\nbuy-now = \"Buy now\"\n";
+    let yaml = "index:\n  learn-more: Learn more\n  buy-now: Buy now\n";
+
+    for (content, format) in [(json, CatalogFormat::Json), (toml, CatalogFormat::Toml), (yaml, CatalogFormat::Yaml)] {
+        let catalog = parse_catalog(Path::new("en"), content, format).unwrap();
+        assert_eq!(catalog["index"]["learn-more"], "Learn more", "{:?}", format);
+        assert_eq!(catalog["index"]["buy-now"], "Buy now", "{:?}", format);
+    }
+}
+
+// Two keys on one line, where the second one repeats an earlier key
+#[test]
+fn test_parse_catalog_duplicate_key_location() {
+    let json = "{\n    \"index\": {\n        \"buy-now\": \"Buy now\",\n        \"learn-more\":\"Learn more\", \"buy-now\": \"Buy\"\n    }\n}";
+    let error = parse_catalog(Path::new("page_text/en.json"), json, CatalogFormat::Json).unwrap_err();
+
+    assert!(matches!(error.kind, TranslationErrorKind::Schema(_)));
+    assert_eq!(error.line, Some(4));
+    assert_eq!(error.key_path.as_deref(), Some("index"));
+    assert!(error.to_string().starts_with("page_text/en.json:4:"), "{}", error);
+    assert!(error.to_string().contains("duplicate key \"buy-now\""), "{}", error);
+
+    let yaml = "index:\n  buy-now: Buy now\n  buy-now: Buy\n";
+    let error = parse_catalog(Path::new("en.yaml"), yaml, CatalogFormat::Yaml).unwrap_err();
+    assert!(error.to_string().contains("duplicate key \"buy-now\""), "{}", error);
+    assert_eq!(error.line, Some(2)); // serde_yaml_ng points at the start of the `index` table
+
+    let toml = "[index]\nbuy-now = \"Buy now\"\nbuy-now = \"Buy\"\n";
+    let error = parse_catalog(Path::new("en.toml"), toml, CatalogFormat::Toml).unwrap_err();
+    assert!(error.to_string().contains("duplicate key"), "{}", error);
+    assert_eq!(error.line, Some(3));
+}
+
+#[test]
+fn test_parse_catalog_wrong_types() {
+    // Text that isn't a string
+    let error = parse_catalog(Path::new("en.json"), "{\n  \"index\": {\n    \"count\": 3\n  }\n}", CatalogFormat::Json).unwrap_err();
+    assert_eq!(error.key_path.as_deref(), Some("index.count"));
+    assert_eq!(error.line, Some(3));
+    assert!(error.to_string().contains("expected a string"), "{}", error);
+
+    // A page that isn't a table of keys
+    let error = parse_catalog(Path::new("en.yaml"), "index: just text\n", CatalogFormat::Yaml).unwrap_err();
+    assert_eq!(error.key_path.as_deref(), Some("index"));
+    assert!(error.to_string().contains("expected a table of translation keys"), "{}", error);
+
+    // A key that can't be referenced as page.key
+    let error = parse_catalog(Path::new("en.toml"), "[index]\n\"learn.more\" = \"x\"\n", CatalogFormat::Toml).unwrap_err();
+    assert!(error.to_string().contains("invalid key \"learn.more\""), "{}", error);
+}
+
+#[test]
+fn test_parse_catalog_syntax_error() {
+    let error = parse_catalog(Path::new("en.json"), "{\n  \"index\": {\n    \"a\": \"b\"\n  \n}", CatalogFormat::Json).unwrap_err();
+    assert!(matches!(error.kind, TranslationErrorKind::Syntax(_)));
+    assert!(error.line.is_some());
+}
+
+// ===================================================================
+// # === Tests for loading a translation directory ===
+// ===================================================================
+
+#[test]
+fn test_load_translation_directory_mixed_formats_and_errors() {
+    let tmp = tempdir().unwrap();
+    let dir = tmp.path();
+    fs::write(dir.join("en.json"), r#"{ "index": { "title": "Hello" } }"#).unwrap();
+    fs::write(dir.join("jp.toml"), "[index]\ntitle = \"こんにちは\"\n").unwrap();
+    fs::write(dir.join("notes.txt"), "not a catalog").unwrap();
+    fs::create_dir_all(dir.join("source-text")).unwrap();
+    fs::write(dir.join("source-text/jp.json"), r#"{ "index": { "title": "Hello" } }"#).unwrap();
+
+    let translations = load_translation_files_from_directory(&DiskFileSystem, dir).unwrap();
+    assert_eq!(translations.len(), 2);
+    assert_eq!(translations["jp"]["index"]["title"], "こんにちは");
+
+    // Every bad file is reported, not just the first
+    fs::write(dir.join("en.yaml"), "index:\n  title: Hello\n").unwrap();
+    fs::write(dir.join("ie.yaml"), "index:\n  title: [1, 2]\n").unwrap();
+    let errors = load_translation_files_from_directory(&DiskFileSystem, dir).unwrap_err();
+    assert_eq!(errors.0.len(), 2);
+    assert!(errors.0.iter().any(|e| matches!(e.kind, TranslationErrorKind::DuplicateLanguage { .. })));
+    assert!(errors.0.iter().any(|e| e.key_path.as_deref() == Some("index.title")));
+}
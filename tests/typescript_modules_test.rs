@@ -0,0 +1,140 @@
+use websiteCompiler::typescript_modules::*;
+use websiteCompiler::vfs::DiskFileSystem;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+// ===================================================================
+// # === Tests for `split_statements` ===
+// ===================================================================
+
+#[test]
+fn test_split_statements() {
+    let source = r#"// The loader
+import { a } from "./a";
+const s = `${ {x: "}"}.x } ; `
+let r = /[;}]/g
+if (r.test(s)) {
+    a();
+} else {
+    console.log("no");
+}
+/* a class */
+export class Thing {
+    go() { return 1 / 2; }
+}
+export const f = () => {
+    return 1;
+};
+type Id = string
+"#;
+    let statements: Vec<&str> = split_statements(source).into_iter().map(|(start, end)| &source[start..end]).collect();
+    assert_eq!(statements, [
+        "// The loader\nimport { a } from \"./a\";\n",
+        "const s = `${ {x: \"}\"}.x } ; `\n",
+        "let r = /[;}]/g\n",
+        "if (r.test(s)) {\n    a();\n} else {\n    console.log(\"no\");\n}\n",
+        "/* a class */\nexport class Thing {\n    go() { return 1 / 2; }\n}\n",
+        "export const f = () => {\n    return 1;\n};\n",
+        "type Id = string\n",
+    ]);
+    assert_eq!(statements.concat(), source);
+}
+
+// ===================================================================
+// # === Tests for `link_typescript_modules` ===
+// ===================================================================
+
+#[test]
+fn test_link_typescript_modules() {
+    let tmp = tempdir().unwrap();
+    let source_dir = tmp.path().join("edit-me");
+    fs::create_dir_all(source_dir.join("shared/code")).unwrap();
+    fs::create_dir_all(source_dir.join("pages/index")).unwrap();
+    fs::write(source_dir.join("shared/code/format.ts"), r#"export function shout(text: string): string {
+    return text.toUpperCase();
+}
+
+export function whisper(text: string): string {
+    return text.toLowerCase();
+}
+"#).unwrap();
+    fs::write(source_dir.join("shared/code/loader.ts"), r#"import { shout } from "./format";
+
+interface Options { loud: boolean }
+const PREFIX = "D-";
+const UNUSED = "never used";
+
+export async function load(options: Options): Promise<void> {
+    console.log(options.loud ? shout(PREFIX) : PREFIX);
+}
+
+export function unusedExport() {
+    return UNUSED;
+}
+
+function helper() { return load({ loud: true }); }
+export { helper as loadLoudly };
+export default function () { return "default"; }
+
+console.log("loader ready");
+"#).unwrap();
+    fs::write(source_dir.join("pages/index/index.ts"), r#"import loaderDefault, { load, loadLoudly } from "<root>/shared/code/loader";
+
+document.addEventListener("DOMContentLoaded", () => load({ loud: false }));
+"#).unwrap();
+
+    let usage = collect_module_usage(&DiskFileSystem, &source_dir);
+    let output_file = tmp.path().join("index.ts");
+    fs::copy(source_dir.join("pages/index/index.ts"), &output_file).unwrap();
+    let modules_linked = link_typescript_modules(&DiskFileSystem, &output_file, &source_dir.join("pages/index/index.ts"), &source_dir, &usage).unwrap();
+    assert_eq!(modules_linked, 2);
+
+    let linked = fs::read_to_string(&output_file).unwrap();
+    let format_start = linked.find("namespace __module_shared_code_format {").unwrap();
+    let loader_start = linked.find("namespace __module_shared_code_loader {").unwrap();
+    assert!(format_start < loader_start, "{}", linked);
+    // Only what is used is kept
+    assert!(linked.contains("export function shout") && !linked.contains("whisper"), "{}", linked);
+    assert!(!linked.contains("unusedExport") && !linked.contains("UNUSED"), "{}", linked);
+    assert!(linked.contains("interface Options") && linked.contains("const PREFIX"), "{}", linked);
+    assert!(linked.contains("console.log(\"loader ready\");"), "{}", linked);
+    // Imports become aliases of the namespace members
+    assert!(linked.contains("import shout = __module_shared_code_format.shout;\n"), "{}", linked);
+    assert!(linked.contains("function helper()") && linked.contains("export import loadLoudly = helper;"), "{}", linked);
+    assert!(linked.contains("export function __default () { return \"default\"; }"), "{}", linked);
+    assert!(linked.ends_with(r#"import loaderDefault = __module_shared_code_loader.__default;
+import load = __module_shared_code_loader.load;
+import loadLoudly = __module_shared_code_loader.loadLoudly;
+
+document.addEventListener("DOMContentLoaded", () => load({ loud: false }));
+"#), "{}", linked);
+
+    // Modules aren't linked into by themselves
+    let module_copy = tmp.path().join("loader.ts");
+    fs::copy(source_dir.join("shared/code/loader.ts"), &module_copy).unwrap();
+    assert_eq!(link_typescript_modules(&DiskFileSystem, &module_copy, &source_dir.join("shared/code/loader.ts"), &source_dir, &usage).unwrap(), 0);
+
+    // A change to a module means every file that imports modules is re-linked
+    let modified: HashSet<String> = [source_dir.join("shared/code/format.ts").to_string_lossy().to_string()].into_iter().collect();
+    let to_relink = typescript_files_to_relink(&DiskFileSystem, &source_dir, &modified);
+    assert_eq!(to_relink, [source_dir.join("pages/index/index.ts").to_string_lossy().to_string()].into_iter().collect::<HashSet<String>>());
+}
+
+#[test]
+fn test_link_errors() {
+    let tmp = tempdir().unwrap();
+    let source_dir = tmp.path();
+    fs::write(source_dir.join("a.ts"), "import { b } from \"./b\";\nexport const a = b;\n").unwrap();
+    fs::write(source_dir.join("b.ts"), "import { a } from \"./a\";\nexport const b = 1;\n").unwrap();
+    fs::write(source_dir.join("main.ts"), "import { a } from \"./a\";\n").unwrap();
+    fs::write(source_dir.join("package.ts"), "import { x } from \"lodash\";\n").unwrap();
+
+    let usage = collect_module_usage(&DiskFileSystem, source_dir);
+    let error = link_typescript_modules(&DiskFileSystem, &source_dir.join("main.ts"), &source_dir.join("main.ts"), source_dir, &usage).unwrap_err();
+    assert!(format!("{:#}", error).contains("circular imports"), "{:#}", error);
+    let error = link_typescript_modules(&DiskFileSystem, &source_dir.join("package.ts"), &source_dir.join("package.ts"), source_dir, &usage).unwrap_err();
+    assert!(format!("{:#}", error).contains("packages aren't supported"), "{:#}", error);
+    assert!(resolve_module_specifier(&DiskFileSystem, "<root>/pages/index/index", Path::new("main.ts"), source_dir).is_err());
+}
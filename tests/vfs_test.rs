@@ -0,0 +1,108 @@
+use websiteCompiler::vfs::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// ===================================================================
+// # === Tests for `MemoryFileSystem` ===
+// ===================================================================
+
+#[test]
+fn test_memory_file_system_files() {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("site/edit-me/pages/index.html", "<p>Hi</p>");
+
+    // (add_file makes the folders)
+    assert!(fs.is_dir(Path::new("site/edit-me/pages")));
+    assert!(fs.is_file(Path::new("site/edit-me/pages/index.html")));
+    assert!(fs.is_file(Path::new("site/edit-me/./pages/../pages/index.html")));
+    assert_eq!(fs.read_to_string(Path::new("site/edit-me/pages/index.html")).unwrap(), "<p>Hi</p>");
+    assert_eq!(fs.file_size(Path::new("site/edit-me/pages/index.html")).unwrap(), 9);
+
+    // Writing needs the folder to exist, like on disk
+    let error = fs.write(Path::new("site/out/index.html"), b"x").unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+    fs.create_dir_all(Path::new("site/out")).unwrap();
+    fs.write(Path::new("site/out/index.html"), b"x").unwrap();
+
+    // create_new only makes files that don't exist
+    assert_eq!(fs.create_new(Path::new("site/out/index.html"), b"y").unwrap_err().kind(), ErrorKind::AlreadyExists);
+    fs.create_new(Path::new("site/out/.lock"), b"1").unwrap();
+
+    // Copying, removing, and reading what isn't there
+    fs.copy(Path::new("site/out/index.html"), Path::new("site/out/copy.html")).unwrap();
+    assert_eq!(fs.read(Path::new("site/out/copy.html")).unwrap(), b"x");
+    fs.remove_file(Path::new("site/out/copy.html")).unwrap();
+    assert!(!fs.exists(Path::new("site/out/copy.html")));
+    assert_eq!(fs.read(Path::new("site/missing.html")).unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn test_memory_file_system_directories() {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("out/b.html", "b");
+    fs.add_file("out/a/index.html", "a");
+    fs.add_file("other/c.html", "c");
+
+    // read_dir: just the folder's own entries, sorted
+    assert_eq!(fs.read_dir(Path::new("out")).unwrap(), vec![PathBuf::from("out/a"), PathBuf::from("out/b.html")]);
+    // walk: everything under it (folders before what is in them)
+    assert_eq!(fs.walk(Path::new("out")), vec![PathBuf::from("out/a"), PathBuf::from("out/a/index.html"), PathBuf::from("out/b.html")]);
+    assert_eq!(fs.walk_files(Path::new("out")), vec![PathBuf::from("out/a/index.html"), PathBuf::from("out/b.html")]);
+
+    // Renaming a folder moves everything in it
+    fs.rename(Path::new("out"), Path::new(".out.previous")).unwrap();
+    assert!(!fs.exists(Path::new("out")));
+    assert_eq!(fs.read_to_string(Path::new(".out.previous/a/index.html")).unwrap(), "a");
+
+    // remove_dir_all removes everything in it, and nothing else
+    fs.remove_dir_all(Path::new(".out.previous")).unwrap();
+    assert_eq!(fs.file_paths(), vec![PathBuf::from("other/c.html")]);
+}
+
+#[test]
+fn test_memory_file_system_modified_times() {
+    let fs = MemoryFileSystem::new();
+    fs.add_file("a.txt", "a");
+    let long_ago = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    fs.set_modified("a.txt", long_ago).unwrap();
+    assert_eq!(fs.modified(Path::new("a.txt")).unwrap(), long_ago);
+
+    // (writing a file updates it)
+    fs.write(Path::new("a.txt"), b"b").unwrap();
+    assert!(fs.modified(Path::new("a.txt")).unwrap() > long_ago);
+    assert!(fs.set_modified("missing.txt", long_ago).is_err());
+
+    // Clones share the files
+    let clone = fs.clone();
+    clone.add_file("b.txt", "b");
+    assert!(fs.is_file(Path::new("b.txt")));
+}
+
+// ===================================================================
+// # === Tests for `MockCommandRunner` ===
+// ===================================================================
+
+#[test]
+fn test_mock_command_runner() {
+    let commands = MockCommandRunner::new()
+        .on("tsc", |args| CommandOutput::success(&format!("compiled {}", args.join(" "))))
+        .on("sass", |_| CommandOutput::failure("Error: expected \";\""));
+
+    let output = commands.run("tsc", &["a.ts".to_string()]).unwrap();
+    assert!(output.success);
+    assert_eq!(output.stdout, "compiled a.ts");
+    let output = commands.run("sass", &[]).unwrap();
+    assert!(!output.success);
+    assert!(output.stderr.contains("expected"));
+
+    // A program without a handler isn't installed
+    assert!(commands.is_available("tsc"));
+    assert!(!commands.is_available("esbuild"));
+    assert_eq!(commands.run("esbuild", &[]).unwrap_err().kind(), ErrorKind::NotFound);
+
+    // Every call is recorded (including the ones that failed)
+    let calls = commands.calls();
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[0], ("tsc".to_string(), vec!["a.ts".to_string()]));
+}